@@ -1,4 +1,4 @@
-use a_tree::{ATree, AttributeDefinition};
+use a_tree::{ATree, AttributeDefinition, AttributeKind};
 use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
 use itertools::Itertools;
 use serde::Deserialize;
@@ -34,6 +34,32 @@ pub fn insert_expression(c: &mut Criterion) {
     });
 }
 
+pub fn delete_expressions(c: &mut Criterion) {
+    c.bench_function("delete_all", |b| {
+        b.iter_batched(
+            || {
+                let attributes = [
+                    AttributeDefinition::boolean("private"),
+                    AttributeDefinition::integer("exchange_id"),
+                ];
+                let mut atree = ATree::new(&attributes).unwrap();
+                for id in 0..1000u64 {
+                    atree
+                        .insert(&id, &format!("private and exchange_id = {id}"))
+                        .unwrap();
+                }
+                atree
+            },
+            |mut atree| {
+                for id in 0..1000u64 {
+                    let _ = std::hint::black_box(atree.delete(&id));
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
 pub fn search(c: &mut Criterion) {
     let attributes = [
         AttributeDefinition::integer("exchange_id"),
@@ -62,23 +88,36 @@ pub fn search(c: &mut Criterion) {
     });
 }
 
+pub fn search_single_predicates(c: &mut Criterion) {
+    let attributes = [
+        AttributeDefinition::boolean("private"),
+        AttributeDefinition::integer("exchange_id"),
+    ];
+    let mut atree = ATree::new(&attributes).unwrap();
+    atree.insert(&0u64, "private").unwrap();
+    for id in 1..1000u64 {
+        atree
+            .insert(&id, &format!("exchange_id = {}", id % 100))
+            .unwrap();
+    }
+    let mut builder = atree.make_event();
+    builder.with_boolean("private", true).unwrap();
+    builder.with_integer("exchange_id", 5).unwrap();
+    let event = builder.build().unwrap();
+    c.bench_function("search_single_predicates", |b| {
+        b.iter(|| {
+            let _ = std::hint::black_box(atree.search(&event));
+        })
+    });
+}
+
 #[derive(Deserialize)]
 struct SearchContent {
-    attributes: HashMap<String, AttributeType>,
+    attributes: HashMap<String, String>,
     events: Vec<HashMap<String, EventValue>>,
     expressions: Vec<Expression>,
 }
 
-#[derive(Deserialize)]
-#[serde(rename_all = "snake_case")]
-enum AttributeType {
-    Boolean,
-    String,
-    StringList,
-    Integer,
-    IntegerList,
-}
-
 #[derive(Deserialize)]
 #[serde(untagged)]
 enum EventValue {
@@ -100,12 +139,13 @@ pub fn search_with_files(c: &mut Criterion) {
     let attributes = content
         .attributes
         .iter()
-        .map(|(name, kind)| match kind {
-            AttributeType::String => AttributeDefinition::string(name),
-            AttributeType::Boolean => AttributeDefinition::boolean(name),
-            AttributeType::Integer => AttributeDefinition::integer(name),
-            AttributeType::StringList => AttributeDefinition::string_list(name),
-            AttributeType::IntegerList => AttributeDefinition::integer_list(name),
+        .map(|(name, kind)| match kind.parse().unwrap() {
+            AttributeKind::String => AttributeDefinition::string(name),
+            AttributeKind::Boolean => AttributeDefinition::boolean(name),
+            AttributeKind::Integer => AttributeDefinition::integer(name),
+            AttributeKind::StringList => AttributeDefinition::string_list(name),
+            AttributeKind::IntegerList => AttributeDefinition::integer_list(name),
+            kind => unimplemented!("{kind} attributes are not used by the benchmarks"),
         })
         .collect_vec();
     let mut atree = ATree::new(&attributes).unwrap();
@@ -153,5 +193,12 @@ pub fn search_with_files(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, insert_expression, search, search_with_files);
+criterion_group!(
+    benches,
+    insert_expression,
+    delete_expressions,
+    search,
+    search_single_predicates,
+    search_with_files
+);
 criterion_main!(benches);
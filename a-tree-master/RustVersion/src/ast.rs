@@ -1,5 +1,9 @@
-use crate::predicates::Predicate;
-use std::hash::Hash;
+use crate::{
+    hash::{self, FnvHasher},
+    predicates::Predicate,
+};
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::hash::{Hash, Hasher};
 
 pub type TreeNode = Box<Node>;
 
@@ -9,6 +13,7 @@ pub enum Node {
     Or(TreeNode, TreeNode),
     Not(TreeNode),
     Value(Predicate),
+    Constant(bool),
 }
 
 #[derive(PartialEq, Clone, Debug)]
@@ -16,6 +21,8 @@ pub enum OptimizedNode {
     And(Box<OptimizedNode>, Box<OptimizedNode>),
     Or(Box<OptimizedNode>, Box<OptimizedNode>),
     Value(Predicate),
+    // Only allowed as the root of an expression
+    Constant(bool),
 }
 
 #[derive(Debug, Hash, Clone, Eq, PartialEq)]
@@ -25,23 +32,105 @@ pub enum Operator {
 }
 
 impl OptimizedNode {
+    // Distinguish the runs of `AND` from the runs of `OR` with the same operands
+    const AND_TAG: u64 = 0x9e37_79b9_7f4a_7c15;
+    const OR_TAG: u64 = 0xbf58_476d_1ce4_e5b9;
+
+    /// The ID of the expression.
+    ///
+    /// The IDs of the operands of a run of the same operator (i.e. `a and b and c`) are mixed and
+    /// summed so that the ID does not depend on their order nor on their grouping, just like the
+    /// node of the run once it is flattened in the tree. Unlike the product (or the sum) of the
+    /// raw IDs that the paper uses, the sum of the mixed IDs does not degenerate when there are
+    /// many operands (i.e. every even factor of a product adds a trailing zero to it).
     #[inline]
     pub fn id(&self) -> u64 {
-        // TODO: Even though the paper specifies that way of computing the ID, I feel as though
-        // this might yield collisions. For example, if there are some expressions such as
-        // (where A = 3, B = 5, C = 2 and D = 6):
-        //
-        // A ∧ B
-        // (C ∧ D) ∨ A
-        //
-        // Then, given the above expressions, there could be a conflict in the expression IDs.
-        // If this is possible, should this implementation be switched for a commutative hashing
-        // strategy?
         match self {
-            Self::And(left, right) => u64::wrapping_mul(left.id(), right.id()),
-            Self::Or(left, right) => u64::wrapping_add(left.id(), right.id()),
+            Self::And(_, _) => hash::mix(self.operands_sum(true) ^ Self::AND_TAG),
+            Self::Or(_, _) => hash::mix(self.operands_sum(false) ^ Self::OR_TAG),
             Self::Value(node) => node.id(),
+            Self::Constant(value) => {
+                let mut hasher = FnvHasher::default();
+                value.hash(&mut hasher);
+                hasher.finish()
+            }
+        }
+    }
+
+    /// The sum of the mixed IDs of the operands of the run of `AND` (or `OR`) that starts at the
+    /// node
+    fn operands_sum(&self, is_and: bool) -> u64 {
+        match self {
+            Self::And(left, right) if is_and => left
+                .operands_sum(is_and)
+                .wrapping_add(right.operands_sum(is_and)),
+            Self::Or(left, right) if !is_and => left
+                .operands_sum(is_and)
+                .wrapping_add(right.operands_sum(is_and)),
+            node => hash::mix(node.id()),
+        }
+    }
+
+    /// Create an `AND` node whose operands are in canonical order so that commutated
+    /// expressions (i.e. `a and b` and `b and a`) are the same node.
+    #[inline]
+    pub(crate) fn and(left: OptimizedNode, right: OptimizedNode) -> Self {
+        let (left, right) = Self::canonical_order(left, right);
+        Self::And(Box::new(left), Box::new(right))
+    }
+
+    /// Create an `OR` node whose operands are in canonical order (see [`OptimizedNode::and()`]).
+    #[inline]
+    pub(crate) fn or(left: OptimizedNode, right: OptimizedNode) -> Self {
+        let (left, right) = Self::canonical_order(left, right);
+        Self::Or(Box::new(left), Box::new(right))
+    }
+
+    #[inline]
+    fn canonical_order(left: OptimizedNode, right: OptimizedNode) -> (Self, Self) {
+        if left.id() > right.id() {
+            (right, left)
+        } else {
+            (left, right)
+        }
+    }
+
+    /// Return the predicates of the expression from the leftmost to the rightmost one.
+    pub fn predicates(&self) -> Vec<&Predicate> {
+        let mut predicates = vec![];
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            match node {
+                Self::And(left, right) | Self::Or(left, right) => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+                Self::Value(predicate) => predicates.push(predicate),
+                Self::Constant(_) => {}
+            }
+        }
+        predicates
+    }
+
+    /// Return the operands of the run of `AND` (or `OR`) that starts at the node, from the
+    /// leftmost to the rightmost one.
+    pub fn operands(&self, is_and: bool) -> Vec<&Self> {
+        let mut operands = vec![];
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            match node {
+                Self::And(left, right) if is_and => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+                Self::Or(left, right) if !is_and => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+                node => operands.push(node),
+            }
         }
+        operands
     }
 
     #[inline]
@@ -53,6 +142,7 @@ impl OptimizedNode {
             Self::And(left, right) => left.cost() + right.cost() + 50,
             Self::Or(left, right) => left.cost() + right.cost() + 60,
             Self::Value(node) => node.cost(),
+            Self::Constant(_) => 0,
         }
     }
 }
@@ -65,26 +155,30 @@ impl Node {
 
     pub fn zero_suppression_filter(self, negate: bool) -> OptimizedNode {
         match (self, negate) {
-            (Self::And(left, right), true) => OptimizedNode::Or(
-                Box::new(left.zero_suppression_filter(true)),
-                Box::new(right.zero_suppression_filter(true)),
+            // The tree has no `NOT` nodes so `(not a) or (not b)` is the cheapest form of
+            // `not (a and b)` even though an `OR` costs more than an `AND`: keeping the `AND`
+            // would need a `NOT` above it, which would be evaluated every time the `AND` is.
+            (Self::And(left, right), true) => OptimizedNode::or(
+                left.zero_suppression_filter(true),
+                right.zero_suppression_filter(true),
             ),
-            (Self::Or(left, right), true) => OptimizedNode::And(
-                Box::new(left.zero_suppression_filter(true)),
-                Box::new(right.zero_suppression_filter(true)),
+            (Self::Or(left, right), true) => OptimizedNode::and(
+                left.zero_suppression_filter(true),
+                right.zero_suppression_filter(true),
             ),
             (Self::Not(value), true) => value.zero_suppression_filter(false),
             (Self::Not(value), false) => value.zero_suppression_filter(true),
             (Self::Value(predicate), true) => OptimizedNode::Value(!predicate),
-            (Self::And(left, right), false) => OptimizedNode::And(
-                Box::new(left.zero_suppression_filter(false)),
-                Box::new(right.zero_suppression_filter(false)),
+            (Self::And(left, right), false) => OptimizedNode::and(
+                left.zero_suppression_filter(false),
+                right.zero_suppression_filter(false),
             ),
-            (Self::Or(left, right), false) => OptimizedNode::Or(
-                Box::new(left.zero_suppression_filter(false)),
-                Box::new(right.zero_suppression_filter(false)),
+            (Self::Or(left, right), false) => OptimizedNode::or(
+                left.zero_suppression_filter(false),
+                right.zero_suppression_filter(false),
             ),
             (Self::Value(predicate), _) => OptimizedNode::Value(predicate),
+            (Self::Constant(value), negate) => OptimizedNode::Constant(value != negate),
         }
     }
 }
@@ -95,7 +189,7 @@ mod tests {
 
     use crate::{
         events::{AttributeDefinition, AttributeTable},
-        predicates::PredicateKind,
+        predicates::{NullOperator, PredicateKind},
         test_utils::{
             ast::{and, not, or, value},
             optimized_node,
@@ -281,6 +375,58 @@ mod tests {
         );
     }
 
+    #[test]
+    fn put_the_operands_of_commutated_expressions_in_the_same_order() {
+        let attributes = define_attributes();
+        let private = Predicate::new(&attributes, "private", PredicateKind::Variable).unwrap();
+        let deals = Predicate::new(
+            &attributes,
+            "deals",
+            PredicateKind::Null(NullOperator::IsNull),
+        )
+        .unwrap();
+
+        assert_eq!(
+            and!(value!(private.clone()), value!(deals.clone())).optimize(),
+            and!(value!(deals.clone()), value!(private.clone())).optimize()
+        );
+        assert_eq!(
+            not!(and!(value!(private.clone()), value!(deals.clone()))).optimize(),
+            or!(not!(value!(deals)), not!(value!(private))).optimize()
+        );
+    }
+
+    #[test]
+    fn cost_a_negated_and_expression_like_the_or_of_its_negated_operands() {
+        let attributes = define_attributes();
+        let private = Predicate::new(&attributes, "private", PredicateKind::Variable).unwrap();
+        let deals = Predicate::new(
+            &attributes,
+            "deals",
+            PredicateKind::Null(NullOperator::IsNull),
+        )
+        .unwrap();
+        let negated = not!(and!(value!(private.clone()), value!(deals.clone()))).optimize();
+
+        assert_eq!(3, count_nodes(&negated));
+        assert_eq!(
+            or!(not!(value!(private.clone())), not!(value!(deals.clone())))
+                .optimize()
+                .cost(),
+            negated.cost()
+        );
+        assert_eq!((!private).cost() + (!deals).cost() + 60, negated.cost());
+    }
+
+    fn count_nodes(node: &OptimizedNode) -> usize {
+        match node {
+            OptimizedNode::And(left, right) | OptimizedNode::Or(left, right) => {
+                1 + count_nodes(left) + count_nodes(right)
+            }
+            OptimizedNode::Value(_) | OptimizedNode::Constant(_) => 1,
+        }
+    }
+
     fn define_attributes() -> AttributeTable {
         let definitions = vec![
             AttributeDefinition::string_list("deals"),
@@ -1,17 +1,51 @@
+#[cfg(feature = "std")]
+use crate::cache::{SearchCache, SearchCacheStats};
 use crate::{
     ast::*,
-    error::ATreeError,
+    collections::{DeterministicHashMap, HashMap},
+    error::{ATreeError, BudgetExceeded, ParserError},
     evaluation::EvaluationResult,
-    events::{AttributeDefinition, AttributeTable, Event, EventBuilder},
-    parser,
-    predicates::Predicate,
-    strings::StringTable,
+    events::{
+        AttributeDefinition, AttributeHandle, AttributeTable, Event, EventBuilder, EventError,
+        UndefinedPolicy,
+    },
+    expr::Expr,
+    hash,
+    parser::{self, NamedLists, ParserLimits},
+    predicates::{ListLiteral, Predicate},
+    strings::{Interner, StringId, StringTable},
 };
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use core::{
+    fmt::Debug,
+    hash::Hash,
+    ops::{ControlFlow, Range},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use fixedbitset::FixedBitSet;
+use itertools::Itertools;
+use lalrpop_util::ParseError;
 use slab::Slab;
-use std::{collections::HashMap, fmt::Debug, hash::Hash};
 
 type NodeId = usize;
-type ExpressionId = u64;
+
+// Identifies the tree that compiled an expression
+static NEXT_TREE_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// The identifier of an expression inside the [`ATree`]
+///
+/// It is a structural hash of the optimized expression: expressions that are equivalent once
+/// optimized share the same [`ExpressionId`] and are thus stored only once. The hash is computed
+/// with a fixed algorithm so an [`ATree`] built with the same attributes yields the same
+/// identifiers for the same expressions across processes. The strings are hashed by their content
+/// so the identifiers do not depend on the order in which the expressions were inserted.
+pub type ExpressionId = u64;
 
 /// The A-Tree data structure as described by the paper
 ///
@@ -19,22 +53,34 @@ type ExpressionId = u64;
 ///
 /// [module documentation]: index.html
 #[derive(Clone, Debug)]
-pub struct ATree<T> {
+pub struct ATree<T, S = StringTable> {
+    id: usize,
     nodes: Slab<Entry<T>>,
-    strings: StringTable,
+    strings: S,
     attributes: AttributeTable,
+    lists: NamedLists,
+    limits: ParserLimits,
     roots: Vec<NodeId>,
-    max_level: usize,
+    root_levels: RootLevels,
     predicates: Vec<NodeId>,
-    expression_to_node: HashMap<ExpressionId, NodeId>,
-    nodes_by_ids: HashMap<T, NodeId>,
+    // Not randomly seeded so that the same insertions always build the same tree
+    expression_to_node: DeterministicHashMap<ExpressionId, NodeId>,
+    nodes_by_ids: DeterministicHashMap<T, NodeId>,
+    profile: Option<HashMap<ExpressionId, NodeProfile>>,
+    expected_matches: usize,
+    dense_matches: bool,
+    expression_texts: Option<HashMap<T, String>>,
+    // The subscription IDs whose expression is a lone `true` or `false`
+    always_matching: Vec<T>,
+    never_matching: Vec<T>,
+    priorities: HashMap<T, i64>,
+    duplicate_policy: DuplicatePolicy,
+    max_roots: Option<usize>,
+    #[cfg(feature = "std")]
+    search_cache: Option<SearchCache<T>>,
 }
 
 impl<T: Eq + Hash + Clone + Debug> ATree<T> {
-    const DEFAULT_PREDICATES: usize = 1000;
-    const DEFAULT_NODES: usize = 2000;
-    const DEFAULT_ROOTS: usize = 50;
-
     /// Create a new [`ATree`] with the attributes that can be used by the inserted arbitrary
     /// boolean expressions along with their types.
     ///
@@ -63,23 +109,107 @@ impl<T: Eq + Hash + Clone + Debug> ATree<T> {
     /// let result = ATree::<u64>::new(&definitions);
     /// assert!(result.is_err());
     /// ```
-    pub fn new(definitions: &[AttributeDefinition]) -> Result<Self, ATreeError> {
-        let attributes = AttributeTable::new(definitions).map_err(ATreeError::Event)?;
-        let strings = StringTable::new();
+    pub fn new(definitions: &[AttributeDefinition]) -> Result<Self, ATreeError<'_>> {
+        Self::with_options(definitions, ATreeOptions::default())
+    }
+
+    /// Create a new [`ATree`] like [`ATree::new()`] but with the specified [`ATreeOptions`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, ATreeOptions, AttributeDefinition};
+    ///
+    /// let definitions = [AttributeDefinition::string("country")];
+    /// let options = ATreeOptions::new().max_strings(1);
+    /// let mut atree = ATree::<u64>::with_options(&definitions, options).unwrap();
+    /// assert!(atree.insert(&1, "country = 'CA'").is_ok());
+    /// assert!(atree.insert(&2, "country = 'US'").is_err());
+    /// ```
+    pub fn with_options(
+        definitions: &[AttributeDefinition],
+        mut options: ATreeOptions,
+    ) -> Result<Self, ATreeError<'_>> {
+        let mut strings = StringTable::new();
+        // The values of the enumerations are interned when the tree is created so the table has
+        // to be set up beforehand
+        #[cfg(feature = "normalization")]
+        strings.set_normalize(options.normalize_strings);
+        if let Some(max_strings) = options.max_strings.take() {
+            strings.set_max_strings(max_strings);
+        }
+        Self::with_interner(definitions, options, strings)
+    }
+}
+
+impl<T: Eq + Hash + Clone + Debug, S: Interner> ATree<T, S> {
+    const DEFAULT_PREDICATES: usize = 1000;
+    const DEFAULT_NODES: usize = 2000;
+    const DEFAULT_ROOTS: usize = 50;
+    const DEFAULT_EXPECTED_MATCHES: usize = 50;
+
+    /// Create a new [`ATree`] like [`ATree::with_options()`] but with a custom [`Interner`] for
+    /// the strings.
+    ///
+    /// The [`ATree`]s that share the same interner (i.e. through a reference counted handle) have
+    /// comparable [`StringId`]s so an [`Event`] built by one of them can be searched in the others.
+    /// [`ATreeOptions::max_strings()`] only applies to the default [`StringTable`] so it is
+    /// rejected with an error here.
+    pub fn with_interner(
+        definitions: &[AttributeDefinition],
+        options: ATreeOptions,
+        mut strings: S,
+    ) -> Result<Self, ATreeError<'_>> {
+        if options.max_strings.is_some() {
+            return Err(ATreeError::UnsupportedOption("max_strings"));
+        }
+        let id = NEXT_TREE_ID.fetch_add(1, Ordering::Relaxed);
+        let mut attributes = AttributeTable::new(definitions).map_err(ATreeError::Event)?;
+        attributes.set_tree_id(id);
+        attributes.set_undefined_policy(options.undefined_policy);
+        attributes.set_reject_unknown_list_elements(options.reject_unknown_list_elements);
+        attributes
+            .intern_enumerations(&mut strings)
+            .map_err(|error| {
+                ATreeError::ParseError(ParseError::User {
+                    error: ParserError::Strings(error),
+                })
+            })?;
+        attributes
+            .check_enumeration_defaults()
+            .map_err(ATreeError::Event)?;
         Ok(Self {
+            id,
             attributes,
             strings,
-            max_level: 1,
+            lists: NamedLists::default(),
+            limits: ParserLimits::new(options.max_depth, options.max_list_length),
+            root_levels: RootLevels::default(),
             roots: Vec::with_capacity(Self::DEFAULT_ROOTS),
             predicates: Vec::with_capacity(Self::DEFAULT_PREDICATES),
             nodes: Slab::with_capacity(Self::DEFAULT_NODES),
-            expression_to_node: HashMap::new(),
-            nodes_by_ids: HashMap::new(),
+            expression_to_node: DeterministicHashMap::default(),
+            nodes_by_ids: DeterministicHashMap::default(),
+            profile: None,
+            expected_matches: Self::DEFAULT_EXPECTED_MATCHES,
+            dense_matches: false,
+            expression_texts: options.keep_expression_texts.then(HashMap::default),
+            always_matching: vec![],
+            never_matching: vec![],
+            priorities: HashMap::default(),
+            duplicate_policy: options.duplicate_policy,
+            max_roots: options.max_roots,
+            #[cfg(feature = "std")]
+            search_cache: None,
         })
     }
 
     /// Insert an arbitrary boolean expression inside the [`ATree`].
     ///
+    /// If the subscription ID is already part of the [`ATree`], its previous expression is either
+    /// replaced or kept with an error depending on the [`DuplicatePolicy`] (see
+    /// [`ATreeOptions::on_duplicate()`]).
+    ///
     /// # Examples
     ///
     /// ```rust
@@ -99,1295 +229,7479 @@ impl<T: Eq + Hash + Clone + Debug> ATree<T> {
         subscription_id: &T,
         expression: &'a str,
     ) -> Result<(), ATreeError<'a>> {
-        let ast = parser::parse(expression, &self.attributes, &mut self.strings)
-            .map_err(ATreeError::ParseError)?;
-        let ast = ast.optimize();
-        self.insert_root(subscription_id, ast);
+        self.forgetting_strings_on_error(|atree| {
+            let ast = atree.parse(expression)?;
+            atree.insert_expression(subscription_id, ast, Some(expression))
+        })
+    }
+
+    /// Insert an arbitrary boolean expression that was built with [`Expr`] instead of the DSL.
+    ///
+    /// The expression is validated against the attributes of the [`ATree`] like the DSL would and
+    /// is then optimized and inserted like the ones inserted with [`ATree::insert()`]. Since it
+    /// has no text, [`ATree::expression_text()`] returns `None` for its subscription ID.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition, Condition, Expr};
+    ///
+    /// let definitions = [
+    ///     AttributeDefinition::boolean("private"),
+    ///     AttributeDefinition::integer("exchange_id")
+    /// ];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// let expression = Expr::and(
+    ///     Expr::predicate("exchange_id", Condition::Equal(5.into())),
+    ///     Expr::not(Expr::predicate("private", Condition::IsTrue)),
+    /// );
+    /// assert!(atree.insert_expr(&1u64, &expression).is_ok());
+    /// let invalid = Expr::predicate("exchange_id", Condition::Equal("5".into()));
+    /// assert!(atree.insert_expr(&2u64, &invalid).is_err());
+    /// ```
+    pub fn insert_expr(
+        &mut self,
+        subscription_id: &T,
+        expression: &Expr,
+    ) -> Result<(), ATreeError<'static>> {
+        self.forgetting_strings_on_error(|atree| {
+            let (ast, _) = expression
+                .to_node(&atree.attributes, &mut atree.strings, &atree.limits)
+                .map_err(ATreeError::ParseError)?;
+            atree.insert_expression(subscription_id, ast.optimize(), None)
+        })
+    }
+
+    /// Insert an arbitrary boolean expression inside the [`ATree`] along with the priority of its
+    /// subscription ID.
+    ///
+    /// The priority is only used to order the results of [`ATree::search_by_priority()`]; the
+    /// subscription IDs inserted with [`ATree::insert()`] have a priority of 0, even when they
+    /// were inserted with a priority before.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [AttributeDefinition::boolean("private")];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// assert!(atree.insert_with_priority(&1u64, "private", 10).is_ok());
+    /// ```
+    pub fn insert_with_priority<'a>(
+        &mut self,
+        subscription_id: &T,
+        expression: &'a str,
+        priority: i64,
+    ) -> Result<(), ATreeError<'a>> {
+        self.forgetting_strings_on_error(|atree| {
+            let ast = atree.parse(expression)?;
+            atree.insert_expression(subscription_id, ast, Some(expression))
+        })?;
+        self.priorities.insert(subscription_id.clone(), priority);
         Ok(())
     }
 
-    fn insert_root(&mut self, subscription_id: &T, root: OptimizedNode) {
-        let expression_id = root.id();
-        if let Some(node_id) = self.expression_to_node.get(&expression_id) {
-            add_subscription_id(
-                subscription_id,
-                *node_id,
-                &mut self.nodes,
-                &mut self.nodes_by_ids,
-            );
-            increment_use_count(*node_id, &mut self.nodes);
-            return;
-        }
+    /// Define a named list of strings that the expressions can reference with `@name` wherever a
+    /// literal list is expected.
+    ///
+    /// The reference is expanded when an expression is parsed so redefining a list only affects
+    /// the expressions inserted afterwards.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [AttributeDefinition::string("country")];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.define_string_list("EU", &["FR", "DE", "IT"]).unwrap();
+    /// assert!(atree.insert(&1u64, "country in @EU").is_ok());
+    /// assert!(atree.insert(&2u64, "country not in @NA").is_err());
+    /// ```
+    pub fn define_string_list(
+        &mut self,
+        name: &str,
+        values: &[&str],
+    ) -> Result<(), ATreeError<'static>> {
+        let ids = self.forgetting_strings_on_error(|atree| {
+            let ids = values
+                .iter()
+                .map(|value| atree.strings.get_or_update(value))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|error| {
+                    ATreeError::ParseError(ParseError::User {
+                        error: ParserError::Strings(error),
+                    })
+                })?;
+            // The lookups are done by ID so the list has to be sorted by ID, not by value
+            atree
+                .limits
+                .check_list_length(ids.into_iter().sorted().dedup().collect())
+                .map_err(ATreeError::ParseError)
+        })?;
+        self.lists
+            .insert(name.to_string(), ListLiteral::StringList(ids));
+        Ok(())
+    }
 
-        let is_and = matches!(&root, OptimizedNode::And(_, _));
-        let cost = root.cost();
-        let node_id = match root {
-            OptimizedNode::And(left, right) | OptimizedNode::Or(left, right) => {
-                let left_id = self.insert_node(*left);
-                let right_id = self.insert_node(*right);
-                let left_entry = &self.nodes[left_id];
-                let right_entry = &self.nodes[right_id];
-                let rnode = ATreeNode::RNode(RNode {
-                    level: 1 + std::cmp::max(left_entry.node.level(), right_entry.node.level()),
-                    operator: if is_and { Operator::And } else { Operator::Or },
-                    children: if left_entry.cost > right_entry.cost {
-                        vec![right_id, left_id]
-                    } else {
-                        vec![left_id, right_id]
-                    },
-                });
-                let node_id = insert_node(
-                    &mut self.expression_to_node,
-                    &mut self.nodes,
-                    &expression_id,
-                    rnode,
-                    Some(subscription_id.clone()),
-                    cost,
-                );
-                if is_and {
-                    choose_access_child(
-                        left_id,
-                        right_id,
-                        node_id,
-                        &mut self.nodes,
-                        &mut self.predicates,
-                    );
-                } else {
-                    add_parent(&mut self.nodes[left_id], node_id);
-                    add_parent(&mut self.nodes[right_id], node_id);
-                    add_predicate(left_id, &self.nodes, &mut self.predicates);
-                    add_predicate(right_id, &self.nodes, &mut self.predicates);
-                }
-                node_id
-            }
-            OptimizedNode::Value(value) => {
-                let lnode = ATreeNode::lnode(&value);
-                let node_id = insert_node(
-                    &mut self.expression_to_node,
-                    &mut self.nodes,
-                    &expression_id,
-                    lnode,
-                    Some(subscription_id.clone()),
-                    cost,
-                );
-                self.predicates.push(node_id);
-                node_id
-            }
-        };
-        self.nodes_by_ids.insert(subscription_id.clone(), node_id);
-        self.roots.push(node_id);
-        self.max_level = get_max_level(&self.roots, &self.nodes);
+    /// Define a named list of integers like [`ATree::define_string_list()`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [AttributeDefinition::integer_list("segment_ids")];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.define_integer_list("sports", &[3, 1, 2]).unwrap();
+    /// assert!(atree.insert(&1u64, "segment_ids one of @sports").is_ok());
+    /// ```
+    pub fn define_integer_list(
+        &mut self,
+        name: &str,
+        values: &[i64],
+    ) -> Result<(), ATreeError<'static>> {
+        let values = self
+            .limits
+            .check_list_length(values.iter().copied().sorted().dedup().collect())
+            .map_err(ATreeError::ParseError)?;
+        self.lists
+            .insert(name.to_string(), ListLiteral::IntegerList(values));
+        Ok(())
     }
 
-    fn insert_node(&mut self, node: OptimizedNode) -> NodeId {
-        let expression_id = node.id();
-        if let Some(node_id) = self.expression_to_node.get(&expression_id) {
-            change_rnode_to_inode(*node_id, &mut self.nodes);
-            increment_use_count(*node_id, &mut self.nodes);
-            return *node_id;
-        }
+    /// Parse and optimize an arbitrary boolean expression so that it can be inserted multiple
+    /// times with [`ATree::insert_compiled()`] without being parsed again.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [AttributeDefinition::string("country")];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// let compiled = atree.compile("country = 'CA'").unwrap();
+    /// assert!(atree.insert_compiled(&1u64, &compiled).is_ok());
+    /// assert!(atree.insert_compiled(&2u64, &compiled).is_ok());
+    /// ```
+    pub fn compile<'a>(
+        &mut self,
+        expression: &'a str,
+    ) -> Result<CompiledExpression, ATreeError<'a>> {
+        let root = self.forgetting_strings_on_error(|atree| atree.parse(expression))?;
+        // The strings are taken from the predicates rather than from the expression so that the
+        // ones of the named lists are checked too
+        let strings = root
+            .predicates()
+            .into_iter()
+            .flat_map(Predicate::strings)
+            .sorted()
+            .dedup()
+            .filter_map(|id| Some((self.strings.resolve(*id)?, *id)))
+            .collect();
+        Ok(CompiledExpression {
+            tree_id: self.id,
+            root,
+            strings,
+            expression: expression.to_string(),
+        })
+    }
 
-        let is_and = matches!(node, OptimizedNode::And(_, _));
-        let cost = node.cost();
-        match node {
-            OptimizedNode::And(left, right) | OptimizedNode::Or(left, right) => {
-                let left_id = self.insert_node(*left);
-                let right_id = self.insert_node(*right);
-                let left_entry = &self.nodes[left_id];
-                let right_entry = &self.nodes[right_id];
-                let inode = INode {
-                    parents: vec![],
-                    level: 1 + std::cmp::max(left_entry.node.level(), right_entry.node.level()),
-                    operator: if is_and { Operator::And } else { Operator::Or },
-                    children: if left_entry.cost > right_entry.cost {
-                        vec![right_id, left_id]
-                    } else {
-                        vec![left_id, right_id]
-                    },
-                };
-                let inode = ATreeNode::INode(inode);
-                let node_id = insert_node(
-                    &mut self.expression_to_node,
-                    &mut self.nodes,
-                    &expression_id,
-                    inode,
-                    None,
-                    cost,
-                );
-                if is_and {
-                    choose_access_child(
-                        left_id,
-                        right_id,
-                        node_id,
-                        &mut self.nodes,
-                        &mut self.predicates,
-                    );
-                } else {
-                    add_parent(&mut self.nodes[left_id], node_id);
-                    add_parent(&mut self.nodes[right_id], node_id);
-                    add_predicate(left_id, &self.nodes, &mut self.predicates);
-                    add_predicate(right_id, &self.nodes, &mut self.predicates);
-                }
-                node_id
-            }
-            OptimizedNode::Value(node) => {
-                let lnode = ATreeNode::lnode(&node);
-                insert_node(
-                    &mut self.expression_to_node,
-                    &mut self.nodes,
-                    &expression_id,
-                    lnode,
-                    None,
-                    cost,
-                )
-            }
+    /// Insert an expression that was compiled with [`ATree::compile()`].
+    ///
+    /// The expression must have been compiled by this [`ATree`] (or by the one it was cloned from
+    /// as long as they still agree on the strings of the expression); otherwise an error is
+    /// returned.
+    pub fn insert_compiled(
+        &mut self,
+        subscription_id: &T,
+        compiled: &CompiledExpression,
+    ) -> Result<(), ATreeError<'static>> {
+        let is_foreign = compiled.tree_id != self.id
+            || compiled
+                .strings
+                .iter()
+                .any(|(value, id)| self.strings.get(value) != *id);
+        if is_foreign {
+            return Err(ATreeError::ForeignCompiledExpression);
         }
+
+        self.insert_expression(
+            subscription_id,
+            compiled.root.clone(),
+            Some(&compiled.expression),
+        )
     }
 
-    /// Create a new [`EventBuilder`] to be able to generate an [`Event`] that will be usable for
-    /// finding the matching arbitrary boolean expressions inside the [`ATree`] via the
-    /// [`ATree::search()`] function.
+    /// Run the insertion and forget the strings it interned if it fails so that a rejected
+    /// expression does not use up [`ATreeOptions::max_strings()`].
     #[inline]
-    pub fn make_event(&self) -> EventBuilder {
-        EventBuilder::new(&self.attributes, &self.strings)
+    fn forgetting_strings_on_error<R, E>(
+        &mut self,
+        insert: impl FnOnce(&mut Self) -> Result<R, E>,
+    ) -> Result<R, E> {
+        let checkpoint = self.strings.checkpoint();
+        let result = insert(self);
+        if result.is_err() {
+            self.strings.rollback(checkpoint);
+        }
+        result
     }
 
-    /// Search the [`ATree`] for arbitrary boolean expressions that match the [`Event`].
-    pub fn search(&self, event: &Event) -> Result<Report<T>, ATreeError> {
-        let mut results = EvaluationResult::new(self.nodes.len());
-        let mut matches = Vec::with_capacity(50);
-
-        // Since the predicates will already be evaluated and their parents will be put into the
-        // queues, then there is no need to keep a queue for them.
-        let mut queues = vec![Vec::with_capacity(50); self.max_level - 1];
-        process_predicates(
-            &self.predicates,
-            &self.nodes,
-            event,
-            &mut matches,
-            &mut results,
-            &mut queues,
-        );
+    #[inline]
+    fn parse<'a>(&mut self, expression: &'a str) -> Result<OptimizedNode, ATreeError<'a>> {
+        let ast = parser::parse(
+            expression,
+            &self.attributes,
+            &mut self.strings,
+            &self.lists,
+            &self.limits,
+        )
+        .map_err(ATreeError::ParseError)?;
+        Ok(ast.optimize())
+    }
 
-        for level in 0..queues.len() {
-            while let Some((node_id, node)) = queues[level].pop() {
-                if results.is_evaluated(node_id) {
-                    continue;
-                }
+    #[inline]
+    fn insert_expression<'a>(
+        &mut self,
+        subscription_id: &T,
+        ast: OptimizedNode,
+        expression: Option<&str>,
+    ) -> Result<(), ATreeError<'a>> {
+        self.clear_search_cache();
+        let is_duplicate = self.nodes_by_ids.contains_key(subscription_id)
+            || self.always_matching.contains(subscription_id)
+            || self.never_matching.contains(subscription_id);
+        if is_duplicate && self.duplicate_policy == DuplicatePolicy::Reject {
+            return Err(ATreeError::DuplicateSubscription);
+        }
+        self.check_max_roots(subscription_id, &ast)?;
+        // The priority is set again by `insert_with_priority()` if needed
+        self.priorities.remove(subscription_id);
+        if is_duplicate {
+            self.replace_expression(subscription_id, ast, expression);
+            self.debug_validate_invariants();
+            return Ok(());
+        }
 
-                let result = evaluate_node(
-                    node_id,
-                    event,
-                    node,
-                    &self.nodes,
-                    &mut results,
-                    &mut matches,
-                );
-                add_matches(result, node, &mut matches);
+        if let OptimizedNode::Constant(value) = ast {
+            self.insert_constant(subscription_id, value);
+        } else {
+            self.insert_root(subscription_id, ast);
+        }
+        self.keep_expression_text(subscription_id, expression);
+        self.debug_validate_invariants();
+        Ok(())
+    }
 
-                if node.is_root() {
-                    continue;
-                }
+    /// Replace the arbitrary boolean expression of a subscription ID by a new one.
+    ///
+    /// The new expression is parsed before touching the [`ATree`] so an invalid expression leaves
+    /// the previous one in place. If the subscription ID is not part of the [`ATree`], the
+    /// expression is simply inserted.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [
+    ///     AttributeDefinition::boolean("private"),
+    ///     AttributeDefinition::integer("exchange_id")
+    /// ];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "exchange_id = 5").unwrap();
+    /// assert!(atree.replace(&1u64, "exchange_id = 6").is_ok());
+    /// assert!(atree.replace(&1u64, "exchange_id = 'invalid'").is_err());
+    /// ```
+    pub fn replace<'a>(
+        &'a mut self,
+        subscription_id: &T,
+        expression: &'a str,
+    ) -> Result<(), ATreeError<'a>> {
+        self.forgetting_strings_on_error(|atree| {
+            let ast = atree.parse(expression)?;
+            atree.check_max_roots(subscription_id, &ast)?;
+            atree.priorities.remove(subscription_id);
+            atree.replace_expression(subscription_id, ast, Some(expression));
+            Ok(())
+        })
+    }
 
-                for parent_id in node.parents() {
-                    let entry = &self.nodes[*parent_id];
-                    let is_evaluated = results.is_evaluated(*parent_id);
-                    if !is_evaluated
-                        && matches!(entry.operator(), Operator::And)
-                        && !result.unwrap_or(true)
-                    {
-                        results.set_result(*parent_id, Some(false));
-                        continue;
-                    }
+    /// Check that giving the expression to the subscription ID does not add a distinct root
+    /// beyond [`ATreeOptions::max_roots()`].
+    fn check_max_roots(
+        &self,
+        subscription_id: &T,
+        ast: &OptimizedNode,
+    ) -> Result<(), ATreeError<'static>> {
+        let Some(max_roots) = self.max_roots else {
+            return Ok(());
+        };
+        if matches!(ast, OptimizedNode::Constant(_)) {
+            return Ok(());
+        }
+        let (expression_id, node_id) = self.find_expression(ast);
+        let is_new_root = node_id.is_none_or(|node_id| !self.roots.contains(&node_id));
+        // Replacing the only use of a root frees it before the new one is added
+        let frees_a_root = self
+            .nodes_by_ids
+            .get(subscription_id)
+            .is_some_and(|node_id| {
+                let entry = &self.nodes[*node_id];
+                entry.use_count == 1 && entry.id != expression_id
+            });
+        if is_new_root && !frees_a_root && self.roots.len() >= max_roots {
+            return Err(ATreeError::CapacityExceeded { max_roots });
+        }
+        Ok(())
+    }
 
-                    if !is_evaluated {
-                        queues[entry.level() - 2].push((*parent_id, entry));
-                    }
-                }
+    fn replace_expression(
+        &mut self,
+        subscription_id: &T,
+        ast: OptimizedNode,
+        expression: Option<&str>,
+    ) {
+        self.clear_search_cache();
+        self.keep_expression_text(subscription_id, expression);
+        if let OptimizedNode::Constant(value) = ast {
+            if let Some(old_node_id) = self.nodes_by_ids.get(subscription_id).copied() {
+                self.delete_node(subscription_id, old_node_id);
             }
+            self.remove_constant(subscription_id);
+            self.insert_constant(subscription_id, value);
+            return;
+        }
+        self.remove_constant(subscription_id);
+        let Some(old_node_id) = self.nodes_by_ids.get(subscription_id).copied() else {
+            self.insert_root(subscription_id, ast);
+            return;
+        };
+        if self.is_same_expression(old_node_id, &ast) {
+            return;
         }
 
-        Ok(Report::new(matches))
+        // Inserting the new expression first keeps the shared sub-expressions alive
+        self.insert_root(subscription_id, ast);
+        let new_node_id = self.nodes_by_ids[subscription_id];
+        self.delete_node(subscription_id, old_node_id);
+        // The new expression might have been a sub-expression of the old one; in that case, deleting
+        // the old expression also detached the subscription ID from the new one.
+        let entry = &mut self.nodes[new_node_id];
+        if !entry.subscription_ids.contains(subscription_id) {
+            entry.subscription_ids.push(subscription_id.clone());
+        }
+        self.nodes_by_ids
+            .insert(subscription_id.clone(), new_node_id);
     }
 
-    #[inline]
-    /// Delete the specified expression
-    pub fn delete(&mut self, subscription_id: &T) {
-        if let Some(node_id) = self.nodes_by_ids.get(subscription_id) {
-            self.delete_node(subscription_id, *node_id);
+    fn insert_constant(&mut self, subscription_id: &T, value: bool) {
+        let subscription_ids = if value {
+            &mut self.always_matching
+        } else {
+            &mut self.never_matching
+        };
+        if !subscription_ids.contains(subscription_id) {
+            subscription_ids.push(subscription_id.clone());
         }
     }
 
+    /// Remove the subscription ID from the constant expressions and return whether it was part of
+    /// them.
+    fn remove_constant(&mut self, subscription_id: &T) -> bool {
+        let length = self.always_matching.len() + self.never_matching.len();
+        self.always_matching.retain(|id| id != subscription_id);
+        self.never_matching.retain(|id| id != subscription_id);
+        length != self.always_matching.len() + self.never_matching.len()
+    }
+
     #[inline]
-    fn delete_node(&mut self, subscription_id: &T, node_id: NodeId) {
-        let children = decrement_use_count(
-            subscription_id,
-            node_id,
-            &mut self.nodes,
-            &mut self.expression_to_node,
-            &mut self.roots,
-            &mut self.predicates,
-            &mut self.nodes_by_ids,
-            &mut self.max_level,
-        );
+    fn clear_search_cache(&mut self) {
+        #[cfg(feature = "std")]
+        if let Some(cache) = &mut self.search_cache {
+            cache.clear();
+        }
+    }
 
-        if let Some(children) = children {
-            for child in children {
-                self.delete_node(subscription_id, child);
+    #[inline]
+    fn keep_expression_text(&mut self, subscription_id: &T, expression: Option<&str>) {
+        if let Some(expression_texts) = &mut self.expression_texts {
+            // The text of a replaced expression must not outlive it
+            match expression {
+                Some(expression) => {
+                    expression_texts.insert(subscription_id.clone(), expression.to_string());
+                }
+                None => {
+                    expression_texts.remove(subscription_id);
+                }
             }
         }
     }
 
-    /// Export the [`ATree`] to the Graphviz format.
-    pub fn to_graphviz(&self) -> String {
-        const DEFAULT_CAPACITY: usize = 100_000;
-        let mut builder = String::with_capacity(DEFAULT_CAPACITY);
-        builder.push_str("digraph {\n");
-        builder.push_str("rankdir = TB;\n");
-        builder.push_str(r#"node [shape = "record"];"#);
-        builder.push('\n');
-        let mut relations = Vec::with_capacity(DEFAULT_CAPACITY);
-        let mut levels = vec![vec![]; self.max_level];
-        for (id, entry) in &self.nodes {
-            match &entry.node {
-                ATreeNode::LNode(LNode {
-                    parents, predicate, ..
-                }) => {
-                    let node = format!(
-                        r#"node_{id} [label = "{{{id} | level: {} | {predicate} | subscriptions: {:?} | l-node}}", style = "rounded"];"#,
-                        entry.level(),
-                        entry.subscription_ids
-                    );
-                    levels[entry.level() - 1].push((id, node));
+    /// Return the expression of the subscription ID as it was inserted.
+    ///
+    /// The expressions are only kept when [`ATreeOptions::keep_expression_texts()`] is enabled;
+    /// otherwise, this always returns `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, ATreeOptions, AttributeDefinition};
+    ///
+    /// let definitions = [AttributeDefinition::boolean("private")];
+    /// let options = ATreeOptions::new().keep_expression_texts();
+    /// let mut atree = ATree::with_options(&definitions, options).unwrap();
+    /// atree.insert(&1u64, "not private").unwrap();
+    /// assert_eq!(Some("not private"), atree.expression_text(&1u64));
+    /// ```
+    pub fn expression_text(&self, subscription_id: &T) -> Option<&str> {
+        self.expression_texts
+            .as_ref()
+            .and_then(|expression_texts| expression_texts.get(subscription_id))
+            .map(String::as_str)
+    }
 
-                    for parent_id in parents {
-                        relations.push(format!("node_{id} -> node_{parent_id};"));
-                    }
+    /// Return the distinct attributes that the expression of the subscription ID refers to, sorted
+    /// by name, or `None` if the subscription ID is not part of the [`ATree`].
+    ///
+    /// The events have to define these attributes for the expression to be fully evaluated.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [
+    ///     AttributeDefinition::boolean("private"),
+    ///     AttributeDefinition::integer("exchange_id")
+    /// ];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "exchange_id = 5 and not private").unwrap();
+    /// assert_eq!(
+    ///     Some(vec!["exchange_id", "private"]),
+    ///     atree.required_attributes(&1u64)
+    /// );
+    /// ```
+    pub fn required_attributes(&self, subscription_id: &T) -> Option<Vec<&str>> {
+        if self.always_matching.contains(subscription_id)
+            || self.never_matching.contains(subscription_id)
+        {
+            return Some(vec![]);
+        }
+
+        let mut stack = vec![*self.nodes_by_ids.get(subscription_id)?];
+        let mut attributes = vec![];
+        while let Some(node_id) = stack.pop() {
+            match &self.nodes[node_id].node {
+                ATreeNode::LNode(LNode { predicate, .. }) => {
+                    attributes.extend_from_slice(predicate.attributes());
                 }
-                ATreeNode::INode(INode {
-                    children,
-                    parents,
-                    operator,
-                    ..
-                }) => {
-                    let node = format!(
-                        r#"node_{id} [label = "{{{id} | level: {} | {operator:#?} | subscriptions: {:?} | i-node}}"];"#,
-                        entry.level(),
-                        entry.subscription_ids
-                    );
-                    levels[entry.level() - 1].push((id, node));
+                node => stack.extend_from_slice(node.children()),
+            }
+        }
+        let names = attributes
+            .into_iter()
+            .sorted()
+            .dedup()
+            .map(|attribute| self.attributes.name(attribute))
+            .sorted()
+            .collect();
+        Some(names)
+    }
 
-                    for parent_id in parents {
-                        relations.push(format!("node_{id} -> node_{parent_id};"));
-                    }
+    /// Explain why the expression of the subscription ID does not match the [`Event`].
+    ///
+    /// Only the expression of the subscription ID is evaluated. `None` is returned if it matches
+    /// or if the subscription ID is not part of the [`ATree`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition, FailureReason};
+    ///
+    /// let definitions = [
+    ///     AttributeDefinition::boolean("private"),
+    ///     AttributeDefinition::integer("exchange_id")
+    /// ];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "exchange_id = 5 and not private").unwrap();
+    /// let mut builder = atree.make_event();
+    /// builder.with_integer("exchange_id", 5).unwrap();
+    /// let event = builder.build().unwrap();
+    /// assert_eq!(
+    ///     Some(FailureReason::MissingAttribute { attribute: "private" }),
+    ///     atree.why_not(&1u64, &event)
+    /// );
+    /// ```
+    pub fn why_not(&self, subscription_id: &T, event: &Event) -> Option<FailureReason<'_>> {
+        if self.never_matching.contains(subscription_id) {
+            return Some(FailureReason::NeverMatches);
+        }
+        let node_id = self.nodes_by_ids.get(subscription_id)?;
+        self.explain_failure(*node_id, event)
+    }
 
-                    for child_id in children {
-                        relations.push(format!("node_{id} -> node_{child_id};"));
-                    }
+    /// Explain which sub-expressions of the subscription ID's expression are satisfied by the
+    /// [`Event`] and whether they are shared with other expressions.
+    ///
+    /// A shared sub-expression is only evaluated once by the searches no matter how many
+    /// expressions contain it. The sub-expressions are listed before the expressions that contain
+    /// them, so the whole expression comes last. `None` is returned if the expression does not
+    /// match or if the subscription ID is not part of the [`ATree`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [
+    ///     AttributeDefinition::boolean("private"),
+    ///     AttributeDefinition::integer("exchange_id")
+    /// ];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "exchange_id = 5 and private").unwrap();
+    /// atree.insert(&2u64, "exchange_id = 5 or not private").unwrap();
+    /// let mut builder = atree.make_event();
+    /// builder.with_integer("exchange_id", 5).unwrap();
+    /// builder.with_boolean("private", true).unwrap();
+    /// let event = builder.build().unwrap();
+    /// let satisfied = atree.why_matches(&1u64, &event).unwrap();
+    /// let shared: Vec<_> = satisfied.iter().filter(|node| node.is_shared).collect();
+    /// assert_eq!(1, shared.len());
+    /// assert_eq!(vec![&2u64], shared[0].shared_with);
+    /// ```
+    pub fn why_matches(
+        &self,
+        subscription_id: &T,
+        event: &Event,
+    ) -> Option<Vec<SatisfiedExpression<'_, T>>> {
+        if self.always_matching.contains(subscription_id) {
+            return Some(vec![]);
+        }
+        let node_id = self.nodes_by_ids.get(subscription_id)?;
+        let mut satisfied = vec![];
+        if !self.collect_satisfied(*node_id, event, &mut satisfied) {
+            return None;
+        }
+        let explanation = satisfied
+            .into_iter()
+            .map(|node_id| {
+                let entry = &self.nodes[node_id];
+                SatisfiedExpression {
+                    expression_id: entry.id,
+                    is_shared: entry.use_count > 1,
+                    shared_with: self.subscribers_of(node_id, subscription_id),
                 }
-                ATreeNode::RNode(RNode {
-                    children, operator, ..
-                }) => {
-                    let node = format!(
-                        r#"node_{id} [label = "{{{id} | level: {} | {operator:#?} | subscriptions: {:?} | r-node}}"];"#,
-                        entry.level(),
-                        entry.subscription_ids
-                    );
-                    levels[entry.level() - 1].push((id, node));
+            })
+            .collect();
+        Some(explanation)
+    }
 
-                    for child_id in children {
-                        relations.push(format!("node_{id} -> node_{child_id};"));
-                    }
+    /// Push the satisfied nodes (children first) and return whether the node itself is satisfied
+    fn collect_satisfied(
+        &self,
+        node_id: NodeId,
+        event: &Event,
+        satisfied: &mut Vec<NodeId>,
+    ) -> bool {
+        let start = satisfied.len();
+        let entry = &self.nodes[node_id];
+        let is_satisfied = match &entry.node {
+            ATreeNode::LNode(_) => entry.evaluate(event) == Some(true),
+            node => match node.operator() {
+                Operator::And => node
+                    .children()
+                    .iter()
+                    .all(|child_id| self.collect_satisfied(*child_id, event, satisfied)),
+                // Every satisfied branch is collected, not only the first one
+                Operator::Or => {
+                    node.children()
+                        .iter()
+                        .filter(|child_id| self.collect_satisfied(**child_id, event, satisfied))
+                        .count()
+                        > 0
                 }
-            }
+            },
+        };
+        if is_satisfied {
+            satisfied.push(node_id);
+        } else {
+            satisfied.truncate(start);
         }
+        is_satisfied
+    }
 
-        builder.push_str("\n// nodes\n");
-        for entries in levels.into_iter().rev() {
-            for (_, node) in entries.iter() {
-                builder.push_str(node);
-                builder.push('\n');
+    /// Return the subscription IDs (other than the excluded one) of the node and its ancestors
+    fn subscribers_of(&self, node_id: NodeId, excluded: &T) -> Vec<&T> {
+        let mut visited = vec![node_id];
+        let mut stack = vec![node_id];
+        let mut subscribers = vec![];
+        while let Some(node_id) = stack.pop() {
+            let entry = &self.nodes[node_id];
+            for subscription_id in &entry.subscription_ids {
+                if subscription_id != excluded && !subscribers.contains(&subscription_id) {
+                    subscribers.push(subscription_id);
+                }
+            }
+            if entry.is_root() {
+                continue;
+            }
+            for parent_id in entry.parents() {
+                if !visited.contains(parent_id) {
+                    visited.push(*parent_id);
+                    stack.push(*parent_id);
+                }
             }
+        }
+        subscribers
+    }
 
-            builder.push_str("{rank = same; ");
-            for (id, _) in entries {
-                builder.push_str(&format!("node_{id}; "));
+    /// Return why the node does not match the event or `None` if it does
+    fn explain_failure(&self, node_id: NodeId, event: &Event) -> Option<FailureReason<'_>> {
+        let entry = &self.nodes[node_id];
+        match &entry.node {
+            ATreeNode::LNode(LNode { predicate, .. }) => {
+                let attribute = self.attributes.name(predicate.attribute());
+                match entry.evaluate(event) {
+                    Some(true) => None,
+                    Some(false) => Some(FailureReason::FailedPredicate {
+                        attribute,
+                        predicate: predicate.to_string(),
+                    }),
+                    None => Some(FailureReason::MissingAttribute { attribute }),
+                }
             }
-            builder.push_str("};\n");
+            node => match node.operator() {
+                Operator::And => node
+                    .children()
+                    .iter()
+                    .find_map(|child_id| self.explain_failure(*child_id, event)),
+                Operator::Or => node
+                    .children()
+                    .iter()
+                    .map(|child_id| self.explain_failure(*child_id, event))
+                    .collect::<Option<Vec<_>>>()
+                    .map(FailureReason::AllBranchesFailed),
+            },
         }
+    }
 
-        builder.push_str("\n// edges\n");
-        for relation in relations {
-            builder.push_str(&relation);
-            builder.push('\n');
+    fn insert_root(&mut self, subscription_id: &T, root: OptimizedNode) {
+        let (expression_id, node_id) = self.find_expression(&root);
+        if let Some(node_id) = &node_id {
+            let is_added = add_subscription_id(
+                subscription_id,
+                *node_id,
+                &mut self.nodes,
+                &mut self.nodes_by_ids,
+            );
+            if is_added {
+                increment_use_count(*node_id, &mut self.nodes);
+            }
+            // The expression might only have been a sub-expression up until now
+            if !self.roots.contains(node_id) {
+                self.roots.push(*node_id);
+                self.root_levels.add(self.nodes[*node_id].level());
+            }
+            add_predicate(*node_id, &self.nodes, &mut self.predicates);
+            return;
         }
 
-        builder.push('}');
-        builder
+        let is_and = matches!(&root, OptimizedNode::And(_, _));
+        let cost = root.cost();
+        let node_id = match root {
+            OptimizedNode::And(left, right) | OptimizedNode::Or(left, right) => {
+                let (children, level) = self.insert_children(is_and, *left, *right);
+                let rnode = ATreeNode::RNode(RNode {
+                    level,
+                    operator: if is_and { Operator::And } else { Operator::Or },
+                    children: children.clone(),
+                });
+                let node_id = insert_node(
+                    &mut self.expression_to_node,
+                    &mut self.nodes,
+                    &expression_id,
+                    rnode,
+                    Some(subscription_id.clone()),
+                    cost,
+                );
+                link_children(
+                    is_and,
+                    &children,
+                    node_id,
+                    &mut self.nodes,
+                    &mut self.predicates,
+                );
+                node_id
+            }
+            OptimizedNode::Value(value) => {
+                self.retain_text(&value);
+                let lnode = ATreeNode::lnode(&value);
+                let node_id = insert_node(
+                    &mut self.expression_to_node,
+                    &mut self.nodes,
+                    &expression_id,
+                    lnode,
+                    Some(subscription_id.clone()),
+                    cost,
+                );
+                self.predicates.push(node_id);
+                node_id
+            }
+            OptimizedNode::Constant(value) => {
+                unreachable!("Constant ({value}) inserted as a regular expression should never happen. This is a bug.")
+            }
+        };
+        self.nodes_by_ids.insert(subscription_id.clone(), node_id);
+        self.roots.push(node_id);
+        self.root_levels.add(self.nodes[node_id].level());
     }
-}
 
-#[inline]
-#[allow(clippy::too_many_arguments)]
-fn decrement_use_count<T: Eq + Hash>(
-    subscription_id: &T,
-    node_id: NodeId,
-    nodes: &mut Slab<Entry<T>>,
-    expression_to_node: &mut HashMap<ExpressionId, NodeId>,
-    roots: &mut Vec<NodeId>,
-    predicates: &mut Vec<NodeId>,
-    nodes_by_ids: &mut HashMap<T, NodeId>,
-    max_level: &mut usize,
-) -> Option<Vec<NodeId>> {
-    let node = &mut nodes[node_id];
-    node.use_count -= 1;
-    let mut children = None;
-    node.subscription_ids.retain(|x| *x != *subscription_id);
-    nodes_by_ids.remove(subscription_id);
-    if node.use_count == 0 {
-        if !node.is_leaf() {
-            children = Some(node.children().to_vec());
+    /// Return the ID of the expression in the [`ATree`] along with its node if it is already
+    /// part of it.
+    ///
+    /// The IDs are hashes so the node of the ID is only shared if it is the same expression;
+    /// otherwise, the next ID is tried until either the same expression or a free ID is found.
+    fn find_expression(&self, node: &OptimizedNode) -> (ExpressionId, Option<NodeId>) {
+        let mut expression_id = node.id();
+        loop {
+            match self.expression_to_node.get(&expression_id) {
+                None => return (expression_id, None),
+                Some(node_id) if self.is_same_expression(*node_id, node) => {
+                    return (expression_id, Some(*node_id));
+                }
+                Some(_) => expression_id = hash::mix(expression_id.wrapping_add(1)),
+            }
         }
-        let expression_id = node.id;
-        roots.retain(|x| *x != node_id);
-        predicates.retain(|x| *x != node_id);
-        *max_level = get_max_level(roots, nodes);
-        expression_to_node.remove(&expression_id);
-        nodes.remove(node_id);
     }
 
-    children
-}
+    /// Check whether the node of the [`ATree`] evaluates the same expression as the node.
+    fn is_same_expression(&self, node_id: NodeId, node: &OptimizedNode) -> bool {
+        let entry = &self.nodes[node_id];
+        let is_and = match (&entry.node, node) {
+            (ATreeNode::LNode(LNode { predicate, .. }), OptimizedNode::Value(value)) => {
+                return predicate == value;
+            }
+            (ATreeNode::LNode(_), _)
+            | (_, OptimizedNode::Value(_) | OptimizedNode::Constant(_)) => {
+                return false;
+            }
+            (_, OptimizedNode::And(_, _)) => true,
+            (_, OptimizedNode::Or(_, _)) => false,
+        };
+        if entry.operator() != if is_and { Operator::And } else { Operator::Or } {
+            return false;
+        }
+        let operands = node.operands(is_and);
+        let children = entry.children();
+        if operands.len() != children.len() {
+            return false;
+        }
+        // The children are sorted by cost rather than in the order of the operands
+        let mut is_matched = vec![false; children.len()];
+        operands.iter().all(|operand| {
+            let operand_id = operand.id();
+            let matched = children
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| !is_matched[*index])
+                .find(|(_, child_id)| {
+                    self.nodes[**child_id].id == operand_id
+                        && self.is_same_expression(**child_id, operand)
+                })
+                .or_else(|| {
+                    children
+                        .iter()
+                        .enumerate()
+                        .filter(|(index, _)| !is_matched[*index])
+                        .find(|(_, child_id)| self.is_same_expression(**child_id, operand))
+                });
+            matched.map(|(index, _)| is_matched[index] = true).is_some()
+        })
+    }
 
-#[inline]
-fn insert_node<T>(
-    expression_to_node: &mut HashMap<ExpressionId, NodeId>,
-    nodes: &mut Slab<Entry<T>>,
-    expression_id: &ExpressionId,
-    node: ATreeNode,
-    subscription_id: Option<T>,
-    cost: u64,
-) -> NodeId {
-    let entry = Entry::new(*expression_id, node, subscription_id, cost);
-    let node_id = nodes.insert(entry);
-    if expression_to_node.insert(*expression_id, node_id).is_some() {
-        unreachable!("{expression_id} is already present; this is a bug");
+    /// Insert the operands of an `AND` (or an `OR`) and return them from the cheapest to the most
+    /// expensive one along with the level of their parent.
+    ///
+    /// A run of the same operator (i.e. `a or b or c or d`) is flattened into a single node so
+    /// that the tree is shallower and the searches have fewer levels to go through.
+    fn insert_children(
+        &mut self,
+        is_and: bool,
+        left: OptimizedNode,
+        right: OptimizedNode,
+    ) -> (Vec<NodeId>, usize) {
+        let mut operands = vec![];
+        flatten_operands(is_and, left, &mut operands);
+        flatten_operands(is_and, right, &mut operands);
+        let mut children = operands
+            .into_iter()
+            .map(|operand| self.insert_node(operand))
+            .collect_vec();
+        children.sort_by_key(|child_id| self.nodes[*child_id].cost);
+        let level = children
+            .iter()
+            .map(|child_id| self.nodes[*child_id].node.level())
+            .max()
+            .unwrap_or_default();
+        (children, 1 + level)
     }
-    node_id
-}
 
-#[inline]
-fn add_parent<T>(entry: &mut Entry<T>, node_id: NodeId) {
-    entry.node.add_parent(node_id);
-}
+    /// Make the events keep the text of the attribute if the predicate needs it
+    #[inline]
+    fn retain_text(&mut self, predicate: &Predicate) {
+        if let Some(attribute) = predicate.text_attribute() {
+            self.attributes.retain_text(attribute);
+        }
+    }
 
-#[inline]
-fn add_subscription_id<T: Eq + Hash + Clone>(
-    subscription_id: &T,
-    node_id: NodeId,
-    nodes: &mut Slab<Entry<T>>,
-    nodes_by_ids: &mut HashMap<T, NodeId>,
-) {
-    nodes[node_id]
-        .subscription_ids
-        .push(subscription_id.clone());
-    nodes_by_ids.insert(subscription_id.clone(), node_id);
-}
+    fn insert_node(&mut self, node: OptimizedNode) -> NodeId {
+        let (expression_id, node_id) = self.find_expression(&node);
+        if let Some(node_id) = &node_id {
+            change_rnode_to_inode(*node_id, &mut self.nodes);
+            increment_use_count(*node_id, &mut self.nodes);
+            return *node_id;
+        }
+
+        let is_and = matches!(node, OptimizedNode::And(_, _));
+        let cost = node.cost();
+        match node {
+            OptimizedNode::And(left, right) | OptimizedNode::Or(left, right) => {
+                let (children, level) = self.insert_children(is_and, *left, *right);
+                let inode = INode {
+                    parents: vec![],
+                    level,
+                    operator: if is_and { Operator::And } else { Operator::Or },
+                    children: children.clone(),
+                };
+                let inode = ATreeNode::INode(inode);
+                let node_id = insert_node(
+                    &mut self.expression_to_node,
+                    &mut self.nodes,
+                    &expression_id,
+                    inode,
+                    None,
+                    cost,
+                );
+                link_children(
+                    is_and,
+                    &children,
+                    node_id,
+                    &mut self.nodes,
+                    &mut self.predicates,
+                );
+                node_id
+            }
+            OptimizedNode::Value(node) => {
+                self.retain_text(&node);
+                let lnode = ATreeNode::lnode(&node);
+                insert_node(
+                    &mut self.expression_to_node,
+                    &mut self.nodes,
+                    &expression_id,
+                    lnode,
+                    None,
+                    cost,
+                )
+            }
+            OptimizedNode::Constant(value) => {
+                unreachable!("Constant ({value}) inside of an expression should never happen. This is a bug.")
+            }
+        }
+    }
+
+    /// Iterate over the distinct expressions that were inserted in the [`ATree`] along with the
+    /// subscription IDs that are attached to them.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [AttributeDefinition::boolean("private")];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "private").unwrap();
+    /// atree.insert(&2u64, "private").unwrap();
+    /// let roots: Vec<_> = atree.iter_roots().collect();
+    /// assert_eq!(1, roots.len());
+    /// assert_eq!(&[1, 2], roots[0].1);
+    /// ```
+    pub fn iter_roots(&self) -> impl Iterator<Item = (ExpressionId, &[T])> + '_ {
+        self.roots
+            .iter()
+            .map(|node_id| &self.nodes[*node_id])
+            .filter(|entry| !entry.subscription_ids.is_empty())
+            .map(|entry| (entry.id, entry.subscription_ids.as_slice()))
+            .chain(
+                [
+                    (true, self.always_matching.as_slice()),
+                    (false, self.never_matching.as_slice()),
+                ]
+                .into_iter()
+                .filter(|(_, subscription_ids)| !subscription_ids.is_empty())
+                .map(|(value, subscription_ids)| {
+                    (OptimizedNode::Constant(value).id(), subscription_ids)
+                }),
+            )
+    }
+
+    /// Create a new [`EventBuilder`] to be able to generate an [`Event`] that will be usable for
+    /// finding the matching arbitrary boolean expressions inside the [`ATree`] via the
+    /// [`ATree::search()`] function.
+    #[inline]
+    pub fn make_event(&self) -> EventBuilder<'_> {
+        EventBuilder::new(&self.attributes, &self.strings)
+    }
+
+    /// Look up the attribute (or one of its aliases) once so that the events can set it without
+    /// looking up its name (i.e. with [`EventBuilder::with_integer_h()`]).
+    ///
+    /// Return `None` if the attribute is not defined. The handle can only be used by the events
+    /// of this [`ATree`] (or of its clones).
+    pub fn attribute_handle(&self, name: &str) -> Option<AttributeHandle> {
+        self.attributes.handle(name)
+    }
+
+    /// Set the amount of matches that a search is expected to return.
+    ///
+    /// This is used as a capacity hint for the [`Report`] returned by the searches so that it is
+    /// allocated only once in workloads where the events match a lot of expressions (or not
+    /// over-allocated in the ones where they match only a few). It defaults to 50.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [AttributeDefinition::boolean("private")];
+    /// let mut atree = ATree::<u64>::new(&definitions).unwrap();
+    /// atree.set_expected_matches(500);
+    /// ```
+    pub fn set_expected_matches(&mut self, expected_matches: usize) {
+        self.expected_matches = expected_matches;
+    }
+
+    /// Collect the matches of the searches for workloads where most of the expressions match.
+    ///
+    /// By default, the subscription IDs are added to the [`Report`] as soon as their expression
+    /// matches, which suits the events that only match a few expressions. When enabled, the search
+    /// only marks the matching expressions and the [`Report`] is built once at the end with the
+    /// exact capacity that it needs, regardless of [`ATree::set_expected_matches()`]. The matches
+    /// are the same either way but their order is unspecified.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [AttributeDefinition::integer("exchange_id")];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// for id in 0..100u64 {
+    ///     atree.insert(&id, &format!("exchange_id <> {id}")).unwrap();
+    /// }
+    /// atree.set_dense_matches(true);
+    /// let mut builder = atree.make_event();
+    /// builder.with_integer("exchange_id", 1).unwrap();
+    /// let event = builder.build().unwrap();
+    ///
+    /// assert_eq!(99, atree.search(&event).unwrap().len());
+    /// ```
+    pub fn set_dense_matches(&mut self, dense_matches: bool) {
+        self.dense_matches = dense_matches;
+    }
+
+    /// Search the [`ATree`] for arbitrary boolean expressions that match the [`Event`].
+    ///
+    /// Each subscription ID is part of the matches at most once.
+    pub fn search(&self, event: &Event) -> Result<Report<'_, T>, ATreeError<'_>> {
+        let (matches, _) = search(
+            &self.nodes,
+            &self.predicates,
+            &self.always_matching,
+            self.root_levels.max_level(),
+            self.expected_matches,
+            self.dense_matches,
+            event,
+        );
+        Ok(matches)
+    }
+
+    /// Search the [`ATree`] like [`ATree::search()`] but put the matches in the `report` instead
+    /// of allocating a new one.
+    ///
+    /// The `report` is [cleared](Report::clear()) first while keeping its capacity so that a hot
+    /// loop can reuse the same [`Report`] for every search. The matches are collected directly in
+    /// it so [`ATree::set_expected_matches()`] and [`ATree::set_dense_matches()`] do not apply.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition, Report};
+    ///
+    /// let definitions = [AttributeDefinition::integer("exchange_id")];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "exchange_id = 1").unwrap();
+    /// atree.insert(&2u64, "exchange_id = 2").unwrap();
+    ///
+    /// let mut report = Report::new();
+    /// for exchange_id in [1, 2] {
+    ///     let mut builder = atree.make_event();
+    ///     builder.with_integer("exchange_id", exchange_id).unwrap();
+    ///     let event = builder.build().unwrap();
+    ///     atree.search_reuse(&event, &mut report);
+    ///     assert_eq!(vec![&(exchange_id as u64)], report.matches());
+    /// }
+    /// ```
+    pub fn search_reuse<'a>(&'a self, event: &Event, report: &mut Report<'a, T>) {
+        report.clear();
+        search_unbounded(
+            &self.nodes,
+            &self.predicates,
+            &self.always_matching,
+            self.root_levels.max_level(),
+            event,
+            report,
+        );
+    }
+
+    /// Search the [`ATree`] like [`ATree::search()`] with a copy of the `base` event whose
+    /// attributes are overridden by `vary`.
+    ///
+    /// This is meant to search many variations of the same event (i.e. one per candidate) without
+    /// building the whole event each time: only the overridden attributes are set again.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [
+    ///     AttributeDefinition::integer("exchange_id"),
+    ///     AttributeDefinition::string("deal_id"),
+    /// ];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "exchange_id = 1 and deal_id = 'deal-1'").unwrap();
+    /// let mut builder = atree.make_event();
+    /// builder.with_integer("exchange_id", 1).unwrap();
+    /// let base = builder.build().unwrap();
+    ///
+    /// for (deal_id, expected) in [("deal-1", vec![&1u64]), ("deal-2", vec![])] {
+    ///     let report = atree
+    ///         .search_varying(&base, |builder| builder.with_string("deal_id", deal_id))
+    ///         .unwrap();
+    ///     assert_eq!(expected, report.matches());
+    /// }
+    /// ```
+    pub fn search_varying<F>(&self, base: &Event, vary: F) -> Result<Report<'_, T>, ATreeError<'_>>
+    where
+        F: FnOnce(&mut EventBuilder<'_>) -> Result<(), EventError>,
+    {
+        let mut builder = EventBuilder::from_event(&self.attributes, &self.strings, base);
+        vary(&mut builder).map_err(ATreeError::Event)?;
+        let event = builder.build().map_err(ATreeError::Event)?;
+        self.search(&event)
+    }
+
+    /// Search the [`ATree`] like [`ATree::search()`] but give up once `max_nodes` nodes were
+    /// evaluated.
+    ///
+    /// The predicates and the intermediary nodes that are evaluated all count towards the budget
+    /// while the ones that are skipped (i.e. because of the propagation on demand) do not. No
+    /// partial results are returned when the budget is exceeded.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition, BudgetExceeded};
+    ///
+    /// let definitions = [
+    ///     AttributeDefinition::boolean("private"),
+    ///     AttributeDefinition::integer("exchange_id"),
+    /// ];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "private and exchange_id = 1").unwrap();
+    /// let mut builder = atree.make_event();
+    /// builder.with_boolean("private", true).unwrap();
+    /// builder.with_integer("exchange_id", 1).unwrap();
+    /// let event = builder.build().unwrap();
+    ///
+    /// assert_eq!(Err(BudgetExceeded { max_nodes: 1 }), atree.search_budgeted(&event, 1).map(|_| ()));
+    /// assert_eq!(vec![&1u64], atree.search_budgeted(&event, 10).unwrap().matches());
+    /// ```
+    pub fn search_budgeted(
+        &self,
+        event: &Event,
+        max_nodes: usize,
+    ) -> Result<Report<'_, T>, BudgetExceeded> {
+        if self.dense_matches {
+            let mut count = CountMatches::default();
+            let results = search_within_budget(
+                &self.nodes,
+                &self.predicates,
+                &self.always_matching,
+                self.root_levels.max_level(),
+                event,
+                &mut count,
+                max_nodes,
+            )?;
+            return Ok(Report::from_results(
+                &self.nodes,
+                &self.always_matching,
+                &results,
+                count,
+            ));
+        }
+        let mut matches = Report::with_capacity(self.expected_matches);
+        search_within_budget(
+            &self.nodes,
+            &self.predicates,
+            &self.always_matching,
+            self.root_levels.max_level(),
+            event,
+            &mut matches,
+            max_nodes,
+        )?;
+        Ok(matches)
+    }
+
+    /// Search the [`ATree`] for arbitrary boolean expressions that match the [`Event`] and call
+    /// `f` with each matching subscription ID as soon as it is found.
+    ///
+    /// Unlike [`ATree::search()`], the matches are not collected so nothing is allocated for them.
+    /// The order in which the subscription IDs are given to `f` is unspecified.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [AttributeDefinition::boolean("private")];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "private").unwrap();
+    /// let mut builder = atree.make_event();
+    /// builder.with_boolean("private", true).unwrap();
+    /// let event = builder.build().unwrap();
+    ///
+    /// let mut count = 0;
+    /// atree.search_for_each(&event, |_| count += 1);
+    /// assert_eq!(1, count);
+    /// ```
+    pub fn search_for_each<F: FnMut(&T)>(&self, event: &Event, f: F) {
+        search_unbounded(
+            &self.nodes,
+            &self.predicates,
+            &self.always_matching,
+            self.root_levels.max_level(),
+            event,
+            &mut ForEachMatch(f),
+        );
+    }
+
+    /// Search the [`ATree`] for arbitrary boolean expressions that match the [`Event`] and return
+    /// the matching subscription IDs as a bitset where each bit is at the position of the
+    /// subscription ID in the [`RootsIndex`].
+    ///
+    /// The matching subscription IDs that are not in the index are left out. Since the positions
+    /// do not depend on the [`ATree`], the bitsets of the searches of several trees that share
+    /// the same index can be combined with the set operations of [`FixedBitSet`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition, RootsIndex};
+    ///
+    /// let definitions = [AttributeDefinition::boolean("private")];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "private").unwrap();
+    /// atree.insert(&2u64, "not private").unwrap();
+    /// let index = RootsIndex::new([2u64, 1u64]);
+    /// let mut builder = atree.make_event();
+    /// builder.with_boolean("private", true).unwrap();
+    /// let event = builder.build().unwrap();
+    ///
+    /// let matches = atree.search_bitset(&event, &index);
+    /// assert_eq!(vec![1], matches.ones().collect::<Vec<_>>());
+    /// ```
+    pub fn search_bitset(&self, event: &Event, roots_index: &RootsIndex<T>) -> FixedBitSet {
+        let mut matches = BitSetMatches {
+            index: roots_index,
+            bits: FixedBitSet::with_capacity(roots_index.len()),
+        };
+        search_unbounded(
+            &self.nodes,
+            &self.predicates,
+            &self.always_matching,
+            self.root_levels.max_level(),
+            event,
+            &mut matches,
+        );
+        matches.bits
+    }
+
+    /// Search the [`ATree`] like [`ATree::search_for_each()`] but send a copy of each matching
+    /// subscription ID through the channel as soon as it is found.
+    ///
+    /// This is meant for producer/consumer setups where the matches are handled by other threads:
+    /// the search blocks whenever the channel is full until the receiver catches up. If the
+    /// receiver is disconnected, the search stops right away (without evaluating the rest of the
+    /// [`ATree`]) and the error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    /// use std::sync::mpsc;
+    ///
+    /// let definitions = [AttributeDefinition::boolean("private")];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "private").unwrap();
+    /// let mut builder = atree.make_event();
+    /// builder.with_boolean("private", true).unwrap();
+    /// let event = builder.build().unwrap();
+    ///
+    /// let (tx, rx) = mpsc::sync_channel(1);
+    /// atree.search_to_sender(&event, &tx).unwrap();
+    /// assert_eq!(Ok(1u64), rx.recv());
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn search_to_sender(
+        &self,
+        event: &Event,
+        tx: &std::sync::mpsc::SyncSender<T>,
+    ) -> Result<(), std::sync::mpsc::SendError<T>>
+    where
+        T: Clone,
+    {
+        let mut result = Ok(());
+        self.try_search_for_each(event, |subscription_id| {
+            result = tx.send(subscription_id.clone());
+            if result.is_ok() {
+                ControlFlow::Continue(())
+            } else {
+                ControlFlow::Break(())
+            }
+        });
+        result
+    }
+
+    /// Search the [`ATree`] like [`ATree::search_for_each()`] but stop as soon as `f` breaks.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    fn try_search_for_each<F: FnMut(&T) -> ControlFlow<()>>(
+        &self,
+        event: &Event,
+        f: F,
+    ) -> EvaluationResult {
+        search_unbounded(
+            &self.nodes,
+            &self.predicates,
+            &self.always_matching,
+            self.root_levels.max_level(),
+            event,
+            &mut TryForEachMatch {
+                f,
+                is_stopped: false,
+            },
+        )
+    }
+
+    /// Search the [`ATree`] for arbitrary boolean expressions that match the [`Event`] and return
+    /// a copy of the matching subscription IDs.
+    ///
+    /// Unlike [`ATree::search()`], the result does not borrow the [`ATree`] so it can be kept while
+    /// the tree is modified.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [AttributeDefinition::boolean("private")];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "private").unwrap();
+    /// let mut builder = atree.make_event();
+    /// builder.with_boolean("private", true).unwrap();
+    /// let event = builder.build().unwrap();
+    ///
+    /// let matches = atree.search_owned(&event);
+    /// atree.delete(&1u64);
+    /// assert_eq!(vec![1u64], matches);
+    /// ```
+    pub fn search_owned(&self, event: &Event) -> Vec<T>
+    where
+        T: Clone,
+    {
+        #[cfg(feature = "std")]
+        if let Some(cache) = &self.search_cache {
+            return cache.get_or_search(event, || self.search_owned_uncached(event));
+        }
+        self.search_owned_uncached(event)
+    }
+
+    fn search_owned_uncached(&self, event: &Event) -> Vec<T> {
+        let (report, _) = search(
+            &self.nodes,
+            &self.predicates,
+            &self.always_matching,
+            self.root_levels.max_level(),
+            self.expected_matches,
+            self.dense_matches,
+            event,
+        );
+        report.matches.into_iter().cloned().collect()
+    }
+
+    /// Search the [`ATree`] and return the result of the expression of each `watched`
+    /// subscription ID, even when it is false or undefined.
+    ///
+    /// The watched expressions are evaluated completely while the other ones are only evaluated
+    /// as far as [`ATree::search()`] would. The subscription IDs that are not in the [`ATree`] are
+    /// left out.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [
+    ///     AttributeDefinition::boolean("private"),
+    ///     AttributeDefinition::integer("exchange_id"),
+    /// ];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "private and exchange_id = 1").unwrap();
+    /// let mut builder = atree.make_event();
+    /// builder.with_boolean("private", false).unwrap();
+    /// let event = builder.build().unwrap();
+    ///
+    /// let results = atree.search_watching(&event, &[1, 2]);
+    /// assert_eq!(1, results.len());
+    /// assert_eq!(Some(&Some(false)), results.get(&1u64));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn search_watching<'a>(
+        &'a self,
+        event: &Event,
+        watched: &[T],
+    ) -> std::collections::HashMap<&'a T, Option<bool>> {
+        let (_, mut results) = search(
+            &self.nodes,
+            &self.predicates,
+            &self.always_matching,
+            self.root_levels.max_level(),
+            self.expected_matches,
+            self.dense_matches,
+            event,
+        );
+        // The matches were already collected by the search
+        let mut ignored = ForEachMatch(|_: &T| {});
+        let mut watching = std::collections::HashMap::with_capacity(watched.len());
+        for subscription_id in watched {
+            let constant = |ids: &'a [T], value| {
+                ids.iter()
+                    .find(|id| *id == subscription_id)
+                    .map(|id| (id, Some(value)))
+            };
+            let result = self
+                .nodes_by_ids
+                .get_key_value(subscription_id)
+                .map(|(id, node_id)| {
+                    let result =
+                        lazy_evaluate(*node_id, event, &self.nodes, &mut results, &mut ignored);
+                    (id, result)
+                })
+                .or_else(|| constant(&self.always_matching, true))
+                .or_else(|| constant(&self.never_matching, false));
+            if let Some((id, result)) = result {
+                watching.insert(id, result);
+            }
+        }
+        watching
+    }
+
+    /// Search the [`ATree`] for arbitrary boolean expressions that match the [`Event`] and return
+    /// the matching subscription IDs sorted by descending priority (see
+    /// [`ATree::insert_with_priority()`]).
+    ///
+    /// Subscription IDs with the same priority are sorted by ascending ID.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [AttributeDefinition::boolean("private")];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert_with_priority(&1u64, "private", 1).unwrap();
+    /// atree.insert_with_priority(&2u64, "private", 2).unwrap();
+    /// let mut builder = atree.make_event();
+    /// builder.with_boolean("private", true).unwrap();
+    /// let event = builder.build().unwrap();
+    ///
+    /// assert_eq!(vec![&2u64, &1u64], atree.search_by_priority(&event).unwrap());
+    /// ```
+    pub fn search_by_priority(&self, event: &Event) -> Result<Vec<&T>, ATreeError<'_>>
+    where
+        T: Ord,
+    {
+        let (report, _) = search(
+            &self.nodes,
+            &self.predicates,
+            &self.always_matching,
+            self.root_levels.max_level(),
+            self.expected_matches,
+            self.dense_matches,
+            event,
+        );
+        let mut matches = report.matches;
+        matches.sort_by(|a, b| {
+            let priority = |id: &T| self.priorities.get(id).copied().unwrap_or_default();
+            priority(b).cmp(&priority(a)).then_with(|| a.cmp(b))
+        });
+        Ok(matches)
+    }
+
+    /// Search the [`ATree`] like [`ATree::search()`] and return the matching subscription IDs
+    /// sorted by the key extracted from them.
+    ///
+    /// Unlike [`ATree::search_by_priority()`], the subscription IDs do not have to implement
+    /// [`Ord`]. The sort is stable so the subscription IDs with the same key are kept in the order
+    /// they were matched in.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// #[derive(Clone, PartialEq, Eq, Hash, Debug)]
+    /// struct Campaign {
+    ///     rank: u32,
+    /// }
+    ///
+    /// let definitions = [AttributeDefinition::boolean("private")];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&Campaign { rank: 2 }, "private").unwrap();
+    /// atree.insert(&Campaign { rank: 1 }, "private").unwrap();
+    /// let mut builder = atree.make_event();
+    /// builder.with_boolean("private", true).unwrap();
+    /// let event = builder.build().unwrap();
+    ///
+    /// let matches = atree.search_sorted_by_key(&event, |campaign| campaign.rank).unwrap();
+    /// assert_eq!(vec![&Campaign { rank: 1 }, &Campaign { rank: 2 }], matches);
+    /// ```
+    pub fn search_sorted_by_key<K, F>(
+        &self,
+        event: &Event,
+        mut key: F,
+    ) -> Result<Vec<&T>, ATreeError<'_>>
+    where
+        K: Ord,
+        F: FnMut(&T) -> K,
+    {
+        let (report, _) = search(
+            &self.nodes,
+            &self.predicates,
+            &self.always_matching,
+            self.root_levels.max_level(),
+            self.expected_matches,
+            self.dense_matches,
+            event,
+        );
+        let mut matches = report.matches;
+        matches.sort_by_key(|id| key(id));
+        Ok(matches)
+    }
+
+    /// Search the [`ATree`] for arbitrary boolean expressions that match every one of the
+    /// [`Event`]s (i.e. all the events of a session).
+    ///
+    /// An empty list of events matches nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [AttributeDefinition::integer("exchange_id")];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "exchange_id < 3").unwrap();
+    /// atree.insert(&2u64, "exchange_id < 2").unwrap();
+    /// let events: Vec<_> = [1, 2]
+    ///     .into_iter()
+    ///     .map(|exchange_id| {
+    ///         let mut builder = atree.make_event();
+    ///         builder.with_integer("exchange_id", exchange_id).unwrap();
+    ///         builder.build().unwrap()
+    ///     })
+    ///     .collect();
+    ///
+    /// assert_eq!(vec![&1u64], atree.search_conjunction(&events).matches());
+    /// ```
+    pub fn search_conjunction<'a>(&'a self, events: &[Event]) -> Report<'a, T> {
+        let search = |event| {
+            let (report, _) = search(
+                &self.nodes,
+                &self.predicates,
+                &self.always_matching,
+                self.root_levels.max_level(),
+                self.expected_matches,
+                self.dense_matches,
+                event,
+            );
+            report
+        };
+        let Some((first, others)) = events.split_first() else {
+            return Report::with_capacity(0);
+        };
+
+        let mut matches = search(first);
+        for event in others {
+            if matches.roots.is_empty() {
+                break;
+            }
+
+            // The subscription IDs of an expression are always matched together so comparing the
+            // matched expressions is enough
+            let matched_roots = search(event)
+                .roots
+                .into_iter()
+                .map(|(expression_id, _)| expression_id)
+                .sorted()
+                .collect_vec();
+            matches
+                .retain_roots(|expression_id| matched_roots.binary_search(&expression_id).is_ok());
+        }
+        matches
+    }
+
+    /// Return an immutable copy of the [`ATree`] that can be shared between threads.
+    ///
+    /// This is meant for copy-on-write updates: the readers search the snapshot without any lock
+    /// while a writer modifies the [`ATree`] and then publishes a new snapshot (i.e. by swapping
+    /// the [`Arc`] that the readers load). Taking a snapshot copies the whole [`ATree`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    /// use std::thread;
+    ///
+    /// let definitions = [AttributeDefinition::boolean("private")];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "private").unwrap();
+    /// let snapshot = atree.snapshot();
+    ///
+    /// let reader = thread::spawn(move || {
+    ///     let mut builder = snapshot.make_event();
+    ///     builder.with_boolean("private", true).unwrap();
+    ///     let event = builder.build().unwrap();
+    ///     snapshot.search_owned(&event)
+    /// });
+    /// atree.insert(&2u64, "private").unwrap();
+    ///
+    /// assert_eq!(vec![1u64], reader.join().unwrap());
+    /// ```
+    pub fn snapshot(&self) -> Arc<Self>
+    where
+        S: Clone,
+    {
+        Arc::new(self.clone())
+    }
+
+    /// Cache the matches of the `capacity` most recently searched events for
+    /// [`ATree::search_owned()`].
+    ///
+    /// This is meant for the workloads where the same events are searched many times in a short
+    /// window. The events are identified by a hash of their attributes and the least recently
+    /// searched one is evicted once the cache is full. Any insertion, replacement or deletion
+    /// clears the whole cache since its matches could be stale. The other searches never use the
+    /// cache since they return more than the matches. A `capacity` of 0 disables the cache.
+    ///
+    /// The cache is shared by all the threads searching the same [`ATree`] (i.e. through
+    /// [`ATree::snapshot()`]) and is protected by a lock: the lock is only held to look up and
+    /// store the matches, not during the search itself, but the threads still contend on it so
+    /// the cache can be slower than searching when the events rarely repeat. A snapshot starts
+    /// with a copy of the cache and then maintains its own. Only available with the `std` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [AttributeDefinition::boolean("private")];
+    /// let mut atree = ATree::new(&definitions).unwrap().with_search_cache(100);
+    /// atree.insert(&1u64, "private").unwrap();
+    /// let mut builder = atree.make_event();
+    /// builder.with_boolean("private", true).unwrap();
+    /// let event = builder.build().unwrap();
+    ///
+    /// assert_eq!(vec![1u64], atree.search_owned(&event));
+    /// assert_eq!(vec![1u64], atree.search_owned(&event));
+    /// assert_eq!(1, atree.search_cache_stats().unwrap().hits);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn with_search_cache(mut self, capacity: usize) -> Self {
+        self.search_cache = (capacity > 0).then(|| SearchCache::new(capacity));
+        self
+    }
+
+    /// Return how the search cache performed or `None` if it was not enabled with
+    /// [`ATree::with_search_cache()`].
+    #[cfg(feature = "std")]
+    pub fn search_cache_stats(&self) -> Option<SearchCacheStats> {
+        self.search_cache.as_ref().map(SearchCache::stats)
+    }
+
+    /// Start recording how many times each predicate is evaluated by [`ATree::search_profiled()`].
+    ///
+    /// This is meant to find the predicates that are the most evaluated across a workload. The
+    /// counts are kept per predicate so a predicate that is removed and inserted again resumes
+    /// from its previous count. How often the nodes are false is recorded as well so that
+    /// [`ATree::reoptimize_ordering()`] can use it.
+    pub fn enable_profiling(&mut self) {
+        self.profile.get_or_insert_with(HashMap::default);
+    }
+
+    /// Search the [`ATree`] like [`ATree::search()`] while recording the predicates that were
+    /// evaluated if the profiling was enabled with [`ATree::enable_profiling()`].
+    ///
+    /// Only the predicates that were actually evaluated are counted; the ones that were skipped
+    /// (i.e. because of the propagation on demand) are not.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [
+    ///     AttributeDefinition::boolean("private"),
+    ///     AttributeDefinition::integer("exchange_id"),
+    /// ];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "exchange_id = 1").unwrap();
+    /// atree.enable_profiling();
+    ///
+    /// let mut builder = atree.make_event();
+    /// builder.with_integer("exchange_id", 1).unwrap();
+    /// let event = builder.build().unwrap();
+    /// atree.search_profiled(&event).unwrap();
+    ///
+    /// let profile = atree.predicate_profile();
+    /// assert_eq!(1, profile.len());
+    /// assert_eq!(1, profile[0].1);
+    /// ```
+    pub fn search_profiled(&mut self, event: &Event) -> Result<Report<'_, T>, ATreeError<'_>> {
+        let (matches, results) = search(
+            &self.nodes,
+            &self.predicates,
+            &self.always_matching,
+            self.root_levels.max_level(),
+            self.expected_matches,
+            self.dense_matches,
+            event,
+        );
+        if let Some(profile) = &mut self.profile {
+            // The predicates that are not an access child are not part of `self.predicates` so
+            // all the nodes have to be looked at
+            for (node_id, entry) in &self.nodes {
+                if results.is_evaluated(node_id) {
+                    let counts = profile.entry(entry.id).or_default();
+                    counts.evaluations += 1;
+                    if results.get_result(node_id) == Some(false) {
+                        counts.failures += 1;
+                    }
+                }
+            }
+        }
+        Ok(matches)
+    }
+
+    /// Return the predicates of the [`ATree`] along with the amount of times they were evaluated
+    /// by [`ATree::search_profiled()`].
+    ///
+    /// The predicates are sorted from the most evaluated to the least evaluated one. The counts
+    /// are all zero if the profiling was not enabled.
+    pub fn predicate_profile(&self) -> Vec<(String, u64)> {
+        self.nodes
+            .iter()
+            .filter_map(|(_, entry)| {
+                let ATreeNode::LNode(LNode { predicate, .. }) = &entry.node else {
+                    return None;
+                };
+                let count = self
+                    .profile
+                    .as_ref()
+                    .and_then(|profile| profile.get(&entry.id))
+                    .map_or(0, |counts| counts.evaluations);
+                Some((predicate.to_string(), count))
+            })
+            .sorted_by(|(_, a), (_, b)| b.cmp(a))
+            .collect()
+    }
+
+    /// Choose the access child of each `AND` node by how often its children were false in the
+    /// searches recorded by [`ATree::search_profiled()`] instead of by their static cost.
+    ///
+    /// The access child is the child that is evaluated eagerly; the other children are only
+    /// evaluated when it is true. Promoting the child that is the most often false thus lets
+    /// the propagation on demand skip more nodes on the recorded workload. The child only changes
+    /// when another one was observed to be false more often, so this does nothing if the
+    /// profiling was not enabled with [`ATree::enable_profiling()`]. The results of the searches
+    /// are not affected.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [
+    ///     AttributeDefinition::boolean("private"),
+    ///     AttributeDefinition::integer_list("segment_ids"),
+    /// ];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "private and segment_ids one of [1, 2, 3]").unwrap();
+    /// atree.enable_profiling();
+    /// let mut builder = atree.make_event();
+    /// builder.with_boolean("private", true).unwrap();
+    /// builder.with_integer_list("segment_ids", &[4]).unwrap();
+    /// let event = builder.build().unwrap();
+    /// atree.search_profiled(&event).unwrap();
+    ///
+    /// atree.reoptimize_ordering();
+    /// assert!(atree.search(&event).unwrap().is_empty());
+    /// ```
+    pub fn reoptimize_ordering(&mut self) {
+        let Some(profile) = &self.profile else {
+            return;
+        };
+        let failure_rate = |node_id: &NodeId| {
+            profile
+                .get(&self.nodes[*node_id].id)
+                .map_or(0.0, NodeProfile::failure_rate)
+        };
+        let changes = self
+            .nodes
+            .iter()
+            .filter(|(_, entry)| !entry.is_leaf() && matches!(entry.operator(), Operator::And))
+            .filter_map(|(parent_id, entry)| {
+                let children = entry.children();
+                let current = *children
+                    .iter()
+                    .find(|child_id| self.nodes[**child_id].parents().contains(&parent_id))?;
+                // The first of the most often false children so that a tie keeps the children
+                // that are the cheapest to evaluate first
+                let (best, rate) = children
+                    .iter()
+                    .map(|child_id| (*child_id, failure_rate(child_id)))
+                    .fold(
+                        None,
+                        |best: Option<(NodeId, f64)>, (child_id, rate)| match best {
+                            Some((_, best_rate)) if best_rate >= rate => best,
+                            _ => Some((child_id, rate)),
+                        },
+                    )?;
+                (rate > failure_rate(&current)).then_some((parent_id, current, best))
+            })
+            .collect_vec();
+        for (parent_id, current, best) in changes {
+            self.nodes[current].node.remove_parent(parent_id);
+            add_parent(&mut self.nodes[best], parent_id);
+            add_predicate(best, &self.nodes, &mut self.predicates);
+        }
+        self.debug_validate_invariants();
+    }
+
+    /// Return the distinct predicates of the [`ATree`] along with the amount of expressions
+    /// (and sub-expressions) that refer to them.
+    ///
+    /// The predicates are shared between the expressions so each of them is listed once. They
+    /// are sorted from the most referenced to the least referenced one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [
+    ///     AttributeDefinition::boolean("private"),
+    ///     AttributeDefinition::integer("exchange_id"),
+    /// ];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "exchange_id = 1 and private").unwrap();
+    /// atree.insert(&2u64, "exchange_id = 1 or not private").unwrap();
+    ///
+    /// let predicates = atree.predicates();
+    /// assert_eq!(3, predicates.len());
+    /// assert_eq!(2, predicates[0].1);
+    /// ```
+    pub fn predicates(&self) -> Vec<(String, usize)> {
+        self.nodes
+            .iter()
+            .filter_map(|(_, entry)| {
+                let ATreeNode::LNode(LNode { predicate, .. }) = &entry.node else {
+                    return None;
+                };
+                Some((predicate.to_string(), entry.use_count))
+            })
+            .sorted_by(|(a_text, a), (b_text, b)| b.cmp(a).then_with(|| a_text.cmp(b_text)))
+            .collect()
+    }
+
+    #[inline]
+    /// Delete the specified expression
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition, DeleteOutcome};
+    ///
+    /// let definitions = [AttributeDefinition::boolean("private")];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "private").unwrap();
+    /// atree.insert(&2u64, "private").unwrap();
+    /// assert_eq!(DeleteOutcome::Detached, atree.delete(&1));
+    /// assert_eq!(DeleteOutcome::Removed { freed_nodes: 1 }, atree.delete(&2));
+    /// assert_eq!(DeleteOutcome::NotFound, atree.delete(&2));
+    /// ```
+    pub fn delete(&mut self, subscription_id: &T) -> DeleteOutcome {
+        self.clear_search_cache();
+        if let Some(expression_texts) = &mut self.expression_texts {
+            expression_texts.remove(subscription_id);
+        }
+        self.priorities.remove(subscription_id);
+        if self.remove_constant(subscription_id) {
+            return DeleteOutcome::Removed { freed_nodes: 0 };
+        }
+        let Some(node_id) = self.nodes_by_ids.get(subscription_id) else {
+            return DeleteOutcome::NotFound;
+        };
+        let freed_nodes = self.delete_node(subscription_id, *node_id);
+        self.debug_validate_invariants();
+        match freed_nodes {
+            0 => DeleteOutcome::Detached,
+            freed_nodes => DeleteOutcome::Removed { freed_nodes },
+        }
+    }
+
+    /// Delete the node and return the amount of nodes that were freed
+    #[inline]
+    fn delete_node(&mut self, subscription_id: &T, node_id: NodeId) -> usize {
+        let children = decrement_use_count(
+            subscription_id,
+            node_id,
+            &mut self.nodes,
+            &mut self.expression_to_node,
+            &mut self.roots,
+            &mut self.predicates,
+            &mut self.nodes_by_ids,
+            &mut self.root_levels,
+        );
+
+        let mut freed_nodes = usize::from(!self.nodes.contains(node_id));
+        if let Some(children) = children {
+            for child in children {
+                freed_nodes += self.delete_node(subscription_id, child);
+            }
+        }
+        freed_nodes
+    }
+
+    /// Check that the internal structure of the [`ATree`] is consistent or describe the first
+    /// inconsistency that was found.
+    ///
+    /// The whole tree is walked so this is meant to track down bugs (i.e. after a sequence of
+    /// insertions and deletions) rather than to be called while searching. The tests check it
+    /// after every modification of the [`ATree`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [
+    ///     AttributeDefinition::boolean("private"),
+    ///     AttributeDefinition::integer("exchange_id")
+    /// ];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "private and exchange_id = 1").unwrap();
+    /// atree.insert(&2u64, "private or exchange_id = 1").unwrap();
+    /// atree.delete(&1u64);
+    /// assert_eq!(Ok(()), atree.validate_invariants());
+    /// ```
+    pub fn validate_invariants(&self) -> Result<(), String> {
+        for (name, node_ids) in [("root", &self.roots), ("predicate", &self.predicates)] {
+            if let Some(node_id) = node_ids.iter().find(|id| !self.nodes.contains(**id)) {
+                return Err(format!("the {name} {node_id} is not a live node"));
+            }
+            let mut is_listed = vec![false; self.nodes.capacity()];
+            for node_id in node_ids {
+                if core::mem::replace(&mut is_listed[*node_id], true) {
+                    return Err(format!("the {name} {node_id} is listed more than once"));
+                }
+            }
+        }
+        if let Some(node_id) = self
+            .predicates
+            .iter()
+            .find(|id| !self.nodes[**id].is_leaf())
+        {
+            return Err(format!("the predicate {node_id} is not a leaf"));
+        }
+
+        // The amount of parents that refer to each node as one of their children and the amount of
+        // children that refer to each node as one of their parents, indexed by node ID
+        let mut references = vec![0; self.nodes.capacity()];
+        let mut linked_children = vec![0; self.nodes.capacity()];
+        for (node_id, entry) in &self.nodes {
+            match self.expression_to_node.get(&entry.id) {
+                Some(mapped_id) if *mapped_id == node_id => {}
+                Some(mapped_id) => {
+                    return Err(format!(
+                        "the expression {} of the node {node_id} is mapped to the node {mapped_id}",
+                        entry.id
+                    ));
+                }
+                None => {
+                    return Err(format!(
+                        "the expression {} of the node {node_id} is not mapped to any node",
+                        entry.id
+                    ));
+                }
+            }
+
+            if !entry.is_root() {
+                for parent_id in entry.parents() {
+                    let is_linked = self.nodes.get(*parent_id).is_some_and(|parent| {
+                        !parent.is_leaf() && parent.children().contains(&node_id)
+                    });
+                    if !is_linked {
+                        return Err(format!(
+                            "the node {node_id} has the parent {parent_id} which does not have it as a child"
+                        ));
+                    }
+                    linked_children[*parent_id] += 1;
+                }
+            }
+
+            if entry.is_leaf() {
+                continue;
+            }
+            for child_id in entry.children() {
+                match self.nodes.get(*child_id) {
+                    None => {
+                        return Err(format!(
+                            "the node {node_id} has the child {child_id} which is not a live node"
+                        ));
+                    }
+                    Some(child) if child.is_root() => {
+                        return Err(format!(
+                            "the node {node_id} has the child {child_id} which is an r-node"
+                        ));
+                    }
+                    Some(_) => references[*child_id] += 1,
+                }
+            }
+        }
+
+        for (node_id, entry) in &self.nodes {
+            if !entry.is_leaf() {
+                // Only the access child of an `and` knows its parent since the other child is only
+                // evaluated on demand
+                let expected = match entry.operator() {
+                    Operator::And => 1,
+                    Operator::Or => entry.children().len(),
+                };
+                let linked = linked_children[node_id];
+                if linked != expected {
+                    return Err(format!(
+                        "the node {node_id} is the parent of {linked} of its children instead of {expected}"
+                    ));
+                }
+            }
+            let expected = entry.subscription_ids.len() + references[node_id];
+            if entry.use_count != expected {
+                return Err(format!(
+                    "the node {node_id} has a use count of {} instead of {expected}",
+                    entry.use_count
+                ));
+            }
+        }
+        if self.expression_to_node.len() != self.nodes.len() {
+            return Err(format!(
+                "{} expressions are mapped for {} nodes",
+                self.expression_to_node.len(),
+                self.nodes.len()
+            ));
+        }
+        for (subscription_id, node_id) in &self.nodes_by_ids {
+            let is_attached = self
+                .nodes
+                .get(*node_id)
+                .is_some_and(|entry| entry.subscription_ids.contains(subscription_id));
+            if !is_attached {
+                return Err(format!(
+                    "the subscription ID {subscription_id:?} is not attached to its node {node_id}"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the invariants of the [`ATree`] after each of its modifications while testing
+    #[inline]
+    fn debug_validate_invariants(&self) {
+        #[cfg(test)]
+        debug_assert_eq!(Ok(()), self.validate_invariants());
+    }
+
+    /// Export the [`ATree`] to the Graphviz format.
+    ///
+    /// The nodes are named by their [`ExpressionId`] and sorted by level and then by ID so that
+    /// equivalent trees give the same output regardless of the order in which their expressions
+    /// were inserted or deleted.
+    pub fn to_graphviz(&self) -> String {
+        self.render_graphviz(None)
+    }
+
+    /// Export the [`ATree`] to the Graphviz format with the nodes colored by the result of their
+    /// evaluation against the [`Event`]: green when they matched, red when they failed and gray
+    /// when they were not evaluated (or their result is undefined).
+    ///
+    /// The [`Event`] is searched the same way as [`ATree::search()`] would.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [AttributeDefinition::boolean("private")];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "private").unwrap();
+    /// let mut builder = atree.make_event();
+    /// builder.with_boolean("private", true).unwrap();
+    /// let event = builder.build().unwrap();
+    ///
+    /// assert!(atree.to_graphviz_trace(&event).contains(r#"fillcolor = "green""#));
+    /// ```
+    pub fn to_graphviz_trace(&self, event: &Event) -> String {
+        let (_, results) = search(
+            &self.nodes,
+            &self.predicates,
+            &self.always_matching,
+            self.root_levels.max_level(),
+            self.expected_matches,
+            self.dense_matches,
+            event,
+        );
+        self.render_graphviz(Some(&results))
+    }
+
+    /// Export the [`ATree`] to JSON with the same content as [`ATree::to_graphviz()`].
+    ///
+    /// The nodes have an `id`, a `type` (`l-node`, `i-node` or `r-node`), a `level`, the
+    /// `subscription_ids` of the expressions they are the root of and either the `operator`
+    /// (`and` or `or`) or the `predicate`. The `edges` go `from` a node `to` its children and
+    /// its parents.
+    ///
+    /// The export fails if one of the subscription IDs cannot be serialized.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [AttributeDefinition::string("country")];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "country = 'CA'").unwrap();
+    ///
+    /// assert!(atree.to_json().unwrap().contains(r#""predicate":"⟨country, =, \"CA\"⟩""#));
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> Result<String, serde_json::Error>
+    where
+        T: serde::Serialize,
+    {
+        let mut edges = Vec::new();
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|(id, entry)| {
+                let (kind, operator, predicate) = match &entry.node {
+                    ATreeNode::LNode(LNode { predicate, .. }) => (
+                        "l-node",
+                        None,
+                        Some(
+                            predicate
+                                .resolve(&self.attributes, &self.strings)
+                                .to_string(),
+                        ),
+                    ),
+                    ATreeNode::INode(_) => ("i-node", Some(entry.operator()), None),
+                    ATreeNode::RNode(_) => ("r-node", Some(entry.operator()), None),
+                };
+                if !entry.is_leaf() {
+                    edges.extend(
+                        entry
+                            .children()
+                            .iter()
+                            .map(|to| JsonEdge { from: id, to: *to }),
+                    );
+                }
+                if !entry.is_root() {
+                    edges.extend(
+                        entry
+                            .parents()
+                            .iter()
+                            .map(|to| JsonEdge { from: id, to: *to }),
+                    );
+                }
+                JsonNode {
+                    id,
+                    kind,
+                    level: entry.level(),
+                    operator: operator.map(|operator| match operator {
+                        Operator::And => "and",
+                        Operator::Or => "or",
+                    }),
+                    predicate,
+                    subscription_ids: &entry.subscription_ids,
+                }
+            })
+            .collect();
+        serde_json::to_string(&JsonTree { nodes, edges })
+    }
+
+    fn render_graphviz(&self, results: Option<&EvaluationResult>) -> String {
+        const DEFAULT_CAPACITY: usize = 100_000;
+        let mut builder = String::with_capacity(DEFAULT_CAPACITY);
+        builder.push_str("digraph {\n");
+        builder.push_str("rankdir = TB;\n");
+        builder.push_str(r#"node [shape = "record"];"#);
+        builder.push('\n');
+        let mut relations = Vec::with_capacity(DEFAULT_CAPACITY);
+        let mut levels = vec![vec![]; self.root_levels.max_level()];
+        for (node_id, entry) in &self.nodes {
+            // The nodes are named by their expression ID so that the output does not depend on
+            // where they were stored
+            let id = entry.id;
+            // The l-nodes are already styled so the fill has to be added to their style
+            let (filled, color, style) = match results {
+                Some(results) => {
+                    let color = match results
+                        .is_evaluated(node_id)
+                        .then(|| results.get_result(node_id))
+                    {
+                        Some(Some(true)) => "green",
+                        Some(Some(false)) => "red",
+                        _ => "gray",
+                    };
+                    let color = format!(r#", fillcolor = "{color}""#);
+                    let style = format!(r#", style = "filled"{color}"#);
+                    (",filled", color, style)
+                }
+                None => ("", String::new(), String::new()),
+            };
+            match &entry.node {
+                ATreeNode::LNode(LNode {
+                    parents, predicate, ..
+                }) => {
+                    let node = format!(
+                        r#"node_{id} [label = "{{{id} | level: {} | {predicate} | subscriptions: {:?} | l-node}}", style = "rounded{filled}"{color}];"#,
+                        entry.level(),
+                        entry.subscription_ids
+                    );
+                    levels[entry.level() - 1].push((id, node));
+
+                    for parent_id in parents {
+                        relations.push((id, self.nodes[*parent_id].id));
+                    }
+                }
+                ATreeNode::INode(INode {
+                    children,
+                    parents,
+                    operator,
+                    ..
+                }) => {
+                    let node = format!(
+                        r#"node_{id} [label = "{{{id} | level: {} | {operator:#?} | subscriptions: {:?} | i-node}}"{style}];"#,
+                        entry.level(),
+                        entry.subscription_ids
+                    );
+                    levels[entry.level() - 1].push((id, node));
+
+                    for parent_id in parents {
+                        relations.push((id, self.nodes[*parent_id].id));
+                    }
+
+                    for child_id in children {
+                        relations.push((id, self.nodes[*child_id].id));
+                    }
+                }
+                ATreeNode::RNode(RNode {
+                    children, operator, ..
+                }) => {
+                    let node = format!(
+                        r#"node_{id} [label = "{{{id} | level: {} | {operator:#?} | subscriptions: {:?} | r-node}}"{style}];"#,
+                        entry.level(),
+                        entry.subscription_ids
+                    );
+                    levels[entry.level() - 1].push((id, node));
+
+                    for child_id in children {
+                        relations.push((id, self.nodes[*child_id].id));
+                    }
+                }
+            }
+        }
+
+        builder.push_str("\n// nodes\n");
+        for mut entries in levels.into_iter().rev() {
+            entries.sort_unstable_by_key(|(id, _)| *id);
+            for (_, node) in entries.iter() {
+                builder.push_str(node);
+                builder.push('\n');
+            }
+
+            builder.push_str("{rank = same; ");
+            for (id, _) in entries {
+                builder.push_str(&format!("node_{id}; "));
+            }
+            builder.push_str("};\n");
+        }
+
+        builder.push_str("\n// edges\n");
+        relations.sort_unstable();
+        for (from, to) in relations {
+            builder.push_str(&format!("node_{from} -> node_{to};\n"));
+        }
+
+        builder.push('}');
+        builder
+    }
+}
+
+/// Validate each expression independently against the attribute definitions without building an
+/// [`ATree`].
+///
+/// The results are returned in the order of the expressions along with their index. Unlike
+/// [`ATree::insert()`], all the invalid predicates of an expression are reported together
+/// instead of only the first one; a syntax error still stops the validation of its expression.
+///
+/// # Examples
+///
+/// ```rust
+/// use a_tree::{validate_all, AttributeDefinition};
+///
+/// let definitions = [
+///     AttributeDefinition::boolean("private"),
+///     AttributeDefinition::integer("exchange_id")
+/// ];
+/// let results = validate_all(&["private", "exchange_id = 'CA'"], &definitions);
+/// assert!(results[0].1.is_ok());
+/// assert!(results[1].1.is_err());
+/// ```
+pub fn validate_all<'a>(
+    expressions: &[&'a str],
+    definitions: &[AttributeDefinition],
+) -> Vec<(usize, Result<(), ATreeError<'a>>)> {
+    let mut strings = StringTable::new();
+    let attributes = AttributeTable::new(definitions)
+        .map_err(ParserError::Event)
+        .and_then(|mut attributes| {
+            attributes
+                .intern_enumerations(&mut strings)
+                .map_err(ParserError::Strings)?;
+            Ok(attributes)
+        });
+    let attributes = match attributes {
+        Ok(attributes) => attributes,
+        // None of the expressions can be validated without their attributes
+        Err(error) => {
+            return (0..expressions.len())
+                .map(|index| {
+                    let error = match error.clone() {
+                        ParserError::Event(error) => ATreeError::Event(error),
+                        error => ATreeError::ParseError(ParseError::User { error }),
+                    };
+                    (index, Err(error))
+                })
+                .collect();
+        }
+    };
+    let (lists, limits) = (NamedLists::default(), ParserLimits::default());
+    expressions
+        .iter()
+        .map(|expression| {
+            parser::validate(expression, &attributes, &mut strings, &lists, &limits)
+                .map_err(ATreeError::ParseError)
+        })
+        .enumerate()
+        .collect()
+}
+
+impl Default for ATreeOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Build an [`ATree`] from an iterator of subscription IDs and their expressions
+///
+/// # Examples
+///
+/// ```rust
+/// use a_tree::{ATreeBuilder, ATreeOptions, AttributeDefinition};
+///
+/// let definitions = [
+///     AttributeDefinition::boolean("private"),
+///     AttributeDefinition::integer("exchange_id"),
+/// ];
+/// let expressions = [(1u64, "private"), (2u64, "exchange_id = 1")];
+/// let atree = ATreeBuilder::new(&definitions)
+///     .options(ATreeOptions::new().keep_expression_texts())
+///     .build(expressions)
+///     .unwrap();
+/// assert_eq!(Some("private"), atree.expression_text(&1u64));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ATreeBuilder<'a> {
+    definitions: &'a [AttributeDefinition],
+    options: ATreeOptions,
+}
+
+impl<'a> ATreeBuilder<'a> {
+    /// Create a builder for an [`ATree`] with the specified attributes and the default options.
+    pub fn new(definitions: &'a [AttributeDefinition]) -> Self {
+        Self {
+            definitions,
+            options: ATreeOptions::new(),
+        }
+    }
+
+    /// Create the [`ATree`] with the specified [`ATreeOptions`] (see [`ATree::with_options()`]).
+    pub fn options(mut self, options: ATreeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Create the [`ATree`] and insert the expressions in order like [`ATree::insert()`] would.
+    ///
+    /// The first expression that cannot be inserted stops the build and its error is returned.
+    pub fn build<T, I>(self, expressions: I) -> Result<ATree<T>, ATreeError<'a>>
+    where
+        T: Eq + Hash + Clone + Debug,
+        I: IntoIterator<Item = (T, &'a str)>,
+    {
+        let mut atree = ATree::with_options(self.definitions, self.options)?;
+        for (subscription_id, expression) in expressions {
+            // Unlike `ATree::insert()`, the errors must not borrow the tree that is returned
+            let ast = atree.parse(expression)?;
+            atree.insert_expression(&subscription_id, ast, Some(expression))?;
+        }
+        Ok(atree)
+    }
+}
+
+/// An arbitrary boolean expression that was parsed and optimized by [`ATree::compile()`]
+#[derive(Clone, Debug)]
+pub struct CompiledExpression {
+    tree_id: usize,
+    root: OptimizedNode,
+    // The strings of the expression along with their ID at compilation time
+    strings: Vec<(String, StringId)>,
+    expression: String,
+}
+
+/// The outcome of the [`ATree::delete()`] function
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DeleteOutcome {
+    /// The subscription ID was not part of the [`ATree`] (i.e. it was never inserted or it was
+    /// already deleted) so nothing was changed
+    NotFound,
+    /// The subscription ID was removed but its expression is still referenced by other
+    /// subscription IDs or expressions
+    Detached,
+    /// The expression of the subscription ID was removed from the [`ATree`] along with the nodes
+    /// that were not referenced anymore
+    Removed {
+        /// The amount of nodes that were freed
+        freed_nodes: usize,
+    },
+}
+
+/// Why an expression does not match an [`Event`] as explained by [`ATree::why_not()`]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum FailureReason<'a> {
+    /// The expression is a lone `false`
+    NeverMatches,
+    /// The predicate on the attribute is false
+    FailedPredicate {
+        /// The name of the attribute
+        attribute: &'a str,
+        /// The predicate as displayed by [`ATree::predicate_profile()`]
+        predicate: String,
+    },
+    /// The attribute needed by a predicate is not defined by the [`Event`]
+    MissingAttribute {
+        /// The name of the attribute
+        attribute: &'a str,
+    },
+    /// All the branches of an `or` failed for the listed reasons
+    AllBranchesFailed(Vec<FailureReason<'a>>),
+}
+
+/// A sub-expression satisfied by an [`Event`] as explained by [`ATree::why_matches()`]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SatisfiedExpression<'a, T> {
+    /// The ID of the sub-expression
+    pub expression_id: ExpressionId,
+    /// Whether the sub-expression is used more than once in the [`ATree`], in which case the
+    /// searches evaluate it only once for all its uses
+    pub is_shared: bool,
+    /// The other subscription IDs whose expressions contain the sub-expression
+    pub shared_with: Vec<&'a T>,
+}
+
+/// What the [`ATree`] does when an expression is inserted for a subscription ID that is already
+/// part of it
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum DuplicatePolicy {
+    /// Replace the previous expression of the subscription ID like [`ATree::replace()`] does
+    #[default]
+    Replace,
+    /// Keep the previous expression and return [`ATreeError::DuplicateSubscription`]
+    Reject,
+}
+
+/// Options to customize the behaviour of an [`ATree`]
+#[derive(Clone, Debug)]
+pub struct ATreeOptions {
+    max_strings: Option<usize>,
+    max_depth: usize,
+    max_list_length: usize,
+    keep_expression_texts: bool,
+    duplicate_policy: DuplicatePolicy,
+    max_roots: Option<usize>,
+    #[cfg(feature = "normalization")]
+    normalize_strings: bool,
+    undefined_policy: UndefinedPolicy,
+    reject_unknown_list_elements: bool,
+}
+
+impl ATreeOptions {
+    /// Create the default options
+    pub const fn new() -> Self {
+        Self {
+            max_strings: None,
+            max_depth: ParserLimits::DEFAULT_MAX_DEPTH,
+            max_list_length: ParserLimits::DEFAULT_MAX_LIST_LENGTH,
+            keep_expression_texts: false,
+            duplicate_policy: DuplicatePolicy::Replace,
+            max_roots: None,
+            #[cfg(feature = "normalization")]
+            normalize_strings: false,
+            undefined_policy: UndefinedPolicy::Unknown,
+            reject_unknown_list_elements: false,
+        }
+    }
+
+    /// Limit how deeply nested the inserted expressions can be (256 by default).
+    ///
+    /// Every boolean operator (`and`, `or` and `not`) adds a level of depth, except that a run of
+    /// the same operator (i.e. `a or b or c`) only counts once unless it is split by parentheses;
+    /// expressions that are deeper than the limit are rejected with an error instead of risking a
+    /// stack overflow.
+    pub const fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Limit how many elements the lists of the inserted expressions can contain (100 000 by
+    /// default).
+    ///
+    /// Expressions with a longer list are rejected with an error so that a single pathological
+    /// expression cannot exhaust the memory or dominate the cost of the searches.
+    pub const fn max_list_length(mut self, max_list_length: usize) -> Self {
+        self.max_list_length = max_list_length;
+        self
+    }
+
+    /// Limit the amount of distinct strings that the [`ATree`] can intern.
+    ///
+    /// It only applies to the default [`StringTable`](crate::StringTable) of the [`ATree`]; the
+    /// custom interners (see [`ATree::with_interner()`]) reject it.
+    /// Interned strings are never evicted, so this acts as a safety valve against unbounded memory
+    /// growth rather than as a cache: once the limit is reached, inserting an expression that
+    /// contains a string that was never seen before fails with an error. Events never add strings
+    /// to the [`ATree`] so they are not affected by this limit. The allowed values of the
+    /// enumeration attributes count towards the limit while the strings of a rejected expression
+    /// do not.
+    pub const fn max_strings(mut self, max_strings: usize) -> Self {
+        self.max_strings = Some(max_strings);
+        self
+    }
+
+    /// Limit how many distinct expressions the [`ATree`] can hold.
+    ///
+    /// Inserting (or replacing) an expression that would add a root beyond the limit fails with
+    /// [`ATreeError::CapacityExceeded`](crate::ATreeError::CapacityExceeded) and leaves the
+    /// [`ATree`] untouched. An expression that is already a root can still be inserted for other
+    /// subscription IDs since it is shared with them, and the expressions that are a lone `true`
+    /// or `false` do not count.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, ATreeOptions, AttributeDefinition};
+    ///
+    /// let definitions = [AttributeDefinition::integer("exchange_id")];
+    /// let options = ATreeOptions::new().max_roots(1);
+    /// let mut atree = ATree::<u64>::with_options(&definitions, options).unwrap();
+    /// assert!(atree.insert(&1, "exchange_id = 1").is_ok());
+    /// assert!(atree.insert(&2, "exchange_id = 2").is_err());
+    /// assert!(atree.insert(&3, "exchange_id = 1").is_ok());
+    /// ```
+    pub const fn max_roots(mut self, max_roots: usize) -> Self {
+        self.max_roots = Some(max_roots);
+        self
+    }
+
+    /// Normalize the strings of the expressions and of the events to their NFC form so that the
+    /// strings that only differ by their Unicode normalization form are equal (disabled by
+    /// default).
+    ///
+    /// It only applies to the default [`StringTable`](crate::StringTable) of the [`ATree`] and
+    /// only to the strings that are compared as a whole: the string matching operators (i.e.
+    /// `starts_with`) still see the text of the events as it is.
+    #[cfg(feature = "normalization")]
+    pub const fn normalize_strings(mut self) -> Self {
+        self.normalize_strings = true;
+        self
+    }
+
+    /// Keep the text of the inserted expressions so that they can be retrieved with
+    /// [`ATree::expression_text()`] (disabled by default since it costs a copy of every
+    /// expression).
+    pub const fn keep_expression_texts(mut self) -> Self {
+        self.keep_expression_texts = true;
+        self
+    }
+
+    /// Choose what happens when an expression is inserted for a subscription ID that is already
+    /// part of the [`ATree`] ([`DuplicatePolicy::Replace`] by default).
+    pub const fn on_duplicate(mut self, duplicate_policy: DuplicatePolicy) -> Self {
+        self.duplicate_policy = duplicate_policy;
+        self
+    }
+
+    /// Choose how the predicates treat the attributes that are undefined in the events
+    /// ([`UndefinedPolicy::Unknown`] by default).
+    ///
+    /// The null checks (i.e. `is null`) are not affected since they are meant to test whether the
+    /// attributes are defined.
+    pub const fn on_undefined(mut self, undefined_policy: UndefinedPolicy) -> Self {
+        self.undefined_policy = undefined_policy;
+        self
+    }
+
+    /// Reject the string lists of the events that contain an element that was never seen in the
+    /// inserted expressions (disabled by default).
+    ///
+    /// By default, such elements are collapsed into a single [`StringId::UNKNOWN`] since they
+    /// cannot match any predicate; this option turns them into an
+    /// [`EventError::UnknownListElement`] to catch the events that do not use the same
+    /// vocabulary as the expressions. The attributes used by string matching predicates are
+    /// exempted since these predicates compare the text of every element.
+    pub const fn reject_unknown_list_elements(mut self) -> Self {
+        self.reject_unknown_list_elements = true;
+        self
+    }
+}
+
+#[inline]
+#[allow(clippy::too_many_arguments)]
+fn decrement_use_count<T: Eq + Hash>(
+    subscription_id: &T,
+    node_id: NodeId,
+    nodes: &mut Slab<Entry<T>>,
+    expression_to_node: &mut DeterministicHashMap<ExpressionId, NodeId>,
+    roots: &mut Vec<NodeId>,
+    predicates: &mut Vec<NodeId>,
+    nodes_by_ids: &mut DeterministicHashMap<T, NodeId>,
+    root_levels: &mut RootLevels,
+) -> Option<Vec<NodeId>> {
+    let Some(node) = nodes.get_mut(node_id) else {
+        debug_assert!(
+            false,
+            "decrementing the use count of the freed node {node_id}"
+        );
+        return None;
+    };
+    // Removing a node that is still in use would corrupt the tree so a decrement at zero is
+    // ignored instead of wrapping around
+    let Some(use_count) = node.use_count.checked_sub(1) else {
+        debug_assert!(false, "decrementing the use count of {node_id} below zero");
+        return None;
+    };
+    node.use_count = use_count;
+    let mut children = None;
+    node.subscription_ids.retain(|x| *x != *subscription_id);
+    nodes_by_ids.remove(subscription_id);
+    if node.use_count == 0 {
+        if !node.is_leaf() {
+            children = Some(node.children().to_vec());
+        }
+        let expression_id = node.id;
+        let root_count = roots.len();
+        roots.retain(|x| *x != node_id);
+        if roots.len() != root_count {
+            root_levels.remove(node.level());
+        }
+        predicates.retain(|x| *x != node_id);
+        expression_to_node.remove(&expression_id);
+        nodes.remove(node_id);
+        // The children that are still referenced elsewhere must not keep a link to the removed node
+        for child_id in children.iter().flatten() {
+            nodes[*child_id].node.remove_parent(node_id);
+        }
+    }
+
+    children
+}
+
+#[inline]
+fn insert_node<T>(
+    expression_to_node: &mut DeterministicHashMap<ExpressionId, NodeId>,
+    nodes: &mut Slab<Entry<T>>,
+    expression_id: &ExpressionId,
+    node: ATreeNode,
+    subscription_id: Option<T>,
+    cost: u64,
+) -> NodeId {
+    let entry = Entry::new(*expression_id, node, subscription_id, cost);
+    let node_id = nodes.insert(entry);
+    if expression_to_node.insert(*expression_id, node_id).is_some() {
+        unreachable!("{expression_id} is already present; this is a bug");
+    }
+    node_id
+}
+
+#[inline]
+fn add_parent<T>(entry: &mut Entry<T>, node_id: NodeId) {
+    entry.node.add_parent(node_id);
+}
+
+#[inline]
+/// Attach the subscription ID to the node and return whether it was not attached yet.
+///
+/// A node never lists the same subscription ID twice so that the searches report each
+/// subscription ID at most once.
+fn add_subscription_id<T: Eq + Hash + Clone>(
+    subscription_id: &T,
+    node_id: NodeId,
+    nodes: &mut Slab<Entry<T>>,
+    nodes_by_ids: &mut DeterministicHashMap<T, NodeId>,
+) -> bool {
+    let subscription_ids = &mut nodes[node_id].subscription_ids;
+    if subscription_ids.contains(subscription_id) {
+        return false;
+    }
+    subscription_ids.push(subscription_id.clone());
+    nodes_by_ids.insert(subscription_id.clone(), node_id);
+    true
+}
+
+#[inline]
+fn increment_use_count<T>(node_id: NodeId, nodes: &mut Slab<Entry<T>>) {
+    let use_count = &mut nodes[node_id].use_count;
+    *use_count = use_count.saturating_add(1);
+}
+
+/// How many times a node was evaluated by the profiled searches and how many of them were false
+#[derive(Clone, Copy, Debug, Default)]
+struct NodeProfile {
+    evaluations: u64,
+    failures: u64,
+}
+
+impl NodeProfile {
+    #[inline]
+    fn failure_rate(&self) -> f64 {
+        if self.evaluations == 0 {
+            0.0
+        } else {
+            self.failures as f64 / self.evaluations as f64
+        }
+    }
+}
+
+/// The number of roots at each level so that the maximum level is maintained without scanning all
+/// the roots whenever one of them is inserted or deleted
+#[derive(Clone, Debug, Default)]
+struct RootLevels {
+    // Indexed by level and never ends with a zero so that its last index is the maximum level
+    counts: Vec<usize>,
+}
+
+impl RootLevels {
+    #[inline]
+    fn add(&mut self, level: usize) {
+        if self.counts.len() <= level {
+            self.counts.resize(level + 1, 0);
+        }
+        self.counts[level] += 1;
+    }
+
+    #[inline]
+    fn remove(&mut self, level: usize) {
+        self.counts[level] -= 1;
+        while self.counts.last() == Some(&0) {
+            self.counts.pop();
+        }
+    }
+
+    #[inline]
+    fn max_level(&self) -> usize {
+        self.counts.len().saturating_sub(1).max(1)
+    }
+}
 
 #[inline]
-fn increment_use_count<T>(node_id: NodeId, nodes: &mut Slab<Entry<T>>) {
-    nodes[node_id].use_count += 1;
+fn change_rnode_to_inode<T>(node_id: NodeId, nodes: &mut Slab<Entry<T>>) {
+    let entry = &mut nodes[node_id];
+    if let ATreeNode::RNode(RNode {
+        children,
+        level,
+        operator,
+    }) = &entry.node
+    {
+        let inode = ATreeNode::INode(INode {
+            parents: vec![],
+            children: children.to_vec(),
+            level: *level,
+            operator: operator.clone(),
+        });
+        entry.node = inode;
+    }
+}
+
+#[inline]
+fn choose_access_child<T>(
+    children: &[NodeId],
+    parent_id: NodeId,
+    nodes: &mut Slab<Entry<T>>,
+    predicates: &mut Vec<NodeId>,
+) {
+    // The children are sorted by cost; the last of the cheapest ones is the access child
+    let Some(accessor_id) = children
+        .iter()
+        .rev()
+        .min_by_key(|child_id| nodes[**child_id].cost)
+        .copied()
+    else {
+        return;
+    };
+    add_parent(&mut nodes[accessor_id], parent_id);
+    add_predicate(accessor_id, nodes, predicates);
+}
+
+/// Link the children to their new parent: only the access child of an `AND` knows its parent
+/// while all the children of an `OR` do.
+#[inline]
+fn link_children<T>(
+    is_and: bool,
+    children: &[NodeId],
+    parent_id: NodeId,
+    nodes: &mut Slab<Entry<T>>,
+    predicates: &mut Vec<NodeId>,
+) {
+    if is_and {
+        choose_access_child(children, parent_id, nodes, predicates);
+    } else {
+        for child_id in children {
+            add_parent(&mut nodes[*child_id], parent_id);
+            add_predicate(*child_id, nodes, predicates);
+        }
+    }
+}
+
+/// Collect the operands of a run of the same operator (i.e. `a`, `b` and `c` for `(a or b) or c`)
+fn flatten_operands(is_and: bool, node: OptimizedNode, operands: &mut Vec<OptimizedNode>) {
+    match node {
+        OptimizedNode::And(left, right) if is_and => {
+            flatten_operands(is_and, *left, operands);
+            flatten_operands(is_and, *right, operands);
+        }
+        OptimizedNode::Or(left, right) if !is_and => {
+            flatten_operands(is_and, *left, operands);
+            flatten_operands(is_and, *right, operands);
+        }
+        node => operands.push(node),
+    }
+}
+
+#[inline]
+fn add_predicate<T>(node_id: NodeId, nodes: &Slab<Entry<T>>, predicates: &mut Vec<NodeId>) {
+    let entry = &nodes[node_id];
+    if entry.is_leaf() && !predicates.contains(&node_id) {
+        predicates.push(node_id);
+    }
+}
+
+#[inline]
+fn search<'a, T>(
+    nodes: &'a Slab<Entry<T>>,
+    predicates: &[NodeId],
+    always_matching: &'a [T],
+    max_level: usize,
+    expected_matches: usize,
+    dense_matches: bool,
+    event: &Event,
+) -> (Report<'a, T>, EvaluationResult) {
+    #[cfg(feature = "tracing")]
+    let span = tracing::info_span!(
+        "search",
+        attributes = event.defined_attributes(),
+        matches = tracing::field::Empty
+    )
+    .entered();
+    let (matches, results) = if dense_matches {
+        let mut count = CountMatches::default();
+        let results = search_unbounded(
+            nodes,
+            predicates,
+            always_matching,
+            max_level,
+            event,
+            &mut count,
+        );
+        let matches = Report::from_results(nodes, always_matching, &results, count);
+        (matches, results)
+    } else {
+        let mut matches = Report::with_capacity(expected_matches);
+        let results = search_unbounded(
+            nodes,
+            predicates,
+            always_matching,
+            max_level,
+            event,
+            &mut matches,
+        );
+        (matches, results)
+    };
+    #[cfg(feature = "tracing")]
+    span.record("matches", matches.len());
+    (matches, results)
+}
+
+#[inline]
+fn search_unbounded<'a, T, M: Matches<'a, T>>(
+    nodes: &'a Slab<Entry<T>>,
+    predicates: &[NodeId],
+    always_matching: &'a [T],
+    max_level: usize,
+    event: &Event,
+    matches: &mut M,
+) -> EvaluationResult {
+    // Each node is evaluated at most once so this budget can never be exceeded
+    match search_within_budget(
+        nodes,
+        predicates,
+        always_matching,
+        max_level,
+        event,
+        matches,
+        usize::MAX,
+    ) {
+        Ok(results) => results,
+        Err(_) => unreachable!("an unlimited search cannot exceed its budget"),
+    }
+}
+
+#[inline]
+fn search_within_budget<'a, T, M: Matches<'a, T>>(
+    nodes: &'a Slab<Entry<T>>,
+    predicates: &[NodeId],
+    always_matching: &'a [T],
+    max_level: usize,
+    event: &Event,
+    matches: &mut M,
+    max_nodes: usize,
+) -> Result<EvaluationResult, BudgetExceeded> {
+    let mut budget = Budget::new(max_nodes);
+    // The node IDs are the slab keys which can exceed the amount of nodes once some of them were
+    // deleted, but never its capacity
+    let mut results = EvaluationResult::new(nodes.capacity());
+    if !always_matching.is_empty() {
+        matches.add(OptimizedNode::Constant(true).id(), always_matching);
+        if matches.is_done() {
+            return Ok(results);
+        }
+    }
+
+    // Since the predicates will already be evaluated and their parents will be put into the
+    // queues, then there is no need to keep a queue for them. The expressions that are a single
+    // predicate are thus fully handled by `process_predicates` and, when the tree only contains
+    // such expressions, no queue is allocated at all.
+    let mut queues = vec![Vec::with_capacity(50); max_level - 1];
+    process_predicates(
+        predicates,
+        nodes,
+        event,
+        matches,
+        &mut results,
+        &mut queues,
+        &mut budget,
+    )?;
+
+    for level in 0..queues.len() {
+        if matches.is_done() {
+            break;
+        }
+        // The queues are indexed from the level 2 since the predicates have no queue
+        #[cfg(feature = "tracing")]
+        tracing::debug!(level = level + 2, queue_size = queues[level].len());
+        while let Some((node_id, node)) = queues[level].pop() {
+            if results.is_evaluated(node_id) {
+                continue;
+            }
+
+            budget.spend()?;
+            let result = evaluate_node(node_id, event, node, nodes, &mut results, matches);
+            add_matches(result, node, matches);
+            if matches.is_done() {
+                break;
+            }
+
+            if node.is_root() {
+                continue;
+            }
+
+            for parent_id in node.parents() {
+                let entry = &nodes[*parent_id];
+                let is_evaluated = results.is_evaluated(*parent_id);
+                if !is_evaluated
+                    && matches!(entry.operator(), Operator::And)
+                    && !result.unwrap_or(true)
+                {
+                    results.set_result(*parent_id, Some(false));
+                    continue;
+                }
+
+                if !is_evaluated {
+                    queues[entry.level() - 2].push((*parent_id, entry));
+                }
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+/// The amount of node evaluations that a search can still do
+struct Budget {
+    max_nodes: usize,
+    remaining: usize,
+}
+
+impl Budget {
+    #[inline]
+    const fn new(max_nodes: usize) -> Self {
+        Self {
+            max_nodes,
+            remaining: max_nodes,
+        }
+    }
+
+    #[inline]
+    fn spend(&mut self) -> Result<(), BudgetExceeded> {
+        self.remaining = self.remaining.checked_sub(1).ok_or(BudgetExceeded {
+            max_nodes: self.max_nodes,
+        })?;
+        Ok(())
+    }
+}
+
+#[inline]
+fn process_predicates<'a, T, M: Matches<'a, T>>(
+    predicates: &[NodeId],
+    nodes: &'a Slab<Entry<T>>,
+    event: &Event,
+    matches: &mut M,
+    results: &mut EvaluationResult,
+    queues: &mut [Vec<(NodeId, &'a Entry<T>)>],
+    budget: &mut Budget,
+) -> Result<(), BudgetExceeded> {
+    for predicate_id in predicates {
+        let node = &nodes[*predicate_id];
+        // The evaluation is delayed as much as possible; if the predicate has no
+        // subscribers and no parents, there is no point in evaluating eagerly and
+        // it should only be evaluated if there is a need for it.
+        let delay_evaluation = node.subscription_ids.is_empty() && node.parents().is_empty();
+        if delay_evaluation || results.is_evaluated(*predicate_id) {
+            continue;
+        }
+
+        budget.spend()?;
+        let result = node.evaluate(event);
+        results.set_result(*predicate_id, result);
+        add_matches(result, node, matches);
+        if matches.is_done() {
+            return Ok(());
+        }
+
+        node.parents()
+            .iter()
+            .map(|parent_id| (*parent_id, &nodes[*parent_id]))
+            .for_each(|(parent_id, parent)| {
+                if matches!(parent.operator(), Operator::And) && !result.unwrap_or(true) {
+                    results.set_result(parent_id, Some(false));
+                } else {
+                    queues[parent.level() - 2].push((parent_id, parent));
+                }
+            })
+    }
+    Ok(())
+}
+
+#[inline]
+fn evaluate_node<'a, T, M: Matches<'a, T>>(
+    node_id: NodeId,
+    event: &Event,
+    node: &'a Entry<T>,
+    nodes: &'a Slab<Entry<T>>,
+    results: &mut EvaluationResult,
+    matches: &mut M,
+) -> Option<bool> {
+    let operator = node.operator();
+    let result = match operator {
+        Operator::And => evaluate_and(node.children(), event, nodes, results, matches),
+        Operator::Or => evaluate_or(node.children(), event, nodes, results, matches),
+    };
+    results.set_result(node_id, result);
+    result
+}
+
+#[inline]
+fn evaluate_and<'a, T, M: Matches<'a, T>>(
+    children: &[NodeId],
+    event: &Event,
+    nodes: &'a Slab<Entry<T>>,
+    results: &mut EvaluationResult,
+    matches: &mut M,
+) -> Option<bool> {
+    let mut acc = Some(true);
+    for child_id in children {
+        let result = lazy_evaluate(*child_id, event, nodes, results, matches);
+        match (acc, result) {
+            (Some(false), _) => {
+                acc = Some(false);
+                break;
+            }
+            (_, Some(false)) => {
+                acc = Some(false);
+                break;
+            }
+            (Some(a), Some(b)) => {
+                acc = Some(a && b);
+            }
+            (_, _) => {
+                acc = None;
+            }
+        }
+    }
+    acc
+}
+
+#[inline]
+fn evaluate_or<'a, T, M: Matches<'a, T>>(
+    children: &[NodeId],
+    event: &Event,
+    nodes: &'a Slab<Entry<T>>,
+    results: &mut EvaluationResult,
+    matches: &mut M,
+) -> Option<bool> {
+    let mut acc = Some(false);
+    for child_id in children {
+        let result = lazy_evaluate(*child_id, event, nodes, results, matches);
+        match (acc, result) {
+            (Some(true), _) => {
+                acc = Some(true);
+                break;
+            }
+            (_, Some(true)) => {
+                acc = Some(true);
+                break;
+            }
+            (Some(a), Some(b)) => {
+                acc = Some(a || b);
+            }
+            (_, _) => {
+                acc = None;
+            }
+        }
+    }
+
+    acc
+}
+
+#[inline]
+fn lazy_evaluate<'a, T, M: Matches<'a, T>>(
+    node_id: NodeId,
+    event: &Event,
+    nodes: &'a Slab<Entry<T>>,
+    results: &mut EvaluationResult,
+    matches: &mut M,
+) -> Option<bool> {
+    if results.is_evaluated(node_id) {
+        return results.get_result(node_id);
+    }
+    let node = &nodes[node_id];
+    let result = if node.is_leaf() {
+        let result = node.evaluate(event);
+        results.set_result(node_id, result);
+        result
+    } else {
+        evaluate_node(node_id, event, node, nodes, results, matches)
+    };
+    add_matches(result, node, matches);
+    result
+}
+
+#[inline]
+fn add_matches<'a, T, M: Matches<'a, T>>(
+    result: Option<bool>,
+    node: &'a Entry<T>,
+    matches: &mut M,
+) {
+    if !node.subscription_ids.is_empty() {
+        if let Some(true) = result {
+            matches.add(node.id, &node.subscription_ids);
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Entry<T> {
+    id: ExpressionId,
+    subscription_ids: Vec<T>,
+    node: ATreeNode,
+    use_count: usize,
+    cost: u64,
+}
+
+impl<T> Entry<T> {
+    fn new(id: ExpressionId, node: ATreeNode, subscription_id: Option<T>, cost: u64) -> Self {
+        Self {
+            id,
+            node,
+            use_count: 1,
+            subscription_ids: subscription_id
+                .map_or_else(Vec::new, |subscription_id| vec![subscription_id]),
+            cost,
+        }
+    }
+
+    #[inline]
+    const fn is_leaf(&self) -> bool {
+        matches!(self.node, ATreeNode::LNode(_))
+    }
+
+    #[inline]
+    const fn is_root(&self) -> bool {
+        matches!(self.node, ATreeNode::RNode(_))
+    }
+
+    #[inline]
+    const fn level(&self) -> usize {
+        self.node.level()
+    }
+
+    #[inline]
+    fn evaluate(&self, event: &Event) -> Option<bool> {
+        self.node.evaluate(event)
+    }
+
+    #[inline]
+    fn operator(&self) -> Operator {
+        self.node.operator()
+    }
+
+    #[inline]
+    fn children(&self) -> &[NodeId] {
+        self.node.children()
+    }
+
+    #[inline]
+    fn parents(&self) -> &[NodeId] {
+        self.node.parents()
+    }
+}
+
+#[derive(Clone, Debug)]
+#[allow(clippy::enum_variant_names)]
+enum ATreeNode {
+    LNode(LNode),
+    INode(INode),
+    RNode(RNode),
+}
+
+impl ATreeNode {
+    #[inline]
+    fn lnode(predicate: &Predicate) -> Self {
+        Self::LNode(LNode {
+            level: 1,
+            parents: vec![],
+            predicate: predicate.clone(),
+        })
+    }
+
+    #[inline]
+    const fn level(&self) -> usize {
+        match self {
+            Self::RNode(node) => node.level,
+            Self::LNode(node) => node.level,
+            Self::INode(node) => node.level,
+        }
+    }
+
+    #[inline]
+    fn evaluate(&self, event: &Event) -> Option<bool> {
+        match self {
+            Self::LNode(node) => node.predicate.evaluate(event),
+            node => unreachable!("evaluating {node:?} which is not a predicate; this is a bug."),
+        }
+    }
+
+    #[inline]
+    fn operator(&self) -> Operator {
+        match self {
+            Self::LNode(_) => {
+                unreachable!("trying to get the operator of leaf node; this is a bug");
+            }
+            Self::RNode(RNode { operator, .. }) | Self::INode(INode { operator, .. }) => {
+                operator.clone()
+            }
+        }
+    }
+
+    #[inline]
+    fn children(&self) -> &[NodeId] {
+        match self {
+            Self::INode(INode { children, .. }) | Self::RNode(RNode { children, .. }) => children,
+            Self::LNode(_) => unreachable!("cannot get children for l-node; this is a bug"),
+        }
+    }
+
+    #[inline]
+    fn parents(&self) -> &[NodeId] {
+        match self {
+            Self::INode(INode { parents, .. }) | Self::LNode(LNode { parents, .. }) => parents,
+            Self::RNode(_) => unreachable!("cannot get parents for r-node; this is a bug"),
+        }
+    }
+
+    #[inline]
+    fn add_parent(&mut self, parent_id: NodeId) {
+        match self {
+            ATreeNode::INode(node) => {
+                node.parents.push(parent_id);
+            }
+            ATreeNode::LNode(node) => {
+                node.parents.push(parent_id);
+            }
+            ATreeNode::RNode(node) => {
+                unreachable!("trying to insert parents to r-node {node:?} which cannot have any parents; this is a bug");
+            }
+        }
+    }
+
+    #[inline]
+    fn remove_parent(&mut self, parent_id: NodeId) {
+        match self {
+            ATreeNode::INode(INode { parents, .. }) | ATreeNode::LNode(LNode { parents, .. }) => {
+                parents.retain(|x| *x != parent_id);
+            }
+            ATreeNode::RNode(_) => {}
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct LNode {
+    parents: Vec<NodeId>,
+    level: usize,
+    predicate: Predicate,
+}
+
+#[derive(Clone, Debug)]
+struct INode {
+    parents: Vec<NodeId>,
+    children: Vec<NodeId>,
+    level: usize,
+    operator: Operator,
+}
+
+#[derive(Clone, Debug)]
+struct RNode {
+    children: Vec<NodeId>,
+    level: usize,
+    operator: Operator,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct JsonTree<'a, T> {
+    nodes: Vec<JsonNode<'a, T>>,
+    edges: Vec<JsonEdge>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct JsonNode<'a, T> {
+    id: NodeId,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    level: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    operator: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    predicate: Option<String>,
+    subscription_ids: &'a [T],
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct JsonEdge {
+    from: NodeId,
+    to: NodeId,
+}
+
+/// Where the search puts the subscription IDs of the matching expressions
+trait Matches<'a, T> {
+    fn add(&mut self, expression_id: ExpressionId, subscription_ids: &'a [T]);
+
+    /// Whether the search can stop since no more matches are needed
+    #[inline]
+    fn is_done(&self) -> bool {
+        false
+    }
+}
+
+impl<'a, T> Matches<'a, T> for Report<'a, T> {
+    #[inline]
+    fn add(&mut self, expression_id: ExpressionId, subscription_ids: &'a [T]) {
+        Report::add(self, expression_id, subscription_ids);
+    }
+}
+
+/// Hand the matching subscription IDs to a callback instead of collecting them
+struct ForEachMatch<F>(F);
+
+impl<'a, T, F: FnMut(&T)> Matches<'a, T> for ForEachMatch<F> {
+    #[inline]
+    fn add(&mut self, _: ExpressionId, subscription_ids: &'a [T]) {
+        subscription_ids.iter().for_each(&mut self.0);
+    }
+}
+
+/// Hand the matching subscription IDs to a callback until it breaks
+struct TryForEachMatch<F> {
+    f: F,
+    is_stopped: bool,
+}
+
+impl<'a, T, F: FnMut(&T) -> ControlFlow<()>> Matches<'a, T> for TryForEachMatch<F> {
+    #[inline]
+    fn add(&mut self, _: ExpressionId, subscription_ids: &'a [T]) {
+        if !self.is_stopped {
+            self.is_stopped = subscription_ids.iter().try_for_each(&mut self.f).is_break();
+        }
+    }
+
+    #[inline]
+    fn is_done(&self) -> bool {
+        self.is_stopped
+    }
+}
+
+/// Set the bits of the matching subscription IDs that are in the index
+struct BitSetMatches<'i, T> {
+    index: &'i RootsIndex<T>,
+    bits: FixedBitSet,
+}
+
+impl<'a, T: Eq + Hash> Matches<'a, T> for BitSetMatches<'_, T> {
+    #[inline]
+    fn add(&mut self, _: ExpressionId, subscription_ids: &'a [T]) {
+        for subscription_id in subscription_ids {
+            if let Some(position) = self.index.position(subscription_id) {
+                self.bits.insert(position);
+            }
+        }
+    }
+}
+
+/// Only count the matches since they are collected from the results once the search is done
+#[derive(Default)]
+struct CountMatches {
+    roots: usize,
+    subscription_ids: usize,
+}
+
+impl<'a, T> Matches<'a, T> for CountMatches {
+    #[inline]
+    fn add(&mut self, _: ExpressionId, subscription_ids: &'a [T]) {
+        self.roots += 1;
+        self.subscription_ids += subscription_ids.len();
+    }
+}
+
+#[derive(Debug)]
+/// Structure that holds the search results from the [`ATree::search()`] function
+pub struct Report<'a, T> {
+    matches: Vec<&'a T>,
+    roots: Vec<(ExpressionId, Range<usize>)>,
+}
+
+impl<'a, T> Report<'a, T> {
+    /// Create an empty report to be filled by [`ATree::search_reuse()`].
+    pub const fn new() -> Self {
+        Self {
+            matches: Vec::new(),
+            roots: Vec::new(),
+        }
+    }
+
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            matches: Vec::with_capacity(capacity),
+            roots: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Collect the matches of a search done with [`CountMatches`]: the expressions that matched
+    /// are the ones that were evaluated to true and that have subscription IDs.
+    fn from_results(
+        nodes: &'a Slab<Entry<T>>,
+        always_matching: &'a [T],
+        results: &EvaluationResult,
+        count: CountMatches,
+    ) -> Self {
+        let mut report = Self {
+            matches: Vec::with_capacity(count.subscription_ids),
+            roots: Vec::with_capacity(count.roots),
+        };
+        if !always_matching.is_empty() {
+            report.add(OptimizedNode::Constant(true).id(), always_matching);
+        }
+        for node_id in results.successes() {
+            if let Some(entry) = nodes.get(node_id) {
+                if !entry.subscription_ids.is_empty() {
+                    report.add(entry.id, &entry.subscription_ids);
+                }
+            }
+        }
+        report
+    }
+
+    #[inline]
+    fn add(&mut self, expression_id: ExpressionId, subscription_ids: &'a [T]) {
+        let start = self.matches.len();
+        self.matches.extend(subscription_ids);
+        self.roots.push((expression_id, start..self.matches.len()));
+    }
+
+    fn retain_roots(&mut self, mut keep: impl FnMut(ExpressionId) -> bool) {
+        let mut matches = Vec::with_capacity(self.matches.len());
+        let mut roots = Vec::with_capacity(self.roots.len());
+        for (expression_id, range) in self.roots.drain(..) {
+            if keep(expression_id) {
+                let start = matches.len();
+                matches.extend_from_slice(&self.matches[range]);
+                roots.push((expression_id, start..matches.len()));
+            }
+        }
+        self.matches = matches;
+        self.roots = roots;
+    }
+
+    #[inline]
+    /// Remove all the search matches while keeping the allocated memory
+    pub fn clear(&mut self) {
+        self.matches.clear();
+        self.roots.clear();
+    }
+
+    #[inline]
+    /// Get the search matches
+    pub fn matches(&self) -> &[&'a T] {
+        &self.matches
+    }
+
+    #[inline]
+    /// Get the amount of search matches
+    pub fn len(&self) -> usize {
+        self.matches.len()
+    }
+
+    #[inline]
+    /// Check if the search did not match any subscription ID
+    pub fn is_empty(&self) -> bool {
+        self.matches.is_empty()
+    }
+
+    #[inline]
+    /// Check if the specified subscription ID is part of the search matches
+    pub fn is_match(&self, subscription_id: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        self.matches.contains(&subscription_id)
+    }
+
+    /// Get the search matches grouped by the expression that matched
+    ///
+    /// Subscription IDs that were inserted with the same expression share the same root and are
+    /// thus returned together.
+    pub fn matched_roots(&self) -> impl Iterator<Item = (ExpressionId, &[&'a T])> + '_ {
+        self.roots
+            .iter()
+            .map(|(expression_id, range)| (*expression_id, &self.matches[range.clone()]))
+    }
+
+    /// Get the search matches as a set for constant time lookups
+    ///
+    /// The set is built on each call, so prefer keeping the result around when checking many IDs.
+    #[cfg(feature = "std")]
+    pub fn matches_set(&self) -> std::collections::HashSet<&'a T>
+    where
+        T: Eq + Hash,
+    {
+        self.matches.iter().copied().collect()
+    }
+}
+
+/// The stable bit positions of the subscription IDs in the bitsets returned by
+/// [`ATree::search_bitset()`].
+///
+/// The index is meant to be built once and shared by the searches (and by the trees whose
+/// results are combined).
+#[derive(Clone, Debug)]
+pub struct RootsIndex<T> {
+    positions: HashMap<T, usize>,
+}
+
+impl<T: Eq + Hash> RootsIndex<T> {
+    /// Create an index where the subscription IDs are at the position where they first appear.
+    pub fn new<I: IntoIterator<Item = T>>(subscription_ids: I) -> Self {
+        let mut positions = HashMap::default();
+        for subscription_id in subscription_ids {
+            let position = positions.len();
+            positions.entry(subscription_id).or_insert(position);
+        }
+        Self { positions }
+    }
+
+    #[inline]
+    /// Get the bit position of the subscription ID if it is in the index
+    pub fn position(&self, subscription_id: &T) -> Option<usize> {
+        self.positions.get(subscription_id).copied()
+    }
+
+    #[inline]
+    /// Get the amount of subscription IDs in the index (i.e. the length of the bitsets)
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    #[inline]
+    /// Check if the index does not contain any subscription ID
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+}
+
+impl<T> Default for Report<'_, T> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-#[inline]
-fn get_max_level<T>(roots: &[NodeId], nodes: &Slab<Entry<T>>) -> usize {
-    roots
-        .iter()
-        .map(|root_id| nodes[*root_id].level())
-        .max()
-        .unwrap_or(1)
-}
+impl<'r, 'a, T> IntoIterator for &'r Report<'a, T> {
+    type Item = &'a T;
+    type IntoIter = core::iter::Copied<core::slice::Iter<'r, &'a T>>;
+
+    /// Iterate over the search matches in the same order as [`Report::matches()`]
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.matches.iter().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        error::ParserError,
+        events::{AttributeKind, AttributeValue},
+        expr::{Condition, Expr},
+        strings::StringTableError,
+    };
+    use lalrpop_util::ParseError;
+    use std::collections::HashSet;
+
+    const AN_INVALID_BOOLEAN_EXPRESSION: &str = "invalid in (1, 2, 3 and";
+    const AN_EXPRESSION: &str = "exchange_id = 1";
+    const A_NOT_EXPRESSION: &str = "not private";
+    const AN_EXPRESSION_WITH_AND_OPERATORS: &str =
+        r#"exchange_id = 1 and deals one of ["deal-1", "deal-2"]"#;
+    const AN_EXPRESSION_WITH_OR_OPERATORS: &str =
+        r#"exchange_id = 1 or deals one of ["deal-1", "deal-2"]"#;
+    const A_COMPLEX_EXPRESSION: &str = r#"exchange_id = 1 and not private and deal_ids one of ["deal-1", "deal-2"] and segment_ids one of [1, 2, 3] and country = 'CA' and city in ['QC'] or country = 'US' and city in ['AZ']"#;
+    const ANOTHER_COMPLEX_EXPRESSION: &str = r#"exchange_id = 1 and not private and deal_ids one of ["deal-1", "deal-2"] and segment_ids one of [1, 2, 3] and country in ['FR', 'GB']"#;
+
+    fn is_sync_and_send<T: Send + Sync>() {}
+
+    #[test]
+    fn support_sync_and_send_traits() {
+        is_sync_and_send::<ATree<u64>>();
+    }
+
+    #[test]
+    fn can_build_an_atree() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::string_list("deals"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::float("bidfloor"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::integer_list("segment_ids"),
+        ];
+
+        let result = ATree::<u64>::new(&definitions);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn return_an_error_on_duplicate_definitions() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string_list("deals"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::float("bidfloor"),
+            AttributeDefinition::integer("country"),
+            AttributeDefinition::integer_list("segment_ids"),
+        ];
+
+        let result = ATree::<u64>::new(&definitions);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn return_an_error_when_the_expressions_exceed_the_maximum_amount_of_strings() {
+        let definitions = [
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string_list("deals"),
+        ];
+        let options = ATreeOptions::new().max_strings(3);
+        let mut atree = ATree::with_options(&definitions, options).unwrap();
+        atree.insert(&1u64, "country = 'CA'").unwrap();
+        atree
+            .insert(&2u64, r#"deals one of ["deal-1", "deal-2"]"#)
+            .unwrap();
+        atree
+            .insert(&3u64, "country = 'CA' or deals one of ['deal-1']")
+            .unwrap();
+
+        let result = atree.insert(&4u64, "country = 'US'");
+
+        assert!(matches!(
+            result,
+            Err(ATreeError::ParseError(ParseError::User {
+                error: ParserError::Strings(StringTableError::LimitReached(3))
+            }))
+        ));
+        let mut builder = atree.make_event();
+        builder.with_string("country", "CA").unwrap();
+        let event = builder.build().unwrap();
+        let mut matches = atree.search(&event).unwrap().matches().to_vec();
+        matches.sort();
+        assert_eq!(vec![&1, &3], matches);
+    }
+
+    #[test]
+    fn return_an_error_when_the_expression_exceeds_the_maximum_depth() {
+        let definitions = [AttributeDefinition::boolean("private")];
+        let options = ATreeOptions::new().max_depth(2);
+        let mut atree = ATree::with_options(&definitions, options).unwrap();
+        atree.insert(&1u64, "private and not private").unwrap();
+
+        let result = atree.insert(&2u64, "private and not not private");
+
+        assert!(matches!(
+            result,
+            Err(ATreeError::ParseError(ParseError::User {
+                error: ParserError::MaximumDepthExceeded(2)
+            }))
+        ));
+    }
+
+    #[test]
+    fn accept_a_long_chain_of_the_same_operator_within_the_maximum_depth() {
+        let definitions = [AttributeDefinition::integer("x")];
+        let mut atree = ATree::new(&definitions).unwrap();
+        let chain = (0..1000).map(|i| format!("x = {i}")).join(" or ");
+
+        atree.insert(&1u64, &chain).unwrap();
+
+        let mut builder = atree.make_event();
+        builder.with_integer("x", 999).unwrap();
+        let event = builder.build().unwrap();
+        assert_eq!(vec![&1u64], atree.search(&event).unwrap().matches());
+    }
+
+    #[test]
+    fn return_an_error_when_a_list_exceeds_the_maximum_length() {
+        let definitions = [AttributeDefinition::integer("exchange_id")];
+        let options = ATreeOptions::new().max_list_length(2);
+        let mut atree = ATree::with_options(&definitions, options).unwrap();
+        atree.insert(&1u64, "exchange_id in [1, 2]").unwrap();
+
+        let result = atree.insert(&2u64, "exchange_id in [1, 2, 3]");
+
+        assert!(matches!(
+            result,
+            Err(ATreeError::ParseError(ParseError::User {
+                error: ParserError::MaximumListLengthExceeded(2)
+            }))
+        ));
+    }
+
+    #[test]
+    fn return_an_error_on_invalid_boolean_expression() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string_list("deals"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::integer_list("segment_ids"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+
+        let result = atree.insert(&1u64, AN_INVALID_BOOLEAN_EXPRESSION);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn return_an_error_on_empty_boolean_expression() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string_list("deals"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::integer_list("segment_ids"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+
+        let result = atree.insert(&1u64, "");
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn can_insert_a_simple_expression() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string_list("deals"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::integer_list("segment_ids"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+
+        let result = atree.insert(&1u64, AN_EXPRESSION);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn can_insert_an_expression_that_refers_to_a_rnode() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string_list("deal_ids"),
+        ];
+        let an_expression = "private or exchange_id = 1";
+        let another_expression =
+            r#"private or exchange_id = 1 or deal_ids one of ["deal-1", "deal-2"]"#;
+        let mut atree = ATree::new(&definitions).unwrap();
+        assert!(atree.insert(&1u64, an_expression).is_ok());
+        assert!(atree.insert(&2u64, another_expression).is_ok());
+    }
+
+    #[test]
+    fn can_insert_the_same_expression_multiple_times() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string_list("deals"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::integer_list("segment_ids"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+
+        assert!(atree.insert(&1u64, AN_EXPRESSION).is_ok());
+        assert!(atree.insert(&2u64, AN_EXPRESSION).is_ok());
+    }
+
+    #[test]
+    fn can_insert_a_negative_expression() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string_list("deals"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::integer_list("segment_ids"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+
+        let result = atree.insert(&1u64, A_NOT_EXPRESSION);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn can_insert_an_expression_with_and_operators() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string_list("deals"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::integer_list("segment_ids"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+
+        let result = atree.insert(&1u64, AN_EXPRESSION_WITH_AND_OPERATORS);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn can_insert_an_expression_with_or_operators() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string_list("deals"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::integer_list("segment_ids"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+
+        let result = atree.insert(&1u64, AN_EXPRESSION_WITH_OR_OPERATORS);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn can_insert_an_expression_with_mixed_operators() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string_list("deal_ids"),
+            AttributeDefinition::integer_list("segment_ids"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string("city"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+
+        let result = atree.insert(&1u64, A_COMPLEX_EXPRESSION);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn can_insert_multiple_expressions_with_mixed_operators() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string_list("deal_ids"),
+            AttributeDefinition::integer_list("segment_ids"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string("city"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+
+        assert!(atree.insert(&1u64, A_COMPLEX_EXPRESSION).is_ok());
+        assert!(atree.insert(&2u64, ANOTHER_COMPLEX_EXPRESSION).is_ok());
+    }
+
+    #[test]
+    fn return_an_error_when_inserting_into_a_tree_without_attributes() {
+        let mut atree = ATree::<u64>::new(&[]).unwrap();
+        let missing =
+            || ParserError::Event(EventError::NonExistingAttribute("exchange_id".to_string()));
+
+        let result = atree.insert(&1u64, "exchange_id = 1");
+        assert!(
+            matches!(result, Err(ATreeError::ParseError(ParseError::User { error })) if error == missing())
+        );
+        let result = atree.insert_expr(
+            &2u64,
+            &Expr::predicate("exchange_id", Condition::Equal(1.into())),
+        );
+        assert!(
+            matches!(result, Err(ATreeError::ParseError(ParseError::User { error })) if error == missing())
+        );
+        assert!(atree.nodes.is_empty());
+        assert!(atree.nodes_by_ids.is_empty());
+    }
+
+    #[test]
+    fn only_match_the_constant_expressions_of_a_tree_without_attributes() {
+        let mut atree = ATree::new(&[]).unwrap();
+        atree.insert(&1u64, "true").unwrap();
+        atree.insert(&2u64, "false").unwrap();
+        assert!(atree.insert(&3u64, "private").is_err());
+        let mut builder = atree.make_event();
+        assert!(builder.with_boolean("private", true).is_err());
+        let event = builder.build().unwrap();
+
+        let report = atree.search(&event).unwrap();
+
+        assert_eq!(vec![&1u64], report.matches());
+        assert_eq!(vec![1u64], atree.search_owned(&event));
+        assert_eq!(None, atree.why_not(&1u64, &event));
+        assert_eq!(
+            Some(FailureReason::NeverMatches),
+            atree.why_not(&2u64, &event)
+        );
+    }
+
+    #[test]
+    fn can_search_an_empty_tree() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string_list("deal_ids"),
+            AttributeDefinition::string_list("deals"),
+            AttributeDefinition::integer_list("segment_ids"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string("city"),
+        ];
+        let atree = ATree::<u64>::new(&definitions).unwrap();
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", false).unwrap();
+        let event = builder.build().unwrap();
+
+        let expected: Vec<&u64> = vec![];
+        let actual = atree.search(&event).unwrap().matches().to_vec();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn can_search_a_single_predicate() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string_list("deal_ids"),
+            AttributeDefinition::string_list("deals"),
+            AttributeDefinition::integer_list("segment_ids"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string("city"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "private").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", true).unwrap();
+        let event = builder.build().unwrap();
+
+        let expected = vec![&1u64];
+        let actual = atree.search(&event).unwrap().matches().to_vec();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn ignore_results_that_are_not_matched() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string_list("deal_ids"),
+            AttributeDefinition::string_list("deals"),
+            AttributeDefinition::integer_list("segment_ids"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string("city"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "private").unwrap();
+        atree.insert(&2u64, A_COMPLEX_EXPRESSION).unwrap();
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", false).unwrap();
+        let event = builder.build().unwrap();
+
+        let expected: Vec<&u64> = vec![];
+        let actual = atree.search(&event).unwrap().matches().to_vec();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn can_search_simple_expressions() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string_list("deal_ids"),
+            AttributeDefinition::string_list("deals"),
+            AttributeDefinition::integer_list("segment_ids"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string("city"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "private").unwrap();
+        atree.insert(&2u64, "not private").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", true).unwrap();
+        let event = builder.build().unwrap();
+
+        let expected = vec![&1u64];
+        let mut actual = atree.search(&event).unwrap().matches().to_vec();
+        actual.sort();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn can_search_complex_expressions() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string_list("deal_ids"),
+            AttributeDefinition::string_list("deals"),
+            AttributeDefinition::integer_list("segment_ids"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string("city"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+
+        atree.insert(&1, A_COMPLEX_EXPRESSION).unwrap();
+        atree.insert(&2, AN_EXPRESSION_WITH_AND_OPERATORS).unwrap();
+        atree.insert(&3, AN_EXPRESSION_WITH_OR_OPERATORS).unwrap();
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        builder.with_boolean("private", true).unwrap();
+        builder
+            .with_string_list("deal_ids", &["deal-1", "deal-2"])
+            .unwrap();
+        builder
+            .with_string_list("deals", &["deal-1", "deal-2"])
+            .unwrap();
+        builder.with_integer_list("segment_ids", &[2, 3]).unwrap();
+        builder.with_string("country", "FR").unwrap();
+        let event = builder.build().unwrap();
+
+        let expected = vec![&2, &3];
+        let mut actual = atree.search(&event).unwrap().matches().to_vec();
+        actual.sort();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn can_search_a_tree_with_multiple_shared_sub_expressions() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string_list("deals"),
+            AttributeDefinition::integer_list("segment_ids"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string("city"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        [
+            (
+                1,
+                r#"exchange_id = 1 and not private and deals one of ["deal-1", "deal-2"]"#,
+            ),
+            (
+                2,
+                r#"exchange_id = 1 and not private and deals one of ["deal-2", "deal-3"]"#,
+            ),
+            (
+                3,
+                r#"exchange_id = 1 and not private and deals one of ["deal-2", "deal-3"] and segment_ids one of [1, 2, 3, 4]"#,
+            ),
+            (
+                4,
+                r#"exchange_id = 1 and not private and deals one of ["deal-2", "deal-3"] and segment_ids one of [5, 6, 7, 8] and country in ["CA", "US"]"#,
+            ),
+        ].into_iter().for_each(|(id, expression)| {
+                atree.insert(&id, expression).unwrap()
+        });
+
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", false).unwrap();
+        builder.with_integer("exchange_id", 1).unwrap();
+        builder
+            .with_string_list("deals", &["deal-1", "deal-3"])
+            .unwrap();
+        builder.with_integer_list("segment_ids", &[2, 3]).unwrap();
+        builder.with_string("country", "CA").unwrap();
+        let event = builder.build().unwrap();
+
+        let mut matches = atree.search(&event).unwrap().matches().to_vec();
+        matches.sort();
+        assert_eq!(vec![&1, &2, &3], matches);
+    }
+
+    #[test]
+    fn return_the_same_matches_when_searching_for_owned_subscription_ids() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "exchange_id = 1").unwrap();
+        atree.insert(&2u64, "exchange_id = 1 and private").unwrap();
+        atree.insert(&3u64, "exchange_id = 2").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        builder.with_boolean("private", true).unwrap();
+        let event = builder.build().unwrap();
+
+        let borrowed = atree
+            .search(&event)
+            .unwrap()
+            .matches()
+            .iter()
+            .map(|id| **id)
+            .sorted()
+            .collect_vec();
+        let owned = atree.search_owned(&event);
+        atree.delete(&1u64);
+
+        assert_eq!(borrowed, owned.into_iter().sorted().collect_vec());
+        assert_eq!(vec![&2u64], atree.search(&event).unwrap().matches());
+    }
+
+    #[test]
+    fn return_the_matches_sorted_by_descending_priority() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree
+            .insert_with_priority(&1u64, "exchange_id = 1", 5)
+            .unwrap();
+        atree.insert_with_priority(&2u64, "private", 20).unwrap();
+        atree
+            .insert_with_priority(&3u64, "private and exchange_id = 1", -1)
+            .unwrap();
+        atree.insert(&4u64, "exchange_id in [1, 2]").unwrap();
+        atree.insert_with_priority(&5u64, "private", 5).unwrap();
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", true).unwrap();
+        builder.with_integer("exchange_id", 1).unwrap();
+        let event = builder.build().unwrap();
+
+        let matches = atree.search_by_priority(&event).unwrap();
+
+        assert_eq!(vec![&2u64, &1u64, &5u64, &4u64, &3u64], matches);
+    }
+
+    #[test]
+    fn only_return_the_expressions_that_match_every_event_of_a_conjunction() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "exchange_id < 3").unwrap();
+        atree.insert(&2u64, "exchange_id < 3").unwrap();
+        atree.insert(&3u64, "private").unwrap();
+        atree.insert(&4u64, "true").unwrap();
+        let event = |exchange_id, private| {
+            let mut builder = atree.make_event();
+            builder.with_integer("exchange_id", exchange_id).unwrap();
+            builder.with_boolean("private", private).unwrap();
+            builder.build().unwrap()
+        };
+        let a = event(1, true);
+        let b = event(2, true);
+        let c = event(3, true);
+
+        let without_c = atree.search_conjunction(&[a.clone(), b.clone()]);
+        let with_c = atree.search_conjunction(&[a, b, c]);
+
+        assert_eq!(
+            vec![&1u64, &2u64, &3u64, &4u64],
+            without_c.matches().iter().copied().sorted().collect_vec()
+        );
+        assert_eq!(
+            vec![&3u64, &4u64],
+            with_c.matches().iter().copied().sorted().collect_vec()
+        );
+        assert!(atree.search_conjunction(&[]).matches().is_empty());
+    }
+
+    #[test]
+    fn can_search_a_snapshot_while_the_original_is_modified() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "exchange_id = 1").unwrap();
+        atree.insert(&2u64, "private").unwrap();
+        let snapshot = atree.snapshot();
+        let readers = (0..4)
+            .map(|_| {
+                let snapshot = Arc::clone(&snapshot);
+                std::thread::spawn(move || {
+                    let mut builder = snapshot.make_event();
+                    builder.with_boolean("private", true).unwrap();
+                    builder.with_integer("exchange_id", 1).unwrap();
+                    let event = builder.build().unwrap();
+                    (0..100)
+                        .map(|_| {
+                            snapshot
+                                .search_owned(&event)
+                                .into_iter()
+                                .sorted()
+                                .collect_vec()
+                        })
+                        .collect_vec()
+                })
+            })
+            .collect_vec();
+
+        atree.delete(&1u64);
+        for id in 3..100u64 {
+            atree.insert(&id, "exchange_id = 1").unwrap();
+        }
+
+        for reader in readers {
+            assert!(reader
+                .join()
+                .unwrap()
+                .into_iter()
+                .all(|matches| matches == vec![1u64, 2u64]));
+        }
+        assert_eq!(2, snapshot.iter_roots().count());
+    }
+
+    #[test]
+    fn only_evaluate_the_predicates_when_the_expressions_are_single_predicates() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&0u64, "private").unwrap();
+        atree.insert(&1u64, "not private").unwrap();
+        for id in 2..100u64 {
+            atree
+                .insert(&id, &format!("exchange_id = {}", id % 10))
+                .unwrap();
+        }
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", true).unwrap();
+        builder.with_integer("exchange_id", 3).unwrap();
+        let event = builder.build().unwrap();
+
+        let report = atree.search_budgeted(&event, 12).unwrap();
+
+        assert_eq!(1, atree.root_levels.max_level());
+        assert_eq!(
+            vec![&0u64, &3, &13, &23, &33, &43, &53, &63, &73, &83, &93],
+            report.matches().iter().copied().sorted().collect_vec()
+        );
+        assert!(atree.search_budgeted(&event, 11).is_err());
+    }
+
+    #[test]
+    fn stop_searching_once_the_budget_is_exceeded() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        for id in 0..1_000u64 {
+            atree
+                .insert(&id, &format!("private and exchange_id <> {id}"))
+                .unwrap();
+        }
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", true).unwrap();
+        builder.with_integer("exchange_id", 1).unwrap();
+        let event = builder.build().unwrap();
+
+        let exceeded = atree.search_budgeted(&event, 10).map(|_| ());
+        let report = atree.search_budgeted(&event, 10_000).unwrap();
+
+        assert_eq!(Err(BudgetExceeded { max_nodes: 10 }), exceeded);
+        assert_eq!(
+            atree.search(&event).unwrap().matches().len(),
+            report.matches().len()
+        );
+        assert_eq!(999, report.matches().len());
+    }
+
+    #[test]
+    fn report_a_subscription_id_attached_twice_to_an_expression_only_once() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "private or exchange_id = 1").unwrap();
+        atree.insert(&2u64, "private or exchange_id = 1").unwrap();
+        let root = atree.parse("private or exchange_id = 1").unwrap();
+        atree.insert_root(&1u64, root);
+        atree.insert(&1u64, "exchange_id = 1 or private").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", true).unwrap();
+        builder.with_integer("exchange_id", 1).unwrap();
+        let event = builder.build().unwrap();
+
+        let report = atree.search(&event).unwrap();
+
+        assert_eq!(
+            vec![&1u64, &2u64],
+            report.matches().iter().copied().sorted().collect_vec()
+        );
+        assert_eq!(DeleteOutcome::Detached, atree.delete(&1u64));
+        assert_eq!(
+            DeleteOutcome::Removed { freed_nodes: 3 },
+            atree.delete(&2u64)
+        );
+    }
+
+    #[test]
+    fn can_refer_to_an_attribute_by_its_alias() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id").with_alias("exchange"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "exchange = 1").unwrap();
+        atree.insert(&2u64, "exchange_id = 1").unwrap();
+        let event = |name| {
+            let mut builder = atree.make_event();
+            builder.with_integer(name, 1).unwrap();
+            builder.build().unwrap()
+        };
+
+        let canonical = atree.search(&event("exchange_id")).unwrap();
+        let alias = atree.search(&event("exchange")).unwrap();
+
+        assert_eq!(1, atree.iter_roots().count());
+        assert_eq!(
+            vec![&1u64, &2u64],
+            canonical.matches().iter().copied().sorted().collect_vec()
+        );
+        assert_eq!(
+            vec![&1u64, &2u64],
+            alias.matches().iter().copied().sorted().collect_vec()
+        );
+        assert_eq!(Some(vec!["exchange_id"]), atree.required_attributes(&1u64));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn return_the_result_of_the_watched_expressions_only() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string("country"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "exchange_id = 1 and private").unwrap();
+        atree.insert(&2u64, "exchange_id = 2").unwrap();
+        atree.insert(&3u64, "exchange_id = 1 or private").unwrap();
+        atree.insert(&4u64, "country = 'CA'").unwrap();
+        atree.insert(&5u64, "false").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", false).unwrap();
+        builder.with_integer("exchange_id", 1).unwrap();
+        let event = builder.build().unwrap();
+
+        let results = atree.search_watching(&event, &[1, 3, 4, 5, 6]);
+
+        assert_eq!(
+            vec![
+                (&1u64, Some(false)),
+                (&3, Some(true)),
+                (&4, None),
+                (&5, Some(false))
+            ],
+            results.into_iter().sorted().collect_vec()
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn return_an_error_when_a_subscription_id_cannot_be_exported_to_json() {
+        #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+        struct Unserializable;
+
+        impl serde::Serialize for Unserializable {
+            fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+                Err(serde::ser::Error::custom("unserializable subscription ID"))
+            }
+        }
+
+        let definitions = [AttributeDefinition::boolean("private")];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&Unserializable, "private").unwrap();
+
+        assert!(atree.to_json().is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn can_export_the_tree_to_json() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string_list("deals"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree
+            .insert(&1u64, "private and deals one of ['deal-1', 'deal-2']")
+            .unwrap();
+        atree
+            .insert(
+                &2u64,
+                "exchange_id = 1 or deals one of ['deal-1', 'deal-2']",
+            )
+            .unwrap();
+
+        let json: serde_json::Value = serde_json::from_str(&atree.to_json().unwrap()).unwrap();
+
+        let nodes = json["nodes"].as_array().unwrap();
+        let edges = json["edges"].as_array().unwrap();
+        let expected_edges: usize = atree
+            .nodes
+            .iter()
+            .map(|(_, entry)| match &entry.node {
+                ATreeNode::LNode(node) => node.parents.len(),
+                ATreeNode::INode(node) => node.parents.len() + node.children.len(),
+                ATreeNode::RNode(node) => node.children.len(),
+            })
+            .sum();
+        assert_eq!(atree.nodes.len(), nodes.len());
+        assert_eq!(expected_edges, edges.len());
+        assert!(nodes.iter().any(|node| node["type"] == "r-node"
+            && node["operator"] == "or"
+            && node["subscription_ids"] == serde_json::json!([2])));
+        assert!(nodes
+            .iter()
+            .any(|node| node["predicate"] == r#"⟨deals, one of, ["deal-1", "deal-2"]⟩"#));
+    }
+
+    #[test]
+    fn can_compare_the_integer_and_float_attributes_with_any_number() {
+        let definitions = [
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::float("bidfloor"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "exchange_id < 1.5").unwrap();
+        atree.insert(&2u64, "bidfloor > 2").unwrap();
+        atree.insert(&3u64, "exchange_id = 1.0").unwrap();
+        let search = |exchange_id, bidfloor| {
+            let mut builder = atree.make_event();
+            builder.with_integer("exchange_id", exchange_id).unwrap();
+            builder.with_float("bidfloor", bidfloor, 1).unwrap();
+            let event = builder.build().unwrap();
+            atree
+                .search(&event)
+                .unwrap()
+                .matches()
+                .iter()
+                .copied()
+                .sorted()
+                .collect_vec()
+        };
+
+        assert_eq!(vec![&1u64, &3u64], search(1, 20));
+        assert_eq!(vec![&2u64], search(2, 21));
+    }
+
+    #[test]
+    fn share_the_nodes_of_commutated_expressions() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string("country"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree
+            .insert(&1u64, "private and (exchange_id = 1 or country = 'CA')")
+            .unwrap();
+        atree
+            .insert(&2u64, "(country = 'CA' or exchange_id = 1) and private")
+            .unwrap();
+
+        assert_eq!(1, atree.iter_roots().count());
+        assert_eq!(5, atree.nodes.len());
+        assert_eq!(DeleteOutcome::Detached, atree.delete(&1u64));
+        assert_eq!(
+            DeleteOutcome::Removed { freed_nodes: 5 },
+            atree.delete(&2u64)
+        );
+    }
+
+    #[test]
+    fn keep_the_maximum_level_up_to_date_when_deleting_expressions() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::integer_list("segment_ids"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        for id in 0..60u64 {
+            let expression = match id % 3 {
+                0 => format!("exchange_id = {id}"),
+                1 => format!("private and exchange_id = {id}"),
+                _ => format!("private and (exchange_id = {id} or segment_ids one of [{id}])"),
+            };
+            atree.insert(&id, &expression).unwrap();
+        }
+        let scanned_max_level = |atree: &ATree<u64>| {
+            atree
+                .roots
+                .iter()
+                .map(|root_id| atree.nodes[*root_id].level())
+                .max()
+                .unwrap_or(1)
+        };
+        assert_eq!(3, atree.root_levels.max_level());
+
+        for id in (0..60u64).rev() {
+            atree.delete(&id);
+
+            assert_eq!(scanned_max_level(&atree), atree.root_levels.max_level());
+        }
+        assert_eq!(1, atree.root_levels.max_level());
+    }
+
+    fn define_why_not_tree() -> ATree<u64> {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string("country"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree
+            .insert(&1u64, "exchange_id > 10 and not private")
+            .unwrap();
+        atree
+            .insert(&2u64, "exchange_id = 1 or country in ['CA', 'US']")
+            .unwrap();
+        atree.insert(&3u64, "false").unwrap();
+        atree
+    }
+
+    #[test]
+    fn explain_why_a_comparison_did_not_match() {
+        let atree = define_why_not_tree();
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 5).unwrap();
+        builder.with_boolean("private", false).unwrap();
+        let event = builder.build().unwrap();
+
+        let reason = atree.why_not(&1u64, &event);
+
+        assert!(matches!(
+            reason,
+            Some(FailureReason::FailedPredicate {
+                attribute: "exchange_id",
+                ..
+            })
+        ));
+        assert!(atree.search(&event).unwrap().matches().is_empty());
+    }
+
+    #[test]
+    fn explain_which_attribute_is_missing() {
+        let atree = define_why_not_tree();
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 15).unwrap();
+        let event = builder.build().unwrap();
+
+        let reason = atree.why_not(&1u64, &event);
+
+        assert_eq!(
+            Some(FailureReason::MissingAttribute {
+                attribute: "private"
+            }),
+            reason
+        );
+    }
+
+    #[test]
+    fn explain_every_branch_of_an_or_that_failed() {
+        let atree = define_why_not_tree();
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 15).unwrap();
+        builder.with_string("country", "FR").unwrap();
+        let event = builder.build().unwrap();
+
+        let reason = atree.why_not(&2u64, &event);
+
+        let Some(FailureReason::AllBranchesFailed(reasons)) = reason else {
+            panic!("expected all the branches to fail, got {reason:?}");
+        };
+        let attributes = reasons
+            .iter()
+            .map(|reason| match reason {
+                FailureReason::FailedPredicate { attribute, .. } => *attribute,
+                reason => panic!("expected a failed predicate, got {reason:?}"),
+            })
+            .sorted()
+            .collect_vec();
+        assert_eq!(vec!["country", "exchange_id"], attributes);
+    }
+
+    #[test]
+    fn explain_nothing_when_the_expression_matches_or_is_unknown() {
+        let atree = define_why_not_tree();
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        builder.with_boolean("private", false).unwrap();
+        let event = builder.build().unwrap();
+
+        assert_eq!(None, atree.why_not(&2u64, &event));
+        assert_eq!(None, atree.why_not(&4u64, &event));
+        assert_eq!(
+            Some(FailureReason::NeverMatches),
+            atree.why_not(&3u64, &event)
+        );
+    }
+
+    #[test]
+    fn flag_the_satisfied_sub_expressions_shared_by_the_matching_expressions() {
+        let definitions = [
+            AttributeDefinition::boolean("a"),
+            AttributeDefinition::boolean("b"),
+            AttributeDefinition::boolean("c"),
+            AttributeDefinition::boolean("d"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "a or (b and c)").unwrap();
+        atree.insert(&2u64, "d or (b and c)").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_boolean("a", false).unwrap();
+        builder.with_boolean("b", true).unwrap();
+        builder.with_boolean("c", true).unwrap();
+        builder.with_boolean("d", false).unwrap();
+        let event = builder.build().unwrap();
+        let b_and_c = atree.nodes[atree.nodes_by_ids[&1u64]]
+            .children()
+            .iter()
+            .map(|child_id| &atree.nodes[*child_id])
+            .find(|entry| !entry.is_leaf())
+            .unwrap()
+            .id;
+
+        let first = atree.why_matches(&1u64, &event).unwrap();
+        let second = atree.why_matches(&2u64, &event).unwrap();
+
+        let shared = |satisfied: &[SatisfiedExpression<'_, u64>]| {
+            satisfied
+                .iter()
+                .filter(|node| node.is_shared)
+                .map(|node| {
+                    (
+                        node.expression_id,
+                        node.shared_with.iter().map(|id| **id).collect_vec(),
+                    )
+                })
+                .collect_vec()
+        };
+        assert_eq!(4, first.len());
+        assert_eq!(vec![(b_and_c, vec![2u64])], shared(&first));
+        assert_eq!(4, second.len());
+        assert_eq!(vec![(b_and_c, vec![1u64])], shared(&second));
+    }
+
+    #[test]
+    fn explain_no_match_when_the_expression_does_not_match() {
+        let atree = define_why_not_tree();
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        builder.with_boolean("private", false).unwrap();
+        let event = builder.build().unwrap();
+
+        assert!(atree.why_matches(&2u64, &event).is_some());
+        assert_eq!(None, atree.why_matches(&1u64, &event));
+        assert_eq!(None, atree.why_matches(&3u64, &event));
+        assert_eq!(None, atree.why_matches(&10u64, &event));
+    }
+
+    #[test]
+    fn evaluate_the_empty_lists_as_empty_sets() {
+        let definitions = [
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string_list("deals"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "exchange_id in []").unwrap();
+        atree.insert(&2u64, "exchange_id not in []").unwrap();
+        atree.insert(&3u64, "country in []").unwrap();
+        atree.insert(&4u64, "deals one of []").unwrap();
+        atree.insert(&5u64, "deals none of []").unwrap();
+        atree.insert(&6u64, "deals all of []").unwrap();
+        atree.insert(&7u64, "[] all of deals").unwrap();
+        let search = |deals: &[&str]| {
+            let mut builder = atree.make_event();
+            builder.with_integer("exchange_id", 1).unwrap();
+            builder.with_string("country", "CA").unwrap();
+            builder.with_string_list("deals", deals).unwrap();
+            let event = builder.build().unwrap();
+            atree
+                .search(&event)
+                .unwrap()
+                .matches()
+                .iter()
+                .copied()
+                .copied()
+                .sorted()
+                .collect_vec()
+        };
+
+        assert_eq!(vec![2, 5, 7], search(&["deal-1"]));
+        assert_eq!(vec![2, 5, 6, 7], search(&[]));
+    }
+
+    #[test]
+    fn match_the_event_lists_that_are_a_subset_of_the_list() {
+        let definitions = [AttributeDefinition::integer_list("segment_ids")];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree
+            .insert(&1u64, "segment_ids subset of [1, 2, 3, 4, 5]")
+            .unwrap();
+        atree
+            .insert(&2u64, "not (segment_ids subset of [1, 2, 3, 4, 5])")
+            .unwrap();
+        atree.insert(&3u64, "[1, 2] subset of segment_ids").unwrap();
+        let search = |segment_ids: &[i64]| {
+            let mut builder = atree.make_event();
+            builder
+                .with_integer_list("segment_ids", segment_ids)
+                .unwrap();
+            let event = builder.build().unwrap();
+            atree
+                .search(&event)
+                .unwrap()
+                .matches()
+                .iter()
+                .copied()
+                .copied()
+                .sorted()
+                .collect_vec()
+        };
+
+        assert_eq!(vec![1, 3], search(&[2, 1]));
+        assert_eq!(vec![1], search(&[1, 5]));
+        assert_eq!(vec![2, 3], search(&[1, 2, 6]));
+        assert_eq!(vec![1], search(&[]));
+    }
+
+    #[test]
+    fn only_change_the_matches_that_depend_on_the_varying_attribute() {
+        let definitions = [
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string("deal_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "exchange_id = 1").unwrap();
+        atree.insert(&2u64, "deal_id = 'deal-1'").unwrap();
+        atree
+            .insert(&3u64, "exchange_id = 1 and deal_id = 'deal-2'")
+            .unwrap();
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        builder.with_string("deal_id", "deal-1").unwrap();
+        let base = builder.build().unwrap();
+        let search_with_deal = |deal_id| {
+            atree
+                .search_varying(&base, |builder| builder.with_string("deal_id", deal_id))
+                .unwrap()
+                .matches()
+                .iter()
+                .copied()
+                .copied()
+                .sorted()
+                .collect_vec()
+        };
+
+        assert_eq!(vec![1, 2], search_with_deal("deal-1"));
+        assert_eq!(vec![1, 3], search_with_deal("deal-2"));
+        assert_eq!(vec![1], search_with_deal("deal-3"));
+        assert_eq!(
+            vec![&1u64, &2],
+            atree
+                .search(&base)
+                .unwrap()
+                .matches()
+                .iter()
+                .copied()
+                .sorted()
+                .collect_vec()
+        );
+        assert!(matches!(
+            atree.search_varying(&base, |builder| builder.with_integer("deal_id", 1)),
+            Err(ATreeError::Event(EventError::WrongType { .. }))
+        ));
+    }
+
+    #[derive(Clone, Debug, Default)]
+    struct SharedStrings(Arc<std::sync::Mutex<StringTable>>);
+
+    impl Interner for SharedStrings {
+        fn get(&self, value: &str) -> StringId {
+            self.0.lock().unwrap().get(value)
+        }
+
+        fn get_or_update(&mut self, value: &str) -> Result<StringId, StringTableError> {
+            self.0.lock().unwrap().get_or_update(value)
+        }
+
+        fn resolve(&self, id: StringId) -> Option<String> {
+            Interner::resolve(&*self.0.lock().unwrap(), id)
+        }
+    }
+
+    #[test]
+    fn share_the_string_ids_between_trees_with_the_same_interner() {
+        let definitions = [AttributeDefinition::string("country")];
+        let strings = SharedStrings::default();
+        let mut atree =
+            ATree::with_interner(&definitions, ATreeOptions::new(), strings.clone()).unwrap();
+        let mut another_atree =
+            ATree::with_interner(&definitions, ATreeOptions::new(), strings).unwrap();
+        atree.insert(&1u64, "country = 'US'").unwrap();
+        another_atree
+            .insert(&2u64, "country in ['FR', 'CA']")
+            .unwrap();
+        atree.insert(&3u64, "country = 'CA'").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_string("country", "CA").unwrap();
+        let event = builder.build().unwrap();
+
+        assert_eq!(atree.strings.get("CA"), another_atree.strings.get("CA"));
+        assert_ne!(atree.strings.get("CA"), atree.strings.get("FR"));
+        assert_eq!(vec![&3u64], atree.search(&event).unwrap().matches());
+        assert_eq!(vec![&2u64], another_atree.search(&event).unwrap().matches());
+    }
+
+    #[test]
+    fn can_iterate_over_the_matches_of_a_report() {
+        let definitions = [AttributeDefinition::integer("exchange_id")];
+        let mut atree = ATree::new(&definitions).unwrap();
+        for id in 1..=3u64 {
+            atree.insert(&id, "exchange_id = 1").unwrap();
+        }
+        atree.insert(&4u64, "exchange_id = 2").unwrap();
+        let event = |exchange_id| {
+            let mut builder = atree.make_event();
+            builder.with_integer("exchange_id", exchange_id).unwrap();
+            builder.build().unwrap()
+        };
+        let report = atree.search(&event(1)).unwrap();
+        let empty = atree.search(&event(3)).unwrap();
+
+        let mut ids = vec![];
+        for id in &report {
+            ids.push(id);
+        }
+
+        assert_eq!(report.matches(), ids);
+        assert_eq!(3, report.len());
+        assert!(!report.is_empty());
+        assert_eq!(0, empty.len());
+        assert!(empty.is_empty());
+        assert_eq!(None, (&empty).into_iter().next());
+    }
+
+    #[test]
+    fn can_refer_to_a_named_list_in_multiple_expressions() {
+        let definitions = [
+            AttributeDefinition::string("country"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::integer_list("segment_ids"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.define_string_list("EU", &["FR", "DE", "FR"]).unwrap();
+        atree.define_integer_list("sports", &[3, 1, 2]).unwrap();
+        atree.insert(&1u64, "country in @EU").unwrap();
+        atree
+            .insert(&2u64, "exchange_id = 1 and country not in @EU")
+            .unwrap();
+        atree.insert(&3u64, "segment_ids one of @sports").unwrap();
+        let event = |country, segment_ids| {
+            let mut builder = atree.make_event();
+            builder.with_string("country", country).unwrap();
+            builder.with_integer("exchange_id", 1).unwrap();
+            builder
+                .with_integer_list("segment_ids", segment_ids)
+                .unwrap();
+            builder.build().unwrap()
+        };
+
+        let european = atree.search(&event("DE", &[2])).unwrap();
+        let other = atree.search(&event("US", &[4])).unwrap();
+
+        assert_eq!(
+            vec![&1u64, &3u64],
+            european.matches().iter().copied().sorted().collect_vec()
+        );
+        assert_eq!(vec![&2u64], other.matches().to_vec());
+    }
+
+    #[test]
+    fn return_an_error_when_referring_to_an_undefined_list() {
+        let definitions = [AttributeDefinition::string("country")];
+        let mut atree = ATree::<u64>::new(&definitions).unwrap();
+
+        let result = atree.insert(&1u64, "country in @EU");
+
+        assert!(matches!(
+            result,
+            Err(ATreeError::ParseError(ParseError::User {
+                error: ParserError::UndefinedList(name)
+            })) if name == "EU"
+        ));
+    }
+
+    #[test]
+    fn return_an_error_when_a_named_list_exceeds_the_maximum_length() {
+        let definitions = [AttributeDefinition::integer("exchange_id")];
+        let options = ATreeOptions::new().max_list_length(2);
+        let mut atree = ATree::<u64>::with_options(&definitions, options).unwrap();
+
+        let result = atree.define_integer_list("exchanges", &[1, 2, 3]);
+
+        assert!(matches!(
+            result,
+            Err(ATreeError::ParseError(ParseError::User {
+                error: ParserError::MaximumListLengthExceeded(2)
+            }))
+        ));
+    }
+
+    #[test]
+    fn can_receive_the_matches_through_a_callback() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::string_list("deals"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::float("bidfloor"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string("city"),
+            AttributeDefinition::string_list("deal_ids"),
+            AttributeDefinition::integer_list("segment_ids"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, A_COMPLEX_EXPRESSION).unwrap();
+        atree.insert(&2u64, ANOTHER_COMPLEX_EXPRESSION).unwrap();
+        atree.insert(&3u64, AN_EXPRESSION).unwrap();
+        atree.insert(&4u64, A_NOT_EXPRESSION).unwrap();
+        atree.insert(&5u64, "true").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        builder.with_boolean("private", false).unwrap();
+        builder.with_string_list("deal_ids", &["deal-1"]).unwrap();
+        builder.with_integer_list("segment_ids", &[2]).unwrap();
+        builder.with_string("country", "FR").unwrap();
+        let event = builder.build().unwrap();
+
+        let mut matches = vec![];
+        atree.search_for_each(&event, |id| matches.push(*id));
+
+        let expected = atree.search(&event).unwrap();
+        assert_eq!(
+            expected
+                .matches()
+                .iter()
+                .map(|id| **id)
+                .sorted()
+                .collect_vec(),
+            matches.into_iter().sorted().collect_vec()
+        );
+        assert_eq!(4, expected.matches().len());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn can_receive_the_matches_through_a_bounded_channel() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        for id in 0..20u64 {
+            atree
+                .insert(&id, &format!("exchange_id = {} or private", id % 3))
+                .unwrap();
+        }
+        atree.insert(&20u64, "not private").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        builder.with_boolean("private", false).unwrap();
+        let event = builder.build().unwrap();
+        let (tx, rx) = std::sync::mpsc::sync_channel(1);
+
+        let matches = std::thread::scope(|scope| {
+            let consumer = scope.spawn(move || rx.into_iter().collect_vec());
+            atree.search_to_sender(&event, &tx).unwrap();
+            drop(tx);
+            consumer.join().unwrap()
+        });
+
+        let expected = atree.search(&event).unwrap();
+        assert_eq!(
+            expected
+                .matches()
+                .iter()
+                .map(|id| **id)
+                .sorted()
+                .collect_vec(),
+            matches.into_iter().sorted().collect_vec()
+        );
+        assert_eq!(8, expected.len());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn stop_sending_the_matches_when_the_receiver_is_disconnected() {
+        let definitions = [AttributeDefinition::integer("exchange_id")];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "exchange_id = 1").unwrap();
+        atree.insert(&2u64, "exchange_id < 2").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        let event = builder.build().unwrap();
+        let (tx, rx) = std::sync::mpsc::sync_channel(2);
+        drop(rx);
+
+        assert!(atree.search_to_sender(&event, &tx).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn stop_the_search_once_the_receiver_is_disconnected_partway_through() {
+        let definitions = [AttributeDefinition::integer("exchange_id")];
+        let mut atree = ATree::new(&definitions).unwrap();
+        for id in 1..=100u64 {
+            atree.insert(&id, &format!("exchange_id < {id}")).unwrap();
+        }
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 0).unwrap();
+        let event = builder.build().unwrap();
+        // The channel has no buffer so the second match is sent once the receiver is dropped
+        let (tx, rx) = std::sync::mpsc::sync_channel(0);
+        let receiver = std::thread::spawn(move || rx.recv().unwrap());
+
+        let result = atree.search_to_sender(&event, &tx);
+        let mut calls = 0;
+        let results = atree.try_search_for_each(&event, |_| {
+            calls += 1;
+            ControlFlow::Break(())
+        });
+
+        assert!(result.is_err());
+        assert!((1..=100).contains(&receiver.join().unwrap()));
+        assert_eq!(1, calls);
+        let evaluated = atree
+            .nodes
+            .iter()
+            .filter(|(node_id, _)| results.is_evaluated(*node_id))
+            .count();
+        assert_eq!(1, evaluated);
+    }
+
+    #[test]
+    fn can_reuse_a_report_across_searches() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        for id in 0..10u64 {
+            atree
+                .insert(&id, &format!("exchange_id = {} or private", id % 3))
+                .unwrap();
+        }
+        atree.insert(&10u64, "true").unwrap();
+        let events =
+            [(0, true), (1, false), (2, false), (3, false)].map(|(exchange_id, private)| {
+                let mut builder = atree.make_event();
+                builder.with_integer("exchange_id", exchange_id).unwrap();
+                builder.with_boolean("private", private).unwrap();
+                builder.build().unwrap()
+            });
+        let mut report = Report::new();
+        atree.search_reuse(&events[0], &mut report);
+        // The first event matches every expression
+        let capacity = report.matches.capacity();
+        assert!(capacity >= 11);
+
+        for event in &events {
+            atree.search_reuse(event, &mut report);
+
+            let expected = atree.search(event).unwrap();
+            assert_eq!(
+                expected.matches().iter().sorted().collect_vec(),
+                report.matches().iter().sorted().collect_vec()
+            );
+            assert_eq!(
+                expected.matched_roots().count(),
+                report.matched_roots().count()
+            );
+            assert_eq!(capacity, report.matches.capacity());
+        }
+    }
+
+    #[test]
+    fn build_the_same_tree_from_an_iterator_as_from_manual_insertions() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::integer_list("segment_ids"),
+        ];
+        let expressions = [
+            (1u64, "private or exchange_id = 1"),
+            (2, "private and segment_ids one of [1, 2]"),
+            (3, "exchange_id = 2"),
+            (4, "not private"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        for (id, expression) in &expressions {
+            atree.insert(id, expression).unwrap();
+        }
+
+        let built = ATreeBuilder::new(&definitions)
+            .build(expressions.iter().copied())
+            .unwrap();
+
+        let events = [(true, 2), (false, 1), (false, 3)].map(|(private, exchange_id)| {
+            let mut builder = atree.make_event();
+            builder.with_boolean("private", private).unwrap();
+            builder.with_integer("exchange_id", exchange_id).unwrap();
+            builder.with_integer_list("segment_ids", &[2]).unwrap();
+            builder.build().unwrap()
+        });
+        for event in &events {
+            assert_eq!(
+                atree
+                    .search(event)
+                    .unwrap()
+                    .matches()
+                    .iter()
+                    .sorted()
+                    .collect_vec(),
+                built
+                    .search(event)
+                    .unwrap()
+                    .matches()
+                    .iter()
+                    .sorted()
+                    .collect_vec()
+            );
+        }
+        assert_eq!(atree.to_graphviz(), built.to_graphviz());
+    }
+
+    #[test]
+    fn stop_building_a_tree_at_the_first_invalid_expression() {
+        let definitions = [AttributeDefinition::boolean("private")];
+        let expressions = vec![(1u64, "private"), (2, "unknown = 1"), (3, "not private")];
+
+        let result = ATreeBuilder::new(&definitions).build(expressions);
+
+        assert!(matches!(result, Err(ATreeError::ParseError(_))));
+    }
+
+    #[test]
+    fn set_the_attribute_of_the_handle_in_the_events() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id").with_alias("exchange"),
+            AttributeDefinition::float("bidfloor"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::integer_list("segment_ids"),
+            AttributeDefinition::string_list("deals"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree
+            .insert(
+                &1u64,
+                r#"deals one of ["deal-1"] and country starts_with "C""#,
+            )
+            .unwrap();
+        let handle = |name| atree.attribute_handle(name).unwrap();
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", true).unwrap();
+        builder.with_integer("exchange_id", 1).unwrap();
+        builder.with_float("bidfloor", 15, 1).unwrap();
+        builder.with_string("country", "CA").unwrap();
+        builder.with_integer_list("segment_ids", &[3, 1]).unwrap();
+        builder
+            .with_string_list("deals", &["deal-1", "deal-2"])
+            .unwrap();
+        let expected = builder.build().unwrap();
+
+        let mut builder = atree.make_event();
+        builder.with_boolean_h(handle("private"), true).unwrap();
+        builder.with_integer_h(handle("exchange"), 1).unwrap();
+        builder.with_float_h(handle("bidfloor"), 15, 1).unwrap();
+        builder.with_string_h(handle("country"), "CA").unwrap();
+        builder
+            .with_integer_list_h(handle("segment_ids"), &[3, 1])
+            .unwrap();
+        builder
+            .with_string_list_h(handle("deals"), &["deal-1", "deal-2"])
+            .unwrap();
+        let event = builder.build().unwrap();
+
+        assert_eq!(expected, event);
+        assert_eq!(handle("exchange_id"), handle("exchange"));
+        assert_eq!(None, atree.attribute_handle("unknown"));
+        assert_eq!(vec![&1u64], atree.search(&event).unwrap().matches());
+        assert_eq!(
+            Err(EventError::WrongType {
+                name: "private".to_string(),
+                expected: AttributeKind::Boolean,
+                actual: AttributeKind::Integer,
+            }),
+            atree.make_event().with_integer_h(handle("private"), 1)
+        );
+    }
+
+    #[test]
+    fn return_an_error_when_using_the_attribute_handle_of_another_tree() {
+        let definitions = [AttributeDefinition::boolean("private")];
+        let atree = ATree::<u64>::new(&definitions).unwrap();
+        let another_atree = ATree::<u64>::new(&definitions).unwrap();
+        let handle = another_atree.attribute_handle("private").unwrap();
+        let mut builder = atree.make_event();
+
+        assert_eq!(
+            Err(EventError::ForeignAttributeHandle),
+            builder.with_boolean_h(handle, true)
+        );
+        assert!(atree
+            .clone()
+            .make_event()
+            .with_boolean_h(atree.attribute_handle("private").unwrap(), true)
+            .is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn can_check_if_a_subscription_id_is_part_of_the_matches() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "private").unwrap();
+        atree.insert(&2u64, "exchange_id = 1").unwrap();
+        atree.insert(&3u64, "not private").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", true).unwrap();
+        builder.with_integer("exchange_id", 1).unwrap();
+        let event = builder.build().unwrap();
+
+        let report = atree.search(&event).unwrap();
+
+        assert!(report.is_match(&1));
+        assert!(report.is_match(&2));
+        assert!(!report.is_match(&3));
+        assert!(!report.is_match(&4));
+        assert_eq!(HashSet::from([&1, &2]), report.matches_set());
+    }
+
+    #[test]
+    fn can_group_the_matches_by_the_expression_that_matched() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "private or exchange_id = 1").unwrap();
+        atree.insert(&2u64, "exchange_id = 1").unwrap();
+        atree.insert(&3u64, "private or exchange_id = 1").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        let event = builder.build().unwrap();
+
+        let report = atree.search(&event).unwrap();
+        let mut roots: Vec<_> = report
+            .matched_roots()
+            .map(|(_, ids)| {
+                let mut ids = ids.to_vec();
+                ids.sort();
+                ids
+            })
+            .collect();
+        roots.sort();
+
+        assert_eq!(vec![vec![&1, &3], vec![&2]], roots);
+        let expression_ids: HashSet<_> = report.matched_roots().map(|(id, _)| id).collect();
+        assert_eq!(2, expression_ids.len());
+    }
+
+    #[test]
+    fn can_iterate_over_the_distinct_expressions() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "private or exchange_id = 1").unwrap();
+        atree.insert(&2u64, "exchange_id = 1").unwrap();
+        atree.insert(&3u64, "private or exchange_id = 1").unwrap();
+        atree
+            .insert(&4u64, "(private or exchange_id = 1) and exchange_id = 1")
+            .unwrap();
+        atree.insert(&5u64, "private or exchange_id = 1").unwrap();
+
+        let mut roots: Vec<_> = atree
+            .iter_roots()
+            .map(|(_, ids)| {
+                let mut ids = ids.to_vec();
+                ids.sort();
+                ids
+            })
+            .collect();
+        roots.sort();
+
+        assert_eq!(vec![vec![1, 3, 5], vec![2], vec![4]], roots);
+        let expression_ids: HashSet<_> = atree.iter_roots().map(|(id, _)| id).collect();
+        assert_eq!(3, expression_ids.len());
+    }
+
+    #[test]
+    fn do_not_iterate_over_sub_expressions_without_subscription_ids() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "private").unwrap();
+        atree.insert(&2u64, "private and exchange_id = 1").unwrap();
+        atree.delete(&1u64);
+
+        let roots: Vec<_> = atree.iter_roots().map(|(_, ids)| ids.to_vec()).collect();
+
+        assert_eq!(vec![vec![2]], roots);
+    }
+
+    #[test]
+    fn can_delete_a_single_predicate() {
+        let definitions = [AttributeDefinition::boolean("private")];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "private").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", true).unwrap();
+        let event = builder.build().unwrap();
+
+        let results = atree.search(&event).unwrap().matches().to_vec();
+        assert_eq!(vec![&1u64], results);
+
+        atree.delete(&1u64);
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", true).unwrap();
+        let event = builder.build().unwrap();
+        let results = atree.search(&event).unwrap().matches().to_vec();
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn deleting_an_expression_only_removes_the_id_not_the_expression_if_it_is_still_referenced() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string_list("deal_ids"),
+            AttributeDefinition::string_list("deals"),
+            AttributeDefinition::integer_list("segment_ids"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string("city"),
+        ];
+        let an_expression = "private or exchange_id = 1";
+        let another_expression =
+            r#"private or exchange_id = 1 or deal_ids one of ["deal-1", "deal-2"]"#;
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, an_expression).unwrap();
+        atree.insert(&2u64, another_expression).unwrap();
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        let event = builder.build().unwrap();
+
+        let results = atree.search(&event).unwrap().matches().to_vec();
+        assert_eq!(
+            vec![&1u64, &2u64],
+            results.into_iter().sorted().collect_vec()
+        );
+
+        atree.delete(&1u64);
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        let event = builder.build().unwrap();
+        let results = atree.search(&event).unwrap().matches().to_vec();
+        assert_eq!(vec![&2u64], results);
+    }
+
+    #[test]
+    fn deleting_an_expression_only_removes_the_id_not_the_expression_if_it_has_multiple_subscription_ids(
+    ) {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let an_expression = "private or exchange_id = 1";
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, an_expression).unwrap();
+        atree.insert(&2u64, an_expression).unwrap();
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        let event = builder.build().unwrap();
+
+        let results = atree.search(&event).unwrap().matches().to_vec();
+        assert_eq!(vec![&1u64, &2u64], results);
+
+        atree.delete(&1u64);
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        let event = builder.build().unwrap();
+        let results = atree.search(&event).unwrap().matches().to_vec();
+        assert_eq!(vec![&2u64], results);
+    }
+
+    #[test]
+    fn can_delete_root_node_when_all_references_are_deleted() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let an_expression = "private or exchange_id = 1";
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, an_expression).unwrap();
+        atree.insert(&2u64, an_expression).unwrap();
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        let event = builder.build().unwrap();
+
+        let results = atree.search(&event).unwrap().matches().to_vec();
+        assert_eq!(vec![&1u64, &2u64], results);
+
+        atree.delete(&1u64);
+        atree.delete(&2u64);
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        let event = builder.build().unwrap();
+        let results = atree.search(&event).unwrap().matches().to_vec();
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn deleting_a_subscription_id_twice_is_a_no_op() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "private and exchange_id = 1").unwrap();
+        atree.insert(&2u64, "private and exchange_id = 1").unwrap();
+        atree.insert(&3u64, "private").unwrap();
+
+        let first = atree.delete(&1u64);
+        let second = atree.delete(&1u64);
+
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", true).unwrap();
+        builder.with_integer("exchange_id", 1).unwrap();
+        let event = builder.build().unwrap();
+        assert_eq!(DeleteOutcome::Detached, first);
+        assert_eq!(DeleteOutcome::NotFound, second);
+        assert_eq!(3, atree.nodes.len());
+        assert_eq!(
+            vec![&2u64, &3u64],
+            atree
+                .search(&event)
+                .unwrap()
+                .matches()
+                .iter()
+                .copied()
+                .sorted()
+                .collect_vec()
+        );
+    }
+
+    #[test]
+    fn deleting_a_non_existing_subscription_id_is_a_no_op() {
+        let definitions = [AttributeDefinition::boolean("private")];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "private").unwrap();
+
+        let outcome = atree.delete(&2u64);
+
+        assert_eq!(DeleteOutcome::NotFound, outcome);
+        assert_eq!(1, atree.nodes.len());
+    }
+
+    #[test]
+    fn deleting_a_subscription_id_of_a_shared_expression_only_detaches_it() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "private or exchange_id = 1").unwrap();
+        atree.insert(&2u64, "private or exchange_id = 1").unwrap();
+
+        let outcome = atree.delete(&1u64);
+
+        assert_eq!(DeleteOutcome::Detached, outcome);
+        assert_eq!(3, atree.nodes.len());
+    }
+
+    #[test]
+    fn deleting_the_last_subscription_id_of_an_expression_removes_it() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "private or exchange_id = 1").unwrap();
+        atree.insert(&2u64, "private or exchange_id = 1").unwrap();
+        atree.insert(&3u64, "private").unwrap();
+        atree.delete(&1u64);
+
+        let outcome = atree.delete(&2u64);
+
+        assert_eq!(DeleteOutcome::Removed { freed_nodes: 2 }, outcome);
+        assert_eq!(1, atree.nodes.len());
+    }
+
+    #[test]
+    fn keep_the_previous_expression_when_replacing_with_an_invalid_one() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "exchange_id = 1").unwrap();
+
+        let result = atree.replace(&1u64, "exchange_id = 'invalid'");
+
+        assert!(result.is_err());
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        let event = builder.build().unwrap();
+        assert_eq!(vec![&1u64], atree.search(&event).unwrap().matches());
+    }
+
+    #[test]
+    fn can_replace_the_expression_of_a_subscription_id() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "exchange_id = 1").unwrap();
+        atree.insert(&2u64, "exchange_id = 1").unwrap();
+
+        atree.replace(&1u64, "exchange_id = 2").unwrap();
+
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        let event = builder.build().unwrap();
+        assert_eq!(vec![&2u64], atree.search(&event).unwrap().matches());
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 2).unwrap();
+        let event = builder.build().unwrap();
+        assert_eq!(vec![&1u64], atree.search(&event).unwrap().matches());
+    }
+
+    #[test]
+    fn can_replace_an_expression_by_one_of_its_sub_expressions() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "exchange_id = 1 and private").unwrap();
+
+        atree.replace(&1u64, "exchange_id = 1").unwrap();
+
+        assert_eq!(1, atree.nodes.len());
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        builder.with_boolean("private", false).unwrap();
+        let event = builder.build().unwrap();
+        assert_eq!(vec![&1u64], atree.search(&event).unwrap().matches());
+        assert_eq!(
+            DeleteOutcome::Removed { freed_nodes: 1 },
+            atree.delete(&1u64)
+        );
+    }
+
+    #[test]
+    fn can_replace_a_non_existing_subscription_id() {
+        let definitions = [AttributeDefinition::boolean("private")];
+        let mut atree = ATree::new(&definitions).unwrap();
+
+        atree.replace(&1u64, "private").unwrap();
+
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", true).unwrap();
+        let event = builder.build().unwrap();
+        assert_eq!(vec![&1u64], atree.search(&event).unwrap().matches());
+    }
+
+    #[test]
+    fn replace_the_expression_of_a_subscription_id_inserted_twice_by_default() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "exchange_id = 1 and private").unwrap();
+
+        atree.insert(&1u64, "exchange_id = 2").unwrap();
+
+        assert_eq!(1, atree.nodes.len());
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 2).unwrap();
+        let event = builder.build().unwrap();
+        assert_eq!(vec![&1u64], atree.search(&event).unwrap().matches());
+        assert_eq!(
+            DeleteOutcome::Removed { freed_nodes: 1 },
+            atree.delete(&1u64)
+        );
+        assert!(atree.nodes.is_empty());
+    }
+
+    #[test]
+    fn return_an_error_when_inserting_more_distinct_expressions_than_the_maximum_of_roots() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let options = ATreeOptions::new().max_roots(2);
+        let mut atree = ATree::with_options(&definitions, options).unwrap();
+        atree.insert(&1u64, "exchange_id = 1 and private").unwrap();
+        atree.insert(&2u64, "exchange_id = 2").unwrap();
+
+        let result = atree.insert(&3u64, "exchange_id = 3");
+
+        assert!(matches!(
+            result,
+            Err(ATreeError::CapacityExceeded { max_roots: 2 })
+        ));
+        assert_eq!(2, atree.roots.len());
+        assert_eq!(None, atree.nodes_by_ids.get(&3u64));
+        assert!(atree.insert(&4u64, "private and exchange_id = 1").is_ok());
+        assert!(atree.insert(&5u64, "true").is_ok());
+        assert_eq!(2, atree.roots.len());
+    }
+
+    #[test]
+    fn allow_replacing_the_expression_of_a_full_tree_when_its_root_is_freed() {
+        let definitions = [AttributeDefinition::integer("exchange_id")];
+        let options = ATreeOptions::new().max_roots(2);
+        let mut atree = ATree::with_options(&definitions, options).unwrap();
+        atree.insert(&1u64, "exchange_id = 1").unwrap();
+        atree.insert(&2u64, "exchange_id = 2").unwrap();
+        atree.insert(&3u64, "exchange_id = 2").unwrap();
+
+        assert!(atree.replace(&1u64, "exchange_id = 3").is_ok());
+        assert!(atree.insert(&1u64, "exchange_id = 4").is_ok());
+        assert!(matches!(
+            atree.replace(&2u64, "exchange_id = 5"),
+            Err(ATreeError::CapacityExceeded { max_roots: 2 })
+        ));
+        atree.delete(&1u64);
+        assert!(atree.insert(&4u64, "exchange_id = 5").is_ok());
+        assert_eq!(2, atree.roots.len());
+    }
+
+    #[test]
+    fn can_reject_a_subscription_id_inserted_twice() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let options = ATreeOptions::new().on_duplicate(DuplicatePolicy::Reject);
+        let mut atree = ATree::with_options(&definitions, options).unwrap();
+        atree.insert(&1u64, "exchange_id = 1 and private").unwrap();
+
+        let result = atree.insert(&1u64, "exchange_id = 2");
+
+        assert!(matches!(result, Err(ATreeError::DuplicateSubscription)));
+        assert_eq!(3, atree.nodes.len());
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        builder.with_boolean("private", true).unwrap();
+        let event = builder.build().unwrap();
+        assert_eq!(vec![&1u64], atree.search(&event).unwrap().matches());
+        assert_eq!(
+            DeleteOutcome::Removed { freed_nodes: 3 },
+            atree.delete(&1u64)
+        );
+        assert!(atree.nodes.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn leave_the_predicates_on_undefined_attributes_unknown_by_default() {
+        let definitions = [
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::integer_list("segment_ids"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree
+            .insert(&1u64, "exchange_id = 2 and segment_ids one of [1, 2]")
+            .unwrap();
+        atree
+            .insert(&2u64, "exchange_id = 1 and not segment_ids one of [1, 2]")
+            .unwrap();
+        atree
+            .insert(&3u64, "exchange_id = 2 or segment_ids one of [1, 2]")
+            .unwrap();
+        atree
+            .insert(&4u64, "exchange_id = 1 or segment_ids one of [1, 2]")
+            .unwrap();
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        let event = builder.build().unwrap();
+
+        let results = atree.search_watching(&event, &[1u64, 2u64, 3u64, 4u64]);
+
+        assert_eq!(
+            HashMap::from([
+                (&1u64, Some(false)),
+                (&2u64, None),
+                (&3u64, None),
+                (&4u64, Some(true))
+            ]),
+            results
+        );
+        assert_eq!(
+            Some(FailureReason::MissingAttribute {
+                attribute: "segment_ids"
+            }),
+            atree.why_not(&2u64, &event)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn can_treat_the_predicates_on_undefined_attributes_as_non_matching() {
+        let definitions = [
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::integer_list("segment_ids"),
+        ];
+        let options = ATreeOptions::new().on_undefined(UndefinedPolicy::NonMatch);
+        let mut atree = ATree::with_options(&definitions, options).unwrap();
+        atree
+            .insert(&1u64, "exchange_id = 2 and segment_ids one of [1, 2]")
+            .unwrap();
+        atree
+            .insert(&2u64, "exchange_id = 1 and not segment_ids one of [1, 2]")
+            .unwrap();
+        atree
+            .insert(&3u64, "exchange_id = 2 or segment_ids one of [1, 2]")
+            .unwrap();
+        atree
+            .insert(&4u64, "exchange_id = 1 or segment_ids one of [1, 2]")
+            .unwrap();
+        atree.insert(&5u64, "segment_ids is null").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        let event = builder.build().unwrap();
+
+        let results = atree.search_watching(&event, &[1u64, 2u64, 3u64, 4u64, 5u64]);
+
+        assert_eq!(
+            HashMap::from([
+                (&1u64, Some(false)),
+                (&2u64, Some(false)),
+                (&3u64, Some(false)),
+                (&4u64, Some(true)),
+                (&5u64, Some(true))
+            ]),
+            results
+        );
+        assert_eq!(
+            Some(FailureReason::FailedPredicate {
+                attribute: "segment_ids",
+                predicate: "⟨attribute(1), none of, [1, 2]⟩".to_string()
+            }),
+            atree.why_not(&2u64, &event)
+        );
+    }
+
+    #[test]
+    fn return_the_same_expression_id_regardless_of_the_interning_order_of_the_strings() {
+        let definitions = [
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string_list("deals"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        let mut another_atree = ATree::new(&definitions).unwrap();
+        another_atree.insert(&1u64, r#"country = "b""#).unwrap();
+        let expression = r#"deals one of ["a", "b"] and country = "a""#;
+
+        atree.insert(&2u64, expression).unwrap();
+        another_atree.insert(&2u64, expression).unwrap();
+
+        let expression_id = |atree: &ATree<u64>| {
+            atree
+                .iter_roots()
+                .find(|(_, ids)| ids.contains(&2))
+                .map(|(id, _)| id)
+        };
+        assert!(expression_id(&atree).is_some());
+        assert_eq!(expression_id(&atree), expression_id(&another_atree));
+    }
+
+    #[test]
+    fn return_the_same_expression_id_across_runs() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string("country"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree
+            .insert(&1u64, r#"exchange_id = 1 and (private or country = "CA")"#)
+            .unwrap();
+
+        let expression_ids: Vec<_> = atree.iter_roots().map(|(id, _)| id).collect();
+
+        assert_eq!(vec![11507429846842733203], expression_ids);
+    }
 
-#[inline]
-fn change_rnode_to_inode<T>(node_id: NodeId, nodes: &mut Slab<Entry<T>>) {
-    let entry = &mut nodes[node_id];
-    if let ATreeNode::RNode(RNode {
-        children,
-        level,
-        operator,
-    }) = &entry.node
-    {
-        let inode = ATreeNode::INode(INode {
-            parents: vec![],
-            children: children.to_vec(),
-            level: *level,
-            operator: operator.clone(),
-        });
-        entry.node = inode;
+    #[test]
+    fn can_search_string_matching_expressions_on_strings_unknown_to_the_expressions() {
+        let definitions = [
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string_list("tags"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree
+            .insert(&1u64, r#"tags any starts_with "promo_""#)
+            .unwrap();
+        atree
+            .insert(&2u64, r#"not (country ends_with "land")"#)
+            .unwrap();
+        let mut builder = atree.make_event();
+        builder.with_string("country", "Finland").unwrap();
+        builder
+            .with_string_list("tags", &["sports", "promo_summer"])
+            .unwrap();
+        let event = builder.build().unwrap();
+        let mut builder = atree.make_event();
+        builder.with_string("country", "Canada").unwrap();
+        builder.with_string_list("tags", &["sports"]).unwrap();
+        let another_event = builder.build().unwrap();
+
+        assert_eq!(vec![&1u64], atree.search(&event).unwrap().matches());
+        assert_eq!(vec![&2u64], atree.search(&another_event).unwrap().matches());
     }
-}
 
-#[inline]
-fn choose_access_child<T>(
-    left_id: NodeId,
-    right_id: NodeId,
-    parent_id: NodeId,
-    nodes: &mut Slab<Entry<T>>,
-    predicates: &mut Vec<NodeId>,
-) {
-    let left_entry = &nodes[left_id];
-    let right_entry = &nodes[right_id];
-    let accessor_id = if left_entry.cost < right_entry.cost {
-        left_id
-    } else {
-        right_id
-    };
-    add_parent(&mut nodes[accessor_id], parent_id);
-    add_predicate(accessor_id, nodes, predicates);
-}
+    #[test]
+    #[cfg(feature = "normalization")]
+    fn match_the_strings_that_only_differ_by_their_normalization_form_when_normalizing() {
+        let definitions = [
+            AttributeDefinition::string("city"),
+            AttributeDefinition::string_list("tags"),
+        ];
+        let options = ATreeOptions::new().normalize_strings();
+        let mut atree = ATree::with_options(&definitions, options).unwrap();
+        atree.insert(&1u64, "city = 'caf\u{e9}'").unwrap();
+        atree.insert(&2u64, "tags one of ['caf\u{e9}']").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_string("city", "cafe\u{301}").unwrap();
+        builder.with_string_list("tags", &["cafe\u{301}"]).unwrap();
+        let event = builder.build().unwrap();
 
-#[inline]
-fn add_predicate<T>(node_id: NodeId, nodes: &Slab<Entry<T>>, predicates: &mut Vec<NodeId>) {
-    let entry = &nodes[node_id];
-    if entry.is_leaf() && !predicates.contains(&node_id) {
-        predicates.push(node_id);
+        assert_eq!(vec![&1u64, &2u64], atree.search(&event).unwrap().matches());
     }
-}
 
-#[inline]
-fn process_predicates<'a, T>(
-    predicates: &[NodeId],
-    nodes: &'a Slab<Entry<T>>,
-    event: &Event,
-    matches: &mut Vec<&'a T>,
-    results: &mut EvaluationResult,
-    queues: &mut [Vec<(NodeId, &'a Entry<T>)>],
-) {
-    for predicate_id in predicates {
-        let node = &nodes[*predicate_id];
-        // The evaluation is delayed as much as possible; if the predicate has no
-        // subscribers and no parents, there is no point in evaluating eagerly and
-        // it should only be evaluated if there is a need for it.
-        let delay_evaluation = node.subscription_ids.is_empty() && node.parents().is_empty();
-        if delay_evaluation || results.is_evaluated(*predicate_id) {
-            continue;
-        }
+    #[test]
+    fn collapse_the_list_elements_unknown_to_the_expressions_by_default() {
+        let definitions = [AttributeDefinition::string_list("deals")];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, r#"deals one of ["deal-1"]"#).unwrap();
+        atree.insert(&2u64, r#"deals none of ["deal-2"]"#).unwrap();
+        atree
+            .insert(&3u64, r#"deals all of ["deal-1", "deal-2"]"#)
+            .unwrap();
+        let mut builder = atree.make_event();
+        builder
+            .with_string_list("deals", &["deal-8", "deal-1", "deal-9"])
+            .unwrap();
+        let event = builder.build().unwrap();
 
-        let result = node.evaluate(event);
-        results.set_result(*predicate_id, result);
-        add_matches(result, node, matches);
+        let deals = atree.attributes.by_name("deals").unwrap();
+        let deal_1 = atree.strings.get("deal-1");
+        assert!(matches!(
+            &event[deals],
+            AttributeValue::StringList(ids) if *ids == [StringId::UNKNOWN, deal_1]
+        ));
+        assert_eq!(vec![&1u64, &2u64], atree.search(&event).unwrap().matches());
+    }
 
-        node.parents()
-            .iter()
-            .map(|parent_id| (*parent_id, &nodes[*parent_id]))
-            .for_each(|(parent_id, parent)| {
-                if matches!(parent.operator(), Operator::And) && !result.unwrap_or(true) {
-                    results.set_result(parent_id, Some(false));
-                } else {
-                    queues[parent.level() - 2].push((parent_id, parent));
-                }
-            })
+    #[test]
+    fn return_an_error_on_list_elements_unknown_to_the_expressions_when_rejecting_them() {
+        let definitions = [
+            AttributeDefinition::string_list("deals"),
+            AttributeDefinition::string_list("tags"),
+        ];
+        let options = ATreeOptions::new().reject_unknown_list_elements();
+        let mut atree = ATree::with_options(&definitions, options).unwrap();
+        atree.insert(&1u64, r#"deals one of ["deal-1"]"#).unwrap();
+        atree
+            .insert(&2u64, r#"tags any starts_with "promo_""#)
+            .unwrap();
+        let mut builder = atree.make_event();
+
+        assert_eq!(
+            Err(EventError::UnknownListElement {
+                name: "deals".to_string(),
+                value: "deal-9".to_string()
+            }),
+            builder.with_string_list("deals", &["deal-1", "deal-9"])
+        );
+        assert!(builder.with_string_list("deals", &["deal-1"]).is_ok());
+        assert!(builder
+            .with_string_list("tags", &["sports", "promo_summer"])
+            .is_ok());
+        let event = builder.build().unwrap();
+        assert_eq!(vec![&1u64, &2u64], atree.search(&event).unwrap().matches());
     }
-}
 
-#[inline]
-fn evaluate_node<'a, T>(
-    node_id: NodeId,
-    event: &Event,
-    node: &'a Entry<T>,
-    nodes: &'a Slab<Entry<T>>,
-    results: &mut EvaluationResult,
-    matches: &mut Vec<&'a T>,
-) -> Option<bool> {
-    let operator = node.operator();
-    let result = match operator {
-        Operator::And => evaluate_and(node.children(), event, nodes, results, matches),
-        Operator::Or => evaluate_or(node.children(), event, nodes, results, matches),
-    };
-    results.set_result(node_id, result);
-    result
-}
+    #[test]
+    fn can_search_expressions_on_enumerations() {
+        let definitions = [AttributeDefinition::enumeration(
+            "log_level",
+            &["debug", "info", "warn", "error"],
+        )];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree
+            .insert(&1u64, "log_level in ['warn', 'error']")
+            .unwrap();
+        atree.insert(&2u64, "log_level = 'debug'").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_string("log_level", "error").unwrap();
+        let event = builder.build().unwrap();
 
-#[inline]
-fn evaluate_and<'a, T>(
-    children: &[NodeId],
-    event: &Event,
-    nodes: &'a Slab<Entry<T>>,
-    results: &mut EvaluationResult,
-    matches: &mut Vec<&'a T>,
-) -> Option<bool> {
-    let mut acc = Some(true);
-    for child_id in children {
-        let result = lazy_evaluate(*child_id, event, nodes, results, matches);
-        match (acc, result) {
-            (Some(false), _) => {
-                acc = Some(false);
-                break;
-            }
-            (_, Some(false)) => {
-                acc = Some(false);
-                break;
-            }
-            (Some(a), Some(b)) => {
-                acc = Some(a && b);
-            }
-            (_, _) => {
-                acc = None;
-            }
+        assert!(atree.insert(&3u64, "log_level <> 'eror'").is_err());
+        assert_eq!(vec![&1u64], atree.search(&event).unwrap().matches());
+    }
+
+    #[test]
+    fn size_the_report_with_the_expected_amount_of_matches() {
+        let definitions = [AttributeDefinition::integer("exchange_id")];
+        let mut atree = ATree::new(&definitions).unwrap();
+        for i in 0..300u64 {
+            atree.insert(&i, &format!("exchange_id <> {i}")).unwrap();
         }
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1000).unwrap();
+        let event = builder.build().unwrap();
+
+        let report = atree.search(&event).unwrap();
+        assert_eq!(300, report.matches().len());
+        assert!(report.matches.capacity() > ATree::<u64>::DEFAULT_EXPECTED_MATCHES);
+        drop(report);
+
+        atree.set_expected_matches(300);
+        let report = atree.search(&event).unwrap();
+        assert_eq!(300, report.matches().len());
+        assert_eq!(300, report.matches.capacity());
+        assert_eq!(300, report.roots.capacity());
+
+        atree.set_expected_matches(5);
+        let report = atree.search(&atree.make_event().build().unwrap()).unwrap();
+        assert!(report.matches().is_empty());
+        assert_eq!(5, report.matches.capacity());
     }
-    acc
-}
 
-#[inline]
-fn evaluate_or<'a, T>(
-    children: &[NodeId],
-    event: &Event,
-    nodes: &'a Slab<Entry<T>>,
-    results: &mut EvaluationResult,
-    matches: &mut Vec<&'a T>,
-) -> Option<bool> {
-    let mut acc = Some(false);
-    for child_id in children {
-        let result = lazy_evaluate(*child_id, event, nodes, results, matches);
-        match (acc, result) {
-            (Some(true), _) => {
-                acc = Some(true);
-                break;
-            }
-            (_, Some(true)) => {
-                acc = Some(true);
-                break;
-            }
-            (Some(a), Some(b)) => {
-                acc = Some(a || b);
-            }
-            (_, _) => {
-                acc = None;
-            }
+    #[test]
+    fn return_the_same_matches_when_collecting_dense_matches() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::integer_list("segment_ids"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&0u64, "true").unwrap();
+        for id in 1..200u64 {
+            let expression = match id % 4 {
+                0 => format!("exchange_id <> {id}"),
+                1 => format!("exchange_id <> {id} and not private"),
+                2 => format!("private or segment_ids one of [{}, 3]", id % 7),
+                _ => "exchange_id > 10 and segment_ids one of [1, 2]".to_string(),
+            };
+            atree.insert(&id, &expression).unwrap();
+        }
+        for id in (1..200u64).step_by(3) {
+            atree.delete(&id);
+        }
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", false).unwrap();
+        builder.with_integer("exchange_id", 9).unwrap();
+        builder.with_integer_list("segment_ids", &[1, 5]).unwrap();
+        let event = builder.build().unwrap();
+
+        let collect = |report: Report<'_, u64>| {
+            report
+                .matched_roots()
+                .map(|(expression_id, ids)| {
+                    (expression_id, ids.iter().map(|id| **id).collect_vec())
+                })
+                .sorted()
+                .collect_vec()
+        };
+
+        let sparse = collect(atree.search(&event).unwrap());
+        atree.set_dense_matches(true);
+        let dense = collect(atree.search(&event).unwrap());
+
+        assert!(sparse.len() > 50);
+        assert_eq!(sparse, dense);
+    }
+
+    #[test]
+    fn allocate_the_dense_matches_once_with_the_exact_capacity() {
+        let definitions = [AttributeDefinition::integer("exchange_id")];
+        let mut atree = ATree::new(&definitions).unwrap();
+        for i in 0..300u64 {
+            atree.insert(&i, &format!("exchange_id <> {i}")).unwrap();
         }
+        atree.insert(&300u64, "exchange_id <> 0").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1000).unwrap();
+        let event = builder.build().unwrap();
+        atree.set_dense_matches(true);
+
+        let report = atree.search(&event).unwrap();
+
+        assert_eq!(301, report.matches().len());
+        assert_eq!(301, report.matches.capacity());
+        assert_eq!(300, report.roots.len());
+        assert_eq!(300, report.roots.capacity());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn search_the_identical_events_once_with_the_search_cache() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap().with_search_cache(10);
+        atree.insert(&1u64, "exchange_id = 1").unwrap();
+        let make_event = |atree: &ATree<u64>| {
+            let mut builder = atree.make_event();
+            builder.with_integer("exchange_id", 1).unwrap();
+            builder.with_boolean("private", true).unwrap();
+            builder.build().unwrap()
+        };
+
+        assert_eq!(vec![1u64], atree.search_owned(&make_event(&atree)));
+        assert_eq!(vec![1u64], atree.search_owned(&make_event(&atree)));
+        assert_eq!(
+            Some(SearchCacheStats {
+                hits: 1,
+                misses: 1,
+                entries: 1
+            }),
+            atree.search_cache_stats()
+        );
+
+        atree.insert(&2u64, "private").unwrap();
+        assert_eq!(
+            Some(0),
+            atree.search_cache_stats().map(|stats| stats.entries)
+        );
+        let matches = atree.search_owned(&make_event(&atree));
+        assert_eq!(vec![1u64, 2u64], matches.into_iter().sorted().collect_vec());
+
+        atree.delete(&1u64);
+        assert_eq!(vec![2u64], atree.search_owned(&make_event(&atree)));
+        assert_eq!(
+            Some(SearchCacheStats {
+                hits: 1,
+                misses: 3,
+                entries: 1
+            }),
+            atree.search_cache_stats()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn do_not_cache_the_searches_by_default() {
+        let definitions = [AttributeDefinition::boolean("private")];
+        let atree = ATree::<u64>::new(&definitions).unwrap();
+        let disabled = ATree::<u64>::new(&definitions)
+            .unwrap()
+            .with_search_cache(0);
+
+        assert_eq!(None, atree.search_cache_stats());
+        assert_eq!(None, disabled.search_cache_stats());
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn record_the_matches_in_a_span_around_the_search() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string("country"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "exchange_id = 1 and private").unwrap();
+        atree.insert(&2u64, "exchange_id = 1").unwrap();
+        atree.insert(&3u64, "exchange_id = 2").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        builder.with_boolean("private", true).unwrap();
+        let event = builder.build().unwrap();
+        let recorder = crate::test_utils::tracing::Recorder::default();
+
+        tracing::subscriber::with_default(recorder.clone(), || {
+            atree.search(&event).unwrap();
+        });
+
+        let spans = recorder.spans();
+        assert_eq!(1, spans.len());
+        let (name, fields) = &spans[0];
+        assert_eq!("search", *name);
+        assert_eq!(Some(&2), fields.get("attributes"));
+        assert_eq!(Some(&2), fields.get("matches"));
+        assert_eq!(
+            vec![HashMap::from_iter([("level", 2), ("queue_size", 1)])],
+            recorder.events()
+        );
     }
 
-    acc
-}
+    #[test]
+    fn only_count_the_predicates_that_were_evaluated_when_profiling() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        // The set operation costs more so `private` is the access child
+        atree
+            .insert(&1u64, "exchange_id in [1, 2] and private")
+            .unwrap();
+        atree.enable_profiling();
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        builder.with_boolean("private", false).unwrap();
+        let short_circuited = builder.build().unwrap();
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        builder.with_boolean("private", true).unwrap();
+        let event = builder.build().unwrap();
+
+        atree.search_profiled(&short_circuited).unwrap();
+        atree.search_profiled(&short_circuited).unwrap();
+        atree.search_profiled(&event).unwrap();
 
-#[inline]
-fn lazy_evaluate<'a, T>(
-    node_id: NodeId,
-    event: &Event,
-    nodes: &'a Slab<Entry<T>>,
-    results: &mut EvaluationResult,
-    matches: &mut Vec<&'a T>,
-) -> Option<bool> {
-    if results.is_evaluated(node_id) {
-        return results.get_result(node_id);
+        assert_eq!(
+            vec![
+                ("⟨attribute(0), id, variable⟩".to_string(), 3),
+                ("⟨attribute(1), in, [1, 2]⟩".to_string(), 1)
+            ],
+            atree.predicate_profile()
+        );
     }
-    let node = &nodes[node_id];
-    let result = if node.is_leaf() {
-        let result = node.evaluate(event);
-        results.set_result(node_id, result);
-        result
-    } else {
-        evaluate_node(node_id, event, node, nodes, results, matches)
-    };
-    add_matches(result, node, matches);
-    result
-}
 
-#[inline]
-fn add_matches<'a, T>(result: Option<bool>, node: &'a Entry<T>, matches: &mut Vec<&'a T>) {
-    if !node.subscription_ids.is_empty() {
-        if let Some(true) = result {
-            for subscription_id in &node.subscription_ids {
-                matches.push(subscription_id);
-            }
-        }
-    }
-}
+    #[test]
+    fn do_not_count_the_evaluated_predicates_when_profiling_is_disabled() {
+        let definitions = [AttributeDefinition::boolean("private")];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "private").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", true).unwrap();
+        let event = builder.build().unwrap();
 
-#[derive(Clone, Debug)]
-struct Entry<T> {
-    id: ExpressionId,
-    subscription_ids: Vec<T>,
-    node: ATreeNode,
-    use_count: usize,
-    cost: u64,
-}
+        atree.search_profiled(&event).unwrap();
+        atree.enable_profiling();
+        atree.search(&event).unwrap();
 
-impl<T> Entry<T> {
-    fn new(id: ExpressionId, node: ATreeNode, subscription_id: Option<T>, cost: u64) -> Self {
-        Self {
-            id,
-            node,
-            use_count: 1,
-            subscription_ids: subscription_id
-                .map_or_else(Vec::new, |subscription_id| vec![subscription_id]),
-            cost,
-        }
+        assert_eq!(
+            vec![("⟨attribute(0), id, variable⟩".to_string(), 0)],
+            atree.predicate_profile()
+        );
     }
 
-    #[inline]
-    const fn is_leaf(&self) -> bool {
-        matches!(self.node, ATreeNode::LNode(_))
+    #[test]
+    fn list_the_shared_predicates_once_with_their_amount_of_references() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "exchange_id = 1 and private").unwrap();
+        atree.insert(&2u64, "exchange_id = 1 or private").unwrap();
+        atree.insert(&3u64, "exchange_id = 2").unwrap();
+
+        assert_eq!(
+            vec![
+                ("⟨attribute(0), id, variable⟩".to_string(), 2),
+                ("⟨attribute(1), =, 1⟩".to_string(), 2),
+                ("⟨attribute(1), =, 2⟩".to_string(), 1)
+            ],
+            atree.predicates()
+        );
     }
 
-    #[inline]
-    const fn is_root(&self) -> bool {
-        matches!(self.node, ATreeNode::RNode(_))
-    }
+    #[test]
+    fn can_search_an_expression_that_was_first_inserted_as_a_sub_expression() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "exchange_id = 1 and private").unwrap();
+        atree.insert(&2u64, "exchange_id = 1").unwrap();
+        atree.insert(&3u64, "private").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        builder.with_boolean("private", true).unwrap();
+        let event = builder.build().unwrap();
 
-    #[inline]
-    const fn level(&self) -> usize {
-        self.node.level()
-    }
+        let mut matches = atree.search(&event).unwrap().matches().to_vec();
+        matches.sort();
 
-    #[inline]
-    fn evaluate(&self, event: &Event) -> Option<bool> {
-        self.node.evaluate(event)
+        assert_eq!(vec![&1, &2, &3], matches);
     }
 
-    #[inline]
-    fn operator(&self) -> Operator {
-        self.node.operator()
+    #[test]
+    fn flatten_a_chain_of_or_into_a_single_node() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::integer_list("segment_ids"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree
+            .insert(
+                &1u64,
+                "private or exchange_id = 1 or country = 'CA' or segment_ids one of [1, 2]",
+            )
+            .unwrap();
+        let root = atree.nodes_by_ids[&1u64];
+        let mut nested = ATree::new(&definitions).unwrap();
+        nested
+            .insert(
+                &1u64,
+                "(private or exchange_id = 1) or (country = 'CA' or segment_ids one of [1, 2])",
+            )
+            .unwrap();
+        let events = [
+            (false, 2, "US", 3),
+            (true, 2, "US", 3),
+            (false, 1, "US", 3),
+            (false, 2, "CA", 3),
+            (false, 2, "US", 2),
+        ]
+        .map(|(private, exchange_id, country, segment_id)| {
+            let mut builder = atree.make_event();
+            builder.with_boolean("private", private).unwrap();
+            builder.with_integer("exchange_id", exchange_id).unwrap();
+            builder.with_string("country", country).unwrap();
+            builder
+                .with_integer_list("segment_ids", &[segment_id])
+                .unwrap();
+            builder.build().unwrap()
+        });
+
+        assert_eq!(5, atree.nodes.len());
+        assert_eq!(4, atree.nodes[root].children().len());
+        assert_eq!(2, atree.nodes[root].level());
+        assert_eq!(2, atree.root_levels.max_level());
+        assert_eq!(atree.to_graphviz(), nested.to_graphviz());
+        assert_eq!(Ok(()), atree.validate_invariants());
+        for (i, event) in events.iter().enumerate() {
+            let expected: Vec<&u64> = if i == 0 { vec![] } else { vec![&1] };
+            assert_eq!(expected, atree.search(event).unwrap().matches());
+            assert_eq!(expected, nested.search(event).unwrap().matches());
+        }
     }
 
-    #[inline]
-    fn children(&self) -> &[NodeId] {
-        self.node.children()
+    #[test]
+    fn flatten_a_chain_of_and_with_a_single_access_child() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::integer_list("segment_ids"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree
+            .insert(
+                &1u64,
+                "private and exchange_id = 1 and segment_ids one of [1, 2] and exchange_id < 5",
+            )
+            .unwrap();
+        let root = atree.nodes_by_ids[&1u64];
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", true).unwrap();
+        builder.with_integer("exchange_id", 1).unwrap();
+        builder.with_integer_list("segment_ids", &[2]).unwrap();
+        let event = builder.build().unwrap();
+
+        assert_eq!(4, atree.nodes[root].children().len());
+        assert_eq!(Ok(()), atree.validate_invariants());
+        assert_eq!(vec![&1u64], atree.search(&event).unwrap().matches());
     }
 
-    #[inline]
-    fn parents(&self) -> &[NodeId] {
-        self.node.parents()
+    #[test]
+    fn share_a_negated_and_expression_with_the_or_of_its_negated_operands() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree
+            .insert(&1u64, "not (private and exchange_id = 1)")
+            .unwrap();
+        let nodes = atree.nodes.len();
+        atree
+            .insert(&2u64, "not private or exchange_id <> 1")
+            .unwrap();
+        let events = [
+            (false, Some(1)),
+            (true, Some(1)),
+            (true, Some(2)),
+            (true, None),
+        ]
+        .map(|(private, exchange_id)| {
+            let mut builder = atree.make_event();
+            builder.with_boolean("private", private).unwrap();
+            if let Some(exchange_id) = exchange_id {
+                builder.with_integer("exchange_id", exchange_id).unwrap();
+            }
+            builder.build().unwrap()
+        });
+
+        assert_eq!(3, nodes);
+        assert_eq!(nodes, atree.nodes.len());
+        for (event, expected) in events
+            .iter()
+            .zip([vec![&1u64, &2], vec![], vec![&1, &2], vec![]])
+        {
+            let mut matches = atree.search(event).unwrap().matches().to_vec();
+            matches.sort();
+            assert_eq!(expected, matches);
+        }
     }
-}
 
-#[derive(Clone, Debug)]
-#[allow(clippy::enum_variant_names)]
-enum ATreeNode {
-    LNode(LNode),
-    INode(INode),
-    RNode(RNode),
-}
+    #[test]
+    fn remove_the_links_to_a_deleted_expression_from_its_remaining_sub_expressions() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "exchange_id = 1 or private").unwrap();
+        atree.insert(&2u64, "private").unwrap();
 
-impl ATreeNode {
-    #[inline]
-    fn lnode(predicate: &Predicate) -> Self {
-        Self::LNode(LNode {
-            level: 1,
-            parents: vec![],
-            predicate: predicate.clone(),
-        })
+        atree.delete(&1u64);
+
+        let (_, entry) = atree.nodes.iter().next().unwrap();
+        assert!(entry.parents().is_empty());
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", true).unwrap();
+        let event = builder.build().unwrap();
+        assert_eq!(vec![&2u64], atree.search(&event).unwrap().matches());
     }
 
-    #[inline]
-    const fn level(&self) -> usize {
-        match self {
-            Self::RNode(node) => node.level,
-            Self::LNode(node) => node.level,
-            Self::INode(node) => node.level,
-        }
+    fn a_tree_with_shared_expressions() -> ATree<u64> {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string("country"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "private or exchange_id = 1").unwrap();
+        atree
+            .insert(&2u64, "private and exchange_id = 2 and country = 'CA'")
+            .unwrap();
+        atree.insert(&3u64, "private and exchange_id = 2").unwrap();
+        atree.insert(&4u64, "private").unwrap();
+        atree
+            .replace(&3u64, "(private or exchange_id = 1) and country = 'US'")
+            .unwrap();
+        atree.delete(&2u64);
+        atree
     }
 
-    #[inline]
-    fn evaluate(&self, event: &Event) -> Option<bool> {
-        match self {
-            Self::LNode(node) => node.predicate.evaluate(event),
-            node => unreachable!("evaluating {node:?} which is not a predicate; this is a bug."),
-        }
+    #[test]
+    fn validate_the_invariants_of_a_tree_after_insertions_and_deletions() {
+        let mut atree = a_tree_with_shared_expressions();
+
+        assert_eq!(Ok(()), atree.validate_invariants());
+        atree.delete(&1u64);
+        atree.delete(&4u64);
+        assert_eq!(Ok(()), atree.validate_invariants());
+        atree.delete(&3u64);
+        assert_eq!(Ok(()), atree.validate_invariants());
+        assert!(atree.nodes.is_empty());
     }
 
-    #[inline]
-    fn operator(&self) -> Operator {
-        match self {
-            Self::LNode(_) => {
-                unreachable!("trying to get the operator of leaf node; this is a bug");
-            }
-            Self::RNode(RNode { operator, .. }) | Self::INode(INode { operator, .. }) => {
-                operator.clone()
-            }
+    #[test]
+    fn describe_the_corruption_of_a_tree_that_breaks_its_invariants() {
+        type Corruption = fn(&mut ATree<u64>) -> String;
+        let corruptions: [Corruption; 5] = [
+            |atree| {
+                atree.predicates.push(1_000);
+                "the predicate 1000 is not a live node".to_string()
+            },
+            |atree| {
+                let leaf = atree.predicates[0];
+                if let ATreeNode::LNode(node) = &mut atree.nodes[leaf].node {
+                    node.parents.push(1_000);
+                }
+                format!("the node {leaf} has the parent 1000 which does not have it as a child")
+            },
+            |atree| {
+                let root = atree.nodes_by_ids[&1u64];
+                let child = atree.nodes[root].children()[0];
+                atree.nodes[child].node.remove_parent(root);
+                format!("the node {root} is the parent of 1 of its children instead of 2")
+            },
+            |atree| {
+                let root = atree.nodes_by_ids[&1u64];
+                atree.nodes[root].use_count += 1;
+                format!("the node {root} has a use count of 3 instead of 2")
+            },
+            |atree| {
+                let root = atree.nodes_by_ids[&1u64];
+                let expression_id = atree.nodes[root].id;
+                atree.expression_to_node.remove(&expression_id);
+                format!(
+                    "the expression {expression_id} of the node {root} is not mapped to any node"
+                )
+            },
+        ];
+
+        for corrupt in corruptions {
+            let mut atree = a_tree_with_shared_expressions();
+            let expected = corrupt(&mut atree);
+
+            assert_eq!(Err(expected), atree.validate_invariants());
         }
     }
 
-    #[inline]
-    fn children(&self) -> &[NodeId] {
-        match self {
-            Self::INode(INode { children, .. }) | Self::RNode(RNode { children, .. }) => children,
-            Self::LNode(_) => unreachable!("cannot get children for l-node; this is a bug"),
-        }
+    #[test]
+    fn can_retrieve_the_text_of_an_inserted_expression() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let options = ATreeOptions::new().keep_expression_texts();
+        let mut atree = ATree::with_options(&definitions, options).unwrap();
+        atree
+            .insert(&1u64, "exchange_id = 1 and   not private")
+            .unwrap();
+        atree.insert(&2u64, "exchange_id = 2").unwrap();
+        atree.replace(&2u64, "exchange_id in [2, 3]").unwrap();
+        atree.insert(&3u64, "private").unwrap();
+        atree.delete(&3u64);
+
+        assert_eq!(
+            Some("exchange_id = 1 and   not private"),
+            atree.expression_text(&1u64)
+        );
+        assert_eq!(Some("exchange_id in [2, 3]"), atree.expression_text(&2u64));
+        assert_eq!(None, atree.expression_text(&3u64));
     }
 
-    #[inline]
-    fn parents(&self) -> &[NodeId] {
-        match self {
-            Self::INode(INode { parents, .. }) | Self::LNode(LNode { parents, .. }) => parents,
-            Self::RNode(_) => unreachable!("cannot get parents for r-node; this is a bug"),
-        }
+    #[test]
+    fn do_not_keep_the_text_of_the_expressions_by_default() {
+        let definitions = [AttributeDefinition::boolean("private")];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "private").unwrap();
+
+        assert_eq!(None, atree.expression_text(&1u64));
     }
 
-    #[inline]
-    fn add_parent(&mut self, parent_id: NodeId) {
-        match self {
-            ATreeNode::INode(node) => {
-                node.parents.push(parent_id);
-            }
-            ATreeNode::LNode(node) => {
-                node.parents.push(parent_id);
-            }
-            ATreeNode::RNode(node) => {
-                unreachable!("trying to insert parents to r-node {node:?} which cannot have any parents; this is a bug");
+    #[test]
+    fn match_the_same_events_with_a_built_expression_as_with_its_dsl() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string("country"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        let expression = Expr::and(
+            Expr::predicate("exchange_id", Condition::Equal(1.into())),
+            Expr::or(
+                Expr::predicate("private", Condition::IsTrue),
+                Expr::not(Expr::predicate(
+                    "country",
+                    Condition::In(vec!["US", "CA"].into()),
+                )),
+            ),
+        );
+        atree.insert_expr(&1u64, &expression).unwrap();
+        atree
+            .insert(
+                &2u64,
+                r#"exchange_id = 1 and (private or not country in ["CA", "US"])"#,
+            )
+            .unwrap();
+
+        assert_eq!(atree.nodes_by_ids[&1u64], atree.nodes_by_ids[&2u64]);
+        let mut matching_events = 0;
+        for exchange_id in [None, Some(1), Some(2)] {
+            for private in [None, Some(true), Some(false)] {
+                for country in [None, Some("CA"), Some("FR")] {
+                    let mut builder = atree.make_event();
+                    if let Some(exchange_id) = exchange_id {
+                        builder.with_integer("exchange_id", exchange_id).unwrap();
+                    }
+                    if let Some(private) = private {
+                        builder.with_boolean("private", private).unwrap();
+                    }
+                    if let Some(country) = country {
+                        builder.with_string("country", country).unwrap();
+                    }
+                    let event = builder.build().unwrap();
+
+                    let report = atree.search(&event).unwrap();
+
+                    assert_eq!(report.is_match(&1u64), report.is_match(&2u64));
+                    if report.is_match(&1u64) {
+                        matching_events += 1;
+                    }
+                }
             }
         }
+        assert_eq!(5, matching_events);
     }
-}
 
-#[derive(Clone, Debug)]
-struct LNode {
-    parents: Vec<NodeId>,
-    level: usize,
-    predicate: Predicate,
-}
+    #[test]
+    fn forget_the_text_of_an_expression_replaced_by_a_built_one() {
+        let definitions = [AttributeDefinition::boolean("private")];
+        let options = ATreeOptions::new().keep_expression_texts();
+        let mut atree = ATree::with_options(&definitions, options).unwrap();
+        atree.insert(&1u64, "private").unwrap();
 
-#[derive(Clone, Debug)]
-struct INode {
-    parents: Vec<NodeId>,
-    children: Vec<NodeId>,
-    level: usize,
-    operator: Operator,
-}
+        atree
+            .insert_expr(
+                &1u64,
+                &Expr::not(Expr::predicate("private", Condition::IsTrue)),
+            )
+            .unwrap();
 
-#[derive(Clone, Debug)]
-struct RNode {
-    children: Vec<NodeId>,
-    level: usize,
-    operator: Operator,
-}
+        assert_eq!(None, atree.expression_text(&1u64));
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", false).unwrap();
+        let event = builder.build().unwrap();
+        assert_eq!(vec![&1u64], atree.search(&event).unwrap().matches());
+    }
 
-#[derive(Debug)]
-/// Structure that holds the search results from the [`ATree::search()`] function
-pub struct Report<'a, T> {
-    matches: Vec<&'a T>,
-}
+    #[test]
+    fn match_every_event_with_a_true_expression() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "true").unwrap();
+        atree.insert(&2u64, "false").unwrap();
+        atree.insert(&3u64, "private").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", true).unwrap();
+        let event = builder.build().unwrap();
+        let empty_event = atree.make_event().build().unwrap();
 
-impl<'a, T> Report<'a, T> {
-    const fn new(matches: Vec<&'a T>) -> Self {
-        Self { matches }
-    }
+        let report = atree.search(&event).unwrap();
+        let empty_report = atree.search(&empty_event).unwrap();
 
-    #[inline]
-    /// Get the search matches
-    pub fn matches(&self) -> &[&'a T] {
-        &self.matches
+        assert_eq!(
+            vec![&1u64, &3u64],
+            report.matches().iter().copied().sorted().collect_vec()
+        );
+        assert_eq!(vec![&1u64], empty_report.matches());
+        assert!(report
+            .matched_roots()
+            .any(|(id, ids)| id == OptimizedNode::Constant(true).id() && ids == [&1u64]));
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
-    const AN_INVALID_BOOLEAN_EXPRESSION: &str = "invalid in (1, 2, 3 and";
-    const AN_EXPRESSION: &str = "exchange_id = 1";
-    const A_NOT_EXPRESSION: &str = "not private";
-    const AN_EXPRESSION_WITH_AND_OPERATORS: &str =
-        r#"exchange_id = 1 and deals one of ["deal-1", "deal-2"]"#;
-    const AN_EXPRESSION_WITH_OR_OPERATORS: &str =
-        r#"exchange_id = 1 or deals one of ["deal-1", "deal-2"]"#;
-    const A_COMPLEX_EXPRESSION: &str = r#"exchange_id = 1 and not private and deal_ids one of ["deal-1", "deal-2"] and segment_ids one of [1, 2, 3] and country = 'CA' and city in ['QC'] or country = 'US' and city in ['AZ']"#;
-    const ANOTHER_COMPLEX_EXPRESSION: &str = r#"exchange_id = 1 and not private and deal_ids one of ["deal-1", "deal-2"] and segment_ids one of [1, 2, 3] and country in ['FR', 'GB']"#;
+    #[test]
+    fn can_delete_and_replace_constant_expressions() {
+        let definitions = [AttributeDefinition::boolean("private")];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "true").unwrap();
+        atree.insert(&2u64, "private").unwrap();
+        atree.insert(&3u64, "false").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", false).unwrap();
+        let event = builder.build().unwrap();
 
-    fn is_sync_and_send<T: Send + Sync>() {}
+        atree.replace(&2u64, "true").unwrap();
+        atree.replace(&3u64, "not private").unwrap();
+        let deleted = atree.delete(&1u64);
+
+        assert_eq!(DeleteOutcome::Removed { freed_nodes: 0 }, deleted);
+        assert_eq!(
+            vec![&2u64, &3u64],
+            atree
+                .search(&event)
+                .unwrap()
+                .matches()
+                .iter()
+                .copied()
+                .sorted()
+                .collect_vec()
+        );
+        assert_eq!(2, atree.iter_roots().count());
+    }
 
     #[test]
-    fn support_sync_and_send_traits() {
-        is_sync_and_send::<ATree<u64>>();
+    fn match_the_events_that_define_any_attribute_of_a_group() {
+        let definitions = [
+            AttributeDefinition::string_list("deal_ids"),
+            AttributeDefinition::integer_list("segment_ids"),
+            AttributeDefinition::integer("exchange_id"),
+        ];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree
+            .insert(&1u64, "any_defined(deal_ids, segment_ids)")
+            .unwrap();
+        atree
+            .insert(&2u64, "not any_defined(deal_ids, segment_ids)")
+            .unwrap();
+        let search = |atree: &ATree<u64>, deals: Option<&[&str]>, segments: Option<&[i64]>| {
+            let mut builder = atree.make_event();
+            builder.with_integer("exchange_id", 1).unwrap();
+            if let Some(deals) = deals {
+                builder.with_string_list("deal_ids", deals).unwrap();
+            }
+            if let Some(segments) = segments {
+                builder.with_integer_list("segment_ids", segments).unwrap();
+            }
+            let event = builder.build().unwrap();
+            atree
+                .search(&event)
+                .unwrap()
+                .matches()
+                .iter()
+                .map(|id| **id)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec![2u64], search(&atree, None, None));
+        assert_eq!(vec![1u64], search(&atree, None, Some(&[1])));
+        assert_eq!(vec![1u64], search(&atree, Some(&["deal-1"]), Some(&[])));
+        assert_eq!(
+            Some(vec!["deal_ids", "segment_ids"]),
+            atree.required_attributes(&1u64)
+        );
     }
 
     #[test]
-    fn can_build_an_atree() {
+    fn report_all_the_invalid_predicates_of_each_expression() {
         let definitions = [
             AttributeDefinition::boolean("private"),
-            AttributeDefinition::string_list("deals"),
             AttributeDefinition::integer("exchange_id"),
-            AttributeDefinition::float("bidfloor"),
             AttributeDefinition::string("country"),
-            AttributeDefinition::integer_list("segment_ids"),
+        ];
+        let expressions = [
+            "exchange_id = 1 and private",
+            "exchange_id = 'CA' and private and country < 5",
+            "country in ['CA', 'US'] or not private",
         ];
 
-        let result = ATree::<u64>::new(&definitions);
+        let results = validate_all(&expressions, &definitions);
+
+        assert_eq!(3, results.len());
+        assert!(matches!(results[0], (0, Ok(()))));
+        assert!(matches!(results[2], (2, Ok(()))));
+        let (index, result) = &results[1];
+        assert_eq!(1, *index);
+        assert!(matches!(
+            result,
+            Err(ATreeError::ParseError(ParseError::User {
+                error: ParserError::Events(errors)
+            })) if errors.len() == 2
+                && errors.iter().all(|error| matches!(error, EventError::UnsupportedOperator { .. }))
+        ));
+    }
 
-        assert!(result.is_ok());
+    #[test]
+    fn report_a_single_invalid_predicate_and_the_syntax_errors_like_the_insertions() {
+        let definitions = [AttributeDefinition::integer("exchange_id")];
+        let expressions = ["exchange_id = 'CA'", "exchange_id = 'CA' and"];
+
+        let results = validate_all(&expressions, &definitions);
+
+        assert!(matches!(
+            &results[0],
+            (
+                0,
+                Err(ATreeError::ParseError(ParseError::User {
+                    error: ParserError::Event(EventError::UnsupportedOperator { .. })
+                }))
+            )
+        ));
+        assert!(matches!(
+            &results[1],
+            (
+                1,
+                Err(ATreeError::ParseError(ParseError::UnrecognizedEof { .. }))
+            )
+        ));
     }
 
     #[test]
-    fn return_an_error_on_duplicate_definitions() {
+    fn report_the_invalid_definitions_for_every_expression() {
         let definitions = [
             AttributeDefinition::boolean("private"),
-            AttributeDefinition::string("country"),
-            AttributeDefinition::string_list("deals"),
-            AttributeDefinition::integer("exchange_id"),
-            AttributeDefinition::float("bidfloor"),
-            AttributeDefinition::integer("country"),
-            AttributeDefinition::integer_list("segment_ids"),
+            AttributeDefinition::boolean("private"),
         ];
 
-        let result = ATree::<u64>::new(&definitions);
+        let results = validate_all(&["private", "not private"], &definitions);
 
-        assert!(result.is_err());
+        assert_eq!(2, results.len());
+        assert!(results.iter().all(|(_, result)| matches!(
+            result,
+            Err(ATreeError::Event(EventError::AlreadyPresent(_)))
+        )));
     }
 
     #[test]
-    fn return_an_error_on_invalid_boolean_expression() {
-        let definitions = [
-            AttributeDefinition::boolean("private"),
-            AttributeDefinition::string("country"),
-            AttributeDefinition::string_list("deals"),
-            AttributeDefinition::integer("exchange_id"),
-            AttributeDefinition::integer_list("segment_ids"),
-        ];
+    fn sort_the_matches_of_subscription_ids_without_order_by_their_key() {
+        #[derive(Clone, PartialEq, Eq, Hash, Debug)]
+        struct Campaign {
+            rank: u32,
+            name: &'static str,
+        }
+
+        let definitions = [AttributeDefinition::integer("exchange_id")];
         let mut atree = ATree::new(&definitions).unwrap();
+        let campaigns = [
+            Campaign { rank: 3, name: "c" },
+            Campaign { rank: 1, name: "a" },
+            Campaign { rank: 4, name: "d" },
+            Campaign { rank: 2, name: "b" },
+        ];
+        for (campaign, expression) in campaigns.iter().zip([
+            "exchange_id = 1",
+            "exchange_id < 5 and exchange_id > 0",
+            "exchange_id = 2",
+            "exchange_id in [1, 2]",
+        ]) {
+            atree.insert(campaign, expression).unwrap();
+        }
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        let event = builder.build().unwrap();
 
-        let result = atree.insert(&1u64, AN_INVALID_BOOLEAN_EXPRESSION);
+        let matches = atree
+            .search_sorted_by_key(&event, |campaign| campaign.rank)
+            .unwrap();
 
-        assert!(result.is_err());
+        assert_eq!(
+            vec!["a", "b", "c"],
+            matches
+                .iter()
+                .map(|campaign| campaign.name)
+                .collect::<Vec<_>>()
+        );
     }
 
     #[test]
-    fn return_an_error_on_empty_boolean_expression() {
+    fn can_list_the_attributes_required_by_an_expression() {
         let definitions = [
             AttributeDefinition::boolean("private"),
-            AttributeDefinition::string("country"),
-            AttributeDefinition::string_list("deals"),
             AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string_list("deal_ids"),
             AttributeDefinition::integer_list("segment_ids"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string("city"),
+            AttributeDefinition::string("continent"),
         ];
         let mut atree = ATree::new(&definitions).unwrap();
-
-        let result = atree.insert(&1u64, "");
-
-        assert!(result.is_err());
+        atree.insert(&1u64, A_COMPLEX_EXPRESSION).unwrap();
+        atree.insert(&2u64, "true").unwrap();
+
+        assert_eq!(
+            Some(vec![
+                "city",
+                "country",
+                "deal_ids",
+                "exchange_id",
+                "private",
+                "segment_ids"
+            ]),
+            atree.required_attributes(&1u64)
+        );
+        assert_eq!(Some(vec![]), atree.required_attributes(&2u64));
+        assert_eq!(None, atree.required_attributes(&3u64));
     }
 
     #[test]
-    fn can_insert_a_simple_expression() {
+    fn can_insert_a_compiled_expression_multiple_times() {
         let definitions = [
             AttributeDefinition::boolean("private"),
             AttributeDefinition::string("country"),
-            AttributeDefinition::string_list("deals"),
-            AttributeDefinition::integer("exchange_id"),
-            AttributeDefinition::integer_list("segment_ids"),
         ];
         let mut atree = ATree::new(&definitions).unwrap();
+        let compiled = atree
+            .compile("country in ['CA', 'US'] and private")
+            .unwrap();
+        for id in [1u64, 2, 3] {
+            atree.insert_compiled(&id, &compiled).unwrap();
+        }
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", true).unwrap();
+        builder.with_string("country", "US").unwrap();
+        let event = builder.build().unwrap();
 
-        let result = atree.insert(&1u64, AN_EXPRESSION);
+        let report = atree.search(&event).unwrap();
 
-        assert!(result.is_ok());
+        assert_eq!(1, atree.iter_roots().count());
+        assert_eq!(
+            vec![&1u64, &2u64, &3u64],
+            report.matches().iter().copied().sorted().collect_vec()
+        );
     }
 
     #[test]
-    fn can_insert_an_expression_that_refers_to_a_rnode() {
+    fn return_an_error_when_inserting_an_expression_compiled_by_another_tree() {
+        let definitions = [AttributeDefinition::string("country")];
+        let mut atree = ATree::new(&definitions).unwrap();
+        let mut another = ATree::<u64>::new(&definitions).unwrap();
+        let compiled = another.compile("country = 'CA'").unwrap();
+        let mut clone = atree.clone();
+        clone.insert(&1u64, "country = 'US'").unwrap();
+        let diverging = atree.compile("country = 'FR'").unwrap();
+
+        assert!(matches!(
+            atree.insert_compiled(&1u64, &compiled),
+            Err(ATreeError::ForeignCompiledExpression)
+        ));
+        assert!(matches!(
+            clone.insert_compiled(&2u64, &diverging),
+            Err(ATreeError::ForeignCompiledExpression)
+        ));
+    }
+
+    #[test]
+    fn return_an_error_when_the_strings_of_a_named_list_diverged_in_the_clone() {
+        let definitions = [AttributeDefinition::string("c")];
+        let mut atree = ATree::new(&definitions).unwrap();
+        let mut clone = atree.clone();
+        atree.define_string_list("EU", &["FR"]).unwrap();
+        clone.insert(&9u64, "c = 'DE'").unwrap();
+        let compiled = atree.compile("c in @EU").unwrap();
+
+        assert!(matches!(
+            clone.insert_compiled(&1u64, &compiled),
+            Err(ATreeError::ForeignCompiledExpression)
+        ));
+        let mut builder = clone.make_event();
+        builder.with_string("c", "DE").unwrap();
+        let event = builder.build().unwrap();
+        assert_eq!(vec![&9u64], clone.search(&event).unwrap().matches());
+    }
+
+    #[test]
+    fn can_render_to_graphviz() {
         let definitions = [
             AttributeDefinition::boolean("private"),
             AttributeDefinition::integer("exchange_id"),
             AttributeDefinition::string_list("deal_ids"),
+            AttributeDefinition::string_list("deals"),
+            AttributeDefinition::integer_list("segment_ids"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string("city"),
         ];
         let an_expression = "private or exchange_id = 1";
         let another_expression =
             r#"private or exchange_id = 1 or deal_ids one of ["deal-1", "deal-2"]"#;
         let mut atree = ATree::new(&definitions).unwrap();
-        assert!(atree.insert(&1u64, an_expression).is_ok());
-        assert!(atree.insert(&2u64, another_expression).is_ok());
+        atree.insert(&1u64, an_expression).unwrap();
+        atree.insert(&2u64, another_expression).unwrap();
+
+        assert!(!atree.to_graphviz().is_empty());
     }
 
     #[test]
-    fn can_insert_the_same_expression_multiple_times() {
+    fn render_the_same_graphviz_regardless_of_the_insertion_order() {
         let definitions = [
             AttributeDefinition::boolean("private"),
-            AttributeDefinition::string("country"),
-            AttributeDefinition::string_list("deals"),
             AttributeDefinition::integer("exchange_id"),
             AttributeDefinition::integer_list("segment_ids"),
         ];
+        let expressions = [
+            (1u64, "private or exchange_id = 1"),
+            (2, "private and segment_ids one of [1, 2]"),
+            (
+                3,
+                "(private or exchange_id = 1) and segment_ids one of [1, 2]",
+            ),
+            (4, "exchange_id = 2"),
+        ];
         let mut atree = ATree::new(&definitions).unwrap();
+        for (id, expression) in &expressions {
+            atree.insert(id, expression).unwrap();
+        }
+        let mut another_atree = ATree::new(&definitions).unwrap();
+        another_atree
+            .insert(&5, "exchange_id = 3 or private")
+            .unwrap();
+        for (id, expression) in expressions.iter().rev() {
+            another_atree.insert(id, expression).unwrap();
+        }
+        another_atree.delete(&5);
 
-        assert!(atree.insert(&1u64, AN_EXPRESSION).is_ok());
-        assert!(atree.insert(&2u64, AN_EXPRESSION).is_ok());
+        assert_eq!(atree.to_graphviz(), another_atree.to_graphviz());
     }
 
     #[test]
-    fn can_insert_a_negative_expression() {
+    fn can_render_the_evaluation_of_an_event_to_graphviz() {
         let definitions = [
             AttributeDefinition::boolean("private"),
-            AttributeDefinition::string("country"),
-            AttributeDefinition::string_list("deals"),
             AttributeDefinition::integer("exchange_id"),
-            AttributeDefinition::integer_list("segment_ids"),
         ];
         let mut atree = ATree::new(&definitions).unwrap();
+        atree
+            .insert(&1u64, "exchange_id = 1 and not private")
+            .unwrap();
+        atree.insert(&2u64, "exchange_id = 2").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", false).unwrap();
+        builder.with_integer("exchange_id", 1).unwrap();
+        let event = builder.build().unwrap();
+        let root = atree.nodes[atree.nodes_by_ids[&1u64]].id;
 
-        let result = atree.insert(&1u64, A_NOT_EXPRESSION);
+        let graphviz = atree.to_graphviz_trace(&event);
 
-        assert!(result.is_ok());
+        assert!(graphviz.contains(&format!(
+            r#"node_{root} [label = "{{{root} | level: 2 | And | subscriptions: [1] | r-node}}", style = "filled", fillcolor = "green"];"#
+        )));
+        assert!(graphviz.contains(r#"fillcolor = "red""#));
+        assert!(!atree.to_graphviz().contains("fillcolor"));
     }
 
     #[test]
-    fn can_insert_an_expression_with_and_operators() {
+    fn match_the_default_value_of_an_attribute_that_the_event_does_not_set() {
         let definitions = [
-            AttributeDefinition::boolean("private"),
-            AttributeDefinition::string("country"),
-            AttributeDefinition::string_list("deals"),
+            AttributeDefinition::boolean("debug").with_default(AttributeValue::Boolean(false)),
             AttributeDefinition::integer("exchange_id"),
-            AttributeDefinition::integer_list("segment_ids"),
         ];
         let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "not debug").unwrap();
+        atree.insert(&2u64, "exchange_id = 1 and debug").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        let event = builder.build().unwrap();
 
-        let result = atree.insert(&1u64, AN_EXPRESSION_WITH_AND_OPERATORS);
-
-        assert!(result.is_ok());
+        assert_eq!(vec![&1u64], atree.search(&event).unwrap().matches());
     }
 
     #[test]
-    fn can_insert_an_expression_with_or_operators() {
+    fn override_the_default_value_of_an_attribute_that_the_event_sets() {
         let definitions = [
-            AttributeDefinition::boolean("private"),
-            AttributeDefinition::string("country"),
-            AttributeDefinition::string_list("deals"),
+            AttributeDefinition::boolean("debug").with_default(AttributeValue::Boolean(false)),
             AttributeDefinition::integer("exchange_id"),
-            AttributeDefinition::integer_list("segment_ids"),
         ];
         let mut atree = ATree::new(&definitions).unwrap();
-
-        let result = atree.insert(&1u64, AN_EXPRESSION_WITH_OR_OPERATORS);
-
-        assert!(result.is_ok());
+        atree.insert(&1u64, "not debug").unwrap();
+        atree.insert(&2u64, "exchange_id = 1 and debug").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_integer("exchange_id", 1).unwrap();
+        builder.with_boolean("debug", true).unwrap();
+        let event = builder.build_and_reset().unwrap();
+        builder.with_undefined("debug").unwrap();
+        let undefined = builder.build_and_reset().unwrap();
+        let reset = builder.build().unwrap();
+
+        assert_eq!(vec![&2u64], atree.search(&event).unwrap().matches());
+        assert!(atree.search(&undefined).unwrap().is_empty());
+        assert_eq!(vec![&1u64], atree.search(&reset).unwrap().matches());
     }
 
     #[test]
-    fn can_insert_an_expression_with_mixed_operators() {
+    fn match_the_text_of_a_default_string() {
+        let mut strings = StringTable::new();
+        let country = strings.get_or_update("CA").unwrap();
         let definitions = [
-            AttributeDefinition::boolean("private"),
-            AttributeDefinition::integer("exchange_id"),
-            AttributeDefinition::string_list("deal_ids"),
-            AttributeDefinition::integer_list("segment_ids"),
-            AttributeDefinition::string("country"),
-            AttributeDefinition::string("city"),
+            AttributeDefinition::string("country").with_default(AttributeValue::String(country))
         ];
-        let mut atree = ATree::new(&definitions).unwrap();
+        let mut atree =
+            ATree::with_interner(&definitions, ATreeOptions::default(), strings).unwrap();
+        atree.insert(&1u64, "country starts_with 'C'").unwrap();
+        let event = atree.make_event().build().unwrap();
 
-        let result = atree.insert(&1u64, A_COMPLEX_EXPRESSION);
+        assert_eq!(vec![&1u64], atree.search(&event).unwrap().matches());
+    }
 
-        assert!(result.is_ok());
+    #[test]
+    fn return_an_error_when_the_default_value_does_not_match_the_kind_of_the_attribute() {
+        let definitions =
+            [AttributeDefinition::boolean("debug").with_default(AttributeValue::Integer(0))];
+
+        let result = ATree::<u64>::new(&definitions);
+
+        assert!(matches!(
+            result,
+            Err(ATreeError::Event(EventError::WrongType {
+                ref name,
+                expected: AttributeKind::Boolean,
+                actual: AttributeKind::Integer,
+            })) if name == "debug"
+        ));
     }
 
     #[test]
-    fn can_insert_multiple_expressions_with_mixed_operators() {
-        let definitions = [
-            AttributeDefinition::boolean("private"),
-            AttributeDefinition::integer("exchange_id"),
-            AttributeDefinition::string_list("deal_ids"),
-            AttributeDefinition::integer_list("segment_ids"),
-            AttributeDefinition::string("country"),
-            AttributeDefinition::string("city"),
-        ];
+    fn round_the_event_values_to_the_float_scale_of_the_attribute() {
+        let definitions = [AttributeDefinition::float("price").with_float_scale(2)];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "price = 1.00").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_float("price", 1004, 3).unwrap();
+        let event = builder.build().unwrap();
+
+        assert_eq!(vec![&1u64], atree.search(&event).unwrap().matches());
+    }
+
+    #[test]
+    fn round_the_literals_and_the_event_values_half_to_even() {
+        let definitions = [AttributeDefinition::float("price").with_float_scale(2)];
         let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "price = 1.005").unwrap();
+        atree.insert(&2u64, "price = 1.00").unwrap();
+        atree.insert(&3u64, "price = 1.01").unwrap();
+        atree.insert(&4u64, "price > 1.005").unwrap();
+        let handle = atree.attribute_handle("price").unwrap();
+        let mut builder = atree.make_event();
+        builder.with_float("price", 1005, 3).unwrap();
+        let by_name = builder.build_and_reset().unwrap();
+        builder.with_float_h(handle, 1005, 3).unwrap();
+        let by_handle = builder.build().unwrap();
+
+        for event in [by_name, by_handle] {
+            let mut matches = atree.search(&event).unwrap().matches().to_vec();
+            matches.sort();
+            assert_eq!(vec![&1u64, &2u64], matches);
+        }
+    }
+
+    #[test]
+    fn return_an_error_when_a_non_float_attribute_has_a_float_scale() {
+        let definitions = [AttributeDefinition::integer("exchange_id").with_float_scale(2)];
 
-        assert!(atree.insert(&1u64, A_COMPLEX_EXPRESSION).is_ok());
-        assert!(atree.insert(&2u64, ANOTHER_COMPLEX_EXPRESSION).is_ok());
+        let result = ATree::<u64>::new(&definitions);
+
+        assert!(matches!(
+            result,
+            Err(ATreeError::Event(EventError::UnsupportedFloatScale {
+                ref name,
+                actual: AttributeKind::Integer,
+            })) if name == "exchange_id"
+        ));
     }
 
     #[test]
-    fn can_search_an_empty_tree() {
-        let definitions = [
-            AttributeDefinition::boolean("private"),
-            AttributeDefinition::integer("exchange_id"),
-            AttributeDefinition::string_list("deal_ids"),
-            AttributeDefinition::string_list("deals"),
-            AttributeDefinition::integer_list("segment_ids"),
-            AttributeDefinition::string("country"),
-            AttributeDefinition::string("city"),
-        ];
-        let atree = ATree::new(&definitions).unwrap();
-        let mut builder = atree.make_event();
-        builder.with_boolean("private", false).unwrap();
-        let event = builder.build().unwrap();
+    fn build_the_same_tree_when_inserting_the_same_expressions_in_the_same_order() {
+        let build = || {
+            let definitions = [
+                AttributeDefinition::boolean("private"),
+                AttributeDefinition::string_list("deal_ids"),
+                AttributeDefinition::integer("exchange_id"),
+                AttributeDefinition::string("country"),
+                AttributeDefinition::string("city"),
+                AttributeDefinition::integer_list("segment_ids"),
+            ];
+            let mut atree = ATree::new(&definitions).unwrap();
+            for (id, expression) in [
+                (1u64, A_COMPLEX_EXPRESSION),
+                (2u64, ANOTHER_COMPLEX_EXPRESSION),
+                (3u64, AN_EXPRESSION),
+                (4u64, A_NOT_EXPRESSION),
+            ] {
+                atree.insert(&id, expression).unwrap();
+            }
+            atree.delete(&3u64);
+            atree
+                .insert(&5u64, "deal_ids one of [\"deal-1\"] or private")
+                .unwrap();
+            atree
+        };
 
-        let expected: Vec<&u64> = vec![];
-        let actual = atree.search(&event).unwrap().matches().to_vec();
-        assert_eq!(expected, actual);
+        assert_eq!(build().to_graphviz(), build().to_graphviz());
     }
 
     #[test]
-    fn can_search_a_single_predicate() {
+    fn set_the_bits_of_the_matching_subscription_ids_in_the_index() {
         let definitions = [
             AttributeDefinition::boolean("private"),
             AttributeDefinition::integer("exchange_id"),
-            AttributeDefinition::string_list("deal_ids"),
-            AttributeDefinition::string_list("deals"),
-            AttributeDefinition::integer_list("segment_ids"),
-            AttributeDefinition::string("country"),
-            AttributeDefinition::string("city"),
         ];
         let mut atree = ATree::new(&definitions).unwrap();
         atree.insert(&1u64, "private").unwrap();
+        atree.insert(&2u64, "exchange_id = 1").unwrap();
+        atree.insert(&3u64, "not private").unwrap();
+        atree.insert(&4u64, "private or exchange_id = 2").unwrap();
+        atree.insert(&5u64, "private").unwrap();
+        let index = RootsIndex::new([4u64, 3, 2, 1, 4]);
         let mut builder = atree.make_event();
         builder.with_boolean("private", true).unwrap();
+        builder.with_integer("exchange_id", 1).unwrap();
         let event = builder.build().unwrap();
 
-        let expected = vec![&1u64];
-        let actual = atree.search(&event).unwrap().matches().to_vec();
-        assert_eq!(expected, actual);
+        let matches = atree.search_bitset(&event, &index);
+
+        assert_eq!(4, index.len());
+        assert_eq!(index.len(), matches.len());
+        assert_eq!(vec![0, 2, 3], matches.ones().collect_vec());
+        let expected = atree
+            .search(&event)
+            .unwrap()
+            .matches()
+            .iter()
+            .filter_map(|subscription_id| index.position(subscription_id))
+            .sorted()
+            .collect_vec();
+        assert_eq!(expected, matches.ones().collect_vec());
     }
 
     #[test]
-    fn ignore_results_that_are_not_matched() {
+    fn promote_the_child_that_is_the_most_often_false_to_the_access_child() {
         let definitions = [
             AttributeDefinition::boolean("private"),
-            AttributeDefinition::integer("exchange_id"),
-            AttributeDefinition::string_list("deal_ids"),
-            AttributeDefinition::string_list("deals"),
             AttributeDefinition::integer_list("segment_ids"),
-            AttributeDefinition::string("country"),
-            AttributeDefinition::string("city"),
         ];
         let mut atree = ATree::new(&definitions).unwrap();
-        atree.insert(&1u64, "private").unwrap();
-        atree.insert(&2u64, A_COMPLEX_EXPRESSION).unwrap();
-        let mut builder = atree.make_event();
-        builder.with_boolean("private", false).unwrap();
-        let event = builder.build().unwrap();
+        atree
+            .insert(&1u64, "private and segment_ids one of [1, 2, 3]")
+            .unwrap();
+        atree.insert(&2u64, "not private").unwrap();
+        let root = atree.nodes_by_ids[&1u64];
+        let children = atree.nodes[root].children().to_vec();
+        let (private, segment_ids) = children
+            .iter()
+            .copied()
+            .sorted_by_key(|child_id| atree.nodes[*child_id].cost)
+            .collect_tuple()
+            .unwrap();
+        let events = (0..100)
+            .map(|i| {
+                let mut builder = atree.make_event();
+                builder.with_boolean("private", i % 10 != 0).unwrap();
+                builder
+                    .with_integer_list("segment_ids", &[if i % 25 == 0 { 1 } else { 4 }])
+                    .unwrap();
+                builder.build().unwrap()
+            })
+            .collect_vec();
+        let search_all = |atree: &ATree<u64>| {
+            events
+                .iter()
+                .map(|event| atree.search(event).unwrap().matches().to_vec())
+                .map(|matches| matches.into_iter().copied().sorted().collect_vec())
+                .collect_vec()
+        };
+        let expected = search_all(&atree);
+        atree.reoptimize_ordering();
+        assert_eq!(&[root], atree.nodes[private].parents());
 
-        let expected: Vec<&u64> = vec![];
-        let actual = atree.search(&event).unwrap().matches().to_vec();
-        assert_eq!(expected, actual);
+        atree.enable_profiling();
+        for event in &events {
+            atree.search_profiled(event).unwrap();
+        }
+        atree.reoptimize_ordering();
+
+        assert!(atree.nodes[private].parents().is_empty());
+        assert_eq!(&[root], atree.nodes[segment_ids].parents());
+        assert_eq!(Ok(()), atree.validate_invariants());
+        assert_eq!(expected, search_all(&atree));
     }
 
     #[test]
-    fn can_search_simple_expressions() {
+    fn do_not_merge_an_expression_with_the_or_of_the_negation_of_its_operands() {
         let definitions = [
-            AttributeDefinition::boolean("private"),
-            AttributeDefinition::integer("exchange_id"),
-            AttributeDefinition::string_list("deal_ids"),
-            AttributeDefinition::string_list("deals"),
-            AttributeDefinition::integer_list("segment_ids"),
-            AttributeDefinition::string("country"),
-            AttributeDefinition::string("city"),
+            AttributeDefinition::boolean("a1"),
+            AttributeDefinition::boolean("a2"),
         ];
         let mut atree = ATree::new(&definitions).unwrap();
-        atree.insert(&1u64, "private").unwrap();
-        atree.insert(&2u64, "not private").unwrap();
+        atree.insert(&0u64, "not (a2 and a1)").unwrap();
+        atree.insert(&1u64, "a1 or a2").unwrap();
+        let search = |value: bool| {
+            let mut builder = atree.make_event();
+            builder.with_boolean("a1", value).unwrap();
+            builder.with_boolean("a2", value).unwrap();
+            let event = builder.build().unwrap();
+            atree.search(&event).unwrap().matches().to_vec()
+        };
+
+        assert_eq!(vec![&0u64], search(false));
+        assert_eq!(vec![&1u64], search(true));
+    }
+
+    #[test]
+    fn match_an_unsorted_list_default_like_the_same_list_set_in_the_event() {
+        let definitions = [AttributeDefinition::integer_list("seg")
+            .with_default(AttributeValue::IntegerList(vec![5, 1, 3, 1]))];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "seg contains 1").unwrap();
+        atree.insert(&2u64, "seg one of [1]").unwrap();
+        atree.insert(&3u64, "seg all of [1, 3, 5]").unwrap();
+        let default = atree.make_event().build().unwrap();
         let mut builder = atree.make_event();
-        builder.with_boolean("private", true).unwrap();
+        builder.with_integer_list("seg", &[5, 1, 3, 1]).unwrap();
         let event = builder.build().unwrap();
+        let search = |event: &Event| {
+            atree
+                .search(event)
+                .unwrap()
+                .matches()
+                .iter()
+                .copied()
+                .copied()
+                .sorted()
+                .collect_vec()
+        };
 
-        let expected = vec![&1u64];
-        let mut actual = atree.search(&event).unwrap().matches().to_vec();
-        actual.sort();
-        assert_eq!(expected, actual);
+        assert_eq!(vec![1, 2, 3], search(&default));
+        assert_eq!(search(&event), search(&default));
     }
 
     #[test]
-    fn can_search_complex_expressions() {
+    fn return_an_error_when_the_default_value_of_an_enumeration_is_not_allowed() {
+        let mut strings = StringTable::new();
+        let trace = strings.get_or_update("trace").unwrap();
         let definitions = [
-            AttributeDefinition::boolean("private"),
-            AttributeDefinition::integer("exchange_id"),
-            AttributeDefinition::string_list("deal_ids"),
-            AttributeDefinition::string_list("deals"),
-            AttributeDefinition::integer_list("segment_ids"),
-            AttributeDefinition::string("country"),
-            AttributeDefinition::string("city"),
+            AttributeDefinition::enumeration("log_level", &["debug", "info"])
+                .with_default(AttributeValue::String(trace)),
         ];
-        let mut atree = ATree::new(&definitions).unwrap();
 
-        atree.insert(&1, A_COMPLEX_EXPRESSION).unwrap();
-        atree.insert(&2, AN_EXPRESSION_WITH_AND_OPERATORS).unwrap();
-        atree.insert(&3, AN_EXPRESSION_WITH_OR_OPERATORS).unwrap();
-        let mut builder = atree.make_event();
-        builder.with_integer("exchange_id", 1).unwrap();
-        builder.with_boolean("private", true).unwrap();
-        builder
-            .with_string_list("deal_ids", &["deal-1", "deal-2"])
-            .unwrap();
-        builder
-            .with_string_list("deals", &["deal-1", "deal-2"])
-            .unwrap();
-        builder.with_integer_list("segment_ids", &[2, 3]).unwrap();
-        builder.with_string("country", "FR").unwrap();
-        let event = builder.build().unwrap();
+        let result = ATree::<u64, _>::with_interner(&definitions, ATreeOptions::default(), strings);
 
-        let expected = vec![&2, &3];
-        let mut actual = atree.search(&event).unwrap().matches().to_vec();
-        actual.sort();
-        assert_eq!(expected, actual);
+        assert!(matches!(
+            result,
+            Err(ATreeError::Event(EventError::NotAllowedValue { ref name, .. }))
+                if name == "log_level"
+        ));
     }
 
     #[test]
-    fn can_search_a_tree_with_multiple_shared_sub_expressions() {
+    fn do_not_use_up_the_maximum_amount_of_strings_with_a_rejected_expression() {
         let definitions = [
-            AttributeDefinition::boolean("private"),
-            AttributeDefinition::integer("exchange_id"),
-            AttributeDefinition::string_list("deals"),
-            AttributeDefinition::integer_list("segment_ids"),
-            AttributeDefinition::string("country"),
-            AttributeDefinition::string("city"),
+            AttributeDefinition::string("c"),
+            AttributeDefinition::integer("x"),
         ];
-        let mut atree = ATree::new(&definitions).unwrap();
-        [
-            (
-                1,
-                r#"exchange_id = 1 and not private and deals one of ["deal-1", "deal-2"]"#,
-            ),
-            (
-                2,
-                r#"exchange_id = 1 and not private and deals one of ["deal-2", "deal-3"]"#,
-            ),
-            (
-                3,
-                r#"exchange_id = 1 and not private and deals one of ["deal-2", "deal-3"] and segment_ids one of [1, 2, 3, 4]"#,
-            ),
-            (
-                4,
-                r#"exchange_id = 1 and not private and deals one of ["deal-2", "deal-3"] and segment_ids one of [5, 6, 7, 8] and country in ["CA", "US"]"#,
-            ),
-        ].into_iter().for_each(|(id, expression)| {
-                atree.insert(&id, expression).unwrap()
-        });
+        let options = ATreeOptions::new().max_strings(3);
+        let mut atree = ATree::with_options(&definitions, options).unwrap();
+
+        let rejected = atree.insert(&1u64, r#"c in ["a", "b", "d", "e"] and x = 'oops'"#);
+        assert!(rejected.is_err());
+        let rejected = atree.insert(&2u64, r#"c in ["a", "b"] and x = 'oops'"#);
+        assert!(rejected.is_err());
 
+        assert!(atree.insert(&3u64, r#"c = "z""#).is_ok());
+        assert!(atree.insert(&4u64, r#"c in ["y", "x"]"#).is_ok());
         let mut builder = atree.make_event();
-        builder.with_boolean("private", false).unwrap();
-        builder.with_integer("exchange_id", 1).unwrap();
-        builder
-            .with_string_list("deals", &["deal-1", "deal-3"])
-            .unwrap();
-        builder.with_integer_list("segment_ids", &[2, 3]).unwrap();
-        builder.with_string("country", "CA").unwrap();
+        builder.with_string("c", "z").unwrap();
         let event = builder.build().unwrap();
-
-        let mut matches = atree.search(&event).unwrap().matches().to_vec();
-        matches.sort();
-        assert_eq!(vec![&1, &2, &3], matches);
+        assert_eq!(vec![&3u64], atree.search(&event).unwrap().matches());
     }
 
     #[test]
-    fn can_delete_a_single_predicate() {
-        let definitions = [AttributeDefinition::boolean("private")];
+    fn reset_the_priority_of_a_subscription_id_that_is_inserted_again_without_one() {
+        let definitions = [AttributeDefinition::boolean("p")];
         let mut atree = ATree::new(&definitions).unwrap();
-        atree.insert(&1u64, "private").unwrap();
+        atree.insert_with_priority(&1u64, "p", 100).unwrap();
+        atree.insert(&1u64, "p").unwrap();
+        atree.insert_with_priority(&2u64, "p", 50).unwrap();
+        atree.insert_with_priority(&3u64, "p", 10).unwrap();
+        atree.replace(&3u64, "p").unwrap();
         let mut builder = atree.make_event();
-        builder.with_boolean("private", true).unwrap();
+        builder.with_boolean("p", true).unwrap();
         let event = builder.build().unwrap();
 
-        let results = atree.search(&event).unwrap().matches().to_vec();
-        assert_eq!(vec![&1u64], results);
+        let matches = atree.search_by_priority(&event).unwrap();
 
-        atree.delete(&1u64);
-        let mut builder = atree.make_event();
-        builder.with_boolean("private", true).unwrap();
-        let event = builder.build().unwrap();
-        let results = atree.search(&event).unwrap().matches().to_vec();
-        assert!(results.is_empty());
+        assert_eq!(vec![&2u64, &1u64, &3u64], matches);
     }
 
     #[test]
-    fn deleting_an_expression_only_removes_the_id_not_the_expression_if_it_is_still_referenced() {
+    fn do_not_merge_large_unrelated_conjunctions() {
         let definitions = [
-            AttributeDefinition::boolean("private"),
-            AttributeDefinition::integer("exchange_id"),
-            AttributeDefinition::string_list("deal_ids"),
-            AttributeDefinition::string_list("deals"),
-            AttributeDefinition::integer_list("segment_ids"),
-            AttributeDefinition::string("country"),
-            AttributeDefinition::string("city"),
+            AttributeDefinition::integer("x"),
+            AttributeDefinition::integer("y"),
         ];
-        let an_expression = "private or exchange_id = 1";
-        let another_expression =
-            r#"private or exchange_id = 1 or deal_ids one of ["deal-1", "deal-2"]"#;
         let mut atree = ATree::new(&definitions).unwrap();
-        atree.insert(&1u64, an_expression).unwrap();
-        atree.insert(&2u64, another_expression).unwrap();
+        let conjunction = |attribute: &str, values: Range<i64>| {
+            values
+                .map(|value| format!("{attribute} <> {value}"))
+                .collect::<Vec<_>>()
+                .join(" and ")
+        };
+        atree.insert(&1u64, &conjunction("x", 0..200)).unwrap();
+        atree.insert(&2u64, &conjunction("y", 1000..1200)).unwrap();
         let mut builder = atree.make_event();
-        builder.with_integer("exchange_id", 1).unwrap();
+        builder.with_integer("x", 5).unwrap();
+        builder.with_integer("y", 5).unwrap();
         let event = builder.build().unwrap();
 
-        let results = atree.search(&event).unwrap().matches().to_vec();
-        assert_eq!(vec![&1u64, &2u64], results);
+        assert_eq!(2, atree.iter_roots().count());
+        assert_eq!(vec![&2u64], atree.search(&event).unwrap().matches());
+    }
 
-        atree.delete(&1u64);
-        let mut builder = atree.make_event();
-        builder.with_integer("exchange_id", 1).unwrap();
-        let event = builder.build().unwrap();
-        let results = atree.search(&event).unwrap().matches().to_vec();
-        assert_eq!(vec![&2u64], results);
+    #[test]
+    fn do_not_share_the_node_of_another_expression_with_the_same_id() {
+        let definitions = [AttributeDefinition::integer("exchange_id")];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "exchange_id = 1").unwrap();
+        // Give the node of the first expression the ID of the second one to simulate a collision
+        let colliding_id = atree.parse("exchange_id = 2").unwrap().id();
+        let node_id = atree.nodes_by_ids[&1u64];
+        let expression_id = atree.nodes[node_id].id;
+        atree.expression_to_node.remove(&expression_id);
+        atree.expression_to_node.insert(colliding_id, node_id);
+        atree.nodes[node_id].id = colliding_id;
+
+        atree.insert(&2u64, "exchange_id = 2").unwrap();
+
+        assert_ne!(node_id, atree.nodes_by_ids[&2u64]);
+        assert_eq!(Ok(()), atree.validate_invariants());
+        for (value, expected) in [(1, 1u64), (2, 2u64)] {
+            let mut builder = atree.make_event();
+            builder.with_integer("exchange_id", value).unwrap();
+            let event = builder.build().unwrap();
+            assert_eq!(vec![&expected], atree.search(&event).unwrap().matches());
+        }
     }
 
     #[test]
-    fn deleting_an_expression_only_removes_the_id_not_the_expression_if_it_has_multiple_subscription_ids(
-    ) {
+    fn replace_an_expression_by_another_one_with_the_same_id() {
+        let definitions = [AttributeDefinition::integer("exchange_id")];
+        let mut atree = ATree::new(&definitions).unwrap();
+        atree.insert(&1u64, "exchange_id = 1").unwrap();
+        // Give the node of the expression the ID of its replacement to simulate a collision
+        let colliding_id = atree.parse("exchange_id = 2").unwrap().id();
+        let node_id = atree.nodes_by_ids[&1u64];
+        let expression_id = atree.nodes[node_id].id;
+        atree.expression_to_node.remove(&expression_id);
+        atree.expression_to_node.insert(colliding_id, node_id);
+        atree.nodes[node_id].id = colliding_id;
+
+        atree.replace(&1u64, "exchange_id = 2").unwrap();
+
+        assert_eq!(Ok(()), atree.validate_invariants());
+        for (value, expected) in [(1, vec![]), (2, vec![&1u64])] {
+            let mut builder = atree.make_event();
+            builder.with_integer("exchange_id", value).unwrap();
+            let event = builder.build().unwrap();
+            assert_eq!(expected, atree.search(&event).unwrap().matches());
+        }
+    }
+
+    #[test]
+    fn match_a_merged_event_whose_fragment_left_an_attribute_to_its_default() {
         let definitions = [
-            AttributeDefinition::boolean("private"),
-            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::integer("x"),
+            AttributeDefinition::integer("y").with_default(AttributeValue::Integer(7)),
         ];
-        let an_expression = "private or exchange_id = 1";
         let mut atree = ATree::new(&definitions).unwrap();
-        atree.insert(&1u64, an_expression).unwrap();
-        atree.insert(&2u64, an_expression).unwrap();
+        atree.insert(&1u64, "x = 1 and y = 3").unwrap();
         let mut builder = atree.make_event();
-        builder.with_integer("exchange_id", 1).unwrap();
-        let event = builder.build().unwrap();
+        builder.with_integer("y", 3).unwrap();
+        let mut event = builder.build().unwrap();
+        let mut builder = atree.make_event();
+        builder.with_integer("x", 1).unwrap();
+        let fragment = builder.build().unwrap();
 
-        let results = atree.search(&event).unwrap().matches().to_vec();
-        assert_eq!(vec![&1u64, &2u64], results);
+        event.merge(&fragment);
 
-        atree.delete(&1u64);
-        let mut builder = atree.make_event();
-        builder.with_integer("exchange_id", 1).unwrap();
-        let event = builder.build().unwrap();
-        let results = atree.search(&event).unwrap().matches().to_vec();
-        assert_eq!(vec![&2u64], results);
+        assert_eq!(vec![&1u64], atree.search(&event).unwrap().matches());
     }
 
     #[test]
-    fn can_delete_root_node_when_all_references_are_deleted() {
-        let definitions = [
-            AttributeDefinition::boolean("private"),
-            AttributeDefinition::integer("exchange_id"),
-        ];
-        let an_expression = "private or exchange_id = 1";
+    fn describe_an_integer_out_of_range_along_with_the_supported_range() {
+        let definitions = [AttributeDefinition::integer("x")];
         let mut atree = ATree::new(&definitions).unwrap();
-        atree.insert(&1u64, an_expression).unwrap();
-        atree.insert(&2u64, an_expression).unwrap();
-        let mut builder = atree.make_event();
-        builder.with_integer("exchange_id", 1).unwrap();
-        let event = builder.build().unwrap();
 
-        let results = atree.search(&event).unwrap().matches().to_vec();
-        assert_eq!(vec![&1u64, &2u64], results);
+        let error = atree.insert(&1u64, "x = 99999999999999999999").unwrap_err();
 
-        atree.delete(&1u64);
-        atree.delete(&2u64);
-        let mut builder = atree.make_event();
-        builder.with_integer("exchange_id", 1).unwrap();
-        let event = builder.build().unwrap();
-        let results = atree.search(&event).unwrap().matches().to_vec();
-        assert!(results.is_empty());
+        assert_eq!(
+            "failed to parse the expression: failed to lex the expression: the integer \
+             99999999999999999999 is outside of the supported range (-9223372036854775808 to \
+             9223372036854775807)",
+            error.to_string()
+        );
     }
 
     #[test]
-    fn can_render_to_graphviz() {
-        let definitions = [
-            AttributeDefinition::boolean("private"),
-            AttributeDefinition::integer("exchange_id"),
-            AttributeDefinition::string_list("deal_ids"),
-            AttributeDefinition::string_list("deals"),
-            AttributeDefinition::integer_list("segment_ids"),
-            AttributeDefinition::string("country"),
-            AttributeDefinition::string("city"),
-        ];
-        let an_expression = "private or exchange_id = 1";
-        let another_expression =
-            r#"private or exchange_id = 1 or deal_ids one of ["deal-1", "deal-2"]"#;
+    fn describe_the_invalid_list_elements_in_the_error_message() {
+        let definitions = [AttributeDefinition::integer_list("ids")];
         let mut atree = ATree::new(&definitions).unwrap();
-        atree.insert(&1u64, an_expression).unwrap();
-        atree.insert(&2u64, another_expression).unwrap();
 
-        assert!(!atree.to_graphviz().is_empty());
+        let nested = atree.insert(&1u64, "ids one of [1, [2]]").unwrap_err();
+        assert_eq!(
+            "failed to parse the expression: list elements must be scalar literals",
+            nested.to_string()
+        );
+        let mixed = atree.insert(&2u64, r#"ids one of [1, "2"]"#).unwrap_err();
+        assert_eq!(
+            "failed to parse the expression: list elements must all have the same type: expected \
+             integer, got string",
+            mixed.to_string()
+        );
+    }
+
+    #[test]
+    fn count_the_values_of_the_enumerations_towards_the_maximum_amount_of_strings() {
+        let definitions = [AttributeDefinition::enumeration(
+            "log_level",
+            &["debug", "info", "warning"],
+        )];
+        let options = ATreeOptions::new().max_strings(2);
+
+        let result = ATree::<u64>::with_options(&definitions, options);
+
+        assert!(matches!(
+            result,
+            Err(ATreeError::ParseError(ParseError::User {
+                error: ParserError::Strings(StringTableError::LimitReached(2))
+            }))
+        ));
+    }
+
+    #[test]
+    fn return_an_error_when_the_maximum_amount_of_strings_is_set_with_a_custom_interner() {
+        let definitions = [AttributeDefinition::string("country")];
+        let options = ATreeOptions::new().max_strings(2);
+
+        let result = ATree::<u64, _>::with_interner(&definitions, options, StringTable::new());
+
+        assert!(matches!(
+            result,
+            Err(ATreeError::UnsupportedOption("max_strings"))
+        ));
     }
 }
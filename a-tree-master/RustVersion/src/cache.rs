@@ -0,0 +1,169 @@
+use crate::{collections::HashMap, events::Event};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// How the search cache of an [`ATree`](crate::ATree) performed since it was enabled (see
+/// [`ATree::with_search_cache()`](crate::ATree::with_search_cache))
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct SearchCacheStats {
+    /// The amount of searches that were answered by the cache
+    pub hits: u64,
+    /// The amount of searches that had to search the tree
+    pub misses: u64,
+    /// The amount of events whose matches are currently cached
+    pub entries: usize,
+}
+
+/// The matches of the most recently searched events, indexed by the fingerprint of the events
+#[derive(Debug)]
+pub(crate) struct SearchCache<T> {
+    capacity: usize,
+    state: Mutex<State<T>>,
+}
+
+#[derive(Clone, Debug)]
+struct State<T> {
+    entries: HashMap<u64, Entry<T>>,
+    // Incremented on each access to find the least recently used entry
+    clock: u64,
+    hits: u64,
+    misses: u64,
+}
+
+#[derive(Clone, Debug)]
+struct Entry<T> {
+    // Kept to tell apart the events whose fingerprints collide
+    event: Event,
+    matches: Vec<T>,
+    last_used: u64,
+}
+
+impl<T: Clone> SearchCache<T> {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::new(State {
+                entries: HashMap::default(),
+                clock: 0,
+                hits: 0,
+                misses: 0,
+            }),
+        }
+    }
+
+    /// Return the cached matches of the event or search them with `search` and cache them
+    pub(crate) fn get_or_search(&self, event: &Event, search: impl FnOnce() -> Vec<T>) -> Vec<T> {
+        let fingerprint = event.fingerprint();
+        {
+            let mut state = self.lock();
+            state.clock += 1;
+            let clock = state.clock;
+            if let Some(entry) = state.entries.get_mut(&fingerprint) {
+                if entry.event == *event {
+                    entry.last_used = clock;
+                    let matches = entry.matches.clone();
+                    state.hits += 1;
+                    return matches;
+                }
+            }
+            state.misses += 1;
+        }
+
+        // The lock is not held while searching so that the searches of other threads are not
+        // serialized behind this one
+        let matches = search();
+        let mut state = self.lock();
+        if !state.entries.contains_key(&fingerprint) && state.entries.len() >= self.capacity {
+            let least_recently_used = state
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(fingerprint, _)| *fingerprint);
+            if let Some(least_recently_used) = least_recently_used {
+                state.entries.remove(&least_recently_used);
+            }
+        }
+        let last_used = state.clock;
+        state.entries.insert(
+            fingerprint,
+            Entry {
+                event: event.clone(),
+                matches: matches.clone(),
+                last_used,
+            },
+        );
+        matches
+    }
+
+    /// Forget all the cached matches since they are stale once the tree is modified
+    pub(crate) fn clear(&mut self) {
+        self.state
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entries
+            .clear();
+    }
+
+    pub(crate) fn stats(&self) -> SearchCacheStats {
+        let state = self.lock();
+        SearchCacheStats {
+            hits: state.hits,
+            misses: state.misses,
+            entries: state.entries.len(),
+        }
+    }
+
+    #[inline]
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        // The state is always left consistent so it can still be used after a panic
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T: Clone> Clone for SearchCache<T> {
+    fn clone(&self) -> Self {
+        Self {
+            capacity: self.capacity,
+            state: Mutex::new(self.lock().clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{AttributeDefinition, AttributeTable, EventBuilder};
+    use crate::strings::StringTable;
+
+    fn make_event(attributes: &AttributeTable, strings: &StringTable, value: i64) -> Event {
+        let mut builder = EventBuilder::new(attributes, strings);
+        builder.with_integer("exchange_id", value).unwrap();
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn evict_the_least_recently_used_event_when_full() {
+        let attributes =
+            AttributeTable::new(&[AttributeDefinition::integer("exchange_id")]).unwrap();
+        let strings = StringTable::new();
+        let cache = SearchCache::new(2);
+        let [first, second, third] =
+            [1, 2, 3].map(|value| make_event(&attributes, &strings, value));
+        cache.get_or_search(&first, || vec![1u64]);
+        cache.get_or_search(&second, || vec![2u64]);
+        cache.get_or_search(&first, || unreachable!());
+
+        cache.get_or_search(&third, || vec![3u64]);
+
+        assert_eq!(vec![1u64], cache.get_or_search(&first, || unreachable!()));
+        assert_eq!(vec![3u64], cache.get_or_search(&third, || unreachable!()));
+        assert_eq!(vec![4u64], cache.get_or_search(&second, || vec![4u64]));
+        assert_eq!(
+            SearchCacheStats {
+                hits: 3,
+                misses: 4,
+                entries: 2
+            },
+            cache.stats()
+        );
+    }
+}
@@ -0,0 +1,24 @@
+//! The hash maps used by the crate.
+//!
+//! With the `std` feature (the default), [`HashMap`] is the map of the standard library. When
+//! building with the `no_std` feature instead, it is provided by `hashbrown` and uses a
+//! deterministic hasher since there is no source of randomness to seed the default one.
+//!
+//! [`DeterministicHashMap`] always uses the deterministic hasher so that the maps that drive the
+//! construction of the tree behave the same across runs.
+use core::hash::BuildHasherDefault;
+
+use crate::hash::FnvHasher;
+
+#[cfg(feature = "std")]
+pub(crate) use std::collections::HashMap;
+
+#[cfg(not(feature = "std"))]
+pub(crate) type HashMap<K, V> = hashbrown::HashMap<K, V, BuildHasherDefault<FnvHasher>>;
+
+#[cfg(feature = "std")]
+pub(crate) type DeterministicHashMap<K, V> =
+    std::collections::HashMap<K, V, BuildHasherDefault<FnvHasher>>;
+
+#[cfg(not(feature = "std"))]
+pub(crate) type DeterministicHashMap<K, V> = HashMap<K, V>;
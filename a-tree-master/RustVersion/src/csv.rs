@@ -0,0 +1,267 @@
+//! Load the attributes and the expressions of an [`ATree`] from CSV (or TSV) files.
+//!
+//! The records are one per line: a field that contains the delimiter or a double quote has to be
+//! quoted with double quotes (the double quotes inside of it are then doubled) and cannot span
+//! multiple lines. Empty lines are ignored and the first line is skipped if it is the header.
+use crate::{
+    atree::ATree,
+    error::ATreeError,
+    events::{AttributeDefinition, AttributeKind},
+};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+const ATTRIBUTES_HEADER: [&str; 2] = ["name", "type"];
+const EXPRESSIONS_HEADER: [&str; 2] = ["id", "expression"];
+
+impl ATree<u64> {
+    /// Create an [`ATree`] from a CSV file of attributes (`name,type`) and a CSV file of
+    /// expressions (`id,expression`).
+    ///
+    /// The types of the attributes are `boolean`, `integer`, `float`, `string`, `integer_list` and
+    /// `string_list`. The rows that cannot be loaded are reported with their line number (or with
+    /// the line 0 if the attributes cannot be loaded as a whole).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::ATree;
+    ///
+    /// let attributes = "name,type\nprivate,boolean\ncountry,string\n";
+    /// let expressions = "id,expression\n1,private\n2,\"country in ['CA', 'US']\"\n";
+    /// let atree = ATree::from_csv(attributes, expressions).unwrap();
+    /// assert_eq!(2, atree.iter_roots().count());
+    /// ```
+    pub fn from_csv(
+        attributes_csv: &str,
+        expressions_csv: &str,
+    ) -> Result<Self, ATreeError<'static>> {
+        Self::from_delimited(attributes_csv, expressions_csv, ',')
+    }
+
+    /// Create an [`ATree`] like [`ATree::from_csv()`] but from TSV files.
+    pub fn from_tsv(
+        attributes_tsv: &str,
+        expressions_tsv: &str,
+    ) -> Result<Self, ATreeError<'static>> {
+        Self::from_delimited(attributes_tsv, expressions_tsv, '\t')
+    }
+
+    fn from_delimited(
+        attributes: &str,
+        expressions: &str,
+        delimiter: char,
+    ) -> Result<Self, ATreeError<'static>> {
+        let definitions = records(attributes, delimiter, ATTRIBUTES_HEADER)
+            .map(|(line, record)| {
+                let [name, kind] = record?;
+                kind.parse()
+                    .ok()
+                    .and_then(|kind| to_definition(&name, kind))
+                    .ok_or_else(|| ATreeError::InvalidRow {
+                        line,
+                        message: format!("unknown attribute type {kind:?}"),
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut atree = ATree::new(&definitions).map_err(|error| match error {
+            ATreeError::Event(error) => ATreeError::Event(error),
+            // The error is about the attributes as a whole rather than about one of their rows
+            error => ATreeError::InvalidRow {
+                line: 0,
+                message: error.to_string(),
+            },
+        })?;
+
+        for (line, record) in records(expressions, delimiter, EXPRESSIONS_HEADER) {
+            let [id, expression] = record?;
+            let id = id.parse::<u64>().map_err(|error| ATreeError::InvalidRow {
+                line,
+                message: format!("invalid ID {id:?}: {error}"),
+            })?;
+            atree
+                .insert(&id, &expression)
+                .map_err(|error| ATreeError::InvalidRow {
+                    line,
+                    message: error.to_string(),
+                })?;
+        }
+        Ok(atree)
+    }
+}
+
+fn to_definition(name: &str, kind: AttributeKind) -> Option<AttributeDefinition> {
+    let definition = match kind {
+        AttributeKind::Boolean => AttributeDefinition::boolean(name),
+        AttributeKind::Integer => AttributeDefinition::integer(name),
+        AttributeKind::Float => AttributeDefinition::float(name),
+        AttributeKind::String => AttributeDefinition::string(name),
+        AttributeKind::IntegerList => AttributeDefinition::integer_list(name),
+        AttributeKind::StringList => AttributeDefinition::string_list(name),
+        // The allowed values of an enumeration cannot be described by the file
+        AttributeKind::Enum => return None,
+    };
+    Some(definition)
+}
+
+/// Iterate over the non-empty records of the file along with their line number (starting at 1),
+/// skipping the header.
+fn records<'a, const N: usize>(
+    input: &'a str,
+    delimiter: char,
+    header: [&'static str; N],
+) -> impl Iterator<Item = (usize, Result<[String; N], ATreeError<'static>>)> + 'a {
+    input
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim_end_matches('\r')))
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(move |(line, record)| (line, split_record(record, delimiter)))
+        .filter(move |(line, record)| {
+            let is_header = record
+                .as_ref()
+                .is_some_and(|fields| fields.iter().map(String::as_str).eq(header));
+            *line != 1 || !is_header
+        })
+        .map(|(line, record)| {
+            let record = record
+                .and_then(|fields| <[String; N]>::try_from(fields).ok())
+                .ok_or_else(|| ATreeError::InvalidRow {
+                    line,
+                    message: format!("expected {N} fields"),
+                });
+            (line, record)
+        })
+}
+
+/// Split a record into its fields or return `None` if a quoted field is not terminated.
+fn split_record(record: &str, delimiter: char) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut characters = record.chars().peekable();
+    loop {
+        let mut field = String::new();
+        if characters.next_if_eq(&'"').is_some() {
+            loop {
+                match characters.next()? {
+                    '"' if characters.next_if_eq(&'"').is_some() => field.push('"'),
+                    '"' => break,
+                    character => field.push(character),
+                }
+            }
+        }
+        while let Some(character) = characters.next_if(|character| *character != delimiter) {
+            field.push(character);
+        }
+        fields.push(field.trim().to_string());
+        if characters.next().is_none() {
+            return Some(fields);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ATTRIBUTES: &str = "name,type
+private,boolean
+exchange_id,integer
+country,string
+segment_ids,integer_list
+";
+
+    fn search(atree: &ATree<u64>, exchange_id: i64, country: &str) -> Vec<u64> {
+        let mut builder = atree.make_event();
+        builder.with_boolean("private", false).unwrap();
+        builder.with_integer("exchange_id", exchange_id).unwrap();
+        builder.with_string("country", country).unwrap();
+        let event = builder.build().unwrap();
+        let mut matches = atree.search_owned(&event);
+        matches.sort();
+        matches
+    }
+
+    #[test]
+    fn can_load_a_tree_from_csv_files() {
+        let expressions = r#"id,expression
+1,not private
+
+2,"exchange_id = 1 and country in [""CA"", ""US""]"
+3,"segment_ids one of [1, 2]"
+"#;
+
+        let atree = ATree::from_csv(ATTRIBUTES, expressions).unwrap();
+
+        assert_eq!(vec![1, 2], search(&atree, 1, "CA"));
+        assert_eq!(vec![1], search(&atree, 2, "CA"));
+    }
+
+    #[test]
+    fn can_load_a_tree_from_tsv_files() {
+        let attributes = "private\tboolean\nexchange_id\tinteger\ncountry\tstring\n";
+        let expressions = "1\texchange_id = 1 and country in ['CA', 'US']\n";
+
+        let atree = ATree::from_tsv(attributes, expressions).unwrap();
+
+        assert_eq!(vec![1], search(&atree, 1, "US"));
+    }
+
+    #[test]
+    fn return_an_error_with_the_line_of_an_unknown_attribute_type() {
+        let attributes = "name,type\nprivate,boolean\ncountry,text\n";
+
+        let result = ATree::from_csv(attributes, "");
+
+        assert!(matches!(
+            result,
+            Err(ATreeError::InvalidRow { line: 3, message }) if message.contains("\"text\"")
+        ));
+    }
+
+    #[test]
+    fn return_an_error_with_the_line_of_an_invalid_expression() {
+        let expressions = "1,private\n2,exchange_id = 'CA'\n";
+
+        let result = ATree::from_csv(ATTRIBUTES, expressions);
+
+        assert!(matches!(
+            result,
+            Err(ATreeError::InvalidRow { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn return_an_error_with_the_line_of_a_malformed_row() {
+        let invalid_id = ATree::from_csv(ATTRIBUTES, "1,private\nfirst,private\n");
+        let missing_field = ATree::from_csv(ATTRIBUTES, "1,private\n\n2\n");
+        let unterminated = ATree::from_csv(ATTRIBUTES, "1,\"private\n");
+
+        assert!(matches!(
+            invalid_id,
+            Err(ATreeError::InvalidRow { line: 2, .. })
+        ));
+        assert!(matches!(
+            missing_field,
+            Err(ATreeError::InvalidRow { line: 3, .. })
+        ));
+        assert!(matches!(
+            unterminated,
+            Err(ATreeError::InvalidRow { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn can_split_the_quoted_fields_of_a_record() {
+        assert_eq!(
+            Some(vec![
+                "1".to_string(),
+                "a, \"b\"".to_string(),
+                "".to_string()
+            ]),
+            split_record(r#"1,"a, ""b""","#, ',')
+        );
+    }
+}
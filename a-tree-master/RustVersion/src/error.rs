@@ -1,18 +1,59 @@
-use crate::{events::EventError, lexer::LexicalError, parser::ATreeParseError};
+use crate::{
+    events::EventError, lexer::LexicalError, parser::ATreeParseError, strings::StringTableError,
+};
+use alloc::{string::String, vec::Vec};
 use thiserror::Error;
 
-#[derive(Debug, PartialEq, Error)]
+#[derive(Clone, Debug, PartialEq, Error)]
 pub enum ParserError {
-    #[error("failed to lex the expression with {0:?}")]
+    #[error("failed to lex the expression: {0}")]
     Lexical(LexicalError),
     #[error("failed with {0:?}")]
     Event(EventError),
+    #[error("failed with {count} errors: {0:?}", count = .0.len())]
+    Events(Vec<EventError>),
+    #[error("failed to intern a string with {0:?}")]
+    Strings(StringTableError),
+    #[error("the expression exceeds the maximum depth of {0}")]
+    MaximumDepthExceeded(usize),
+    #[error("the expression contains a list longer than the maximum length of {0}")]
+    MaximumListLengthExceeded(usize),
+    #[error("the list @{0} is not defined")]
+    UndefinedList(String),
+    #[error("attribute {0} is read as a keyword; quote it with backticks (i.e. `{0}`)")]
+    KeywordAttribute(String),
+    #[error("list elements must be scalar literals")]
+    NestedList,
+    #[error("list elements must all have the same type: expected {expected}, got {actual}")]
+    MixedList {
+        expected: &'static str,
+        actual: &'static str,
+    },
 }
 
 #[derive(Debug, Error)]
 pub enum ATreeError<'a> {
-    #[error("failed to parse the expression with {0:?}")]
+    #[error("failed to parse the expression: {0}")]
     ParseError(ATreeParseError<'a>),
     #[error("failed with {0:?}")]
     Event(EventError),
+    #[error("the compiled expression was produced by another tree")]
+    ForeignCompiledExpression,
+    #[error("the subscription ID is already part of the tree")]
+    DuplicateSubscription,
+    #[error("the tree already holds the maximum of {max_roots} distinct expressions")]
+    CapacityExceeded { max_roots: usize },
+    #[error("the option {0} cannot be used with a custom interner")]
+    UnsupportedOption(&'static str),
+    #[error("failed to load line {line} with {message}")]
+    InvalidRow { line: usize, message: String },
+}
+
+/// The error returned by [`ATree::search_budgeted()`](crate::ATree::search_budgeted) when the
+/// search needs more node evaluations than its budget allows
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
+#[error("the search exceeded its budget of {max_nodes} node evaluations")]
+pub struct BudgetExceeded {
+    /// The budget that was exceeded
+    pub max_nodes: usize,
 }
@@ -1,3 +1,5 @@
+use alloc::{vec, vec::Vec};
+
 #[derive(Debug)]
 pub struct EvaluationResult {
     failed: Vec<u64>,
@@ -49,6 +51,20 @@ impl EvaluationResult {
         Some(!failed && success)
     }
 
+    /// Iterate over the IDs of the expressions that were evaluated to true in ascending order
+    pub fn successes(&self) -> impl Iterator<Item = usize> + '_ {
+        self.success.iter().enumerate().flat_map(|(bucket, entry)| {
+            let mut entry = *entry;
+            core::iter::from_fn(move || {
+                (entry != 0).then(|| {
+                    let position_in_entry = entry.trailing_zeros() as usize;
+                    entry &= entry - 1;
+                    bucket * Self::EXPRESSIONS_PER_BUCKET + position_in_entry
+                })
+            })
+        })
+    }
+
     #[inline]
     const fn set_bit(entries: &mut [u64], id: usize) {
         let position_in_entry: usize = id % Self::EXPRESSIONS_PER_BUCKET;
@@ -137,4 +153,20 @@ mod tests {
         assert!(results.is_evaluated(AN_ID_THAT_EXCEEDS_U64));
         assert_eq!(Some(false), results.get_result(AN_ID_THAT_EXCEEDS_U64));
     }
+
+    #[test]
+    fn can_iterate_over_the_successful_results_in_order() {
+        let mut results = EvaluationResult::new(SIZE);
+
+        results.set_result(AN_ID_THAT_EXCEEDS_U64, Some(true));
+        results.set_result(AN_ID, Some(true));
+        results.set_result(2, Some(false));
+        results.set_result(3, None);
+        results.set_result(SIZE - 1, Some(true));
+
+        assert_eq!(
+            vec![AN_ID, AN_ID_THAT_EXCEEDS_U64, SIZE - 1],
+            results.successes().collect::<Vec<_>>()
+        );
+    }
 }
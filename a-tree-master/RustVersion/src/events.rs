@@ -1,23 +1,30 @@
 use crate::{
-    predicates::PredicateKind,
-    strings::{StringId, StringTable},
-};
-use itertools::Itertools;
-use rust_decimal::Decimal;
-use std::{
     collections::HashMap,
+    strings::{Interner, StringId, StringTableError},
+};
+use alloc::{
+    borrow::ToOwned,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{
     fmt::{Display, Formatter},
     ops::Index,
+    str::FromStr,
 };
+use fixedbitset::FixedBitSet;
+use itertools::Itertools;
+use rust_decimal::Decimal;
 use thiserror::Error;
 
-#[derive(Error, PartialEq, Debug)]
+#[derive(Error, Clone, PartialEq, Debug)]
 pub enum EventError {
     #[error("attribute {0} has already been defined")]
     AlreadyPresent(String),
     #[error("event is missing some attributes")]
     MissingAttributes,
-    #[error("ABE refers to non-existing attribute '{0:?}'")]
+    #[error("ABE refers to non-existing attribute '{0}'")]
     NonExistingAttribute(String),
     #[error("{name:?}: wrong types => expected: {expected:?}, found: {actual:?}")]
     WrongType {
@@ -25,31 +32,79 @@ pub enum EventError {
         expected: AttributeKind,
         actual: AttributeKind,
     },
-    #[error("{name:?}: mismatching types => expected: {expected:?}, found: {actual:?}")]
-    MismatchingTypes {
+    #[error("{name:?}: value is not one of the allowed values {allowed:?}")]
+    NotAllowedValue { name: String, allowed: Vec<String> },
+    #[error("{name:?}: the list element {value:?} is not part of any expression")]
+    UnknownListElement { name: String, value: String },
+    #[error("'{name}' is a scalar attribute ({actual}) but '{operator}' requires a list attribute; did you mean '{suggestion}'?")]
+    ScalarWithListOperator {
         name: String,
-        expected: AttributeKind,
-        actual: PredicateKind,
+        operator: String,
+        actual: AttributeKind,
+        suggestion: &'static str,
+    },
+    #[error("'{name}' is a list attribute ({actual}) but '{operator}' requires a scalar attribute; did you mean '{suggestion}'?")]
+    ListWithSetOperator {
+        name: String,
+        operator: String,
+        actual: AttributeKind,
+        suggestion: &'static str,
+    },
+    #[error("{name:?}: only the float attributes can have a float scale, got {actual}")]
+    UnsupportedFloatScale { name: String, actual: AttributeKind },
+    #[error("the attribute handle was created by another tree")]
+    ForeignAttributeHandle,
+    #[error("unknown attribute kind {0:?}")]
+    UnknownAttributeKind(String),
+    #[error("{operator} require {expected}, got {actual:?} for '{name}'")]
+    UnsupportedOperator {
+        name: String,
+        operator: &'static str,
+        expected: &'static str,
+        actual: AttributeKind,
     },
 }
 
 /// An [`Event`] builder
 ///
-/// During the builder creation, it will set all the attributes to `undefined`. If some attributes
-/// are not assigned, they will be left `undefined`.
+/// During the builder creation, it will set all the attributes to their default value (see
+/// [`AttributeDefinition::with_default()`]) or to `undefined`. If some attributes are not
+/// assigned, they will keep that value.
 #[derive(Debug)]
 pub struct EventBuilder<'atree> {
     by_ids: Vec<AttributeValue>,
+    texts: HashMap<AttributeId, Vec<String>>,
+    // The attributes that were set explicitly rather than left to their default value
+    set: FixedBitSet,
     attributes: &'atree AttributeTable,
-    strings: &'atree StringTable,
+    strings: &'atree dyn Interner,
 }
 
 impl<'atree> EventBuilder<'atree> {
-    pub(crate) fn new(attributes: &'atree AttributeTable, strings: &'atree StringTable) -> Self {
+    pub(crate) fn new(attributes: &'atree AttributeTable, strings: &'atree dyn Interner) -> Self {
+        let mut builder = Self {
+            attributes,
+            strings,
+            by_ids: attributes.defaults().to_vec(),
+            texts: HashMap::default(),
+            set: FixedBitSet::with_capacity(attributes.defaults().len()),
+        };
+        builder.add_default_texts();
+        builder
+    }
+
+    /// Create a builder whose attributes start with the values of the event
+    pub(crate) fn from_event(
+        attributes: &'atree AttributeTable,
+        strings: &'atree dyn Interner,
+        event: &Event,
+    ) -> Self {
         Self {
             attributes,
             strings,
-            by_ids: vec![AttributeValue::Undefined; attributes.len()],
+            by_ids: event.values.clone(),
+            texts: event.texts.clone(),
+            set: event.set.clone(),
         }
     }
 
@@ -78,7 +133,85 @@ impl<'atree> EventBuilder<'atree> {
     /// let event = builder.build().unwrap();
     /// ```
     pub fn build(self) -> Result<Event, EventError> {
-        Ok(Event(self.by_ids))
+        Ok(Event {
+            values: self.by_ids,
+            texts: self.texts,
+            set: self.set,
+            undefined_policy: self.attributes.undefined_policy(),
+        })
+    }
+
+    /// Build the corresponding [`Event`] like [`EventBuilder::build()`] but without consuming the
+    /// builder, so that its attributes can be tweaked to build the next event.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [
+    ///     AttributeDefinition::boolean("private"),
+    ///     AttributeDefinition::integer("exchange_id"),
+    /// ];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "exchange_id = 2 and private").unwrap();
+    ///
+    /// let mut builder = atree.make_event();
+    /// builder.with_integer("exchange_id", 1).unwrap();
+    /// builder.with_boolean("private", true).unwrap();
+    /// let event = builder.build_ref();
+    /// builder.with_integer("exchange_id", 2).unwrap();
+    /// let another_event = builder.build_ref();
+    ///
+    /// assert!(atree.search(&event).unwrap().is_empty());
+    /// assert_eq!(vec![&1u64], atree.search(&another_event).unwrap().matches());
+    /// ```
+    pub fn build_ref(&self) -> Event {
+        Event {
+            values: self.by_ids.clone(),
+            texts: self.texts.clone(),
+            set: self.set.clone(),
+            undefined_policy: self.attributes.undefined_policy(),
+        }
+    }
+
+    /// Build the corresponding [`Event`] like [`EventBuilder::build()`] and then
+    /// [`EventBuilder::reset()`] the builder so that it can be used for the next event.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [AttributeDefinition::integer("exchange_id")];
+    /// let atree = ATree::<u64>::new(&definitions).unwrap();
+    ///
+    /// let mut builder = atree.make_event();
+    /// let events: Vec<_> = (1..=3)
+    ///     .map(|exchange_id| {
+    ///         builder.with_integer("exchange_id", exchange_id).unwrap();
+    ///         builder.build_and_reset().unwrap()
+    ///     })
+    ///     .collect();
+    /// ```
+    pub fn build_and_reset(&mut self) -> Result<Event, EventError> {
+        let event = Event {
+            values: self.by_ids.clone(),
+            texts: core::mem::take(&mut self.texts),
+            set: self.set.clone(),
+            undefined_policy: self.attributes.undefined_policy(),
+        };
+        self.reset();
+        Ok(event)
+    }
+
+    /// Set every attribute back to its default value (or to undefined) without releasing the
+    /// memory of the builder.
+    pub fn reset(&mut self) {
+        self.by_ids.clone_from_slice(self.attributes.defaults());
+        self.texts.clear();
+        self.set.clear();
+        self.add_default_texts();
     }
 
     /// Set the specified boolean attribute.
@@ -87,7 +220,8 @@ impl<'atree> EventBuilder<'atree> {
     pub fn with_boolean(&mut self, name: &str, value: bool) -> Result<(), EventError> {
         self.add_value(name, AttributeKind::Boolean, || {
             AttributeValue::Boolean(value)
-        })
+        })?;
+        Ok(())
     }
 
     /// Set the specified integer attribute.
@@ -96,26 +230,32 @@ impl<'atree> EventBuilder<'atree> {
     pub fn with_integer(&mut self, name: &str, value: i64) -> Result<(), EventError> {
         self.add_value(name, AttributeKind::Integer, || {
             AttributeValue::Integer(value)
-        })
+        })?;
+        Ok(())
     }
 
     /// Set the specified float attribute.
     ///
     /// The specified attribute must exist within the [`crate::ATree`] and its type must be float.
+    ///
+    /// The value is rounded to the float scale of the attribute if it has one (see
+    /// [`AttributeDefinition::with_float_scale()`]).
     pub fn with_float(&mut self, name: &str, number: i64, scale: u32) -> Result<(), EventError> {
-        self.add_value(name, AttributeKind::Float, || {
-            AttributeValue::Float(Decimal::new(number, scale))
-        })
+        let index = self.lookup(name)?;
+        self.set_float(index, name, number, scale)
     }
 
     /// Set the specified string attribute.
     ///
-    /// The specified attribute must exist within the [`crate::ATree`] and its type must be string.
+    /// The specified attribute must exist within the [`crate::ATree`] and its type must be string
+    /// or enumeration.
     pub fn with_string(&mut self, name: &str, value: &str) -> Result<(), EventError> {
-        self.add_value(name, AttributeKind::String, || {
+        let index = self.add_value(name, AttributeKind::String, || {
             let string_index = self.strings.get(value);
             AttributeValue::String(string_index)
-        })
+        })?;
+        self.add_texts(index, &[value]);
+        Ok(())
     }
 
     /// Set the specified list of integers attribute.
@@ -123,10 +263,63 @@ impl<'atree> EventBuilder<'atree> {
     /// The specified attribute must exist within the [`crate::ATree`] and its type must be a list
     /// of integers.
     pub fn with_integer_list(&mut self, name: &str, value: &[i64]) -> Result<(), EventError> {
+        self.with_integer_list_iter(name, value.iter().copied())
+    }
+
+    /// Set the specified list of integers attribute like [`EventBuilder::with_integer_list()`]
+    /// but from any iterator of integers, so they don't have to be collected beforehand.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [AttributeDefinition::integer_list("segment_ids")];
+    /// let atree = ATree::<u64>::new(&definitions).unwrap();
+    /// let mut builder = atree.make_event();
+    /// assert!(builder.with_integer_list_iter("segment_ids", 1..=3).is_ok());
+    /// ```
+    pub fn with_integer_list_iter<I>(&mut self, name: &str, values: I) -> Result<(), EventError>
+    where
+        I: IntoIterator<Item = i64>,
+    {
         self.add_value(name, AttributeKind::IntegerList, || {
-            let values = value.iter().sorted().unique().cloned().collect_vec();
+            let values = values.into_iter().sorted().dedup().collect_vec();
             AttributeValue::IntegerList(values)
-        })
+        })?;
+        Ok(())
+    }
+
+    /// Set the specified list of integers attribute like [`EventBuilder::with_integer_list()`]
+    /// but without sorting the list.
+    ///
+    /// The list must already be sorted in ascending order and contain no duplicates; otherwise,
+    /// the searches will return wrong results. This is only checked in debug builds (where it
+    /// panics).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [AttributeDefinition::integer_list("segment_ids")];
+    /// let atree = ATree::<u64>::new(&definitions).unwrap();
+    /// let mut builder = atree.make_event();
+    /// assert!(builder.with_sorted_integer_list("segment_ids", &[1, 2, 3]).is_ok());
+    /// ```
+    pub fn with_sorted_integer_list(
+        &mut self,
+        name: &str,
+        value: &[i64],
+    ) -> Result<(), EventError> {
+        debug_assert!(
+            value.windows(2).all(|pair| pair[0] < pair[1]),
+            "the list of {name} is not sorted or contains duplicates"
+        );
+        self.add_value(name, AttributeKind::IntegerList, || {
+            AttributeValue::IntegerList(value.to_vec())
+        })?;
+        Ok(())
     }
 
     /// Set the specified attribute to `undefined`.
@@ -138,61 +331,432 @@ impl<'atree> EventBuilder<'atree> {
             .by_name(name)
             .ok_or_else(|| EventError::NonExistingAttribute(name.to_string()))?;
         self.by_ids[index.0] = AttributeValue::Undefined;
+        self.texts.remove(&index);
+        self.set.insert(index.0);
         Ok(())
     }
 
+    /// Set the boolean attribute of the handle like [`EventBuilder::with_boolean()`] but without
+    /// looking up its name.
+    ///
+    /// The handle must come from the [`crate::ATree`] that created the builder (see
+    /// [`crate::ATree::attribute_handle()`]).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [
+    ///     AttributeDefinition::boolean("private"),
+    ///     AttributeDefinition::integer("exchange_id"),
+    /// ];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "private and exchange_id = 1").unwrap();
+    /// let private = atree.attribute_handle("private").unwrap();
+    /// let exchange_id = atree.attribute_handle("exchange_id").unwrap();
+    ///
+    /// let mut builder = atree.make_event();
+    /// builder.with_boolean_h(private, true).unwrap();
+    /// builder.with_integer_h(exchange_id, 1).unwrap();
+    /// let event = builder.build().unwrap();
+    /// assert_eq!(vec![&1u64], atree.search(&event).unwrap().matches());
+    /// ```
+    pub fn with_boolean_h(
+        &mut self,
+        handle: AttributeHandle,
+        value: bool,
+    ) -> Result<(), EventError> {
+        let (index, name) = self.resolve(handle)?;
+        self.try_set_value(index, name, AttributeKind::Boolean, || {
+            Ok(AttributeValue::Boolean(value))
+        })?;
+        Ok(())
+    }
+
+    /// Set the integer attribute of the handle like [`EventBuilder::with_integer()`] but without
+    /// looking up its name.
+    pub fn with_integer_h(
+        &mut self,
+        handle: AttributeHandle,
+        value: i64,
+    ) -> Result<(), EventError> {
+        let (index, name) = self.resolve(handle)?;
+        self.try_set_value(index, name, AttributeKind::Integer, || {
+            Ok(AttributeValue::Integer(value))
+        })?;
+        Ok(())
+    }
+
+    /// Set the float attribute of the handle like [`EventBuilder::with_float()`] but without
+    /// looking up its name.
+    pub fn with_float_h(
+        &mut self,
+        handle: AttributeHandle,
+        number: i64,
+        scale: u32,
+    ) -> Result<(), EventError> {
+        let (index, name) = self.resolve(handle)?;
+        self.set_float(index, name, number, scale)
+    }
+
+    fn set_float(
+        &mut self,
+        index: AttributeId,
+        name: &str,
+        number: i64,
+        scale: u32,
+    ) -> Result<(), EventError> {
+        self.try_set_value(index, name, AttributeKind::Float, || {
+            let value = self
+                .attributes
+                .round_float(index, Decimal::new(number, scale));
+            Ok(AttributeValue::Float(value))
+        })?;
+        Ok(())
+    }
+
+    /// Set the string attribute of the handle like [`EventBuilder::with_string()`] but without
+    /// looking up its name.
+    pub fn with_string_h(
+        &mut self,
+        handle: AttributeHandle,
+        value: &str,
+    ) -> Result<(), EventError> {
+        let (index, name) = self.resolve(handle)?;
+        let index = self.try_set_value(index, name, AttributeKind::String, || {
+            Ok(AttributeValue::String(self.strings.get(value)))
+        })?;
+        self.add_texts(index, &[value]);
+        Ok(())
+    }
+
+    /// Set the list of integers attribute of the handle like
+    /// [`EventBuilder::with_integer_list()`] but without looking up its name.
+    pub fn with_integer_list_h(
+        &mut self,
+        handle: AttributeHandle,
+        value: &[i64],
+    ) -> Result<(), EventError> {
+        let (index, name) = self.resolve(handle)?;
+        self.try_set_value(index, name, AttributeKind::IntegerList, || {
+            let values = value.iter().copied().sorted().dedup().collect_vec();
+            Ok(AttributeValue::IntegerList(values))
+        })?;
+        Ok(())
+    }
+
+    /// Set the list of strings attribute of the handle like
+    /// [`EventBuilder::with_string_list()`] but without looking up its name.
+    pub fn with_string_list_h(
+        &mut self,
+        handle: AttributeHandle,
+        values: &[&str],
+    ) -> Result<(), EventError> {
+        let (index, name) = self.resolve(handle)?;
+        self.set_string_list(index, name, values)
+    }
+
     /// Set the specified string list attribute.
     ///
     /// The specified attribute must exist within the [`crate::ATree`] and its type must be a list
     /// of strings.
+    ///
+    /// Only the strings that were seen in the inserted expressions are kept as distinct elements:
+    /// the other ones cannot match any predicate so they all share [`StringId::UNKNOWN`] and are
+    /// collapsed into a single element. [`crate::ATreeOptions::reject_unknown_list_elements()`]
+    /// turns them into an error instead.
     pub fn with_string_list(&mut self, name: &str, values: &[&str]) -> Result<(), EventError> {
-        self.add_value(name, AttributeKind::StringList, || {
-            let values: Vec<_> = values
-                .iter()
-                .map(|v| self.strings.get(v))
+        self.with_string_list_iter(name, values)
+    }
+
+    /// Set the specified string list attribute like [`EventBuilder::with_string_list()`] but from
+    /// any iterator of strings, so they don't have to be collected beforehand.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [AttributeDefinition::string_list("deal_ids")];
+    /// let atree = ATree::<u64>::new(&definitions).unwrap();
+    /// let mut builder = atree.make_event();
+    /// let deal_ids = (1..=3).map(|i| format!("deal-{i}"));
+    /// assert!(builder.with_string_list_iter("deal_ids", deal_ids).is_ok());
+    /// ```
+    pub fn with_string_list_iter<I, S>(&mut self, name: &str, values: I) -> Result<(), EventError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let index = self.lookup(name)?;
+        self.set_string_list(index, name, values)
+    }
+
+    fn set_string_list<I, S>(
+        &mut self,
+        index: AttributeId,
+        name: &str,
+        values: I,
+    ) -> Result<(), EventError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let retains_text = self.attributes.retains_text(index);
+        // The predicates reading the texts (i.e. string matching) need every element, known or not
+        let rejects_unknown = self.attributes.rejects_unknown_list_elements() && !retains_text;
+        let strings = self.strings;
+        let mut texts = Vec::new();
+        let index = self.try_set_value(index, name, AttributeKind::StringList, || {
+            let values = values
+                .into_iter()
+                .map(|value| {
+                    let value = value.as_ref();
+                    if retains_text {
+                        texts.push(value.to_string());
+                    }
+                    let id = strings.get(value);
+                    if rejects_unknown && id == StringId::UNKNOWN {
+                        return Err(EventError::UnknownListElement {
+                            name: name.to_owned(),
+                            value: value.to_owned(),
+                        });
+                    }
+                    Ok(id)
+                })
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter()
                 .sorted()
-                .unique()
+                .dedup()
                 .collect();
-            AttributeValue::StringList(values)
-        })
+            Ok(AttributeValue::StringList(values))
+        })?;
+        if retains_text {
+            // Like the interned strings, the texts form a set so that they can be counted
+            texts.sort_unstable();
+            texts.dedup();
+            self.texts.insert(index, texts);
+        }
+        Ok(())
     }
 
-    fn add_value<F>(&mut self, name: &str, actual: AttributeKind, f: F) -> Result<(), EventError>
+    fn add_value<F>(
+        &mut self,
+        name: &str,
+        actual: AttributeKind,
+        f: F,
+    ) -> Result<AttributeId, EventError>
     where
         F: FnOnce() -> AttributeValue,
     {
-        let index = self
-            .attributes
-            .by_name(name)
-            .ok_or_else(|| EventError::NonExistingAttribute(name.to_string()))?;
+        self.try_add_value(name, actual, || Ok(f()))
+    }
+
+    /// Set the attribute like [`EventBuilder::add_value()`] unless the value cannot be built, in
+    /// which case the attribute is left untouched.
+    fn try_add_value<F>(
+        &mut self,
+        name: &str,
+        actual: AttributeKind,
+        f: F,
+    ) -> Result<AttributeId, EventError>
+    where
+        F: FnOnce() -> Result<AttributeValue, EventError>,
+    {
+        let index = self.lookup(name)?;
+        self.try_set_value(index, name, actual, f)
+    }
+
+    /// Set the attribute like [`EventBuilder::try_add_value()`] once it has been looked up.
+    fn try_set_value<F>(
+        &mut self,
+        index: AttributeId,
+        name: &str,
+        actual: AttributeKind,
+        f: F,
+    ) -> Result<AttributeId, EventError>
+    where
+        F: FnOnce() -> Result<AttributeValue, EventError>,
+    {
         let expected = self.attributes.by_id(index);
-        if expected != actual {
+        // The enumerations are set like any other string
+        let is_enumeration = expected == AttributeKind::Enum && actual == AttributeKind::String;
+        if expected != actual && !is_enumeration {
             return Err(EventError::WrongType {
                 name: name.to_owned(),
                 expected,
                 actual,
             });
         }
-        self.by_ids[index.0] = f();
-        Ok(())
+        self.by_ids[index.0] = f()?;
+        self.set.insert(index.0);
+        Ok(index)
+    }
+
+    #[inline]
+    fn lookup(&self, name: &str) -> Result<AttributeId, EventError> {
+        self.attributes
+            .by_name(name)
+            .ok_or_else(|| EventError::NonExistingAttribute(name.to_string()))
+    }
+
+    /// Check that the handle was created by the [`crate::ATree`] of the builder and return the
+    /// attribute along with its name.
+    #[inline]
+    fn resolve(&self, handle: AttributeHandle) -> Result<(AttributeId, &'atree str), EventError> {
+        if handle.tree_id != self.attributes.tree_id() {
+            return Err(EventError::ForeignAttributeHandle);
+        }
+        Ok((handle.id, self.attributes.name(handle.id)))
+    }
+
+    /// Keep the text of the default strings like [`EventBuilder::add_texts()`] does for the ones
+    /// that are set.
+    fn add_default_texts(&mut self) {
+        for (i, value) in self.attributes.defaults().iter().enumerate() {
+            let ids = match value {
+                AttributeValue::String(id) => core::slice::from_ref(id),
+                AttributeValue::StringList(ids) => ids.as_slice(),
+                _ => continue,
+            };
+            let index = AttributeId(i);
+            if self.attributes.retains_text(index) {
+                let texts = ids
+                    .iter()
+                    .filter_map(|id| self.strings.resolve(*id))
+                    .sorted_unstable()
+                    .dedup()
+                    .collect();
+                self.texts.insert(index, texts);
+            }
+        }
+    }
+
+    /// Keep the text of the strings if some predicates need it (i.e. string matching) since the
+    /// strings that were not seen in the expressions all share the same [`StringId`].
+    fn add_texts(&mut self, index: AttributeId, values: &[&str]) {
+        if self.attributes.retains_text(index) {
+            let texts = values.iter().map(|value| value.to_string()).collect();
+            self.texts.insert(index, texts);
+        }
     }
 }
 
 /// An event that can be used by the [`crate::atree::ATree`] structure to match arbitrary boolean
 /// expressions
-#[derive(Clone, Debug)]
-pub struct Event(Vec<AttributeValue>);
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Event {
+    values: Vec<AttributeValue>,
+    texts: HashMap<AttributeId, Vec<String>>,
+    // The attributes that were set explicitly by the builder of the event
+    set: FixedBitSet,
+    undefined_policy: UndefinedPolicy,
+}
+
+impl Event {
+    /// Overwrite the attributes of the event with the ones that were set in `other`.
+    ///
+    /// The attributes that were not set by the builder of `other` (whether they are `undefined`
+    /// or hold their default value) are left untouched which allows to assemble an event
+    /// incrementally from partial events; the ones explicitly set to `undefined` (see
+    /// [`EventBuilder::with_undefined()`]) are overwritten too. Both events are expected to have
+    /// been built by the same [`crate::ATree`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [
+    ///     AttributeDefinition::integer("exchange_id"),
+    ///     AttributeDefinition::string("country"),
+    /// ];
+    /// let atree = ATree::<u64>::new(&definitions).unwrap();
+    ///
+    /// let mut builder = atree.make_event();
+    /// builder.with_integer("exchange_id", 1).unwrap();
+    /// let mut event = builder.build().unwrap();
+    ///
+    /// let mut builder = atree.make_event();
+    /// builder.with_string("country", "CA").unwrap();
+    /// let fragment = builder.build().unwrap();
+    ///
+    /// // The event now has both its `exchange_id` and its `country` defined
+    /// event.merge(&fragment);
+    /// ```
+    pub fn merge(&mut self, other: &Event) {
+        debug_assert_eq!(
+            self.values.len(),
+            other.values.len(),
+            "merging events built from different attribute tables"
+        );
+        for i in other.set.ones() {
+            self.values[i] = other.values[i].clone();
+            let index = AttributeId(i);
+            match other.texts.get(&index) {
+                Some(texts) => self.texts.insert(index, texts.clone()),
+                None => self.texts.remove(&index),
+            };
+        }
+        self.set.union_with(&other.set);
+    }
+
+    /// Hash the values of the event along with the texts of its strings.
+    ///
+    /// The texts are part of the hash since the strings that are not interned all share the same
+    /// ID while the string matching predicates can still tell them apart.
+    #[cfg(feature = "std")]
+    pub(crate) fn fingerprint(&self) -> u64 {
+        use core::hash::{Hash, Hasher};
+
+        let mut hasher = crate::hash::FnvHasher::default();
+        self.values.hash(&mut hasher);
+        for (id, texts) in self.texts.iter().sorted_by_key(|(id, _)| **id) {
+            id.hash(&mut hasher);
+            texts.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Return the text of the string (or list of strings) attribute.
+    ///
+    /// The text is only kept for the attributes that are used by some string matching predicates.
+    #[inline]
+    pub(crate) fn texts(&self, index: AttributeId) -> &[String] {
+        self.texts.get(&index).map_or(&[], Vec::as_slice)
+    }
+
+    /// Return the amount of attributes that are defined in the event.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn defined_attributes(&self) -> usize {
+        self.values
+            .iter()
+            .filter(|value| !matches!(value, AttributeValue::Undefined))
+            .count()
+    }
+
+    /// Return the result of the predicates (other than the null checks) on the undefined
+    /// attributes.
+    #[inline]
+    pub(crate) const fn undefined_result(&self) -> Option<bool> {
+        match self.undefined_policy {
+            UndefinedPolicy::Unknown => None,
+            UndefinedPolicy::NonMatch => Some(false),
+        }
+    }
+}
 
 impl Index<AttributeId> for Event {
     type Output = AttributeValue;
 
     #[inline]
     fn index(&self, index: AttributeId) -> &Self::Output {
-        &self.0[index.0]
+        &self.values[index.0]
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum AttributeValue {
     Boolean(bool),
     Integer(i64),
@@ -203,52 +767,278 @@ pub enum AttributeValue {
     Undefined,
 }
 
+impl AttributeValue {
+    /// The kind of the attribute that can hold the value or `None` when it is undefined
+    const fn kind(&self) -> Option<AttributeKind> {
+        match self {
+            Self::Boolean(_) => Some(AttributeKind::Boolean),
+            Self::Integer(_) => Some(AttributeKind::Integer),
+            Self::Float(_) => Some(AttributeKind::Float),
+            Self::String(_) => Some(AttributeKind::String),
+            Self::IntegerList(_) => Some(AttributeKind::IntegerList),
+            Self::StringList(_) => Some(AttributeKind::StringList),
+            Self::Undefined => None,
+        }
+    }
+}
+
+/// How the predicates (other than the null checks) treat the attributes that are undefined in an
+/// [`Event`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum UndefinedPolicy {
+    /// The result of the predicate is unknown so it is neither true nor false: `not` keeps it
+    /// unknown, `and` can still be false and `or` can still be true
+    #[default]
+    Unknown,
+    /// The predicate is false, just like its negation
+    NonMatch,
+}
+
 #[derive(Clone, Debug)]
 pub struct AttributeTable {
     by_names: HashMap<String, AttributeId>,
     by_ids: Vec<AttributeKind>,
+    names: Vec<String>,
+    // Whether the events have to keep the text of the attribute (i.e. for string matching or for
+    // the length of a list of strings)
+    retained_texts: Vec<bool>,
+    // The values that the attributes have in the events that do not set them
+    defaults: Vec<AttributeValue>,
+    // The amount of decimal places that the floats are rounded to
+    float_scales: Vec<Option<u32>>,
+    enumerations: HashMap<AttributeId, Enumeration>,
+    undefined_policy: UndefinedPolicy,
+    reject_unknown_list_elements: bool,
+    // The ID of the tree that owns the table so that the handles of other trees are rejected
+    tree_id: usize,
+}
+
+/// The values allowed for an enumeration attribute
+#[derive(Clone, Debug)]
+pub struct Enumeration {
+    values: Vec<String>,
+    ids: Vec<StringId>,
+}
+
+impl Enumeration {
+    #[inline]
+    pub fn contains(&self, id: StringId) -> bool {
+        self.ids.binary_search(&id).is_ok()
+    }
+
+    #[inline]
+    pub fn values(&self) -> &[String] {
+        &self.values
+    }
 }
 
 #[derive(Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Debug, Hash)]
 pub struct AttributeId(usize);
 
+/// An attribute that was looked up once by [`crate::ATree::attribute_handle()`] so that the
+/// events can set it without looking up its name each time
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct AttributeHandle {
+    tree_id: usize,
+    id: AttributeId,
+}
+
 impl Display for AttributeId {
-    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> core::fmt::Result {
         write!(formatter, "attribute({})", self.0)
     }
-}
+}
+
+impl AttributeTable {
+    pub fn new(definitions: &[AttributeDefinition]) -> Result<Self, EventError> {
+        let size = definitions.len();
+        let mut by_names = HashMap::with_capacity_and_hasher(size, Default::default());
+        let mut by_ids = Vec::with_capacity(size);
+        let mut names = Vec::with_capacity(size);
+        let mut defaults = Vec::with_capacity(size);
+        let mut float_scales = Vec::with_capacity(size);
+        let mut enumerations = HashMap::default();
+        for (i, definition) in definitions.iter().enumerate() {
+            // The aliases resolve to the same attribute but the errors use the canonical name
+            for name in core::iter::once(&definition.name).chain(&definition.aliases) {
+                if by_names.contains_key(name) {
+                    return Err(EventError::AlreadyPresent(name.to_owned()));
+                }
+                by_names.insert(name.to_owned(), AttributeId(i));
+            }
+            if let Some(actual) = definition.default.kind() {
+                // The enumerations default to a string like the ones they are set to
+                let is_enumeration =
+                    definition.kind == AttributeKind::Enum && actual == AttributeKind::String;
+                if definition.kind != actual && !is_enumeration {
+                    return Err(EventError::WrongType {
+                        name: definition.name.to_owned(),
+                        expected: definition.kind.clone(),
+                        actual,
+                    });
+                }
+            }
+            if definition.float_scale.is_some() && definition.kind != AttributeKind::Float {
+                return Err(EventError::UnsupportedFloatScale {
+                    name: definition.name.to_owned(),
+                    actual: definition.kind.clone(),
+                });
+            }
+            names.push(definition.name.to_owned());
+            by_ids.push(definition.kind.clone());
+            // The lists are sorted and deduplicated like the ones that are set in the events
+            defaults.push(match (&definition.default, definition.float_scale) {
+                (AttributeValue::Float(value), Some(scale)) => {
+                    AttributeValue::Float(value.round_dp(scale))
+                }
+                (AttributeValue::IntegerList(values), _) => {
+                    AttributeValue::IntegerList(values.iter().copied().sorted().dedup().collect())
+                }
+                (AttributeValue::StringList(values), _) => {
+                    AttributeValue::StringList(values.iter().copied().sorted().dedup().collect())
+                }
+                (default, _) => default.clone(),
+            });
+            float_scales.push(definition.float_scale);
+            if definition.kind == AttributeKind::Enum {
+                let enumeration = Enumeration {
+                    values: definition.values.clone(),
+                    ids: vec![],
+                };
+                enumerations.insert(AttributeId(i), enumeration);
+            }
+        }
+
+        Ok(Self {
+            by_names,
+            retained_texts: vec![false; by_ids.len()],
+            by_ids,
+            names,
+            defaults,
+            float_scales,
+            enumerations,
+            undefined_policy: UndefinedPolicy::default(),
+            reject_unknown_list_elements: false,
+            tree_id: 0,
+        })
+    }
+
+    /// Intern the allowed values of the enumerations so that the predicates can be validated
+    /// against them.
+    pub fn intern_enumerations(
+        &mut self,
+        strings: &mut dyn Interner,
+    ) -> Result<(), StringTableError> {
+        for enumeration in self.enumerations.values_mut() {
+            enumeration.ids = enumeration
+                .values
+                .iter()
+                .map(|value| strings.get_or_update(value))
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter()
+                .sorted()
+                .dedup()
+                .collect();
+        }
+        Ok(())
+    }
+
+    #[inline]
+    /// Check that the default values of the enumerations are among their allowed values once the
+    /// latter are interned (see [`AttributeTable::intern_enumerations()`]).
+    pub fn check_enumeration_defaults(&self) -> Result<(), EventError> {
+        for (id, enumeration) in &self.enumerations {
+            if let AttributeValue::String(value) = &self.defaults[id.0] {
+                if !enumeration.contains(*value) {
+                    return Err(EventError::NotAllowedValue {
+                        name: self.names[id.0].clone(),
+                        allowed: enumeration.values.clone(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn enumeration(&self, id: AttributeId) -> Option<&Enumeration> {
+        self.enumerations.get(&id)
+    }
+
+    #[inline]
+    pub fn by_name(&self, name: &str) -> Option<AttributeId> {
+        self.by_names.get(name).cloned()
+    }
+
+    #[inline]
+    pub fn by_id(&self, id: AttributeId) -> AttributeKind {
+        self.by_ids[id.0].clone()
+    }
+
+    #[inline]
+    pub fn name(&self, id: AttributeId) -> &str {
+        &self.names[id.0]
+    }
+
+    #[inline]
+    pub fn defaults(&self) -> &[AttributeValue] {
+        &self.defaults
+    }
+
+    #[inline]
+    pub fn retain_text(&mut self, id: AttributeId) {
+        self.retained_texts[id.0] = true;
+    }
+
+    #[inline]
+    pub fn set_undefined_policy(&mut self, undefined_policy: UndefinedPolicy) {
+        self.undefined_policy = undefined_policy;
+    }
+
+    #[inline]
+    pub const fn undefined_policy(&self) -> UndefinedPolicy {
+        self.undefined_policy
+    }
+
+    #[inline]
+    pub fn set_reject_unknown_list_elements(&mut self, reject: bool) {
+        self.reject_unknown_list_elements = reject;
+    }
 
-impl AttributeTable {
-    pub fn new(definitions: &[AttributeDefinition]) -> Result<Self, EventError> {
-        let size = definitions.len();
-        let mut by_names = HashMap::with_capacity(size);
-        let mut by_ids = Vec::with_capacity(size);
-        for (i, definition) in definitions.iter().enumerate() {
-            let name = definition.name.to_owned();
-            if by_names.contains_key(&name) {
-                return Err(EventError::AlreadyPresent(name));
-            }
+    #[inline]
+    pub const fn rejects_unknown_list_elements(&self) -> bool {
+        self.reject_unknown_list_elements
+    }
 
-            by_names.insert(name, AttributeId(i));
-            by_ids.push(definition.kind.clone());
+    /// Round the float to the scale of the attribute (if it has one).
+    #[inline]
+    pub fn round_float(&self, id: AttributeId, value: Decimal) -> Decimal {
+        match self.float_scales[id.0] {
+            Some(scale) => value.round_dp(scale),
+            None => value,
         }
+    }
 
-        Ok(Self { by_names, by_ids })
+    #[inline]
+    pub fn set_tree_id(&mut self, tree_id: usize) {
+        self.tree_id = tree_id;
     }
 
     #[inline]
-    pub fn by_name(&self, name: &str) -> Option<AttributeId> {
-        self.by_names.get(name).cloned()
+    pub const fn tree_id(&self) -> usize {
+        self.tree_id
     }
 
     #[inline]
-    pub fn by_id(&self, id: AttributeId) -> AttributeKind {
-        self.by_ids[id.0].clone()
+    pub fn handle(&self, name: &str) -> Option<AttributeHandle> {
+        self.by_name(name).map(|id| AttributeHandle {
+            tree_id: self.tree_id,
+            id,
+        })
     }
 
     #[inline]
-    pub fn len(&self) -> usize {
-        self.by_ids.len()
+    pub fn retains_text(&self, id: AttributeId) -> bool {
+        self.retained_texts[id.0]
     }
 }
 
@@ -257,8 +1047,15 @@ impl AttributeTable {
 pub struct AttributeDefinition {
     name: String,
     kind: AttributeKind,
+    values: Vec<String>,
+    aliases: Vec<String>,
+    default: AttributeValue,
+    float_scale: Option<u32>,
 }
 
+/// The type of an attribute
+///
+/// It is written in snake case (i.e. `integer_list`) when displayed or parsed.
 #[derive(Clone, PartialEq, Debug)]
 pub enum AttributeKind {
     Boolean,
@@ -267,6 +1064,48 @@ pub enum AttributeKind {
     String,
     IntegerList,
     StringList,
+    Enum,
+}
+
+impl AttributeKind {
+    const ALL: [Self; 7] = [
+        Self::Boolean,
+        Self::Integer,
+        Self::Float,
+        Self::String,
+        Self::IntegerList,
+        Self::StringList,
+        Self::Enum,
+    ];
+
+    const fn name(&self) -> &'static str {
+        match self {
+            Self::Boolean => "boolean",
+            Self::Integer => "integer",
+            Self::Float => "float",
+            Self::String => "string",
+            Self::IntegerList => "integer_list",
+            Self::StringList => "string_list",
+            Self::Enum => "enum",
+        }
+    }
+}
+
+impl Display for AttributeKind {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(formatter, "{}", self.name())
+    }
+}
+
+impl FromStr for AttributeKind {
+    type Err = EventError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name() == value)
+            .ok_or_else(|| EventError::UnknownAttributeKind(value.to_string()))
+    }
 }
 
 impl AttributeDefinition {
@@ -276,6 +1115,10 @@ impl AttributeDefinition {
         Self {
             name: name.to_owned(),
             kind,
+            values: vec![],
+            aliases: vec![],
+            default: AttributeValue::Undefined,
+            float_scale: None,
         }
     }
 
@@ -285,6 +1128,10 @@ impl AttributeDefinition {
         Self {
             name: name.to_owned(),
             kind,
+            values: vec![],
+            aliases: vec![],
+            default: AttributeValue::Undefined,
+            float_scale: None,
         }
     }
 
@@ -294,6 +1141,10 @@ impl AttributeDefinition {
         Self {
             name: name.to_owned(),
             kind,
+            values: vec![],
+            aliases: vec![],
+            default: AttributeValue::Undefined,
+            float_scale: None,
         }
     }
 
@@ -303,6 +1154,10 @@ impl AttributeDefinition {
         Self {
             name: name.to_owned(),
             kind,
+            values: vec![],
+            aliases: vec![],
+            default: AttributeValue::Undefined,
+            float_scale: None,
         }
     }
 
@@ -312,6 +1167,10 @@ impl AttributeDefinition {
         Self {
             name: name.to_owned(),
             kind,
+            values: vec![],
+            aliases: vec![],
+            default: AttributeValue::Undefined,
+            float_scale: None,
         }
     }
 
@@ -321,19 +1180,160 @@ impl AttributeDefinition {
         Self {
             name: name.to_owned(),
             kind,
+            values: vec![],
+            aliases: vec![],
+            default: AttributeValue::Undefined,
+            float_scale: None,
+        }
+    }
+
+    /// Create an enumeration attribute definition.
+    ///
+    /// An enumeration is a string whose values are restricted to the specified ones: the
+    /// expressions that compare it to any other value are rejected.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [AttributeDefinition::enumeration(
+    ///     "log_level",
+    ///     &["debug", "info", "warn", "error"],
+    /// )];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// assert!(atree.insert(&1u64, "log_level = 'debug'").is_ok());
+    /// assert!(atree.insert(&2u64, "log_level = 'debgu'").is_err());
+    /// ```
+    pub fn enumeration(name: &str, values: &[&str]) -> Self {
+        let kind = AttributeKind::Enum;
+        Self {
+            name: name.to_owned(),
+            kind,
+            values: values.iter().map(|value| value.to_string()).collect(),
+            aliases: vec![],
+            default: AttributeValue::Undefined,
+            float_scale: None,
         }
     }
+
+    /// Add another name that refers to the same attribute (i.e. while an attribute is being
+    /// renamed).
+    ///
+    /// The expressions and the events can use either name.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [AttributeDefinition::integer("exchange_id").with_alias("exchange")];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "exchange = 1").unwrap();
+    /// let mut builder = atree.make_event();
+    /// builder.with_integer("exchange_id", 1).unwrap();
+    /// let event = builder.build().unwrap();
+    /// assert_eq!(vec![&1u64], atree.search(&event).unwrap().matches());
+    /// ```
+    pub fn with_alias(mut self, alias: &str) -> Self {
+        self.aliases.push(alias.to_owned());
+        self
+    }
+
+    /// Set the value that the attribute has in the events that do not set it, instead of
+    /// `undefined`, so that the expressions referring to it are always evaluated.
+    ///
+    /// The kind of the value must match the kind of the attribute, otherwise the [`crate::ATree`]
+    /// cannot be created. The strings have to be interned by the [`Interner`] that is given to
+    /// [`crate::ATree::with_interner()`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition, AttributeValue};
+    ///
+    /// let definitions = [
+    ///     AttributeDefinition::boolean("debug").with_default(AttributeValue::Boolean(false)),
+    ///     AttributeDefinition::integer("exchange_id"),
+    /// ];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "not debug").unwrap();
+    /// let mut builder = atree.make_event();
+    /// builder.with_integer("exchange_id", 1).unwrap();
+    /// let event = builder.build().unwrap();
+    /// assert_eq!(vec![&1u64], atree.search(&event).unwrap().matches());
+    /// ```
+    pub fn with_default(mut self, value: AttributeValue) -> Self {
+        self.default = value;
+        self
+    }
+
+    /// Round the values of a float attribute to `scale` decimal places, both in the expressions
+    /// and in the events, so that they are compared consistently.
+    ///
+    /// The values are rounded half to even (i.e. banker's rounding) so `1.005` and `1.015` are
+    /// rounded to `1.00` and `1.02` with a scale of 2. Only the float attributes can have a
+    /// scale, otherwise the [`crate::ATree`] cannot be created.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use a_tree::{ATree, AttributeDefinition};
+    ///
+    /// let definitions = [AttributeDefinition::float("price").with_float_scale(2)];
+    /// let mut atree = ATree::new(&definitions).unwrap();
+    /// atree.insert(&1u64, "price = 1.00").unwrap();
+    /// let mut builder = atree.make_event();
+    /// builder.with_float("price", 1004, 3).unwrap();
+    /// let event = builder.build().unwrap();
+    /// assert_eq!(vec![&1u64], atree.search(&event).unwrap().matches());
+    /// ```
+    pub fn with_float_scale(mut self, scale: u32) -> Self {
+        self.float_scale = Some(scale);
+        self
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::strings::StringTable;
+    use std::collections::HashSet;
 
     #[test]
     fn can_create_an_attribute_table_with_no_attributes() {
         assert!(AttributeTable::new(&[]).is_ok())
     }
 
+    #[test]
+    fn return_an_error_when_setting_any_attribute_of_an_event_without_attributes() {
+        let attributes = AttributeTable::new(&[]).unwrap();
+        let strings = StringTable::new();
+        let mut builder = EventBuilder::new(&attributes, &strings);
+        let missing = || Err(EventError::NonExistingAttribute("exchange_id".to_string()));
+
+        assert_eq!(missing(), builder.with_boolean("exchange_id", true));
+        assert_eq!(missing(), builder.with_integer("exchange_id", 1));
+        assert_eq!(missing(), builder.with_float("exchange_id", 15, 1));
+        assert_eq!(missing(), builder.with_string("exchange_id", "1"));
+        assert_eq!(missing(), builder.with_integer_list("exchange_id", &[1]));
+        assert_eq!(
+            missing(),
+            builder.with_sorted_integer_list("exchange_id", &[1])
+        );
+        assert_eq!(missing(), builder.with_string_list("exchange_id", &["1"]));
+        assert_eq!(missing(), builder.with_undefined("exchange_id"));
+        assert!(builder.build().is_ok());
+    }
+
+    #[test]
+    fn name_the_missing_attribute_in_the_error() {
+        assert_eq!(
+            "ABE refers to non-existing attribute 'exchange_id'",
+            EventError::NonExistingAttribute("exchange_id".to_string()).to_string()
+        );
+    }
+
     #[test]
     fn can_create_an_attribute_table_with_some_attributes() {
         let definitions = [
@@ -363,6 +1363,68 @@ mod tests {
         assert!(AttributeTable::new(&definitions).is_err());
     }
 
+    #[test]
+    fn can_convert_the_attribute_kinds_to_and_from_strings() {
+        for kind in AttributeKind::ALL {
+            assert_eq!(Ok(kind.clone()), kind.to_string().parse::<AttributeKind>());
+        }
+        assert_eq!("integer_list", AttributeKind::IntegerList.to_string());
+    }
+
+    #[test]
+    fn return_an_error_when_parsing_an_unknown_attribute_kind() {
+        assert_eq!(
+            Err(EventError::UnknownAttributeKind("list".to_string())),
+            "list".parse::<AttributeKind>()
+        );
+        assert!("Integer".parse::<AttributeKind>().is_err());
+    }
+
+    #[test]
+    fn resolve_the_aliases_to_the_same_attribute() {
+        let attributes = AttributeTable::new(&[
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id")
+                .with_alias("exchange")
+                .with_alias("exchange-id"),
+        ])
+        .unwrap();
+
+        let id = attributes.by_name("exchange_id");
+
+        assert_eq!(Some(AttributeId(1)), id);
+        assert_eq!(id, attributes.by_name("exchange"));
+        assert_eq!(id, attributes.by_name("exchange-id"));
+        assert_eq!("exchange_id", attributes.name(AttributeId(1)));
+    }
+
+    #[test]
+    fn return_an_error_on_aliases_that_are_already_defined() {
+        let definitions = [
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id").with_alias("private"),
+        ];
+
+        assert_eq!(
+            Err(EventError::AlreadyPresent("private".to_string())),
+            AttributeTable::new(&definitions).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn can_define_attributes_that_are_named_like_keywords_or_need_quoting() {
+        let definitions = [
+            AttributeDefinition::boolean("order"),
+            AttributeDefinition::integer("android"),
+            AttributeDefinition::string("user.country"),
+            AttributeDefinition::integer("len"),
+            AttributeDefinition::boolean("or"),
+            AttributeDefinition::string("country").with_alias("in"),
+        ];
+
+        assert!(AttributeTable::new(&definitions).is_ok());
+    }
+
     #[test]
     fn can_add_a_boolean_attribute_value() {
         let attributes = AttributeTable::new(&[AttributeDefinition::boolean("private")]).unwrap();
@@ -479,6 +1541,154 @@ mod tests {
         assert!(builder.build().is_ok());
     }
 
+    #[test]
+    fn can_reuse_a_builder_once_it_is_reset() {
+        let attributes = AttributeTable::new(&[
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::boolean("private"),
+        ])
+        .unwrap();
+        let mut strings = StringTable::new();
+        let country = strings.get_or_update("CA").unwrap();
+        let mut builder = EventBuilder::new(&attributes, &strings);
+        builder.with_integer("exchange_id", 1).unwrap();
+        builder.with_string("country", "CA").unwrap();
+        let first = builder.build_and_reset().unwrap();
+        builder.with_boolean("private", true).unwrap();
+        builder.reset();
+        builder.with_integer("exchange_id", 2).unwrap();
+        let second = builder.build_and_reset().unwrap();
+
+        let exchange_id = attributes.by_name("exchange_id").unwrap();
+        let country_id = attributes.by_name("country").unwrap();
+        let private = attributes.by_name("private").unwrap();
+        assert!(matches!(first[exchange_id], AttributeValue::Integer(1)));
+        assert!(matches!(first[country_id], AttributeValue::String(id) if id == country));
+        assert!(matches!(first[private], AttributeValue::Undefined));
+        assert!(matches!(second[exchange_id], AttributeValue::Integer(2)));
+        assert!(matches!(second[country_id], AttributeValue::Undefined));
+        assert!(matches!(second[private], AttributeValue::Undefined));
+        assert!(builder
+            .by_ids
+            .iter()
+            .all(|value| matches!(value, AttributeValue::Undefined)));
+    }
+
+    #[test]
+    fn set_the_same_lists_from_iterators_as_from_slices() {
+        let attributes = AttributeTable::new(&[
+            AttributeDefinition::integer_list("segment_ids"),
+            AttributeDefinition::string_list("deals"),
+        ])
+        .unwrap();
+        let mut strings = StringTable::new();
+        strings.get_or_update("deal-1").unwrap();
+        strings.get_or_update("deal-2").unwrap();
+        let mut builder = EventBuilder::new(&attributes, &strings);
+        builder
+            .with_integer_list("segment_ids", &[3, 1, 2, 3])
+            .unwrap();
+        builder
+            .with_string_list("deals", &["deal-2", "deal-1", "deal-3"])
+            .unwrap();
+        let from_slices = builder.build_and_reset().unwrap();
+        builder.with_integer_list_iter("segment_ids", 1..4).unwrap();
+        let deals = HashSet::from(["deal-3", "deal-1", "deal-2"].map(String::from));
+        builder.with_string_list_iter("deals", deals).unwrap();
+        let from_iterators = builder.build().unwrap();
+
+        assert_eq!(from_slices, from_iterators);
+    }
+
+    #[test]
+    fn keep_the_texts_of_the_strings_set_from_an_iterator() {
+        let mut attributes =
+            AttributeTable::new(&[AttributeDefinition::string_list("deals")]).unwrap();
+        let deals = attributes.by_name("deals").unwrap();
+        attributes.retain_text(deals);
+        let strings = StringTable::new();
+        let mut builder = EventBuilder::new(&attributes, &strings);
+        builder
+            .with_string_list_iter("deals", ["deal-1", "deal-2"].iter())
+            .unwrap();
+        let event = builder.build().unwrap();
+
+        assert_eq!(["deal-1", "deal-2"].map(String::from), event.texts(deals));
+    }
+
+    #[test]
+    fn return_an_error_when_setting_a_list_from_an_iterator_with_the_wrong_type() {
+        let attributes =
+            AttributeTable::new(&[AttributeDefinition::integer("exchange_id")]).unwrap();
+        let strings = StringTable::new();
+        let mut builder = EventBuilder::new(&attributes, &strings);
+
+        assert!(matches!(
+            builder.with_integer_list_iter("exchange_id", 1..3),
+            Err(EventError::WrongType { .. })
+        ));
+        assert!(matches!(
+            builder.with_string_list_iter("exchange_id", ["1"]),
+            Err(EventError::WrongType { .. })
+        ));
+    }
+
+    #[test]
+    fn keep_the_attributes_of_the_builder_when_building_by_reference() {
+        let attributes = AttributeTable::new(&[
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string("country"),
+        ])
+        .unwrap();
+        let mut strings = StringTable::new();
+        let country = strings.get_or_update("CA").unwrap();
+        let mut builder = EventBuilder::new(&attributes, &strings);
+        builder.with_integer("exchange_id", 1).unwrap();
+        builder.with_string("country", "CA").unwrap();
+        let first = builder.build_ref();
+        builder.with_integer("exchange_id", 2).unwrap();
+        let second = builder.build_ref();
+
+        let exchange_id = attributes.by_name("exchange_id").unwrap();
+        let country_id = attributes.by_name("country").unwrap();
+        assert!(matches!(first[exchange_id], AttributeValue::Integer(1)));
+        assert!(matches!(second[exchange_id], AttributeValue::Integer(2)));
+        assert!(matches!(first[country_id], AttributeValue::String(id) if id == country));
+        assert!(matches!(second[country_id], AttributeValue::String(id) if id == country));
+        assert_eq!(second, builder.build().unwrap());
+    }
+
+    #[test]
+    fn can_set_an_already_sorted_list_of_integers() {
+        let attributes =
+            AttributeTable::new(&[AttributeDefinition::integer_list("segment_ids")]).unwrap();
+        let strings = StringTable::new();
+        let mut builder = EventBuilder::new(&attributes, &strings);
+        builder
+            .with_integer_list("segment_ids", &[3, 1, 2, 3])
+            .unwrap();
+        let sorted = builder.build_and_reset().unwrap();
+        builder
+            .with_sorted_integer_list("segment_ids", &[1, 2, 3])
+            .unwrap();
+        let presorted = builder.build().unwrap();
+
+        assert_eq!(sorted, presorted);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "not sorted")]
+    fn panic_on_unsorted_lists_of_integers_in_debug_builds() {
+        let attributes =
+            AttributeTable::new(&[AttributeDefinition::integer_list("segment_ids")]).unwrap();
+        let strings = StringTable::new();
+        let mut builder = EventBuilder::new(&attributes, &strings);
+
+        let _ = builder.with_sorted_integer_list("segment_ids", &[1, 1, 2]);
+    }
+
     #[test]
     fn can_create_an_event_with_a_missing_attribute() {
         let attributes = AttributeTable::new(&[AttributeDefinition::boolean("private")]).unwrap();
@@ -498,4 +1708,150 @@ mod tests {
 
         assert!(result.is_err());
     }
+
+    #[test]
+    fn can_merge_the_defined_attributes_of_another_event() {
+        let attributes = AttributeTable::new(&[
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::boolean("private"),
+        ])
+        .unwrap();
+        let mut strings = StringTable::new();
+        let country = strings.get_or_update("CA").unwrap();
+        let mut builder = EventBuilder::new(&attributes, &strings);
+        builder.with_integer("exchange_id", 1).unwrap();
+        let mut event = builder.build().unwrap();
+        let mut builder = EventBuilder::new(&attributes, &strings);
+        builder.with_string("country", "CA").unwrap();
+        let fragment = builder.build().unwrap();
+
+        event.merge(&fragment);
+
+        let exchange_id = attributes.by_name("exchange_id").unwrap();
+        let country_id = attributes.by_name("country").unwrap();
+        let private = attributes.by_name("private").unwrap();
+        assert!(matches!(event[exchange_id], AttributeValue::Integer(1)));
+        assert!(matches!(event[country_id], AttributeValue::String(id) if id == country));
+        assert!(matches!(event[private], AttributeValue::Undefined));
+    }
+
+    #[test]
+    fn overwrite_the_attributes_that_are_defined_in_both_events_when_merging() {
+        let attributes = AttributeTable::new(&[
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::boolean("private"),
+        ])
+        .unwrap();
+        let strings = StringTable::new();
+        let mut builder = EventBuilder::new(&attributes, &strings);
+        builder.with_integer("exchange_id", 1).unwrap();
+        builder.with_boolean("private", true).unwrap();
+        let mut event = builder.build().unwrap();
+        let mut builder = EventBuilder::new(&attributes, &strings);
+        builder.with_integer("exchange_id", 2).unwrap();
+        let fragment = builder.build().unwrap();
+
+        event.merge(&fragment);
+
+        let exchange_id = attributes.by_name("exchange_id").unwrap();
+        let private = attributes.by_name("private").unwrap();
+        assert!(matches!(event[exchange_id], AttributeValue::Integer(2)));
+        assert!(matches!(event[private], AttributeValue::Boolean(true)));
+    }
+
+    #[test]
+    fn keep_the_attributes_that_were_left_to_their_default_value_when_merging() {
+        let attributes = AttributeTable::new(&[
+            AttributeDefinition::integer("x"),
+            AttributeDefinition::integer("y").with_default(AttributeValue::Integer(7)),
+        ])
+        .unwrap();
+        let strings = StringTable::new();
+        let mut builder = EventBuilder::new(&attributes, &strings);
+        builder.with_integer("y", 3).unwrap();
+        let mut event = builder.build().unwrap();
+        let mut builder = EventBuilder::new(&attributes, &strings);
+        builder.with_integer("x", 1).unwrap();
+        let fragment = builder.build().unwrap();
+
+        event.merge(&fragment);
+
+        let x = attributes.by_name("x").unwrap();
+        let y = attributes.by_name("y").unwrap();
+        assert!(matches!(event[x], AttributeValue::Integer(1)));
+        assert!(matches!(event[y], AttributeValue::Integer(3)));
+    }
+
+    #[test]
+    fn overwrite_the_attributes_that_were_explicitly_undefined_when_merging() {
+        let attributes = AttributeTable::new(&[
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::boolean("private"),
+        ])
+        .unwrap();
+        let strings = StringTable::new();
+        let mut builder = EventBuilder::new(&attributes, &strings);
+        builder.with_integer("exchange_id", 1).unwrap();
+        builder.with_boolean("private", true).unwrap();
+        let mut event = builder.build().unwrap();
+        let mut builder = EventBuilder::new(&attributes, &strings);
+        builder.with_undefined("private").unwrap();
+        let fragment = builder.build().unwrap();
+
+        event.merge(&fragment);
+
+        let exchange_id = attributes.by_name("exchange_id").unwrap();
+        let private = attributes.by_name("private").unwrap();
+        assert!(matches!(event[exchange_id], AttributeValue::Integer(1)));
+        assert!(matches!(event[private], AttributeValue::Undefined));
+    }
+
+    #[test]
+    fn events_built_with_the_same_values_are_equal() {
+        let attributes = AttributeTable::new(&[
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::float("bidfloor"),
+            AttributeDefinition::string_list("deals"),
+            AttributeDefinition::boolean("private"),
+        ])
+        .unwrap();
+        let mut strings = StringTable::new();
+        strings.get_or_update("deal-1").unwrap();
+        let build = |exchange_id| {
+            let mut builder = EventBuilder::new(&attributes, &strings);
+            builder.with_integer("exchange_id", exchange_id).unwrap();
+            builder.with_float("bidfloor", 150, 2).unwrap();
+            builder.with_string_list("deals", &["deal-1"]).unwrap();
+            builder.build().unwrap()
+        };
+
+        assert_eq!(build(1), build(1));
+        assert_ne!(build(1), build(2));
+        assert_eq!(AttributeValue::Undefined, AttributeValue::Undefined);
+    }
+
+    #[test]
+    fn merging_an_event_yields_the_same_event_as_building_it_whole() {
+        let attributes = AttributeTable::new(&[
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::boolean("private"),
+        ])
+        .unwrap();
+        let strings = StringTable::new();
+        let mut builder = EventBuilder::new(&attributes, &strings);
+        builder.with_integer("exchange_id", 1).unwrap();
+        let mut event = builder.build().unwrap();
+        let mut builder = EventBuilder::new(&attributes, &strings);
+        builder.with_boolean("private", true).unwrap();
+        let fragment = builder.build().unwrap();
+        let mut builder = EventBuilder::new(&attributes, &strings);
+        builder.with_integer("exchange_id", 1).unwrap();
+        builder.with_boolean("private", true).unwrap();
+        let expected = builder.build().unwrap();
+
+        event.merge(&fragment);
+
+        assert_eq!(expected, event);
+    }
 }
@@ -0,0 +1,587 @@
+use crate::{
+    ast::Node,
+    error::ParserError,
+    events::AttributeTable,
+    parser::{ATreeParseError, ParserLimits},
+    predicates::{
+        ComparisonOperator, ComparisonValue, ContainsOperator, EqualityOperator, FlagOperator,
+        ListLiteral, ListOperator, NullOperator, Predicate, PredicateKind, PrimitiveLiteral,
+        SetOperator, StringMatchOperator,
+    },
+    strings::Interner,
+};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::ops::RangeInclusive;
+use itertools::Itertools;
+use lalrpop_util::ParseError;
+use rust_decimal::Decimal;
+
+/// An arbitrary boolean expression built without going through the DSL
+///
+/// The expressions are inserted with [`ATree::insert_expr()`](crate::ATree::insert_expr) which
+/// validates them against the attributes of the [`ATree`](crate::ATree) like the DSL would.
+///
+/// # Examples
+///
+/// ```rust
+/// use a_tree::{Condition, Expr};
+///
+/// // exchange_id = 1 and (private or not country in ["CA", "US"])
+/// let expression = Expr::and(
+///     Expr::predicate("exchange_id", Condition::Equal(1.into())),
+///     Expr::or(
+///         Expr::predicate("private", Condition::IsTrue),
+///         Expr::not(Expr::predicate("country", Condition::In(vec!["CA", "US"].into()))),
+///     ),
+/// );
+/// ```
+#[derive(Clone, PartialEq, Debug)]
+pub struct Expr(ExprNode);
+
+#[derive(Clone, PartialEq, Debug)]
+enum ExprNode {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Predicate(String, Condition),
+}
+
+impl Expr {
+    /// Create an expression that is true when both `left` and `right` are true
+    pub fn and(left: Expr, right: Expr) -> Self {
+        Self(ExprNode::And(Box::new(left), Box::new(right)))
+    }
+
+    /// Create an expression that is true when either `left` or `right` is true
+    pub fn or(left: Expr, right: Expr) -> Self {
+        Self(ExprNode::Or(Box::new(left), Box::new(right)))
+    }
+
+    /// Create an expression that is true when `expression` is false
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(expression: Expr) -> Self {
+        Self(ExprNode::Not(Box::new(expression)))
+    }
+
+    /// Create an expression that applies the `condition` to the attribute named `attribute`
+    pub fn predicate(attribute: &str, condition: Condition) -> Self {
+        Self(ExprNode::Predicate(attribute.to_string(), condition))
+    }
+
+    /// Turn the expression into the AST that the DSL would have produced along with its depth
+    pub(crate) fn to_node(
+        &self,
+        attributes: &AttributeTable,
+        strings: &mut dyn Interner,
+        limits: &ParserLimits,
+    ) -> Result<(Node, usize), ATreeParseError<'static>> {
+        match &self.0 {
+            ExprNode::And(left, right) => {
+                let (left, left_depth) = left.to_node(attributes, strings, limits)?;
+                let (right, right_depth) = right.to_node(attributes, strings, limits)?;
+                let depth = limits.check_depth(1 + core::cmp::max(left_depth, right_depth))?;
+                Ok((Node::And(Box::new(left), Box::new(right)), depth))
+            }
+            ExprNode::Or(left, right) => {
+                let (left, left_depth) = left.to_node(attributes, strings, limits)?;
+                let (right, right_depth) = right.to_node(attributes, strings, limits)?;
+                let depth = limits.check_depth(1 + core::cmp::max(left_depth, right_depth))?;
+                Ok((Node::Or(Box::new(left), Box::new(right)), depth))
+            }
+            ExprNode::Not(expression) => {
+                let (expression, depth) = expression.to_node(attributes, strings, limits)?;
+                let depth = limits.check_depth(1 + depth)?;
+                Ok((Node::Not(Box::new(expression)), depth))
+            }
+            ExprNode::Predicate(attribute, condition) => {
+                let kind = condition.to_kind(strings, limits)?;
+                let predicate = Predicate::new(attributes, attribute, kind).map_err(|error| {
+                    ParseError::User {
+                        error: ParserError::Event(error),
+                    }
+                })?;
+                Ok((Node::Value(predicate), 0))
+            }
+        }
+    }
+}
+
+/// The condition that an attribute has to satisfy in an [`Expr`]
+///
+/// Each condition has the same meaning and works with the same kinds of attributes as its
+/// equivalent in the DSL (i.e. [`Condition::OneOf`] is `one of`).
+#[derive(Clone, PartialEq, Debug)]
+pub enum Condition {
+    /// The boolean attribute is true (i.e. `private`)
+    IsTrue,
+    /// `<`
+    LessThan(Number),
+    /// `<=`
+    LessThanEqual(Number),
+    /// `>`
+    GreaterThan(Number),
+    /// `>=`
+    GreaterThanEqual(Number),
+    /// `=`
+    Equal(Literal),
+    /// `<>`
+    NotEqual(Literal),
+    /// `is null`
+    IsNull,
+    /// `is not null`
+    IsNotNull,
+    /// `is empty`
+    IsEmpty,
+    /// `is not empty`
+    IsNotEmpty,
+    /// `in`
+    In(List),
+    /// `not in`
+    NotIn(List),
+    /// `in` with an inclusive range (i.e. `hour in 9..=16`)
+    InRange(RangeInclusive<i64>),
+    /// `not in` with an inclusive range
+    NotInRange(RangeInclusive<i64>),
+    /// `one of`
+    OneOf(List),
+    /// `none of`
+    NoneOf(List),
+    /// `all of`
+    AllOf(List),
+    /// `starts_with`
+    StartsWith(String),
+    /// `ends_with`
+    EndsWith(String),
+    /// `like`
+    Like(String),
+    /// `not like`
+    NotLike(String),
+    /// `contains`
+    Contains(Literal),
+    /// `not contains`
+    NotContains(Literal),
+    /// `has_flag`
+    HasFlag(i64),
+    /// `not has_flag`
+    NotHasFlag(i64),
+    /// `has_any_flag`
+    HasAnyFlag(i64),
+    /// `not has_any_flag`
+    NotHasAnyFlag(i64),
+}
+
+impl Condition {
+    fn to_kind(
+        &self,
+        strings: &mut dyn Interner,
+        limits: &ParserLimits,
+    ) -> Result<PredicateKind, ATreeParseError<'static>> {
+        let kind = match self {
+            Self::IsTrue => PredicateKind::Variable,
+            Self::LessThan(value) => {
+                PredicateKind::Comparison(ComparisonOperator::LessThan, value.to_value())
+            }
+            Self::LessThanEqual(value) => {
+                PredicateKind::Comparison(ComparisonOperator::LessThanEqual, value.to_value())
+            }
+            Self::GreaterThan(value) => {
+                PredicateKind::Comparison(ComparisonOperator::GreaterThan, value.to_value())
+            }
+            Self::GreaterThanEqual(value) => {
+                PredicateKind::Comparison(ComparisonOperator::GreaterThanEqual, value.to_value())
+            }
+            Self::Equal(value) => {
+                PredicateKind::Equality(EqualityOperator::Equal, value.to_literal(strings)?)
+            }
+            Self::NotEqual(value) => {
+                PredicateKind::Equality(EqualityOperator::NotEqual, value.to_literal(strings)?)
+            }
+            Self::IsNull => PredicateKind::Null(NullOperator::IsNull),
+            Self::IsNotNull => PredicateKind::Null(NullOperator::IsNotNull),
+            Self::IsEmpty => PredicateKind::Null(NullOperator::IsEmpty),
+            Self::IsNotEmpty => PredicateKind::Null(NullOperator::IsNotEmpty),
+            Self::In(list) => {
+                PredicateKind::Set(SetOperator::In, list.to_literal(strings, limits)?)
+            }
+            Self::NotIn(list) => {
+                PredicateKind::Set(SetOperator::NotIn, list.to_literal(strings, limits)?)
+            }
+            Self::InRange(range) => PredicateKind::Range(SetOperator::In, range.clone()),
+            Self::NotInRange(range) => PredicateKind::Range(SetOperator::NotIn, range.clone()),
+            Self::OneOf(list) => {
+                PredicateKind::List(ListOperator::OneOf, list.to_literal(strings, limits)?)
+            }
+            Self::NoneOf(list) => {
+                PredicateKind::List(ListOperator::NoneOf, list.to_literal(strings, limits)?)
+            }
+            Self::AllOf(list) => {
+                PredicateKind::List(ListOperator::AllOf, list.to_literal(strings, limits)?)
+            }
+            Self::StartsWith(pattern) => {
+                PredicateKind::StringMatch(StringMatchOperator::StartsWith, pattern.clone())
+            }
+            Self::EndsWith(pattern) => {
+                PredicateKind::StringMatch(StringMatchOperator::EndsWith, pattern.clone())
+            }
+            Self::Like(pattern) => {
+                PredicateKind::StringMatch(StringMatchOperator::Like, pattern.clone())
+            }
+            Self::NotLike(pattern) => {
+                PredicateKind::StringMatch(StringMatchOperator::NotLike, pattern.clone())
+            }
+            Self::Contains(value) => {
+                PredicateKind::Contains(ContainsOperator::Contains, value.to_literal(strings)?)
+            }
+            Self::NotContains(value) => {
+                PredicateKind::Contains(ContainsOperator::NotContains, value.to_literal(strings)?)
+            }
+            Self::HasFlag(mask) => PredicateKind::Flag(FlagOperator::All, *mask),
+            Self::NotHasFlag(mask) => PredicateKind::Flag(FlagOperator::NotAll, *mask),
+            Self::HasAnyFlag(mask) => PredicateKind::Flag(FlagOperator::Any, *mask),
+            Self::NotHasAnyFlag(mask) => PredicateKind::Flag(FlagOperator::NotAny, *mask),
+        };
+        Ok(kind)
+    }
+}
+
+/// A number that an attribute is compared with in an [`Expr`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Number {
+    Integer(i64),
+    /// A float written as `number` × 10<sup>-`scale`</sup> like
+    /// [`EventBuilder::with_float()`](crate::EventBuilder::with_float)
+    Float(i64, u32),
+}
+
+impl Number {
+    fn to_value(self) -> ComparisonValue {
+        match self {
+            Self::Integer(value) => ComparisonValue::Integer(value),
+            Self::Float(number, scale) => {
+                ComparisonValue::Float(Decimal::new(number, scale).normalize())
+            }
+        }
+    }
+}
+
+impl From<i64> for Number {
+    fn from(value: i64) -> Self {
+        Self::Integer(value)
+    }
+}
+
+/// A literal value that an attribute is compared with in an [`Expr`]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Literal {
+    Integer(i64),
+    /// A float written as `number` × 10<sup>-`scale`</sup> like
+    /// [`EventBuilder::with_float()`](crate::EventBuilder::with_float)
+    Float(i64, u32),
+    String(String),
+}
+
+impl Literal {
+    fn to_literal(
+        &self,
+        strings: &mut dyn Interner,
+    ) -> Result<PrimitiveLiteral, ATreeParseError<'static>> {
+        let literal = match self {
+            Self::Integer(value) => PrimitiveLiteral::Integer(*value),
+            Self::Float(number, scale) => {
+                PrimitiveLiteral::Float(Decimal::new(*number, *scale).normalize())
+            }
+            Self::String(value) => {
+                PrimitiveLiteral::String(strings.get_or_update(value).map_err(|error| {
+                    ParseError::User {
+                        error: ParserError::Strings(error),
+                    }
+                })?)
+            }
+        };
+        Ok(literal)
+    }
+}
+
+impl From<i64> for Literal {
+    fn from(value: i64) -> Self {
+        Self::Integer(value)
+    }
+}
+
+impl From<&str> for Literal {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<String> for Literal {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+/// A list of values that an attribute is compared with in an [`Expr`]
+///
+/// The order of the values and their duplicates do not matter.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum List {
+    Integers(Vec<i64>),
+    Strings(Vec<String>),
+}
+
+impl List {
+    fn to_literal(
+        &self,
+        strings: &mut dyn Interner,
+        limits: &ParserLimits,
+    ) -> Result<ListLiteral, ATreeParseError<'static>> {
+        let literal = match self {
+            // Like `[]` in the DSL, the predicates retype the empty lists for the string attributes
+            Self::Strings(values) if values.is_empty() => ListLiteral::IntegerList(Vec::new()),
+            Self::Integers(values) => ListLiteral::IntegerList(
+                limits
+                    .check_list_length(values.clone())?
+                    .into_iter()
+                    .sorted()
+                    .dedup()
+                    .collect(),
+            ),
+            Self::Strings(values) => {
+                let ids = limits
+                    .check_list_length(values.iter().collect())?
+                    .into_iter()
+                    .map(|value| strings.get_or_update(value))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|error| ParseError::User {
+                        error: ParserError::Strings(error),
+                    })?;
+                // The lookups are done by ID so the list has to be sorted by ID, not by value
+                ListLiteral::StringList(ids.into_iter().sorted().dedup().collect())
+            }
+        };
+        Ok(literal)
+    }
+}
+
+impl From<Vec<i64>> for List {
+    fn from(values: Vec<i64>) -> Self {
+        Self::Integers(values)
+    }
+}
+
+impl From<Vec<&str>> for List {
+    fn from(values: Vec<&str>) -> Self {
+        Self::Strings(values.into_iter().map(ToString::to_string).collect())
+    }
+}
+
+impl From<Vec<String>> for List {
+    fn from(values: Vec<String>) -> Self {
+        Self::Strings(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        events::{AttributeDefinition, EventError},
+        parser::{self, NamedLists},
+        strings::StringTable,
+    };
+    use alloc::vec;
+
+    fn define_attributes() -> AttributeTable {
+        let definitions = vec![
+            AttributeDefinition::boolean("private"),
+            AttributeDefinition::integer("exchange_id"),
+            AttributeDefinition::float("price"),
+            AttributeDefinition::string("country"),
+            AttributeDefinition::string_list("deals"),
+            AttributeDefinition::integer_list("segment_ids"),
+        ];
+        AttributeTable::new(&definitions).unwrap()
+    }
+
+    fn to_node(
+        expression: &Expr,
+        attributes: &AttributeTable,
+        strings: &mut StringTable,
+    ) -> Result<Node, ATreeParseError<'static>> {
+        expression
+            .to_node(attributes, strings, &ParserLimits::default())
+            .map(|(node, _)| node)
+    }
+
+    fn parse(input: &str, attributes: &AttributeTable, strings: &mut StringTable) -> Node {
+        parser::parse(
+            input,
+            attributes,
+            strings,
+            &NamedLists::default(),
+            &ParserLimits::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn build_the_same_predicates_as_the_dsl() {
+        let attributes = define_attributes();
+        let mut strings = StringTable::new();
+        let cases = [
+            ("private", Condition::IsTrue),
+            ("exchange_id < 5", Condition::LessThan(5.into())),
+            ("exchange_id <= 5", Condition::LessThanEqual(5.into())),
+            ("exchange_id > 5", Condition::GreaterThan(5.into())),
+            (
+                "price >= 1.50",
+                Condition::GreaterThanEqual(Number::Float(15, 1)),
+            ),
+            ("exchange_id = 5", Condition::Equal(5.into())),
+            ("price <> 1.5", Condition::NotEqual(Literal::Float(150, 2))),
+            ("country = 'CA'", Condition::Equal("CA".into())),
+            ("country is null", Condition::IsNull),
+            ("country is not null", Condition::IsNotNull),
+            ("deals is empty", Condition::IsEmpty),
+            ("deals is not empty", Condition::IsNotEmpty),
+            (
+                r#"country in ["US", "CA"]"#,
+                Condition::In(vec!["CA", "US", "CA"].into()),
+            ),
+            (
+                "exchange_id not in [3, 1]",
+                Condition::NotIn(vec![1, 3].into()),
+            ),
+            ("country not in []", Condition::NotIn(List::Strings(vec![]))),
+            ("exchange_id in 9..=16", Condition::InRange(9..=16)),
+            ("exchange_id not in 9..17", Condition::NotInRange(9..=16)),
+            (
+                "segment_ids one of [1, 2]",
+                Condition::OneOf(vec![2, 1, 2].into()),
+            ),
+            (
+                r#"deals none of ["deal-1"]"#,
+                Condition::NoneOf(vec!["deal-1"].into()),
+            ),
+            (
+                "segment_ids all of []",
+                Condition::AllOf(List::Integers(vec![])),
+            ),
+            ("country starts_with 'U'", Condition::StartsWith("U".into())),
+            ("country ends_with 'A'", Condition::EndsWith("A".into())),
+            ("country like 'C_'", Condition::Like("C_".into())),
+            ("country not like 'C_'", Condition::NotLike("C_".into())),
+            (
+                "deals contains 'deal-1'",
+                Condition::Contains("deal-1".into()),
+            ),
+            (
+                "segment_ids not contains 2",
+                Condition::NotContains(2.into()),
+            ),
+            ("exchange_id has_flag 4", Condition::HasFlag(4)),
+            ("exchange_id not has_flag 4", Condition::NotHasFlag(4)),
+            ("exchange_id has_any_flag 6", Condition::HasAnyFlag(6)),
+            (
+                "exchange_id not has_any_flag 6",
+                Condition::NotHasAnyFlag(6),
+            ),
+        ];
+
+        for (dsl, condition) in cases {
+            let attribute = dsl.split(' ').next().unwrap();
+            let expression = Expr::predicate(attribute, condition);
+
+            let actual = to_node(&expression, &attributes, &mut strings);
+
+            assert_eq!(Ok(parse(dsl, &attributes, &mut strings)), actual, "{dsl}");
+        }
+    }
+
+    #[test]
+    fn build_the_same_boolean_operators_as_the_dsl() {
+        let attributes = define_attributes();
+        let mut strings = StringTable::new();
+        let expression = Expr::or(
+            Expr::and(
+                Expr::predicate("private", Condition::IsTrue),
+                Expr::not(Expr::predicate("exchange_id", Condition::Equal(1.into()))),
+            ),
+            Expr::predicate("country", Condition::IsNull),
+        );
+
+        let actual = to_node(&expression, &attributes, &mut strings);
+
+        assert_eq!(
+            Ok(parse(
+                "(private and not exchange_id = 1) or country is null",
+                &attributes,
+                &mut strings
+            )),
+            actual
+        );
+    }
+
+    #[test]
+    fn return_an_error_when_the_attribute_does_not_exist() {
+        let attributes = define_attributes();
+        let mut strings = StringTable::new();
+        let expression = Expr::predicate("unknown", Condition::IsTrue);
+
+        let actual = to_node(&expression, &attributes, &mut strings);
+
+        assert_eq!(
+            Err(ParseError::User {
+                error: ParserError::Event(EventError::NonExistingAttribute("unknown".to_string()))
+            }),
+            actual
+        );
+    }
+
+    #[test]
+    fn return_an_error_when_the_condition_does_not_fit_the_attribute() {
+        let attributes = define_attributes();
+        let mut strings = StringTable::new();
+        let expression = Expr::and(
+            Expr::predicate("private", Condition::IsTrue),
+            Expr::predicate("exchange_id", Condition::Equal("1".into())),
+        );
+
+        let actual = to_node(&expression, &attributes, &mut strings);
+
+        assert!(matches!(
+            actual,
+            Err(ParseError::User {
+                error: ParserError::Event(EventError::UnsupportedOperator { .. })
+            })
+        ));
+    }
+
+    #[test]
+    fn return_an_error_when_the_expression_exceeds_the_limits() {
+        let attributes = define_attributes();
+        let mut strings = StringTable::new();
+        let limits = ParserLimits::new(1, 2);
+        let deep = Expr::not(Expr::not(Expr::predicate("private", Condition::IsTrue)));
+        let long = Expr::predicate("segment_ids", Condition::OneOf(vec![1, 2, 3].into()));
+
+        assert_eq!(
+            Err(ParseError::User {
+                error: ParserError::MaximumDepthExceeded(1)
+            }),
+            deep.to_node(&attributes, &mut strings, &limits)
+                .map(|(node, _)| node)
+        );
+        assert_eq!(
+            Err(ParseError::User {
+                error: ParserError::MaximumListLengthExceeded(2)
+            }),
+            long.to_node(&attributes, &mut strings, &limits)
+                .map(|(node, _)| node)
+        );
+    }
+}
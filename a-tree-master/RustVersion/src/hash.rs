@@ -0,0 +1,159 @@
+use core::hash::Hasher;
+
+/// A [Fowler–Noll–Vo (FNV-1a)](https://en.wikipedia.org/wiki/Fowler%E2%80%93Noll%E2%80%93Vo_hash_function)
+/// hasher.
+///
+/// Unlike the default hasher of the standard library, it is not randomly seeded and its algorithm
+/// is fixed, which makes it usable without `std` and yields the same hashes for the same inputs
+/// across processes, builds and Rust versions. The integers are always hashed as little-endian
+/// bytes (and `usize`/`isize` as 64 bits integers) so that the hashes do not depend on the
+/// platform either.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct FnvHasher(u64);
+
+impl FnvHasher {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+}
+
+impl FnvHasher {
+    /// Get the hash with its bits mixed by the finalizer of MurmurHash3 (`fmix64`).
+    ///
+    /// The raw FNV-1a hashes of the inputs that only differ by their last bytes differ by a fixed
+    /// amount, which cancels out once the hashes are added or multiplied together (i.e. by
+    /// `OptimizedNode::id()`). Once mixed, each bit of the hash affects all the bits of the output.
+    #[inline]
+    pub(crate) const fn finish_mixed(&self) -> u64 {
+        mix(self.0)
+    }
+}
+
+/// Mix the bits of the hash with the finalizer of MurmurHash3 (`fmix64`).
+#[inline]
+pub(crate) const fn mix(mut hash: u64) -> u64 {
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^= hash >> 33;
+    hash
+}
+
+impl Default for FnvHasher {
+    #[inline]
+    fn default() -> Self {
+        Self(Self::OFFSET_BASIS)
+    }
+}
+
+impl Hasher for FnvHasher {
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+    }
+
+    #[inline]
+    fn write_u8(&mut self, i: u8) {
+        self.write(&[i]);
+    }
+
+    #[inline]
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    #[inline]
+    fn write_i8(&mut self, i: i8) {
+        self.write_u8(i as u8);
+    }
+
+    #[inline]
+    fn write_i16(&mut self, i: i16) {
+        self.write_u16(i as u16);
+    }
+
+    #[inline]
+    fn write_i32(&mut self, i: i32) {
+        self.write_u32(i as u32);
+    }
+
+    #[inline]
+    fn write_i64(&mut self, i: i64) {
+        self.write_u64(i as u64);
+    }
+
+    #[inline]
+    fn write_i128(&mut self, i: i128) {
+        self.write_u128(i as u128);
+    }
+
+    #[inline]
+    fn write_isize(&mut self, i: isize) {
+        self.write_i64(i as i64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn return_the_offset_basis_for_an_empty_input() {
+        assert_eq!(0xcbf2_9ce4_8422_2325, FnvHasher::default().finish());
+    }
+
+    #[test]
+    fn return_the_reference_hash_of_a_known_input() {
+        let mut hasher = FnvHasher::default();
+        hasher.write(b"a");
+        assert_eq!(0xaf63_dc4c_8601_ec8c, hasher.finish());
+    }
+
+    #[test]
+    fn return_the_reference_mixed_hash_of_an_empty_input() {
+        assert_eq!(0xefd0_1f60_ba99_2926, FnvHasher::default().finish_mixed());
+    }
+
+    #[test]
+    fn hash_the_pointer_sized_integers_as_64_bits_integers() {
+        let mut a = FnvHasher::default();
+        a.write_usize(42);
+        let mut b = FnvHasher::default();
+        b.write_u64(42);
+        assert_eq!(a.finish(), b.finish());
+
+        let mut a = FnvHasher::default();
+        a.write_isize(-42);
+        let mut b = FnvHasher::default();
+        b.write_i64(-42);
+        assert_eq!(a.finish(), b.finish());
+    }
+}
@@ -1,7 +1,15 @@
 use crate::error::ParserError;
+use alloc::{
+    format,
+    string::{String, ToString},
+};
+use core::{
+    num::{IntErrorKind, ParseIntError},
+    ops::RangeInclusive,
+    str::FromStr,
+};
 use logos::{Logos, SpannedIter};
 use rust_decimal::Decimal;
-use std::{num::ParseIntError, str::FromStr};
 use thiserror::Error;
 
 #[derive(Default, Error, Debug, Clone, PartialEq)]
@@ -11,8 +19,12 @@ pub enum LexicalError {
     InvalidToken,
     #[error("failed to parse integer: {0:?}")]
     Integer(ParseIntError),
+    #[error("the integer {0} is outside of the supported range ({min} to {max})", min = i64::MIN, max = i64::MAX)]
+    IntegerOutOfRange(String),
     #[error("failed to parse float: {0:?}")]
     Float(rust_decimal::Error),
+    #[error("the range is empty")]
+    EmptyRange,
 }
 
 #[derive(Clone, Debug, Logos, PartialEq)]
@@ -38,11 +50,42 @@ pub enum Token<'source> {
     #[token("not in")]
     NotIn,
     #[token("one of")]
+    #[token("overlaps")]
     OneOf,
     #[token("none of")]
+    #[token("disjoint")]
     NoneOf,
     #[token("all of")]
+    #[token("subset of")]
     AllOf,
+    #[token("all")]
+    All,
+    #[token("any")]
+    Any,
+    #[token("any_defined")]
+    AnyDefined,
+    #[token("len")]
+    Length,
+    #[token("starts_with")]
+    StartsWith,
+    #[token("ends_with")]
+    EndsWith,
+    #[token("like")]
+    Like,
+    #[token("not like")]
+    NotLike,
+    #[token("contains")]
+    Contains,
+    #[token("not contains")]
+    NotContains,
+    #[token("has_flag")]
+    HasFlag,
+    #[token("not has_flag")]
+    NotHasFlag,
+    #[token("has_any_flag")]
+    HasAnyFlag,
+    #[token("not has_any_flag")]
+    NotHasAnyFlag,
     #[token("is null")]
     IsNull,
     #[token("is not null")]
@@ -51,6 +94,8 @@ pub enum Token<'source> {
     IsEmpty,
     #[token("is not empty")]
     IsNotEmpty,
+    #[token("null")]
+    Null,
     #[token("and")]
     #[token("&&")]
     And,
@@ -67,8 +112,13 @@ pub enum Token<'source> {
     RightSquareBracket,
     #[token(",")]
     Comma,
-    #[regex(r"-?[0-9]+", |lex| lex.slice().parse::<i64>().map_err(LexicalError::Integer))]
+    #[regex(r"-?[0-9]+", |lex| parse_integer(lex.slice()))]
+    #[regex(r"-?0[xX][0-9a-fA-F]+", |lex| parse_integer_with_radix(lex.slice(), 16))]
+    #[regex(r"-?0[bB][01]+", |lex| parse_integer_with_radix(lex.slice(), 2))]
+    #[regex(r"-?0[oO][0-7]+", |lex| parse_integer_with_radix(lex.slice(), 8))]
     IntegerLiteral(i64),
+    #[regex(r"-?[0-9]+\.\.=?-?[0-9]+", |lex| parse_range(lex.slice()))]
+    RangeLiteral(RangeInclusive<i64>),
     #[regex(r#"(\"(\\.|[^"\\])*\"|\'(\\.|[^'\\])*\')"#, |lex| lex.slice().trim_matches(['\'', '"']))]
     StringLiteral(&'source str),
     #[regex(r"[0-9]+\.[0-9]*", |lex| Decimal::from_str(lex.slice()).map_err(LexicalError::Float))]
@@ -78,10 +128,98 @@ pub enum Token<'source> {
     BooleanLiteral(bool),
     #[regex("[a-zA-Z_][a-zA-Z0-9_-]*", |lex| lex.slice())]
     Identifier(&'source str),
+    #[regex("`[^`]+`", |lex| lex.slice().trim_matches('`'))]
+    QuotedIdentifier(&'source str),
+    #[regex("@[a-zA-Z_][a-zA-Z0-9_-]*", |lex| &lex.slice()[1..])]
+    ListReference(&'source str),
 }
 
-impl std::fmt::Display for Token<'_> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+fn parse_integer(literal: &str) -> Result<i64, LexicalError> {
+    literal
+        .parse::<i64>()
+        .map_err(|error| to_integer_error(literal, error))
+}
+
+/// Parse an integer literal prefixed by its base (i.e. `0x`, `0b` or `0o`) with an optional sign.
+fn parse_integer_with_radix(literal: &str, radix: u32) -> Result<i64, LexicalError> {
+    let (sign, digits) = literal
+        .strip_prefix('-')
+        .map_or(("", literal), |digits| ("-", digits));
+    let digits = &digits[2..];
+    i64::from_str_radix(&format!("{sign}{digits}"), radix)
+        .map_err(|error| to_integer_error(literal, error))
+}
+
+/// Report the literals that do not fit in an `i64` along with the literal itself
+fn to_integer_error(literal: &str, error: ParseIntError) -> LexicalError {
+    match error.kind() {
+        IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => {
+            LexicalError::IntegerOutOfRange(literal.to_string())
+        }
+        _ => LexicalError::Integer(error),
+    }
+}
+
+/// Parse a range of integers, either exclusive (`9..17`) or inclusive (`9..=16`), into its
+/// inclusive form.
+fn parse_range(literal: &str) -> Result<RangeInclusive<i64>, LexicalError> {
+    let (start, end) = literal.split_once("..").ok_or(LexicalError::InvalidToken)?;
+    let start = parse_integer(start.trim())?;
+    let (end, is_inclusive) = end
+        .strip_prefix('=')
+        .map_or((end, false), |end| (end, true));
+    let end = parse_integer(end.trim())?;
+    let end = if is_inclusive {
+        Some(end)
+    } else {
+        end.checked_sub(1)
+    };
+    match end {
+        Some(end) if start <= end => Ok(start..=end),
+        _ => Err(LexicalError::EmptyRange),
+    }
+}
+
+/// Check whether the name is read as a keyword (or a literal) instead of an identifier
+pub fn is_reserved(name: &str) -> bool {
+    let mut tokens = Lexer::new(name);
+    match (tokens.next(), tokens.next()) {
+        (Some(Ok((_, token, _))), None) => !matches!(token, Token::Identifier(_)),
+        _ => false,
+    }
+}
+
+/// Convert a byte offset of the expression (i.e. from the location of a parse error) to its line
+/// and column, both starting at 1.
+///
+/// The column is counted in characters rather than in bytes so that it points to the right place
+/// in expressions that contain non-ASCII characters. An offset that is not on a character
+/// boundary points to the character that contains it and an offset past the end of the
+/// expression points right after its last character.
+///
+/// # Examples
+///
+/// ```rust
+/// use a_tree::line_and_column;
+///
+/// let expression = "country = 'Québec'\nand";
+/// assert_eq!((1, 11), line_and_column(expression, 10));
+/// assert_eq!((2, 1), line_and_column(expression, expression.find("and").unwrap()));
+/// ```
+pub fn line_and_column(expression: &str, offset: usize) -> (usize, usize) {
+    let mut offset = offset.min(expression.len());
+    while !expression.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &expression[..offset];
+    let (line, start) = before
+        .match_indices('\n')
+        .fold((1, 0), |(line, _), (i, _)| (line + 1, i + 1));
+    (line, before[start..].chars().count() + 1)
+}
+
+impl core::fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{:?}", self)
     }
 }
@@ -108,6 +246,12 @@ impl<'input> Iterator for Lexer<'input> {
             let token = token.map(|token| match token {
                 // FIXME: This is a bug in Locos where regex take priority over all...
                 Token::Identifier("not") => Token::Not,
+                Token::Identifier("all") => Token::All,
+                Token::Identifier("any") => Token::Any,
+                Token::Identifier("overlaps") => Token::OneOf,
+                Token::Identifier("disjoint") => Token::NoneOf,
+                Token::Identifier("starts_with") => Token::StartsWith,
+                Token::Identifier("ends_with") => Token::EndsWith,
                 other => other,
             });
 
@@ -119,8 +263,9 @@ impl<'input> Iterator for Lexer<'input> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::vec::Vec;
 
-    fn lex_tokens(input: &str) -> Result<Vec<Token>, ParserError> {
+    fn lex_tokens(input: &str) -> Result<Vec<Token<'_>>, ParserError> {
         Lexer::new(input)
             .map(|value| match value {
                 Ok((_, token, _)) => Ok(token),
@@ -129,6 +274,28 @@ mod tests {
             .collect()
     }
 
+    #[test]
+    fn count_the_columns_in_characters_after_non_ascii_strings() {
+        let expression = "name = \"🚀é\" and\n  city = 'Montréal' 1";
+        let offsets = Lexer::new(expression)
+            .map(|value| value.unwrap().0)
+            .collect::<Vec<_>>();
+
+        assert_eq!((1, 1), line_and_column(expression, offsets[0]));
+        assert_eq!((1, 13), line_and_column(expression, offsets[3]));
+        assert_eq!((2, 10), line_and_column(expression, offsets[6]));
+        assert_eq!((2, 21), line_and_column(expression, offsets[7]));
+    }
+
+    #[test]
+    fn clamp_the_offsets_that_are_not_on_a_character_boundary() {
+        let expression = "name = \"🚀\"";
+
+        assert_eq!((1, 9), line_and_column(expression, 9));
+        assert_eq!((1, 11), line_and_column(expression, 1000));
+        assert_eq!((1, 1), line_and_column("", 3));
+    }
+
     #[test]
     fn can_lex_less_than() {
         let actual = lex_tokens("<").unwrap();
@@ -197,18 +364,195 @@ mod tests {
         assert_eq!(vec![Token::NoneOf], actual);
     }
 
+    #[test]
+    fn can_lex_overlaps_as_one_of() {
+        let actual = lex_tokens("overlaps").unwrap();
+        let other = lex_tokens("ids overlaps [1]").unwrap();
+        assert_eq!(vec![Token::OneOf], actual);
+        assert_eq!(lex_tokens("ids one of [1]").unwrap(), other);
+    }
+
+    #[test]
+    fn can_lex_disjoint_as_none_of() {
+        let actual = lex_tokens("disjoint").unwrap();
+        let other = lex_tokens("ids disjoint [1]").unwrap();
+        assert_eq!(vec![Token::NoneOf], actual);
+        assert_eq!(lex_tokens("ids none of [1]").unwrap(), other);
+    }
+
     #[test]
     fn can_lex_all_of() {
         let actual = lex_tokens("all of").unwrap();
         assert_eq!(vec![Token::AllOf], actual);
     }
 
+    #[test]
+    fn can_lex_subset_of_as_all_of() {
+        let actual = lex_tokens("subset of").unwrap();
+        let other = lex_tokens("ids subset of [1]").unwrap();
+        assert_eq!(vec![Token::AllOf], actual);
+        assert_eq!(lex_tokens("ids all of [1]").unwrap(), other);
+    }
+
+    #[test]
+    fn can_lex_quantifiers() {
+        let actual = lex_tokens("all").unwrap();
+        let other = lex_tokens("any").unwrap();
+        assert_eq!(vec![Token::All], actual);
+        assert_eq!(vec![Token::Any], other);
+    }
+
+    #[test]
+    fn can_lex_string_matching_operators() {
+        let actual = lex_tokens(r#"tags any starts_with "promo_""#).unwrap();
+        assert_eq!(
+            vec![
+                Token::Identifier("tags"),
+                Token::Any,
+                Token::StartsWith,
+                Token::StringLiteral("promo_")
+            ],
+            actual
+        );
+        let actual = lex_tokens("ends_with").unwrap();
+        assert_eq!(vec![Token::EndsWith], actual);
+    }
+
+    #[test]
+    fn can_lex_like_operators() {
+        let actual = lex_tokens(r#"path like "/shop/%""#).unwrap();
+        let other = lex_tokens(r#"path not like "/shop/%""#).unwrap();
+        assert_eq!(
+            vec![
+                Token::Identifier("path"),
+                Token::Like,
+                Token::StringLiteral("/shop/%")
+            ],
+            actual
+        );
+        assert_eq!(
+            vec![
+                Token::Identifier("path"),
+                Token::NotLike,
+                Token::StringLiteral("/shop/%")
+            ],
+            other
+        );
+    }
+
+    #[test]
+    fn can_lex_contains_operators() {
+        let actual = lex_tokens(r#"deals contains "deal-1""#).unwrap();
+        let other = lex_tokens("segment_ids not contains 1").unwrap();
+        assert_eq!(
+            vec![
+                Token::Identifier("deals"),
+                Token::Contains,
+                Token::StringLiteral("deal-1")
+            ],
+            actual
+        );
+        assert_eq!(
+            vec![
+                Token::Identifier("segment_ids"),
+                Token::NotContains,
+                Token::IntegerLiteral(1)
+            ],
+            other
+        );
+    }
+
+    #[test]
+    fn can_lex_an_any_defined_expression() {
+        let actual = lex_tokens("any_defined(deals, segment_ids)").unwrap();
+        assert_eq!(
+            vec![
+                Token::AnyDefined,
+                Token::LeftParenthesis,
+                Token::Identifier("deals"),
+                Token::Comma,
+                Token::Identifier("segment_ids"),
+                Token::RightParenthesis
+            ],
+            actual
+        );
+    }
+
+    #[test]
+    fn can_lex_a_length_comparison() {
+        let actual = lex_tokens("len(segment_ids) >= 3").unwrap();
+        assert_eq!(
+            vec![
+                Token::Length,
+                Token::LeftParenthesis,
+                Token::Identifier("segment_ids"),
+                Token::RightParenthesis,
+                Token::GreaterThanEqual,
+                Token::IntegerLiteral(3)
+            ],
+            actual
+        );
+    }
+
+    #[test]
+    fn can_lex_flag_operators() {
+        let actual = lex_tokens("perms has_flag 0x4 and perms not has_flag 1").unwrap();
+        let other = lex_tokens("perms has_any_flag 0x6 or perms not has_any_flag 2").unwrap();
+        assert_eq!(
+            vec![
+                Token::Identifier("perms"),
+                Token::HasFlag,
+                Token::IntegerLiteral(4),
+                Token::And,
+                Token::Identifier("perms"),
+                Token::NotHasFlag,
+                Token::IntegerLiteral(1)
+            ],
+            actual
+        );
+        assert_eq!(
+            vec![
+                Token::Identifier("perms"),
+                Token::HasAnyFlag,
+                Token::IntegerLiteral(6),
+                Token::Or,
+                Token::Identifier("perms"),
+                Token::NotHasAnyFlag,
+                Token::IntegerLiteral(2)
+            ],
+            other
+        );
+    }
+
+    #[test]
+    fn can_lex_a_quantified_expression() {
+        let actual = lex_tokens("all segment_ids > 100").unwrap();
+        assert_eq!(
+            vec![
+                Token::All,
+                Token::Identifier("segment_ids"),
+                Token::GreaterThan,
+                Token::IntegerLiteral(100)
+            ],
+            actual
+        );
+    }
+
     #[test]
     fn can_lex_is_null() {
         let actual = lex_tokens("is null").unwrap();
         assert_eq!(vec![Token::IsNull], actual);
     }
 
+    #[test]
+    fn can_lex_null() {
+        let actual = lex_tokens("country = null").unwrap();
+        assert_eq!(
+            vec![Token::Identifier("country"), Token::Equal, Token::Null],
+            actual
+        );
+    }
+
     #[test]
     fn can_lex_is_not_null() {
         let actual = lex_tokens("is not null").unwrap();
@@ -277,6 +621,84 @@ mod tests {
         assert_eq!(vec![Token::IntegerLiteral(-123)], actual);
     }
 
+    #[test]
+    fn can_lex_hexadecimal_integer() {
+        let actual = lex_tokens("0xFF").unwrap();
+        let other = lex_tokens("0x1a").unwrap();
+        assert_eq!(vec![Token::IntegerLiteral(255)], actual);
+        assert_eq!(vec![Token::IntegerLiteral(26)], other);
+    }
+
+    #[test]
+    fn can_lex_binary_integer() {
+        let actual = lex_tokens("0b101").unwrap();
+        assert_eq!(vec![Token::IntegerLiteral(5)], actual);
+    }
+
+    #[test]
+    fn can_lex_octal_integer() {
+        let actual = lex_tokens("0o17").unwrap();
+        assert_eq!(vec![Token::IntegerLiteral(15)], actual);
+    }
+
+    #[test]
+    fn can_lex_negative_integer_with_a_base_prefix() {
+        let actual = lex_tokens("-0xFF").unwrap();
+        let other = lex_tokens("-0b11").unwrap();
+        let another = lex_tokens("-0o10").unwrap();
+        assert_eq!(vec![Token::IntegerLiteral(-255)], actual);
+        assert_eq!(vec![Token::IntegerLiteral(-3)], other);
+        assert_eq!(vec![Token::IntegerLiteral(-8)], another);
+    }
+
+    #[test]
+    fn can_lex_the_smallest_hexadecimal_integer() {
+        let actual = lex_tokens("-0x8000000000000000").unwrap();
+        assert_eq!(vec![Token::IntegerLiteral(i64::MIN)], actual);
+    }
+
+    #[test]
+    fn return_an_error_on_overflowing_integer_with_a_base_prefix() {
+        let actual = lex_tokens("0x8000000000000000");
+        let other =
+            lex_tokens("0b10000000000000000000000000000000000000000000000000000000000000000");
+        assert_eq!(
+            Err(ParserError::Lexical(LexicalError::IntegerOutOfRange(
+                "0x8000000000000000".to_string()
+            ))),
+            actual
+        );
+        assert!(matches!(
+            other,
+            Err(ParserError::Lexical(LexicalError::IntegerOutOfRange(_)))
+        ));
+    }
+
+    #[test]
+    fn return_an_error_with_the_literal_and_the_range_on_overflowing_integer() {
+        let actual = lex_tokens("segment_ids one of [9223372036854775808]");
+        let negative = lex_tokens("-9223372036854775809");
+        let range = lex_tokens("1..99999999999999999999");
+
+        let expected = LexicalError::IntegerOutOfRange("9223372036854775808".to_string());
+        assert_eq!(Err(ParserError::Lexical(expected.clone())), actual);
+        assert_eq!(
+            "the integer 9223372036854775808 is outside of the supported range \
+             (-9223372036854775808 to 9223372036854775807)",
+            expected.to_string()
+        );
+        assert_eq!(
+            Err(ParserError::Lexical(LexicalError::IntegerOutOfRange(
+                "-9223372036854775809".to_string()
+            ))),
+            negative
+        );
+        assert!(matches!(
+            range,
+            Err(ParserError::Lexical(LexicalError::IntegerOutOfRange(_)))
+        ));
+    }
+
     #[test]
     fn can_lex_float() {
         let actual = lex_tokens("123.123").unwrap();
@@ -285,6 +707,28 @@ mod tests {
         assert_eq!(vec![Token::FloatLiteral(Decimal::new(123, 0))], other);
     }
 
+    #[test]
+    fn can_lex_range() {
+        let exclusive = lex_tokens("9..17").unwrap();
+        let inclusive = lex_tokens("-9..=-1").unwrap();
+        assert_eq!(vec![Token::RangeLiteral(9..=16)], exclusive);
+        assert_eq!(vec![Token::RangeLiteral(-9..=-1)], inclusive);
+    }
+
+    #[test]
+    fn return_an_error_on_empty_range() {
+        let exclusive = lex_tokens("17..17");
+        let inclusive = lex_tokens("17..=16");
+        assert_eq!(
+            Err(ParserError::Lexical(LexicalError::EmptyRange)),
+            exclusive
+        );
+        assert_eq!(
+            Err(ParserError::Lexical(LexicalError::EmptyRange)),
+            inclusive
+        );
+    }
+
     #[test]
     fn can_lex_boolean() {
         let actual = lex_tokens("true").unwrap();
@@ -299,6 +743,43 @@ mod tests {
         assert_eq!(vec![Token::Identifier("deal_ids")], actual);
     }
 
+    #[test]
+    fn can_lex_quoted_identifier() {
+        let actual = lex_tokens("`user.country` = 'CA'").unwrap();
+        assert_eq!(
+            vec![
+                Token::QuotedIdentifier("user.country"),
+                Token::Equal,
+                Token::StringLiteral("CA")
+            ],
+            actual
+        );
+    }
+
+    #[test]
+    fn do_not_turn_quoted_identifiers_into_keywords() {
+        let actual = lex_tokens("`not`").unwrap();
+        assert_eq!(vec![Token::QuotedIdentifier("not")], actual);
+    }
+
+    #[test]
+    fn return_an_error_on_empty_quoted_identifier() {
+        assert!(lex_tokens("``").is_err());
+    }
+
+    #[test]
+    fn can_lex_list_reference() {
+        let actual = lex_tokens("country in @EU").unwrap();
+        assert_eq!(
+            vec![
+                Token::Identifier("country"),
+                Token::In,
+                Token::ListReference("EU")
+            ],
+            actual
+        );
+    }
+
     #[test]
     fn can_lex_empty_string() {
         let actual = lex_tokens("\"\"").unwrap();
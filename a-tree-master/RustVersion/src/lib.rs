@@ -48,12 +48,72 @@
 //! The A-Tree crate support a DSL to allow easy creation of arbitrary boolean expressions (ABE).
 //! The following operators are supported:
 //!
-//! * Boolean operators: `and` (`&&`), `or` (`||`), `not` (`!`) and `variable` where `variable` is a defined attribute for the A-Tree;
-//! * Comparison: `<`, `<=`, `>`, `>=`. They work for `integer` and `float`;
-//! * Equality: `=` and `<>`. They work for `integer`, `float` and `string`;
-//! * Null: `is null`, `is not null` (for variables), `is empty` and `is not empty` (for lists);
-//! * Set: `in` and `not in`. They work for list of `integer` or for list of `string`;
-//! * List: `one of`, `none of` and `all of`. They work for list of `integer` and list of `string`.
+//! * Boolean operators: `and` (`&&`), `or` (`||`), `not` (`!`) and `variable` where `variable` is a defined attribute for the A-Tree.
+//!   `not` binds tighter than `and` which binds tighter than `or` so `a or b and c` is
+//!   `a or (b and c)` and `not a or b` is `(not a) or b`. Releases up to 0.5.0 gave `and` and `or`
+//!   the same precedence and grouped them from left to right, so add parentheses to expressions
+//!   that relied on it;
+//! * Comparison: `<`, `<=`, `>`, `>=`. They work for `integer` and `float`. The `float` values are
+//!   exact decimals and their scale does not matter (i.e. `1.5` and `1.50` are the same value).
+//!   The integers and the floats can be compared with each other (i.e. `exchange_id < 1.5`);
+//! * Equality: `=` and `<>`. They work for `integer`, `float` and `string`. An integer is equal to
+//!   a float with the same value (i.e. `exchange_id = 1.0`);
+//! * Null: `is null` and `is not null` (for all variables), `is empty` and `is not empty` (for
+//!   lists). A list that was never set is null but neither empty nor non-empty. `= null` and
+//!   `<> null` are read as `is null` and `is not null`. `any_defined(deal_ids, segment_ids)` is true
+//!   when at least one of the attributes is defined;
+//! * Set: `in` and `not in`. They work for list of `integer` or for list of `string`. For `integer`,
+//!   they also work with a range that is either exclusive (`hour in 9..17`) or inclusive
+//!   (`hour in 9..=16`);
+//! * List: `one of` (or `overlaps`), `none of` (or `disjoint`) and `all of` (or `subset of`). They
+//!   work for list of `integer` and list of `string`. The list can also be written on the left side
+//!   of the operator: `[1, 2] all of segment_ids` is true when `segment_ids` contains both `1` and
+//!   `2` while `segment_ids all of [1, 2]` is true when all the elements of `segment_ids` are in
+//!   `[1, 2]` (so an empty `segment_ids` is a subset of any list);
+//! * Empty lists: `[]` is an empty set of the type of the attribute so `in []` and `one of []`
+//!   never match, `not in []` and `none of []` always match (when the attribute is defined) and
+//!   `all of []` only matches an empty list. `is empty` is the clearer way to write the latter;
+//! * Quantifiers: `all` and `any` followed by a comparison (i.e. `all segment_ids > 100`). They work
+//!   for list of `integer`; `all` is true for an empty list while `any` is false.
+//! * String matching: `starts_with` and `ends_with` (i.e. `country starts_with "U"`). They work for
+//!   `string` and, with a quantifier, for list of `string` (i.e. `tags any starts_with "promo_"` or
+//!   `all tags ends_with "_2024"`). `like` and `not like` match a SQL pattern where `%` is any
+//!   string, `_` is any character and `\` escapes the character that follows it (i.e.
+//!   `path like "/shop/%"`). The events only keep the text of the strings that are matched this way
+//!   so they have to be built after the expressions are inserted;
+//! * Contains: `contains` and `not contains` (i.e. `deals contains "deal-1"`). They work for list of
+//!   `integer` with an integer and for list of `string` with a string;
+//! * Flags: `has_flag` and `has_any_flag` along with their `not` forms (i.e. `perms has_flag 0x4`).
+//!   They work for `integer` used as a bit mask: `has_flag` is true when all the bits of the mask
+//!   are set (so always for a mask of `0`) while `has_any_flag` is true when at least one of them
+//!   is set (so never for a mask of `0`);
+//! * Length: `len(segment_ids) > 3` compares the amount of distinct elements of a list of
+//!   `integer` or of `string` with an integer using `<`, `<=`, `>` or `>=`. Like the
+//!   string matching, the lists of strings are counted from their text so the events have to be
+//!   built after the expressions are inserted.
+//!
+//! Enumeration attributes (see [`AttributeDefinition::enumeration`]) are strings restricted to a
+//! fixed set of values: they support the same operators as the `string` attributes but the
+//! expressions that compare them with any other value are rejected when they are inserted.
+//!
+//! The predicates on an attribute that is not defined in the event (other than the null checks)
+//! are neither true nor false, so `not` does not make them match. They can be made false instead
+//! with [`ATreeOptions::on_undefined()`].
+//!
+//! A lone `true` (or `false`) is also a valid expression: it matches every event (or none of
+//! them) and cannot be combined with other expressions.
+//!
+//! Attribute names that contain other characters than letters, digits, `_` and `-` can be quoted
+//! with backticks (i.e. `` `user.country` = "CA" ``). So do the names that are read as keywords
+//! (i.e. `` `len` = 5 `` or `` `contains` ``); the expressions that use them without backticks
+//! are rejected with a hint to quote them.
+//!
+//! The lists shared by many expressions can be defined once with
+//! [`ATree::define_string_list()`] (or [`ATree::define_integer_list()`]) and then referenced by
+//! name wherever a literal list is expected (i.e. `country in @EU`).
+//!
+//! Integer literals can be written in decimal (`255`), hexadecimal (`0xFF`), binary (`0b11111111`)
+//! or octal (`0o377`) and can be negative (`-0xFF`).
 //!
 //! As an example, the following would all be valid ABEs:
 //!
@@ -63,6 +123,9 @@
 //! (log_level = 'debug') and (month in [1, 2, 3] and day in [15, 16]) or (month in [4, 5, 6] and day in [10, 11])
 //! ```
 //!
+//! The expressions can also be built without the DSL (i.e. from a query builder) with [`Expr`]
+//! and inserted with [`ATree::insert_expr()`].
+//!
 //! # Optimizations
 //!
 //! The A-Tree is a data structure that can efficiently search a large amount of arbitrary boolean
@@ -71,7 +134,11 @@
 //!
 //! * Search for duplicated intermediary boolean expressions nodes (i.e. if there are two
 //!   expressions such as `(A ∧ (B ∧ C))` and `(D ∨ (B ∧ C))`, the tree will find the common
-//!   sub-expression `(B ∧ C)` and will make both expression refer to the common node);
+//!   sub-expression `(B ∧ C)` and will make both expression refer to the common node). The
+//!   operands of `and` and `or` are put in a canonical order so that `(B ∧ C)` and `(C ∧ B)` are
+//!   the same node;
+//! * Flatten the runs of the same operator (i.e. `a or b or c or d`) into a single node so that
+//!   the tree is shallower and the searches go through fewer levels;
 //! * Convert the strings to IDs to accelerate comparison and search;
 //! * Sort the lists of strings/integers and remove duplicates;
 //! * Sort the sub-expressions by cost:
@@ -84,12 +151,49 @@
 //! * _Zero suppression filter_: Reduce the amount of nodes to evaluate by applying
 //!   De Morgan's laws and eliminating the NOT nodes;
 //! * _Propagation on demand_: Choose an access child for the AND operators and only
-//!   propagate the result if the access child is true.
+//!   propagate the result if the access child is true The access children can be chosen again by how
+//!   often they were false on a profiled workload (see `ATree::reoptimize_ordering`).
+//!
+//! # Features
+//!
+//! * `std` (enabled by default): Use the standard library;
+//! * `no_std`: Only rely on `alloc` so that the crate can be used without the standard library
+//!   (i.e. `default-features = false, features = ["no_std"]`). The hash maps are then provided by
+//!   `hashbrown` with a deterministic hasher and `Report::matches_set`,
+//!   `ATree::search_watching`, `ATree::search_to_sender` and `ATree::with_search_cache` are not
+//!   available;
+//! * `csv`: Load an [`ATree`] from CSV or TSV files of attributes and expressions (see
+//!   `ATree::from_csv`);
+//! * `serde`: Export the structure of an [`ATree`] to JSON (see `ATree::to_json`);
+//! * `tracing`: Emit a `search` span for the searches that record the amount of attributes
+//!   defined in the [`Event`] and the amount of matches. The sizes of the queues of each level
+//!   are recorded as `debug` events;
+//! * `normalization`: Normalize the strings to their NFC form when they are interned so that
+//!   the strings that only differ by their Unicode normalization form are equal (see
+//!   `ATreeOptions::normalize_strings`).
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(any(feature = "std", feature = "no_std")))]
+compile_error!("either the `std` feature (the default) or the `no_std` feature must be enabled");
+
+extern crate alloc;
+// The tests rely on the standard library (i.e. for `vec!` and `format!`) even without `std`
+#[cfg(all(test, not(feature = "std")))]
+#[macro_use]
+extern crate std;
+
 mod ast;
 mod atree;
+#[cfg(feature = "std")]
+mod cache;
+mod collections;
+#[cfg(feature = "csv")]
+mod csv;
 mod error;
 mod evaluation;
 mod events;
+mod expr;
+mod hash;
 mod lexer;
 mod parser;
 mod predicates;
@@ -98,7 +202,20 @@ mod strings;
 mod test_utils;
 
 pub use crate::{
-    atree::{ATree, Report},
-    error::ATreeError,
-    events::{AttributeDefinition, Event, EventBuilder, EventError},
+    atree::{
+        validate_all, ATree, ATreeBuilder, ATreeOptions, CompiledExpression, DeleteOutcome,
+        DuplicatePolicy, ExpressionId, FailureReason, Report, RootsIndex, SatisfiedExpression,
+    },
+    error::{ATreeError, BudgetExceeded},
+    events::{
+        AttributeDefinition, AttributeHandle, AttributeKind, AttributeValue, Event, EventBuilder,
+        EventError, UndefinedPolicy,
+    },
+    expr::{Condition, Expr, List, Literal, Number},
+    lexer::line_and_column,
+    strings::{Interner, StringId, StringTable, StringTableError},
 };
+
+#[cfg(feature = "std")]
+pub use crate::cache::SearchCacheStats;
+pub use fixedbitset::FixedBitSet;
@@ -1,9 +1,17 @@
 use crate::{
     ast::Node,
+    collections::HashMap,
     error::ParserError,
-    events::AttributeTable,
-    lexer::{Lexer, Token},
-    strings::StringTable,
+    events::{AttributeTable, EventError},
+    lexer::{self, Lexer, Token},
+    predicates::ListLiteral,
+    strings::Interner,
+};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
 };
 use lalrpop_util::{lalrpop_mod, ParseError};
 
@@ -13,14 +21,322 @@ use self::grammar::TreeParser;
 
 pub type ATreeParseError<'a> = ParseError<usize, Token<'a>, ParserError>;
 
+/// The lists referenced by name (i.e. `@EU`) in the expressions
+pub type NamedLists = HashMap<String, ListLiteral>;
+
+#[derive(Clone, Copy, Debug)]
+pub struct ParserLimits {
+    max_depth: usize,
+    max_list_length: usize,
+}
+
+impl ParserLimits {
+    pub const DEFAULT_MAX_DEPTH: usize = 256;
+    pub const DEFAULT_MAX_LIST_LENGTH: usize = 100_000;
+
+    pub const fn new(max_depth: usize, max_list_length: usize) -> Self {
+        Self {
+            max_depth,
+            max_list_length,
+        }
+    }
+
+    #[inline]
+    pub fn check_depth<'a>(&self, depth: usize) -> Result<usize, ATreeParseError<'a>> {
+        if depth > self.max_depth {
+            Err(ParseError::User {
+                error: ParserError::MaximumDepthExceeded(self.max_depth),
+            })
+        } else {
+            Ok(depth)
+        }
+    }
+
+    #[inline]
+    pub fn check_list_length<'a, T>(&self, values: Vec<T>) -> Result<Vec<T>, ATreeParseError<'a>> {
+        if values.len() > self.max_list_length {
+            Err(ParseError::User {
+                error: ParserError::MaximumListLengthExceeded(self.max_list_length),
+            })
+        } else {
+            Ok(values)
+        }
+    }
+}
+
+impl Default for ParserLimits {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_MAX_DEPTH, Self::DEFAULT_MAX_LIST_LENGTH)
+    }
+}
+
+/// An expression being parsed along with its depth.
+///
+/// The operands of a run of the same operator (i.e. `a or b or c`) are collected until the run
+/// is complete. The run only counts as one level of depth since it is flattened into a single
+/// node of the tree, and it is built as a balanced tree so that a long run does not make the AST
+/// deep enough to overflow the stack of the recursive functions working on it.
+#[derive(Debug)]
+pub struct Run {
+    // Whether the operands are combined with `and` (or `or`) or `None` for a single operand
+    is_and: Option<bool>,
+    operands: Vec<Node>,
+    depth: usize,
+}
+
+impl Run {
+    #[inline]
+    pub fn leaf(node: Node) -> Self {
+        Self::with_depth(node, 0)
+    }
+
+    #[inline]
+    pub fn with_depth(node: Node, depth: usize) -> Self {
+        Self {
+            is_and: None,
+            operands: vec![node],
+            depth,
+        }
+    }
+
+    #[inline]
+    pub const fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// End the run of the expression so that it is a single operand of the next operator.
+    #[inline]
+    pub fn grouped(self) -> Self {
+        let depth = self.depth;
+        Self::with_depth(self.into_node(), depth)
+    }
+
+    /// Combine the two expressions with an `and` (or an `or`) operator.
+    pub fn combine<'a>(
+        self,
+        right: Run,
+        is_and: bool,
+        limits: &ParserLimits,
+    ) -> Result<Self, ATreeParseError<'a>> {
+        let depth =
+            limits.check_depth(self.operand_depth(is_and).max(right.operand_depth(is_and)))?;
+        let mut operands = self.into_operands(is_and);
+        operands.extend(right.into_operands(is_and));
+        Ok(Self {
+            is_and: Some(is_and),
+            operands,
+            depth,
+        })
+    }
+
+    /// The depth of the expression once it is an operand of the operator
+    #[inline]
+    fn operand_depth(&self, is_and: bool) -> usize {
+        if self.is_and == Some(is_and) {
+            self.depth
+        } else {
+            self.depth + 1
+        }
+    }
+
+    #[inline]
+    fn into_operands(self, is_and: bool) -> Vec<Node> {
+        if self.is_and == Some(is_and) {
+            self.operands
+        } else {
+            vec![self.into_node()]
+        }
+    }
+
+    pub fn into_node(mut self) -> Node {
+        match self.is_and {
+            Some(is_and) => balance(is_and, &mut self.operands),
+            None => self.operands.remove(0),
+        }
+    }
+}
+
+/// Combine the operands into a balanced tree where the leftmost operands are the deepest ones
+/// (i.e. `a or b or c` is `(a or b) or c`).
+fn balance(is_and: bool, operands: &mut Vec<Node>) -> Node {
+    if operands.len() == 1 {
+        return operands.remove(0);
+    }
+    let mut right = operands.split_off(operands.len() - operands.len() / 2);
+    let left = Box::new(balance(is_and, operands));
+    let right = Box::new(balance(is_and, &mut right));
+    if is_and {
+        Node::And(left, right)
+    } else {
+        Node::Or(left, right)
+    }
+}
+
+/// The errors of the predicates that are collected while parsing an expression instead of failing
+/// on the first one
+#[derive(Debug, Default)]
+pub struct PredicateErrors(Option<Vec<EventError>>);
+
+impl PredicateErrors {
+    fn collecting() -> Self {
+        Self(Some(vec![]))
+    }
+
+    /// Fail on the error of the predicate or, when the errors are collected, keep it and replace
+    /// the predicate by a placeholder so that the rest of the expression is still validated.
+    #[inline]
+    pub fn recover<'a>(&mut self, error: EventError) -> Result<Node, ATreeParseError<'a>> {
+        match &mut self.0 {
+            Some(errors) => {
+                errors.push(error);
+                Ok(Node::Constant(false))
+            }
+            None => Err(ParseError::User {
+                error: ParserError::Event(error),
+            }),
+        }
+    }
+}
+
 #[inline]
 pub fn parse<'a>(
     input: &'a str,
     attributes: &AttributeTable,
-    strings: &mut StringTable,
+    strings: &mut dyn Interner,
+    lists: &NamedLists,
+    limits: &ParserLimits,
 ) -> Result<Node, ATreeParseError<'a>> {
     let lexer = Lexer::new(input);
-    TreeParser::new().parse(attributes, strings, lexer)
+    let mut errors = PredicateErrors::default();
+    TreeParser::new()
+        .parse(attributes, strings, lists, limits, &mut errors, lexer)
+        .map_err(|error| explain_error(input, attributes, error))
+}
+
+/// Parse the expression like [`parse()`] but report the errors of all its predicates instead of
+/// only the first one.
+pub fn validate<'a>(
+    input: &'a str,
+    attributes: &AttributeTable,
+    strings: &mut dyn Interner,
+    lists: &NamedLists,
+    limits: &ParserLimits,
+) -> Result<(), ATreeParseError<'a>> {
+    let lexer = Lexer::new(input);
+    let mut errors = PredicateErrors::collecting();
+    TreeParser::new()
+        .parse(attributes, strings, lists, limits, &mut errors, lexer)
+        .map_err(|error| explain_error(input, attributes, error))?;
+    let mut errors = errors.0.unwrap_or_default();
+    let error = match errors.len() {
+        0 => return Ok(()),
+        1 => ParserError::Event(errors.remove(0)),
+        _ => ParserError::Events(errors),
+    };
+    Err(ParseError::User { error })
+}
+
+#[inline]
+fn explain_error<'a>(
+    input: &'a str,
+    attributes: &AttributeTable,
+    error: ATreeParseError<'a>,
+) -> ATreeParseError<'a> {
+    explain_keyword_error(input, attributes, explain_list_error(input, error))
+}
+
+/// Replace the generic error of an unexpected token (or of the unexpected end of the expression)
+/// by a targeted one when a keyword up to that token is also the name of an attribute, since such
+/// an attribute has to be quoted with backticks to be read as an attribute.
+fn explain_keyword_error<'a>(
+    input: &'a str,
+    attributes: &AttributeTable,
+    error: ATreeParseError<'a>,
+) -> ATreeParseError<'a> {
+    let end = match error {
+        ParseError::UnrecognizedToken {
+            token: (_, _, end), ..
+        } => end,
+        ParseError::UnrecognizedEof { .. } => input.len(),
+        _ => return error,
+    };
+    let keyword = Lexer::new(input)
+        .map_while(Result::ok)
+        .take_while(|(_, _, token_end)| *token_end <= end)
+        .map(|(start, _, token_end)| &input[start..token_end])
+        .filter(|name| lexer::is_reserved(name) && attributes.by_name(name).is_some())
+        .last();
+    match keyword {
+        Some(name) => ParseError::User {
+            error: ParserError::KeywordAttribute(name.to_string()),
+        },
+        None => error,
+    }
+}
+
+/// Replace the generic error of an unexpected token by a targeted one when the token is an
+/// element of a list literal that is either a list itself or a literal of another type than the
+/// first element.
+fn explain_list_error<'a>(input: &'a str, error: ATreeParseError<'a>) -> ATreeParseError<'a> {
+    let ParseError::UnrecognizedToken {
+        token: (start, ref unexpected, _),
+        ..
+    } = error
+    else {
+        return error;
+    };
+    // The groups (i.e. the brackets and the parentheses) that are still open before the token
+    // with the type of their first element when they are a list of literals
+    let mut groups: Vec<(bool, Option<&'static str>)> = vec![];
+    let mut is_element = false;
+    for (_, token, _) in Lexer::new(input)
+        .map_while(Result::ok)
+        .take_while(|(offset, _, _)| *offset < start)
+    {
+        match token {
+            Token::LeftSquareBracket => groups.push((true, None)),
+            Token::LeftParenthesis => groups.push((false, None)),
+            Token::RightSquareBracket | Token::RightParenthesis => {
+                groups.pop();
+            }
+            ref token if is_element => {
+                if let (Some(kind), Some((_, first @ None))) =
+                    (literal_kind(token), groups.last_mut())
+                {
+                    *first = Some(kind);
+                }
+            }
+            _ => {}
+        }
+        is_element = matches!(
+            token,
+            Token::LeftSquareBracket | Token::LeftParenthesis | Token::Comma
+        );
+    }
+    let error = match (groups.last(), unexpected) {
+        (Some((is_list, first)), Token::LeftSquareBracket | Token::LeftParenthesis)
+            if is_element && (*is_list || first.is_some()) =>
+        {
+            ParserError::NestedList
+        }
+        (Some((_, Some(expected))), unexpected) if is_element => match literal_kind(unexpected) {
+            Some(actual) if actual != *expected => ParserError::MixedList { expected, actual },
+            _ => return error,
+        },
+        _ => return error,
+    };
+    ParseError::User { error }
+}
+
+#[inline]
+const fn literal_kind(token: &Token) -> Option<&'static str> {
+    match token {
+        Token::IntegerLiteral(_) => Some("integer"),
+        Token::StringLiteral(_) => Some("string"),
+        Token::FloatLiteral(_) => Some("float"),
+        Token::BooleanLiteral(_) => Some("boolean"),
+        _ => None,
+    }
 }
 
 #[cfg(test)]
@@ -28,22 +344,38 @@ mod tests {
     use super::*;
     use crate::{
         ast::*,
-        events::AttributeDefinition,
+        events::{AttributeDefinition, AttributeKind, EventError},
         predicates::{
-            ComparisonOperator, ComparisonValue, EqualityOperator, ListLiteral, ListOperator,
-            NullOperator, Predicate, PredicateKind, PrimitiveLiteral, SetOperator,
+            ComparisonOperator, ComparisonValue, ContainsOperator, EqualityOperator, FlagOperator,
+            ListLiteral, ListOperator, NullOperator, Predicate, PredicateKind, PrimitiveLiteral,
+            Quantifier, SetOperator, StringMatchOperator,
         },
+        strings::StringTable,
         test_utils::{
             ast::{and, not, or, value},
             predicates::{
                 all_of, comparison_integer, equal, greater_than, greater_than_equal, integer_list,
                 is_empty, is_not_empty, is_not_null, is_null, less_than, less_than_equal, none_of,
-                not_equal, one_of, predicate, primitive_integer, set_in, set_not_in, string_list,
-                variable,
+                not_equal, one_of, predicate, primitive_integer, primitive_string, quantified,
+                range_in, range_not_in, set_in, set_not_in, string_list, superset_of, variable,
             },
         },
     };
 
+    fn parse<'a>(
+        input: &'a str,
+        attributes: &AttributeTable,
+        strings: &mut StringTable,
+    ) -> Result<Node, ATreeParseError<'a>> {
+        super::parse(
+            input,
+            attributes,
+            strings,
+            &NamedLists::default(),
+            &ParserLimits::default(),
+        )
+    }
+
     #[test]
     fn return_an_error_on_empty_input() {
         let attributes = define_attributes();
@@ -269,232 +601,1148 @@ mod tests {
     }
 
     #[test]
-    fn can_parse_is_null_expression() {
+    fn can_parse_quantified_expressions() {
         let mut strings = StringTable::new();
         let attributes = define_attributes();
 
-        let parsed = parse("exchange_id is null", &attributes, &mut strings);
+        let all = parse("all segment_ids > 100", &attributes, &mut strings);
+        let any = parse("any segment_ids <= -5", &attributes, &mut strings);
 
-        assert_eq!(Ok(value!(is_null!(&attributes, "exchange_id"))), parsed);
+        assert_eq!(
+            Ok(value!(quantified!(
+                &attributes,
+                "segment_ids",
+                Quantifier::All,
+                ComparisonOperator::GreaterThan,
+                comparison_integer!(100)
+            ))),
+            all
+        );
+        assert_eq!(
+            Ok(value!(quantified!(
+                &attributes,
+                "segment_ids",
+                Quantifier::Any,
+                ComparisonOperator::LessThanEqual,
+                comparison_integer!(-5)
+            ))),
+            any
+        );
     }
 
     #[test]
-    fn can_parse_is_not_null_expression() {
+    fn can_parse_string_matching_expressions() {
         let mut strings = StringTable::new();
         let attributes = define_attributes();
 
-        let parsed = parse("exchange_id is not null", &attributes, &mut strings);
+        let starts_with = parse(r#"country starts_with "U""#, &attributes, &mut strings);
+        let ends_with = parse(r#"city ends_with 'ville'"#, &attributes, &mut strings);
 
-        assert_eq!(Ok(value!(is_not_null!(&attributes, "exchange_id"))), parsed);
+        assert_eq!(
+            Ok(value!(predicate!(
+                &attributes,
+                "country",
+                PredicateKind::StringMatch(StringMatchOperator::StartsWith, "U".to_string())
+            ))),
+            starts_with
+        );
+        assert_eq!(
+            Ok(value!(predicate!(
+                &attributes,
+                "city",
+                PredicateKind::StringMatch(StringMatchOperator::EndsWith, "ville".to_string())
+            ))),
+            ends_with
+        );
     }
 
     #[test]
-    fn can_parse_is_empty_expression() {
+    fn can_parse_like_expressions() {
         let mut strings = StringTable::new();
         let attributes = define_attributes();
 
-        let parsed = parse("deals is empty", &attributes, &mut strings);
+        let like = parse(r#"city like "/shop/%""#, &attributes, &mut strings);
+        let not_like = parse(r#"city not like '100\%_'"#, &attributes, &mut strings);
+        let quantified = parse(r#"deals any like "deal-_""#, &attributes, &mut strings);
 
-        assert_eq!(Ok(value!(is_empty!(&attributes, "deals"))), parsed);
+        assert_eq!(
+            Ok(value!(predicate!(
+                &attributes,
+                "city",
+                PredicateKind::StringMatch(StringMatchOperator::Like, "/shop/%".to_string())
+            ))),
+            like
+        );
+        assert_eq!(
+            Ok(value!(predicate!(
+                &attributes,
+                "city",
+                PredicateKind::StringMatch(StringMatchOperator::NotLike, r"100\%_".to_string())
+            ))),
+            not_like
+        );
+        assert_eq!(
+            Ok(value!(predicate!(
+                &attributes,
+                "deals",
+                PredicateKind::QuantifiedStringMatch(
+                    Quantifier::Any,
+                    StringMatchOperator::Like,
+                    "deal-_".to_string()
+                )
+            ))),
+            quantified
+        );
+        assert!(parse(r#"price like "1%""#, &attributes, &mut strings).is_err());
     }
 
     #[test]
-    fn can_parse_is_not_empty_expression() {
+    fn can_parse_contains_expressions() {
         let mut strings = StringTable::new();
         let attributes = define_attributes();
 
-        let parsed = parse("deals is not empty", &attributes, &mut strings);
+        let contains = parse(r#"deals contains "deal-1""#, &attributes, &mut strings);
+        let not_contains = parse("ids not contains 3", &attributes, &mut strings);
 
-        assert_eq!(Ok(value!(is_not_empty!(&attributes, "deals"))), parsed);
+        assert_eq!(
+            Ok(value!(predicate!(
+                &attributes,
+                "deals",
+                PredicateKind::Contains(
+                    ContainsOperator::Contains,
+                    primitive_string!(strings.get("deal-1"))
+                )
+            ))),
+            contains
+        );
+        assert_eq!(
+            Ok(value!(predicate!(
+                &attributes,
+                "ids",
+                PredicateKind::Contains(ContainsOperator::NotContains, primitive_integer!(3))
+            ))),
+            not_contains
+        );
     }
 
     #[test]
-    fn return_an_error_on_an_empty_list() {
+    fn can_parse_length_comparisons() {
         let mut strings = StringTable::new();
         let attributes = define_attributes();
 
-        let parsed = parse("deals one of []", &attributes, &mut strings);
+        let integers = parse("len(ids) > 3", &attributes, &mut strings);
+        let strings_length = parse("len(deals) <= 2", &attributes, &mut strings);
 
-        assert!(parsed.is_err());
+        assert_eq!(
+            Ok(value!(predicate!(
+                &attributes,
+                "ids",
+                PredicateKind::Length(ComparisonOperator::GreaterThan, 3)
+            ))),
+            integers
+        );
+        assert_eq!(
+            Ok(value!(predicate!(
+                &attributes,
+                "deals",
+                PredicateKind::Length(ComparisonOperator::LessThanEqual, 2)
+            ))),
+            strings_length
+        );
     }
 
     #[test]
-    fn can_parse_one_of_list_expression_with_single_element_integer_list() {
+    fn return_an_error_when_comparing_the_length_of_an_attribute_that_is_not_a_list() {
         let mut strings = StringTable::new();
         let attributes = define_attributes();
 
-        let parsed = parse("ids one of [1]", &attributes, &mut strings);
-
-        assert_eq!(
-            Ok(value!(one_of!(&attributes, "ids", integer_list!(vec![1])))),
-            parsed
-        );
+        assert!(parse("len(price) > 3", &attributes, &mut strings).is_err());
+        assert!(parse("len(country) > 3", &attributes, &mut strings).is_err());
+        assert!(parse("len(ids) > 1.5", &attributes, &mut strings).is_err());
+        assert!(parse("len(ids, deals) > 1", &attributes, &mut strings).is_err());
     }
 
     #[test]
-    fn can_parse_one_of_list_expression_with_integer_list() {
+    fn can_parse_flag_expressions() {
         let mut strings = StringTable::new();
         let attributes = define_attributes();
 
-        let parsed = parse("ids one of [1, 2, 3]", &attributes, &mut strings);
+        let has_flag = parse("price has_flag 0x4", &attributes, &mut strings);
+        let has_any_flag = parse("price not has_any_flag 0b110", &attributes, &mut strings);
 
         assert_eq!(
-            Ok(value!(one_of!(
+            Ok(value!(predicate!(
                 &attributes,
-                "ids",
-                integer_list!(vec![1, 2, 3])
+                "price",
+                PredicateKind::Flag(FlagOperator::All, 4)
             ))),
-            parsed
+            has_flag
+        );
+        assert_eq!(
+            Ok(value!(predicate!(
+                &attributes,
+                "price",
+                PredicateKind::Flag(FlagOperator::NotAny, 6)
+            ))),
+            has_any_flag
         );
     }
 
     #[test]
-    fn can_parse_one_of_list_expression_with_integer_list_in_square_brackets() {
+    fn return_an_error_when_the_flags_are_not_tested_with_an_integer_mask() {
         let mut strings = StringTable::new();
         let attributes = define_attributes();
 
-        let parsed = parse("ids one of [1, 2, 3]", &attributes, &mut strings);
+        assert!(parse("price has_flag 1.5", &attributes, &mut strings).is_err());
+        assert!(parse("price has_any_flag 'CA'", &attributes, &mut strings).is_err());
+        assert!(parse("ids has_flag 1", &attributes, &mut strings).is_err());
+        assert!(parse("country has_any_flag 1", &attributes, &mut strings).is_err());
+    }
 
-        assert_eq!(
-            Ok(value!(one_of!(
-                &attributes,
-                "ids",
-                integer_list!(vec![1, 2, 3])
-            ))),
-            parsed
-        );
+    #[test]
+    fn return_an_error_when_the_contained_value_does_not_match_the_list() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        assert!(parse("deals contains 1", &attributes, &mut strings).is_err());
+        assert!(parse("ids contains 'deal-1'", &attributes, &mut strings).is_err());
+        assert!(parse("ids contains 1.5", &attributes, &mut strings).is_err());
+        assert!(parse("country contains 'CA'", &attributes, &mut strings).is_err());
+        assert!(parse("deals contains ['deal-1']", &attributes, &mut strings).is_err());
     }
 
     #[test]
-    fn can_parse_one_of_list_expression_with_single_element_string_list() {
+    fn can_parse_an_any_defined_expression() {
         let mut strings = StringTable::new();
         let attributes = define_attributes();
 
-        let parsed = parse(r##"deals one of ["deal-1"]"##, &attributes, &mut strings);
+        let any_defined = parse(
+            "any_defined(segment_ids, deals, segment_ids)",
+            &attributes,
+            &mut strings,
+        );
 
         assert_eq!(
-            Ok(value!(one_of!(
+            Ok(value!(Predicate::any_defined(
                 &attributes,
-                "deals",
-                string_list!(vec![strings.get("deal-1")])
-            ))),
-            parsed
+                &["deals", "segment_ids"]
+            )
+            .unwrap())),
+            any_defined
+        );
+        assert_eq!(
+            parse("any_defined(deals, segment_ids)", &attributes, &mut strings),
+            any_defined
         );
     }
 
     #[test]
-    fn can_parse_one_of_list_expression_with_string_list() {
+    fn suggest_the_set_operators_when_using_a_list_operator_on_a_scalar_attribute() {
         let mut strings = StringTable::new();
         let attributes = define_attributes();
+        let expected = Err(ParseError::User {
+            error: ParserError::Event(EventError::ScalarWithListOperator {
+                name: "country".to_string(),
+                operator: "one of".to_string(),
+                actual: AttributeKind::String,
+                suggestion: "in",
+            }),
+        });
 
-        let parsed = parse(
-            r##"deals one of ["deal-1", "deal-2", "deal-3"]"##,
-            &attributes,
-            &mut strings,
+        assert_eq!(
+            expected,
+            parse(r#"country one of ["CA", "US"]"#, &attributes, &mut strings)
+        );
+        assert_eq!(
+            "'price' is a scalar attribute (integer) but 'none of' requires a list attribute; did you mean 'not in'?",
+            error_message(parse("price none of [1, 2]", &attributes, &mut strings))
         );
+    }
+
+    #[test]
+    fn suggest_the_list_operators_when_using_a_set_operator_on_a_list_attribute() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
 
         assert_eq!(
-            Ok(value!(one_of!(
-                &attributes,
-                "deals",
-                string_list!(vec![
-                    strings.get("deal-1"),
-                    strings.get("deal-2"),
-                    strings.get("deal-3")
-                ])
-            ))),
-            parsed
+            "'deals' is a list attribute (string_list) but 'in' requires a scalar attribute; did you mean 'one of'?",
+            error_message(parse(r#"deals in ["deal-1"]"#, &attributes, &mut strings))
         );
+        assert_eq!(
+            "'ids' is a list attribute (integer_list) but 'not in' requires a scalar attribute; did you mean 'none of'?",
+            error_message(parse("ids not in [1, 2]", &attributes, &mut strings))
+        );
+    }
+
+    fn error_message(result: Result<Node, ATreeParseError<'_>>) -> String {
+        match result {
+            Err(ParseError::User {
+                error: ParserError::Event(error),
+            }) => error.to_string(),
+            other => panic!("expected an event error, got {other:?}"),
+        }
     }
 
     #[test]
-    fn can_parse_all_of_list_expression_with_integer_list() {
+    fn return_an_error_when_a_list_contains_a_list() {
         let mut strings = StringTable::new();
         let attributes = define_attributes();
+        let expected = Err(ParseError::User {
+            error: ParserError::NestedList,
+        });
+
+        for expression in [
+            r#"deals one of ["deal-1", ["deal-2"]]"#,
+            r#"deals one of [["deal-1"]]"#,
+            "ids in (1, (2, 3))",
+            "ids all of [1, (2)]",
+        ] {
+            assert_eq!(expected, parse(expression, &attributes, &mut strings));
+        }
+        assert_eq!(
+            Some("list elements must be scalar literals".to_string()),
+            parse(r#"deals one of [["deal-1"]]"#, &attributes, &mut strings)
+                .err()
+                .map(|error| error.to_string())
+        );
+    }
 
-        let parsed = parse("ids all of [1, 2, 3]", &attributes, &mut strings);
+    #[test]
+    fn return_an_error_when_a_list_mixes_the_types_of_its_elements() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
 
         assert_eq!(
-            Ok(value!(all_of!(
+            Err(ParseError::User {
+                error: ParserError::MixedList {
+                    expected: "integer",
+                    actual: "string"
+                }
+            }),
+            parse(r#"ids one of [1, "a"]"#, &attributes, &mut strings)
+        );
+        assert_eq!(
+            Err(ParseError::User {
+                error: ParserError::MixedList {
+                    expected: "string",
+                    actual: "float"
+                }
+            }),
+            parse(
+                r#"deals in ("deal-1", "deal-2", 3.5)"#,
                 &attributes,
-                "ids",
-                integer_list!(vec![1, 2, 3])
-            ))),
-            parsed
+                &mut strings
+            )
         );
     }
 
     #[test]
-    fn sort_lists_when_parsing_an_expression_that_contains_a_list() {
+    fn keep_the_generic_error_when_the_unexpected_token_is_not_a_list_element() {
         let mut strings = StringTable::new();
         let attributes = define_attributes();
 
-        let parsed = parse(
-            "ids all of [12, 8, 10, 11, 9, 4, 3, 4, 5, 1, 0, 6, 7, 3, 4, 1, 2, 3]",
+        assert!(matches!(
+            parse(r#"(exchange_id = 1 "a")"#, &attributes, &mut strings),
+            Err(ParseError::UnrecognizedToken { .. })
+        ));
+        assert!(matches!(
+            parse(r#"ids one of [1 "a"]"#, &attributes, &mut strings),
+            Err(ParseError::UnrecognizedToken { .. })
+        ));
+        assert!(matches!(
+            parse("(private) (private)", &attributes, &mut strings),
+            Err(ParseError::UnrecognizedToken { .. })
+        ));
+    }
+
+    #[test]
+    fn return_an_error_when_any_defined_refers_to_a_non_existing_attribute() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+        let expected = Err(ParseError::User {
+            error: ParserError::Event(EventError::NonExistingAttribute("unknown".to_string())),
+        });
+
+        let parsed = parse("any_defined(deals, unknown)", &attributes, &mut strings);
+
+        assert_eq!(expected, parsed);
+        assert!(parse("any_defined()", &attributes, &mut strings).is_err());
+    }
+
+    #[test]
+    fn can_parse_quantified_string_matching_expressions_in_both_orders() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let any = parse(
+            r#"deals any starts_with "promo_""#,
             &attributes,
             &mut strings,
         );
+        let all = parse(r#"all deals ends_with "_2024""#, &attributes, &mut strings);
 
         assert_eq!(
-            Ok(value!(all_of!(
+            Ok(value!(predicate!(
                 &attributes,
-                "ids",
-                integer_list!(vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12])
+                "deals",
+                PredicateKind::QuantifiedStringMatch(
+                    Quantifier::Any,
+                    StringMatchOperator::StartsWith,
+                    "promo_".to_string()
+                )
             ))),
-            parsed
+            any
+        );
+        assert_eq!(
+            parse(
+                r#"any deals starts_with "promo_""#,
+                &attributes,
+                &mut strings
+            ),
+            any
         );
+        assert_eq!(
+            Ok(value!(predicate!(
+                &attributes,
+                "deals",
+                PredicateKind::QuantifiedStringMatch(
+                    Quantifier::All,
+                    StringMatchOperator::EndsWith,
+                    "_2024".to_string()
+                )
+            ))),
+            all
+        );
+    }
+
+    #[test]
+    fn return_an_error_when_matching_the_strings_of_a_non_string_attribute() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        assert!(parse(r#"price starts_with "1""#, &attributes, &mut strings).is_err());
+        assert!(parse(r#"ids any starts_with "1""#, &attributes, &mut strings).is_err());
+        assert!(parse(r#"country any starts_with "U""#, &attributes, &mut strings).is_err());
+    }
+
+    #[test]
+    fn can_parse_an_allowed_value_of_an_enumeration() {
+        let mut strings = StringTable::new();
+        let attributes = define_enumeration_attributes(&mut strings);
+
+        let equality = parse("log_level = 'debug'", &attributes, &mut strings);
+        let set = parse("log_level in ['warn', 'error']", &attributes, &mut strings);
+
+        assert_eq!(
+            Ok(value!(equal!(
+                &attributes,
+                "log_level",
+                primitive_string!(strings.get("debug"))
+            ))),
+            equality
+        );
+        assert_eq!(
+            Ok(value!(set_in!(
+                &attributes,
+                "log_level",
+                string_list!(vec![strings.get("warn"), strings.get("error")])
+            ))),
+            set
+        );
+    }
+
+    #[test]
+    fn return_an_error_when_using_a_value_that_is_not_allowed_by_an_enumeration() {
+        let mut strings = StringTable::new();
+        let attributes = define_enumeration_attributes(&mut strings);
+        let expected = Err(ParseError::User {
+            error: ParserError::Event(EventError::NotAllowedValue {
+                name: "log_level".to_string(),
+                allowed: vec![
+                    "debug".to_string(),
+                    "info".to_string(),
+                    "warn".to_string(),
+                    "error".to_string(),
+                ],
+            }),
+        });
+
+        assert_eq!(
+            expected,
+            parse("log_level = 'debgu'", &attributes, &mut strings)
+        );
+        assert_eq!(
+            expected,
+            parse("log_level in ['warn', 'eror']", &attributes, &mut strings)
+        );
+        assert!(parse("log_level > 2", &attributes, &mut strings).is_err());
+    }
+
+    #[test]
+    fn can_parse_a_lone_boolean_literal() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        assert_eq!(
+            Ok(Node::Constant(true)),
+            parse("true", &attributes, &mut strings)
+        );
+        assert_eq!(
+            Ok(Node::Constant(false)),
+            parse("false", &attributes, &mut strings)
+        );
+    }
+
+    #[test]
+    fn return_an_error_on_boolean_literal_inside_of_an_expression() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        assert!(parse("true and private", &attributes, &mut strings).is_err());
+        assert!(parse("not false", &attributes, &mut strings).is_err());
+        assert!(parse("(true)", &attributes, &mut strings).is_err());
+    }
+
+    #[test]
+    fn return_an_error_on_quantified_expression_over_a_scalar_attribute() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let parsed = parse("all exchange_id > 100", &attributes, &mut strings);
+
+        assert!(parsed.is_err());
+    }
+
+    #[test]
+    fn can_parse_is_null_expression() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let parsed = parse("exchange_id is null", &attributes, &mut strings);
+
+        assert_eq!(Ok(value!(is_null!(&attributes, "exchange_id"))), parsed);
+    }
+
+    #[test]
+    fn can_parse_is_not_null_expression() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let parsed = parse("exchange_id is not null", &attributes, &mut strings);
+
+        assert_eq!(Ok(value!(is_not_null!(&attributes, "exchange_id"))), parsed);
+    }
+
+    #[test]
+    fn can_parse_quoted_identifiers() {
+        let mut strings = StringTable::new();
+        let attributes = AttributeTable::new(&[
+            AttributeDefinition::string("user.country"),
+            AttributeDefinition::integer("geo-region"),
+        ])
+        .unwrap();
+
+        let parsed = parse(
+            "`user.country` = 'CA' and `geo-region` > 1",
+            &attributes,
+            &mut strings,
+        );
+        let mismatched = parse("`user.country` > 1", &attributes, &mut strings);
+
+        assert_eq!(
+            Ok(and!(
+                value!(equal!(
+                    &attributes,
+                    "user.country",
+                    primitive_string!(strings.get("CA"))
+                )),
+                value!(greater_than!(
+                    &attributes,
+                    "geo-region",
+                    comparison_integer!(1)
+                ))
+            )),
+            parsed
+        );
+        assert!(mismatched.is_err());
+    }
+
+    #[test]
+    fn return_an_error_with_a_hint_when_an_attribute_named_like_a_keyword_is_not_quoted() {
+        let mut strings = StringTable::new();
+        let attributes = AttributeTable::new(&[
+            AttributeDefinition::integer("len"),
+            AttributeDefinition::boolean("contains"),
+            AttributeDefinition::string("or"),
+        ])
+        .unwrap();
+
+        let quoted = parse(
+            "`len` = 5 and `contains` and `or` = 'a'",
+            &attributes,
+            &mut strings,
+        );
+
+        assert!(quoted.is_ok());
+        for (expression, keyword) in [
+            ("len = 5", "len"),
+            ("contains", "contains"),
+            ("`len` = 5 and contains", "contains"),
+            ("or = 'a'", "or"),
+        ] {
+            assert_eq!(
+                Err(ParseError::User {
+                    error: ParserError::KeywordAttribute(keyword.to_string())
+                }),
+                parse(expression, &attributes, &mut strings),
+                "{expression}"
+            );
+        }
+    }
+
+    #[test]
+    fn give_the_same_id_to_floats_that_only_differ_by_their_scale() {
+        let mut strings = StringTable::new();
+        let attributes = AttributeTable::new(&[AttributeDefinition::float("price")]).unwrap();
+
+        let short = parse("price = 1.5", &attributes, &mut strings);
+        let long = parse("price = 1.50", &attributes, &mut strings);
+        let compared = parse("price < 1.500", &attributes, &mut strings);
+
+        let (Ok(Node::Value(short)), Ok(Node::Value(long)), Ok(Node::Value(compared))) =
+            (short, long, compared)
+        else {
+            panic!("failed to parse the expressions");
+        };
+        assert_eq!(short.id(), long.id());
+        assert_eq!(short.to_string(), long.to_string());
+        assert!(compared.to_string().ends_with(", 1.5⟩"));
+    }
+
+    #[test]
+    fn can_parse_an_equality_with_null_as_a_null_check() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let is_null = parse("country = null", &attributes, &mut strings);
+        let is_not_null = parse("country <> null", &attributes, &mut strings);
+
+        assert_eq!(Ok(value!(is_null!(&attributes, "country"))), is_null);
+        assert_eq!(
+            Ok(value!(is_not_null!(&attributes, "country"))),
+            is_not_null
+        );
+    }
+
+    #[test]
+    fn can_parse_null_checks_on_list_attributes() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let is_null = parse("deals is null", &attributes, &mut strings);
+        let is_not_null = parse("segment_ids is not null", &attributes, &mut strings);
+
+        assert_eq!(Ok(value!(is_null!(&attributes, "deals"))), is_null);
+        assert_eq!(
+            Ok(value!(is_not_null!(&attributes, "segment_ids"))),
+            is_not_null
+        );
+    }
+
+    #[test]
+    fn can_parse_is_empty_expression() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let parsed = parse("deals is empty", &attributes, &mut strings);
+
+        assert_eq!(Ok(value!(is_empty!(&attributes, "deals"))), parsed);
+    }
+
+    #[test]
+    fn can_parse_is_not_empty_expression() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let parsed = parse("deals is not empty", &attributes, &mut strings);
+
+        assert_eq!(Ok(value!(is_not_empty!(&attributes, "deals"))), parsed);
+    }
+
+    #[test]
+    fn can_parse_an_empty_list_of_the_type_of_the_attribute() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let strings_list = parse("deals one of []", &attributes, &mut strings);
+        let integers_list = parse("[] all of ids", &attributes, &mut strings);
+
+        assert_eq!(
+            Ok(value!(one_of!(&attributes, "deals", string_list!(vec![])))),
+            strings_list
+        );
+        assert_eq!(
+            Ok(value!(superset_of!(
+                &attributes,
+                "ids",
+                integer_list!(vec![])
+            ))),
+            integers_list
+        );
+    }
+
+    #[test]
+    fn can_parse_one_of_list_expression_with_single_element_integer_list() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let parsed = parse("ids one of [1]", &attributes, &mut strings);
+
+        assert_eq!(
+            Ok(value!(one_of!(&attributes, "ids", integer_list!(vec![1])))),
+            parsed
+        );
+    }
+
+    #[test]
+    fn can_parse_one_of_list_expression_with_integer_list() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let parsed = parse("ids one of [1, 2, 3]", &attributes, &mut strings);
+
+        assert_eq!(
+            Ok(value!(one_of!(
+                &attributes,
+                "ids",
+                integer_list!(vec![1, 2, 3])
+            ))),
+            parsed
+        );
+    }
+
+    #[test]
+    fn can_parse_one_of_list_expression_with_integer_list_in_square_brackets() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let parsed = parse("ids one of [1, 2, 3]", &attributes, &mut strings);
+
+        assert_eq!(
+            Ok(value!(one_of!(
+                &attributes,
+                "ids",
+                integer_list!(vec![1, 2, 3])
+            ))),
+            parsed
+        );
+    }
+
+    #[test]
+    fn can_parse_one_of_list_expression_with_single_element_string_list() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let parsed = parse(r##"deals one of ["deal-1"]"##, &attributes, &mut strings);
+
+        assert_eq!(
+            Ok(value!(one_of!(
+                &attributes,
+                "deals",
+                string_list!(vec![strings.get("deal-1")])
+            ))),
+            parsed
+        );
+    }
+
+    #[test]
+    fn can_parse_one_of_list_expression_with_string_list() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let parsed = parse(
+            r##"deals one of ["deal-1", "deal-2", "deal-3"]"##,
+            &attributes,
+            &mut strings,
+        );
+
+        assert_eq!(
+            Ok(value!(one_of!(
+                &attributes,
+                "deals",
+                string_list!(vec![
+                    strings.get("deal-1"),
+                    strings.get("deal-2"),
+                    strings.get("deal-3")
+                ])
+            ))),
+            parsed
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "normalization")]
+    fn deduplicate_the_string_list_values_that_only_differ_by_their_normalization_form() {
+        let mut strings = StringTable::new();
+        strings.set_normalize(true);
+        let attributes = define_attributes();
+
+        let parsed = parse(
+            "deals one of ['caf\u{e9}', 'cafe\u{301}']",
+            &attributes,
+            &mut strings,
+        );
+
+        assert_eq!(
+            Ok(value!(one_of!(
+                &attributes,
+                "deals",
+                string_list!(vec![strings.get("caf\u{e9}")])
+            ))),
+            parsed
+        );
+    }
+
+    #[test]
+    fn can_parse_all_of_list_expression_with_integer_list() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let parsed = parse("ids all of [1, 2, 3]", &attributes, &mut strings);
+
+        assert_eq!(
+            Ok(value!(all_of!(
+                &attributes,
+                "ids",
+                integer_list!(vec![1, 2, 3])
+            ))),
+            parsed
+        );
+    }
+
+    #[test]
+    fn sort_lists_when_parsing_an_expression_that_contains_a_list() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let parsed = parse(
+            "ids all of [12, 8, 10, 11, 9, 4, 3, 4, 5, 1, 0, 6, 7, 3, 4, 1, 2, 3]",
+            &attributes,
+            &mut strings,
+        );
+
+        assert_eq!(
+            Ok(value!(all_of!(
+                &attributes,
+                "ids",
+                integer_list!(vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12])
+            ))),
+            parsed
+        );
+    }
+
+    #[test]
+    fn can_parse_a_list_of_integers_with_different_bases() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let parsed = parse("ids one of [0o7, 0x1, 0b10]", &attributes, &mut strings);
+
+        assert_eq!(
+            Ok(value!(one_of!(
+                &attributes,
+                "ids",
+                integer_list!(vec![1, 2, 7])
+            ))),
+            parsed
+        );
+    }
+
+    #[test]
+    fn can_parse_all_of_list_expression_with_string_list() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let parsed = parse(
+            r##"deals all of ["deal-1", "deal-2", "deal-3"]"##,
+            &attributes,
+            &mut strings,
+        );
+
+        assert_eq!(
+            Ok(value!(all_of!(
+                &attributes,
+                "deals",
+                string_list!(vec![
+                    strings.get("deal-1"),
+                    strings.get("deal-2"),
+                    strings.get("deal-3")
+                ])
+            ))),
+            parsed
+        );
+    }
+
+    #[test]
+    fn can_parse_all_of_list_expression_with_parenthesis() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let parsed = parse(
+            r##"deals all of ("deal-1", "deal-2", "deal-3")"##,
+            &attributes,
+            &mut strings,
+        );
+
+        assert_eq!(
+            Ok(value!(all_of!(
+                &attributes,
+                "deals",
+                string_list!(vec![
+                    strings.get("deal-1"),
+                    strings.get("deal-2"),
+                    strings.get("deal-3")
+                ])
+            ))),
+            parsed
+        );
+    }
+
+    #[test]
+    fn can_parse_overlaps_and_disjoint_as_one_of_and_none_of() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let overlaps = parse(
+            r#"deals overlaps ["deal-1", "deal-2"]"#,
+            &attributes,
+            &mut strings,
+        );
+        let one_of = parse(
+            r#"deals one of ["deal-1", "deal-2"]"#,
+            &attributes,
+            &mut strings,
+        );
+        let disjoint = parse("ids disjoint [1, 2, 3]", &attributes, &mut strings);
+        let none_of = parse("ids none of [1, 2, 3]", &attributes, &mut strings);
+
+        assert!(overlaps.is_ok());
+        assert_eq!(one_of, overlaps);
+        assert!(disjoint.is_ok());
+        assert_eq!(none_of, disjoint);
+    }
+
+    #[test]
+    fn can_parse_one_of_and_none_of_with_the_list_on_the_left_side() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let one_of = parse(
+            r#"["deal-1", "deal-2"] one of deals"#,
+            &attributes,
+            &mut strings,
+        );
+        let none_of = parse("(1, 2, 3) none of ids", &attributes, &mut strings);
+
+        assert_eq!(
+            Ok(value!(one_of!(
+                &attributes,
+                "deals",
+                string_list!(vec![strings.get("deal-1"), strings.get("deal-2")])
+            ))),
+            one_of
+        );
+        assert_eq!(
+            parse(
+                r#"deals one of ["deal-1", "deal-2"]"#,
+                &attributes,
+                &mut strings
+            ),
+            one_of
+        );
+        assert_eq!(
+            Ok(value!(none_of!(
+                &attributes,
+                "ids",
+                integer_list!(vec![1, 2, 3])
+            ))),
+            none_of
+        );
+        assert_eq!(
+            parse("ids none of (1, 2, 3)", &attributes, &mut strings),
+            none_of
+        );
+    }
+
+    #[test]
+    fn can_parse_all_of_with_the_list_on_the_left_side_as_a_superset() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let parsed = parse("[3, 1, 2] all of ids", &attributes, &mut strings);
+
+        assert_eq!(
+            Ok(value!(superset_of!(
+                &attributes,
+                "ids",
+                integer_list!(vec![1, 2, 3])
+            ))),
+            parsed
+        );
+        assert_ne!(
+            parse("ids all of [1, 2, 3]", &attributes, &mut strings),
+            parsed
+        );
+    }
+
+    const NEGATIONS: [(&str, &str); 21] = [
+        ("private", "not private"),
+        ("exchange_id = 1", "exchange_id <> 1"),
+        ("country = 'CA'", "country <> 'CA'"),
+        ("exchange_id < 1", "exchange_id >= 1"),
+        ("exchange_id <= 1", "exchange_id > 1"),
+        ("exchange_id in [1, 2]", "exchange_id not in [1, 2]"),
+        ("country in ['CA', 'US']", "country not in ['CA', 'US']"),
+        ("exchange_id in 1..=5", "exchange_id not in 1..=5"),
+        ("deals one of ['deal-1']", "deals none of ['deal-1']"),
+        ("ids one of [1, 2]", "ids none of [1, 2]"),
+        ("deals is null", "deals is not null"),
+        ("deals is empty", "deals is not empty"),
+        ("all segment_ids > 1", "any segment_ids <= 1"),
+        ("country like 'C%'", "country not like 'C%'"),
+        ("deals any like 'deal%'", "deals all not like 'deal%'"),
+        ("deals contains 'deal-1'", "deals not contains 'deal-1'"),
+        ("ids contains 1", "ids not contains 1"),
+        ("price has_flag 0x4", "price not has_flag 0x4"),
+        ("price has_any_flag 0x6", "price not has_any_flag 0x6"),
+        ("len(ids) > 3", "len(ids) <= 3"),
+        (
+            "exchange_id = 1 and private",
+            "exchange_id <> 1 or not private",
+        ),
+    ];
+
+    fn optimize(
+        input: &str,
+        attributes: &AttributeTable,
+        strings: &mut StringTable,
+    ) -> OptimizedNode {
+        parse(input, attributes, strings).unwrap().optimize()
+    }
+
+    #[test]
+    fn negate_the_predicates_like_their_hand_written_negation() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        for (expression, negation) in NEGATIONS {
+            assert_eq!(
+                optimize(negation, &attributes, &mut strings),
+                optimize(&format!("not ({expression})"), &attributes, &mut strings),
+                "{expression}"
+            );
+            assert_eq!(
+                optimize(expression, &attributes, &mut strings),
+                optimize(&format!("not ({negation})"), &attributes, &mut strings),
+                "{negation}"
+            );
+        }
     }
 
     #[test]
-    fn can_parse_all_of_list_expression_with_string_list() {
+    fn negate_the_operators_without_syntax_into_their_internal_negation() {
         let mut strings = StringTable::new();
         let attributes = define_attributes();
+        let deals = |strings: &StringTable| {
+            string_list!(vec![strings.get("deal-1"), strings.get("deal-2")])
+        };
 
-        let parsed = parse(
-            r##"deals all of ["deal-1", "deal-2", "deal-3"]"##,
+        let not_all_of = optimize(
+            "not (deals all of ['deal-1', 'deal-2'])",
             &attributes,
             &mut strings,
         );
+        let not_superset_of = optimize("not ([1, 2] all of ids)", &attributes, &mut strings);
+        let not_starts_with = optimize("not (country starts_with 'C')", &attributes, &mut strings);
 
         assert_eq!(
-            Ok(value!(all_of!(
+            OptimizedNode::Value(predicate!(
                 &attributes,
                 "deals",
-                string_list!(vec![
-                    strings.get("deal-1"),
-                    strings.get("deal-2"),
-                    strings.get("deal-3")
-                ])
-            ))),
-            parsed
+                PredicateKind::List(ListOperator::NotAllOf, deals(&strings))
+            )),
+            not_all_of
+        );
+        assert_eq!(
+            OptimizedNode::Value(predicate!(
+                &attributes,
+                "ids",
+                PredicateKind::List(ListOperator::NotSupersetOf, integer_list!(vec![1, 2]))
+            )),
+            not_superset_of
+        );
+        assert_eq!(
+            OptimizedNode::Value(predicate!(
+                &attributes,
+                "country",
+                PredicateKind::StringMatch(StringMatchOperator::NotStartsWith, "C".to_string())
+            )),
+            not_starts_with
         );
     }
 
     #[test]
-    fn can_parse_all_of_list_expression_with_parenthesis() {
+    fn cancel_out_a_double_negation_of_any_predicate() {
         let mut strings = StringTable::new();
         let attributes = define_attributes();
+        let expressions = NEGATIONS
+            .into_iter()
+            .map(|(expression, _)| expression)
+            .chain([
+                "deals all of ['deal-1', 'deal-2']",
+                "[1, 2] all of ids",
+                "country starts_with 'C'",
+            ]);
+
+        for expression in expressions {
+            assert_eq!(
+                optimize(expression, &attributes, &mut strings),
+                optimize(
+                    &format!("not (not ({expression}))"),
+                    &attributes,
+                    &mut strings
+                ),
+                "{expression}"
+            );
+        }
+    }
 
-        let parsed = parse(
-            r##"deals all of ("deal-1", "deal-2", "deal-3")"##,
-            &attributes,
-            &mut strings,
-        );
+    #[test]
+    fn can_parse_a_list_on_the_left_side_inside_a_parenthesized_expression() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let parsed = parse("((1, 2) one of ids) and private", &attributes, &mut strings);
 
         assert_eq!(
-            Ok(value!(all_of!(
-                &attributes,
-                "deals",
-                string_list!(vec![
-                    strings.get("deal-1"),
-                    strings.get("deal-2"),
-                    strings.get("deal-3")
-                ])
-            ))),
+            Ok(and!(
+                value!(one_of!(&attributes, "ids", integer_list!(vec![1, 2]))),
+                value!(variable!(&attributes, "private"))
+            )),
             parsed
         );
     }
 
+    #[test]
+    fn return_an_error_when_the_list_on_the_left_side_does_not_match_the_attribute() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let parsed = parse(r#"["a", "b"] all of ids"#, &attributes, &mut strings);
+
+        assert!(parsed.is_err());
+    }
+
     #[test]
     fn can_parse_none_of_list_expression_with_integer_list() {
         let mut strings = StringTable::new();
@@ -619,11 +1867,74 @@ mod tests {
     }
 
     #[test]
-    fn return_an_error_on_set_expression_with_empty_set() {
+    fn can_parse_in_range_expressions() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let exclusive = parse("exchange_id in 9..17", &attributes, &mut strings);
+        let inclusive = parse("exchange_id in 9..=17", &attributes, &mut strings);
+
+        assert_eq!(
+            Ok(value!(range_in!(&attributes, "exchange_id", 9..=16))),
+            exclusive
+        );
+        assert_eq!(
+            Ok(value!(range_in!(&attributes, "exchange_id", 9..=17))),
+            inclusive
+        );
+    }
+
+    #[test]
+    fn can_parse_not_in_range_expressions() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let parsed = parse("exchange_id not in -5..5", &attributes, &mut strings);
+
+        assert_eq!(
+            Ok(value!(range_not_in!(&attributes, "exchange_id", -5..=4))),
+            parsed
+        );
+    }
+
+    #[test]
+    fn return_an_error_on_range_expression_over_a_non_integer_attribute() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        assert!(parse("deal in 9..17", &attributes, &mut strings).is_err());
+        assert!(parse("ids in 9..17", &attributes, &mut strings).is_err());
+        assert!(parse("exchange_id in 17..9", &attributes, &mut strings).is_err());
+    }
+
+    #[test]
+    fn can_parse_set_expression_with_empty_set() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let integers = parse(r##"exchange_id not in []"##, &attributes, &mut strings);
+        let strings_set = parse(r##"deal in []"##, &attributes, &mut strings);
+
+        assert_eq!(
+            Ok(value!(set_not_in!(
+                &attributes,
+                "exchange_id",
+                integer_list!(vec![])
+            ))),
+            integers
+        );
+        assert_eq!(
+            Ok(value!(set_in!(&attributes, "deal", string_list!(vec![])))),
+            strings_set
+        );
+    }
+
+    #[test]
+    fn return_an_error_on_set_expression_with_empty_parenthesis() {
         let mut strings = StringTable::new();
         let attributes = define_attributes();
 
-        let parsed = parse(r##"exchange_id not in []"##, &attributes, &mut strings);
+        let parsed = parse(r##"exchange_id not in ()"##, &attributes, &mut strings);
 
         assert!(parsed.is_err());
     }
@@ -695,23 +2006,24 @@ mod tests {
         );
 
         assert_eq!(
+            // The runs of the same operator are built as balanced trees
             Ok(and!(
                 and!(
-                    and!(
-                        value!(equal!(&attributes, "exchange_id", primitive_integer!(1))),
-                        value!(variable!(&attributes, "private"))
-                    ),
+                    value!(equal!(&attributes, "exchange_id", primitive_integer!(1))),
+                    value!(variable!(&attributes, "private"))
+                ),
+                and!(
                     value!(none_of!(
                         &attributes,
                         "deal_ids",
                         string_list!(vec![strings.get("deal-2"), strings.get("deal-4")])
+                    )),
+                    value!(one_of!(
+                        &attributes,
+                        "deal_ids",
+                        string_list!(vec![strings.get("deal-1"), strings.get("deal-3")])
                     ))
-                ),
-                value!(one_of!(
-                    &attributes,
-                    "deal_ids",
-                    string_list!(vec![strings.get("deal-1"), strings.get("deal-3")])
-                ))
+                )
             )),
             parsed
         );
@@ -784,23 +2096,24 @@ mod tests {
         );
 
         assert_eq!(
+            // The runs of the same operator are built as balanced trees
             Ok(or!(
                 or!(
-                    or!(
-                        value!(equal!(&attributes, "exchange_id", primitive_integer!(1))),
-                        value!(variable!(&attributes, "private"))
-                    ),
+                    value!(equal!(&attributes, "exchange_id", primitive_integer!(1))),
+                    value!(variable!(&attributes, "private"))
+                ),
+                or!(
                     value!(none_of!(
                         &attributes,
                         "deal_ids",
                         string_list!(vec![strings.get("deal-2"), strings.get("deal-4")])
+                    )),
+                    value!(one_of!(
+                        &attributes,
+                        "deal_ids",
+                        string_list!(vec![strings.get("deal-1"), strings.get("deal-3")])
                     ))
-                ),
-                value!(one_of!(
-                    &attributes,
-                    "deal_ids",
-                    string_list!(vec![strings.get("deal-1"), strings.get("deal-3")])
-                ))
+                )
             )),
             parsed
         );
@@ -844,77 +2157,293 @@ mod tests {
             &mut strings,
         );
 
+        // `and` binds tighter than `or`
         assert_eq!(
-            Ok(and!(
+            Ok(or!(
+                and!(
+                    and!(
+                        value!(equal!(&attributes, "exchange_id", primitive_integer!(1))),
+                        value!(variable!(&attributes, "private"))
+                    ),
+                    value!(one_of!(
+                        &attributes,
+                        "deal_ids",
+                        string_list!(vec![strings.get("deal-1"), strings.get("deal-2")])
+                    ))
+                ),
                 and!(
                     and!(
                         and!(
-                            and!(
-                                and!(
-                                    or!(
-                                        and!(
-                                            and!(
-                                                value!(equal!(
-                                                    &attributes,
-                                                    "exchange_id",
-                                                    primitive_integer!(1)
-                                                )),
-                                                value!(variable!(&attributes, "private"))
-                                            ),
-                                            value!(one_of!(
-                                                &attributes,
-                                                "deal_ids",
-                                                string_list!(vec![
-                                                    strings.get("deal-1"),
-                                                    strings.get("deal-2")
-                                                ])
-                                            ))
-                                        ),
-                                        value!(equal!(
-                                            &attributes,
-                                            "exchange_id",
-                                            primitive_integer!(2)
-                                        ))
-                                    ),
-                                    value!(variable!(&attributes, "private"))
-                                ),
-                                value!(one_of!(
-                                    &attributes,
-                                    "deal_ids",
-                                    string_list!(vec![
-                                        strings.get("deal-3"),
-                                        strings.get("deal-4")
-                                    ])
-                                ))
-                            ),
+                            value!(equal!(&attributes, "exchange_id", primitive_integer!(2))),
+                            value!(variable!(&attributes, "private"))
+                        ),
+                        and!(
+                            value!(one_of!(
+                                &attributes,
+                                "deal_ids",
+                                string_list!(vec![strings.get("deal-3"), strings.get("deal-4")])
+                            )),
                             value!(one_of!(
                                 &attributes,
                                 "segment_ids",
                                 integer_list!(vec![1, 2, 3, 4, 5, 6])
                             ))
+                        )
+                    ),
+                    and!(
+                        and!(
+                            value!(set_in!(
+                                &attributes,
+                                "continent",
+                                string_list!(vec![strings.get("NA")])
+                            )),
+                            value!(set_in!(
+                                &attributes,
+                                "country",
+                                string_list!(vec![strings.get("CA"), strings.get("US")])
+                            ))
                         ),
                         value!(set_in!(
                             &attributes,
-                            "continent",
-                            string_list!(vec![strings.get("NA")])
+                            "city",
+                            string_list!(vec![strings.get("QC"), strings.get("TN")])
                         ))
-                    ),
-                    value!(set_in!(
-                        &attributes,
-                        "country",
-                        string_list!(vec![strings.get("CA"), strings.get("US")])
-                    ))
-                ),
-                value!(set_in!(
-                    &attributes,
-                    "city",
-                    string_list!(vec![strings.get("QC"), strings.get("TN")])
-                ))
+                    )
+                )
             )),
             parsed
         );
     }
 
+    #[test]
+    fn give_precedence_to_and_over_or() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+        let a = value!(variable!(&attributes, "private"));
+        let b = value!(equal!(&attributes, "exchange_id", primitive_integer!(1)));
+        let c = value!(is_null!(&attributes, "country"));
+
+        let or_and = parse(
+            "private or exchange_id = 1 and country is null",
+            &attributes,
+            &mut strings,
+        );
+        let and_or = parse(
+            "private and exchange_id = 1 or country is null",
+            &attributes,
+            &mut strings,
+        );
+
+        assert_eq!(Ok(or!(a.clone(), and!(b.clone(), c.clone()))), or_and);
+        assert_eq!(Ok(or!(and!(a, b), c)), and_or);
+    }
+
+    #[test]
+    fn give_precedence_to_not_over_or() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+
+        let parsed = parse("not private or exchange_id = 1", &attributes, &mut strings);
+
+        assert_eq!(
+            Ok(or!(
+                not!(value!(variable!(&attributes, "private"))),
+                value!(equal!(&attributes, "exchange_id", primitive_integer!(1)))
+            )),
+            parsed
+        );
+    }
+
+    #[test]
+    fn give_the_same_precedence_to_the_symbols_as_to_the_words() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+        let a = value!(variable!(&attributes, "private"));
+        let b = value!(equal!(&attributes, "exchange_id", primitive_integer!(1)));
+        let c = value!(is_null!(&attributes, "country"));
+        let and_or = or!(and!(a.clone(), b.clone()), c.clone());
+        let or_and = or!(a.clone(), and!(b.clone(), c.clone()));
+        let not_and = and!(not!(a.clone()), b.clone());
+        let cases = [
+            (
+                "private && exchange_id = 1 || country is null",
+                "private and exchange_id = 1 or country is null",
+                &and_or,
+            ),
+            (
+                "private && exchange_id = 1 or country is null",
+                "private and exchange_id = 1 || country is null",
+                &and_or,
+            ),
+            (
+                "private || exchange_id = 1 && country is null",
+                "private or exchange_id = 1 and country is null",
+                &or_and,
+            ),
+            (
+                "private || exchange_id = 1 and country is null",
+                "private or exchange_id = 1 && country is null",
+                &or_and,
+            ),
+            (
+                "!private && exchange_id = 1",
+                "not private and exchange_id = 1",
+                &not_and,
+            ),
+            (
+                "!private and exchange_id = 1",
+                "not private && exchange_id = 1",
+                &not_and,
+            ),
+        ];
+
+        for (symbols, words, expected) in cases {
+            let with_symbols = parse(symbols, &attributes, &mut strings);
+            let with_words = parse(words, &attributes, &mut strings);
+
+            assert_eq!(Ok(expected.clone()), with_symbols, "{symbols}");
+            assert_eq!(with_words, with_symbols, "{symbols}");
+        }
+    }
+
+    #[test]
+    fn return_an_error_on_deeply_nested_expressions() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+        let nested = (0..10_000).fold(String::from("private"), |expression, _| {
+            format!("(private and {expression})")
+        });
+        let negated = format!("{}private", "not ".repeat(10_000));
+
+        let nested = parse(&nested, &attributes, &mut strings);
+        let negated = parse(&negated, &attributes, &mut strings);
+
+        let expected = Err(ParseError::User {
+            error: ParserError::MaximumDepthExceeded(ParserLimits::DEFAULT_MAX_DEPTH),
+        });
+        assert_eq!(expected, nested);
+        assert_eq!(expected, negated);
+    }
+
+    #[test]
+    fn count_a_run_of_the_same_operator_as_a_single_level_of_depth() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+        let limits = ParserLimits::new(2, ParserLimits::DEFAULT_MAX_LIST_LENGTH);
+        let chain = (0..1000)
+            .map(|i| format!("exchange_id = {i}"))
+            .collect::<Vec<_>>()
+            .join(" or ");
+
+        let parsed = parse(&chain, &attributes, &mut strings);
+        let within = super::parse(
+            "private and (private or private or private) and private",
+            &attributes,
+            &mut strings,
+            &NamedLists::default(),
+            &limits,
+        );
+        let exceeding = super::parse(
+            "private and (private or (private and not private))",
+            &attributes,
+            &mut strings,
+            &NamedLists::default(),
+            &limits,
+        );
+
+        assert!(parsed.is_ok());
+        assert!(within.is_ok());
+        assert_eq!(
+            Err(ParseError::User {
+                error: ParserError::MaximumDepthExceeded(2)
+            }),
+            exceeding
+        );
+    }
+
+    #[test]
+    fn can_parse_expressions_up_to_the_maximum_depth() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+        let limits = ParserLimits::new(3, ParserLimits::DEFAULT_MAX_LIST_LENGTH);
+
+        let within = super::parse(
+            "private and (private or not private)",
+            &attributes,
+            &mut strings,
+            &NamedLists::default(),
+            &limits,
+        );
+        let exceeding = super::parse(
+            "not (private and (private or not private))",
+            &attributes,
+            &mut strings,
+            &NamedLists::default(),
+            &limits,
+        );
+
+        assert!(within.is_ok());
+        assert_eq!(
+            Err(ParseError::User {
+                error: ParserError::MaximumDepthExceeded(3)
+            }),
+            exceeding
+        );
+    }
+
+    #[test]
+    fn can_parse_lists_up_to_the_maximum_length() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+        let limits = ParserLimits::new(ParserLimits::DEFAULT_MAX_DEPTH, 3);
+
+        let within = super::parse(
+            "exchange_id in [1, 2, 3]",
+            &attributes,
+            &mut strings,
+            &NamedLists::default(),
+            &limits,
+        );
+        let exceeding = super::parse(
+            r#"deals one of ("deal-1", "deal-2", "deal-3", "deal-4")"#,
+            &attributes,
+            &mut strings,
+            &NamedLists::default(),
+            &limits,
+        );
+
+        assert!(within.is_ok());
+        assert_eq!(
+            Err(ParseError::User {
+                error: ParserError::MaximumListLengthExceeded(3)
+            }),
+            exceeding
+        );
+    }
+
+    #[test]
+    fn return_an_error_on_lists_longer_than_the_default_maximum_length() {
+        let mut strings = StringTable::new();
+        let attributes = define_attributes();
+        let values = (0..=ParserLimits::DEFAULT_MAX_LIST_LENGTH)
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let expression = format!("exchange_id in [{values}]");
+
+        let parsed = parse(&expression, &attributes, &mut strings);
+
+        assert_eq!(
+            Err(ParseError::User {
+                error: ParserError::MaximumListLengthExceeded(
+                    ParserLimits::DEFAULT_MAX_LIST_LENGTH
+                )
+            }),
+            parsed
+        );
+    }
+
     #[test]
     fn can_parse_an_expression_with_multiple_parenthesis_levels() {
         let mut strings = StringTable::new();
@@ -971,4 +2500,14 @@ mod tests {
         ];
         AttributeTable::new(&definitions).unwrap()
     }
+
+    fn define_enumeration_attributes(strings: &mut StringTable) -> AttributeTable {
+        let definitions = vec![AttributeDefinition::enumeration(
+            "log_level",
+            &["debug", "info", "warn", "error"],
+        )];
+        let mut attributes = AttributeTable::new(&definitions).unwrap();
+        attributes.intern_enumerations(strings).unwrap();
+        attributes
+    }
 }
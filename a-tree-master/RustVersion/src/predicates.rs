@@ -1,13 +1,21 @@
 use crate::{
-    events::{AttributeId, AttributeKind, AttributeTable, AttributeValue, Event, EventError},
+    events::{
+        AttributeId, AttributeKind, AttributeTable, AttributeValue, Enumeration, Event, EventError,
+    },
+    hash::FnvHasher,
     strings::StringId,
 };
-use rust_decimal::Decimal;
-use std::{
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
     fmt::{Display, Formatter},
     hash::{Hash, Hasher},
-    ops::Not,
+    ops::{Not, RangeInclusive},
 };
+use itertools::Itertools;
+use rust_decimal::Decimal;
 
 #[derive(Eq, PartialEq, Hash, Debug, Clone)]
 pub struct Predicate {
@@ -25,7 +33,12 @@ impl Predicate {
             .by_name(name)
             .ok_or_else(|| EventError::NonExistingAttribute(name.to_string()))
             .and_then(|id| {
+                let kind = kind.with_empty_lists_typed_as(&attributes.by_id(id));
                 validate_predicate(name, &kind, &attributes.by_id(id))?;
+                let kind = kind.with_floats_rounded_by(attributes, id);
+                if let Some(enumeration) = attributes.enumeration(id) {
+                    validate_enumeration(name, &kind, enumeration)?;
+                }
                 Ok(Predicate {
                     attribute: id,
                     kind,
@@ -33,12 +46,33 @@ impl Predicate {
             })
     }
 
+    /// Build a predicate that is true when at least one of the attributes is defined in the event.
+    ///
+    /// `names` cannot be empty, which the grammar guarantees.
+    pub fn any_defined(attributes: &AttributeTable, names: &[&str]) -> Result<Self, EventError> {
+        let ids: Vec<_> = names
+            .iter()
+            .map(|name| {
+                attributes
+                    .by_name(name)
+                    .ok_or_else(|| EventError::NonExistingAttribute(name.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .sorted()
+            .dedup()
+            .collect();
+        Ok(Predicate {
+            attribute: ids[0],
+            kind: PredicateKind::AnyDefined(ids),
+        })
+    }
+
     #[inline]
     pub fn id(&self) -> u64 {
-        use std::hash::DefaultHasher;
-        let mut hasher = DefaultHasher::new();
+        let mut hasher = FnvHasher::default();
         self.hash(&mut hasher);
-        hasher.finish()
+        hasher.finish_mixed()
     }
 
     #[inline]
@@ -46,19 +80,107 @@ impl Predicate {
         self.kind.cost()
     }
 
+    #[inline]
+    pub fn attribute(&self) -> AttributeId {
+        self.attribute
+    }
+
+    /// Return all the attributes read by this predicate.
+    #[inline]
+    pub fn attributes(&self) -> &[AttributeId] {
+        match &self.kind {
+            PredicateKind::AnyDefined(ids) | PredicateKind::NoneDefined(ids) => ids,
+            _ => core::slice::from_ref(&self.attribute),
+        }
+    }
+
+    /// Return the interned strings that this predicate compares the attribute with.
+    #[inline]
+    pub fn strings(&self) -> &[StringId] {
+        match &self.kind {
+            PredicateKind::Equality(_, PrimitiveLiteral::String(id))
+            | PredicateKind::Contains(_, PrimitiveLiteral::String(id)) => core::slice::from_ref(id),
+            PredicateKind::Set(_, ListLiteral::StringList(ids))
+            | PredicateKind::List(_, ListLiteral::StringList(ids)) => ids,
+            _ => &[],
+        }
+    }
+
+    /// Return the attribute whose text has to be kept by the events for this predicate to be
+    /// evaluated, if any.
+    #[inline]
+    pub fn text_attribute(&self) -> Option<AttributeId> {
+        // The strings that are not interned all share the same ID so a list of strings has to be
+        // counted from its texts
+        matches!(
+            self.kind,
+            PredicateKind::StringMatch(_, _)
+                | PredicateKind::QuantifiedStringMatch(_, _, _)
+                | PredicateKind::Length(_, _)
+        )
+        .then_some(self.attribute)
+    }
+
+    #[cfg(feature = "serde")]
+    /// Display the predicate with the name of its attribute and the text of its strings.
+    #[inline]
+    pub fn resolve<'a>(
+        &'a self,
+        attributes: &'a AttributeTable,
+        strings: &'a dyn crate::strings::Interner,
+    ) -> ResolvedPredicate<'a> {
+        ResolvedPredicate {
+            predicate: self,
+            attributes,
+            strings,
+        }
+    }
+
     pub fn evaluate(&self, event: &Event) -> Option<bool> {
         let value = &event[self.attribute];
         match (&self.kind, value) {
-            (PredicateKind::Null(operator), value) => Some(operator.evaluate(value)),
-            (_, AttributeValue::Undefined) => None,
+            // Like the null checks, they are the ones that look for the undefined attributes
+            (PredicateKind::AnyDefined(ids), _) => Some(is_any_defined(ids, event)),
+            (PredicateKind::NoneDefined(ids), _) => Some(!is_any_defined(ids, event)),
+            (PredicateKind::Null(operator), value) => operator.evaluate(value),
+            (_, AttributeValue::Undefined) => event.undefined_result(),
             (PredicateKind::Variable, AttributeValue::Boolean(value)) => Some(*value),
             (PredicateKind::NegatedVariable, AttributeValue::Boolean(value)) => Some(!*value),
             (PredicateKind::Set(operator, haystack), needle) => {
                 Some(operator.evaluate(haystack, needle))
             }
+            (PredicateKind::Range(operator, range), AttributeValue::Integer(value)) => {
+                Some(operator.evaluate_range(range, value))
+            }
+            (PredicateKind::Contains(operator, needle), haystack) => {
+                Some(operator.evaluate(needle, haystack))
+            }
+            (PredicateKind::Flag(operator, mask), AttributeValue::Integer(value)) => {
+                Some(operator.evaluate(*mask, *value))
+            }
+            (PredicateKind::Length(operator, length), AttributeValue::IntegerList(list)) => {
+                Some(operator.apply(&(list.len() as i64), length))
+            }
+            (PredicateKind::Length(operator, length), AttributeValue::StringList(_)) => {
+                let texts = event.texts(self.attribute);
+                Some(operator.apply(&(texts.len() as i64), length))
+            }
             (PredicateKind::Comparison(operator, a), b) => Some(operator.evaluate(a, b)),
             (PredicateKind::Equality(operator, a), b) => Some(operator.evaluate(a, b)),
             (PredicateKind::List(operator, a), b) => Some(operator.evaluate(a, b)),
+            (PredicateKind::Quantified(quantifier, operator, a), b) => {
+                Some(quantifier.evaluate(operator, a, b))
+            }
+            (PredicateKind::StringMatch(operator, pattern), AttributeValue::String(_)) => event
+                .texts(self.attribute)
+                .first()
+                .map(|text| operator.apply(text, pattern)),
+            (
+                PredicateKind::QuantifiedStringMatch(quantifier, operator, pattern),
+                AttributeValue::StringList(_),
+            ) => Some(quantifier.apply(event.texts(self.attribute), |text| {
+                operator.apply(text, pattern)
+            })),
             (kind, value) => {
                 unreachable!("Invalid => got: {kind:?} with {value:?}");
             }
@@ -78,8 +200,90 @@ impl Not for Predicate {
 }
 
 impl Display for Predicate {
-    fn fmt(&self, formatter: &mut Formatter) -> std::fmt::Result {
-        write!(formatter, "⟨{}, {}⟩", self.attribute, self.kind)
+    fn fmt(&self, formatter: &mut Formatter) -> core::fmt::Result {
+        match &self.kind {
+            PredicateKind::AnyDefined(ids) | PredicateKind::NoneDefined(ids) => {
+                write!(formatter, "⟨[{}], {}⟩", ids.iter().join(", "), self.kind)
+            }
+            kind => write!(formatter, "⟨{}, {kind}⟩", self.attribute),
+        }
+    }
+}
+
+#[inline]
+fn is_any_defined(ids: &[AttributeId], event: &Event) -> bool {
+    ids.iter()
+        .any(|id| !matches!(event[*id], AttributeValue::Undefined))
+}
+
+#[cfg(feature = "serde")]
+/// A [`Predicate`] displayed with the name of its attribute and the text of its strings
+pub struct ResolvedPredicate<'a> {
+    predicate: &'a Predicate,
+    attributes: &'a AttributeTable,
+    strings: &'a dyn crate::strings::Interner,
+}
+
+#[cfg(feature = "serde")]
+impl ResolvedPredicate<'_> {
+    /// Quote the text of the string or fall back on its ID if it cannot be resolved.
+    fn quote(&self, id: StringId) -> String {
+        self.strings
+            .resolve(id)
+            .map(|value| alloc::format!("{value:?}"))
+            .unwrap_or_else(|| alloc::format!("{id:?}"))
+    }
+
+    fn quote_all(&self, ids: &[StringId]) -> String {
+        ids.iter().map(|id| self.quote(*id)).join(", ")
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Display for ResolvedPredicate<'_> {
+    fn fmt(&self, formatter: &mut Formatter) -> core::fmt::Result {
+        let name = self.attributes.name(self.predicate.attribute);
+        match &self.predicate.kind {
+            PredicateKind::Set(operator, ListLiteral::StringList(ids)) => {
+                write!(formatter, "⟨{name}, {operator}, [{}]⟩", self.quote_all(ids))
+            }
+            PredicateKind::List(operator, ListLiteral::StringList(ids)) => {
+                write!(formatter, "⟨{name}, {operator}, [{}]⟩", self.quote_all(ids))
+            }
+            PredicateKind::Equality(operator, PrimitiveLiteral::String(id)) => {
+                write!(formatter, "⟨{name}, {operator}, {}⟩", self.quote(*id))
+            }
+            PredicateKind::Contains(operator, PrimitiveLiteral::String(id)) => {
+                write!(formatter, "⟨{name}, {operator}, {}⟩", self.quote(*id))
+            }
+            kind @ (PredicateKind::AnyDefined(ids) | PredicateKind::NoneDefined(ids)) => {
+                let names = ids.iter().map(|id| self.attributes.name(*id)).join(", ");
+                write!(formatter, "⟨[{names}], {kind}⟩")
+            }
+            kind => write!(formatter, "⟨{name}, {kind}⟩"),
+        }
+    }
+}
+
+fn validate_enumeration(
+    name: &str,
+    kind: &PredicateKind,
+    enumeration: &Enumeration,
+) -> Result<(), EventError> {
+    let is_allowed = match kind {
+        PredicateKind::Equality(_, PrimitiveLiteral::String(id)) => enumeration.contains(*id),
+        PredicateKind::Set(_, ListLiteral::StringList(ids)) => {
+            ids.iter().all(|id| enumeration.contains(*id))
+        }
+        _ => true,
+    };
+    if is_allowed {
+        Ok(())
+    } else {
+        Err(EventError::NotAllowedValue {
+            name: name.to_string(),
+            allowed: enumeration.values().to_vec(),
+        })
     }
 }
 
@@ -90,21 +294,40 @@ fn validate_predicate(
 ) -> Result<(), EventError> {
     match (&kind, attribute_kind) {
         (PredicateKind::Set(_, ListLiteral::StringList(_)), AttributeKind::String) => Ok(()),
+        (PredicateKind::Set(_, ListLiteral::StringList(_)), AttributeKind::Enum) => Ok(()),
         (PredicateKind::Set(_, ListLiteral::IntegerList(_)), AttributeKind::Integer) => Ok(()),
+        (PredicateKind::Range(_, _), AttributeKind::Integer) => Ok(()),
+
+        // The integers and the floats can be compared with each other
+        (PredicateKind::Comparison(_, _), AttributeKind::Integer | AttributeKind::Float) => Ok(()),
+        (
+            PredicateKind::Equality(_, PrimitiveLiteral::Integer(_) | PrimitiveLiteral::Float(_)),
+            AttributeKind::Integer | AttributeKind::Float,
+        ) => Ok(()),
+        (PredicateKind::Equality(_, PrimitiveLiteral::String(_)), AttributeKind::String) => Ok(()),
+        (PredicateKind::Equality(_, PrimitiveLiteral::String(_)), AttributeKind::Enum) => Ok(()),
 
-        (PredicateKind::Comparison(_, ComparisonValue::Integer(_)), AttributeKind::Integer) => {
+        (PredicateKind::List(_, ListLiteral::IntegerList(_)), AttributeKind::IntegerList) => Ok(()),
+        (PredicateKind::List(_, ListLiteral::StringList(_)), AttributeKind::StringList) => Ok(()),
+        (PredicateKind::Contains(_, PrimitiveLiteral::Integer(_)), AttributeKind::IntegerList) => {
             Ok(())
         }
-        (PredicateKind::Comparison(_, ComparisonValue::Float(_)), AttributeKind::Float) => Ok(()),
-
-        (PredicateKind::Equality(_, PrimitiveLiteral::Integer(_)), AttributeKind::Integer) => {
+        (PredicateKind::Contains(_, PrimitiveLiteral::String(_)), AttributeKind::StringList) => {
+            Ok(())
+        }
+        (PredicateKind::Flag(_, _), AttributeKind::Integer) => Ok(()),
+        (PredicateKind::Length(_, _), AttributeKind::IntegerList | AttributeKind::StringList) => {
             Ok(())
         }
-        (PredicateKind::Equality(_, PrimitiveLiteral::Float(_)), AttributeKind::Float) => Ok(()),
-        (PredicateKind::Equality(_, PrimitiveLiteral::String(_)), AttributeKind::String) => Ok(()),
 
-        (PredicateKind::List(_, ListLiteral::IntegerList(_)), AttributeKind::IntegerList) => Ok(()),
-        (PredicateKind::List(_, ListLiteral::StringList(_)), AttributeKind::StringList) => Ok(()),
+        (
+            PredicateKind::Quantified(_, _, ComparisonValue::Integer(_)),
+            AttributeKind::IntegerList,
+        ) => Ok(()),
+
+        (PredicateKind::StringMatch(_, _), AttributeKind::String) => Ok(()),
+        (PredicateKind::StringMatch(_, _), AttributeKind::Enum) => Ok(()),
+        (PredicateKind::QuantifiedStringMatch(_, _, _), AttributeKind::StringList) => Ok(()),
 
         (PredicateKind::Variable, AttributeKind::Boolean) => Ok(()),
         (PredicateKind::NegatedVariable, AttributeKind::Boolean) => Ok(()),
@@ -116,16 +339,133 @@ fn validate_predicate(
         (PredicateKind::Null(NullOperator::IsNull), AttributeKind::Integer) => Ok(()),
         (PredicateKind::Null(NullOperator::IsNull), AttributeKind::Float) => Ok(()),
         (PredicateKind::Null(NullOperator::IsNull), AttributeKind::String) => Ok(()),
+        (PredicateKind::Null(NullOperator::IsNull), AttributeKind::Enum) => Ok(()),
         (PredicateKind::Null(NullOperator::IsNull), AttributeKind::Boolean) => Ok(()),
+        (PredicateKind::Null(NullOperator::IsNull), AttributeKind::StringList) => Ok(()),
+        (PredicateKind::Null(NullOperator::IsNull), AttributeKind::IntegerList) => Ok(()),
         (PredicateKind::Null(NullOperator::IsNotNull), AttributeKind::Integer) => Ok(()),
         (PredicateKind::Null(NullOperator::IsNotNull), AttributeKind::Float) => Ok(()),
         (PredicateKind::Null(NullOperator::IsNotNull), AttributeKind::String) => Ok(()),
+        (PredicateKind::Null(NullOperator::IsNotNull), AttributeKind::Enum) => Ok(()),
         (PredicateKind::Null(NullOperator::IsNotNull), AttributeKind::Boolean) => Ok(()),
-        (actual, expected) => Err(EventError::MismatchingTypes {
-            name: name.to_string(),
-            expected: expected.clone(),
-            actual: (*actual).clone(),
-        }),
+        (PredicateKind::Null(NullOperator::IsNotNull), AttributeKind::StringList) => Ok(()),
+        (PredicateKind::Null(NullOperator::IsNotNull), AttributeKind::IntegerList) => Ok(()),
+        (kind, actual) => {
+            if let Some(error) = suggest_list_form(name, kind, actual) {
+                return Err(error);
+            }
+            let (operator, expected) = describe_requirement(kind);
+            Err(EventError::UnsupportedOperator {
+                name: name.to_string(),
+                operator,
+                expected,
+                actual: actual.clone(),
+            })
+        }
+    }
+}
+
+/// Suggest the operator to use when a list operator is applied to a scalar attribute (or a set
+/// operator to a list attribute) with elements of the right type since it is a common mix-up.
+fn suggest_list_form(
+    name: &str,
+    kind: &PredicateKind,
+    actual: &AttributeKind,
+) -> Option<EventError> {
+    match (kind, actual) {
+        (
+            PredicateKind::List(operator, ListLiteral::StringList(_)),
+            AttributeKind::String | AttributeKind::Enum,
+        )
+        | (PredicateKind::List(operator, ListLiteral::IntegerList(_)), AttributeKind::Integer) => {
+            let suggestion = match operator {
+                ListOperator::OneOf => "in",
+                ListOperator::NoneOf => "not in",
+                _ => return None,
+            };
+            Some(EventError::ScalarWithListOperator {
+                name: name.to_string(),
+                operator: operator.to_string(),
+                actual: actual.clone(),
+                suggestion,
+            })
+        }
+        (PredicateKind::Set(operator, ListLiteral::StringList(_)), AttributeKind::StringList)
+        | (PredicateKind::Set(operator, ListLiteral::IntegerList(_)), AttributeKind::IntegerList) =>
+        {
+            let suggestion = match operator {
+                SetOperator::In => "one of",
+                SetOperator::NotIn => "none of",
+            };
+            Some(EventError::ListWithSetOperator {
+                name: name.to_string(),
+                operator: operator.to_string(),
+                actual: actual.clone(),
+                suggestion,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Describe the operator of the predicate along with the attributes it supports so that the
+/// rejected expressions can be fixed.
+fn describe_requirement(kind: &PredicateKind) -> (&'static str, &'static str) {
+    const BOOLEAN: &str = "a boolean attribute";
+    const INTEGER: &str = "an integer attribute";
+    const NUMBER: &str = "an integer or a float attribute";
+    const STRING: &str = "a string attribute";
+    const INTEGER_LIST: &str = "a list of integers attribute";
+    const STRING_LIST: &str = "a list of strings attribute";
+    match kind {
+        PredicateKind::Variable | PredicateKind::NegatedVariable => ("boolean variables", BOOLEAN),
+        PredicateKind::Set(_, ListLiteral::IntegerList(_)) => {
+            ("set operators with integers", INTEGER)
+        }
+        PredicateKind::Set(_, ListLiteral::StringList(_)) => ("set operators with strings", STRING),
+        PredicateKind::Range(_, _) => ("set operators with a range", INTEGER),
+        PredicateKind::Comparison(_, _) => ("comparison operators", NUMBER),
+        PredicateKind::Equality(_, PrimitiveLiteral::Integer(_) | PrimitiveLiteral::Float(_)) => {
+            ("equality operators with a number", NUMBER)
+        }
+        PredicateKind::Equality(_, PrimitiveLiteral::String(_)) => {
+            ("equality operators with a string", STRING)
+        }
+        PredicateKind::List(_, ListLiteral::IntegerList(_)) => {
+            ("list operators with integers", INTEGER_LIST)
+        }
+        PredicateKind::List(_, ListLiteral::StringList(_)) => {
+            ("list operators with strings", STRING_LIST)
+        }
+        PredicateKind::Contains(_, PrimitiveLiteral::Integer(_)) => {
+            ("contains with an integer", INTEGER_LIST)
+        }
+        PredicateKind::Contains(_, PrimitiveLiteral::String(_)) => {
+            ("contains with a string", STRING_LIST)
+        }
+        PredicateKind::Contains(_, PrimitiveLiteral::Float(_)) => (
+            "contains with a float",
+            "an integer or a string instead of a float",
+        ),
+        PredicateKind::Flag(_, _) => ("flag operators", INTEGER),
+        PredicateKind::Length(_, _) => ("length comparisons", "a list attribute"),
+        PredicateKind::Quantified(_, _, ComparisonValue::Integer(_)) => {
+            ("quantified comparisons", INTEGER_LIST)
+        }
+        PredicateKind::Quantified(_, _, ComparisonValue::Float(_)) => (
+            "quantified comparisons with a float",
+            "an integer instead of a float",
+        ),
+        PredicateKind::StringMatch(_, _) => ("string matching operators", STRING),
+        PredicateKind::QuantifiedStringMatch(_, _, _) => {
+            ("quantified string matching operators", STRING_LIST)
+        }
+        PredicateKind::Null(NullOperator::IsEmpty | NullOperator::IsNotEmpty) => {
+            ("emptiness checks", "a list attribute")
+        }
+        PredicateKind::Null(NullOperator::IsNull | NullOperator::IsNotNull)
+        | PredicateKind::AnyDefined(_)
+        | PredicateKind::NoneDefined(_) => ("null checks", "any attribute"),
     }
 }
 
@@ -138,6 +478,16 @@ pub enum PredicateKind {
     Equality(EqualityOperator, PrimitiveLiteral),
     List(ListOperator, ListLiteral),
     Null(NullOperator),
+    Quantified(Quantifier, ComparisonOperator, ComparisonValue),
+    StringMatch(StringMatchOperator, String),
+    QuantifiedStringMatch(Quantifier, StringMatchOperator, String),
+    // Added last to keep the IDs of the existing predicates stable
+    Range(SetOperator, RangeInclusive<i64>),
+    Contains(ContainsOperator, PrimitiveLiteral),
+    AnyDefined(Vec<AttributeId>),
+    NoneDefined(Vec<AttributeId>),
+    Flag(FlagOperator, i64),
+    Length(ComparisonOperator, i64),
 }
 
 impl PredicateKind {
@@ -145,6 +495,44 @@ impl PredicateKind {
     const LOGARITHMIC_COST: u64 = 1;
     const LIST_COST: u64 = 2;
 
+    /// The empty lists are parsed as lists of integers since their values cannot tell their type
+    /// so they are turned into lists of strings for the attributes holding strings.
+    fn with_empty_lists_typed_as(self, kind: &AttributeKind) -> Self {
+        let is_string = matches!(
+            kind,
+            AttributeKind::String | AttributeKind::Enum | AttributeKind::StringList
+        );
+        match self {
+            Self::Set(operator, ListLiteral::IntegerList(values))
+                if values.is_empty() && is_string =>
+            {
+                Self::Set(operator, ListLiteral::StringList(Vec::new()))
+            }
+            Self::List(operator, ListLiteral::IntegerList(values))
+                if values.is_empty() && is_string =>
+            {
+                Self::List(operator, ListLiteral::StringList(Vec::new()))
+            }
+            kind => kind,
+        }
+    }
+
+    /// Round the float literals like the values of the events for the attributes that have a
+    /// float scale (see [`crate::AttributeDefinition::with_float_scale()`]).
+    fn with_floats_rounded_by(self, attributes: &AttributeTable, id: AttributeId) -> Self {
+        match self {
+            Self::Comparison(operator, ComparisonValue::Float(value)) => Self::Comparison(
+                operator,
+                ComparisonValue::Float(attributes.round_float(id, value).normalize()),
+            ),
+            Self::Equality(operator, PrimitiveLiteral::Float(value)) => Self::Equality(
+                operator,
+                PrimitiveLiteral::Float(attributes.round_float(id, value).normalize()),
+            ),
+            kind => kind,
+        }
+    }
+
     #[inline]
     pub fn cost(&self) -> u64 {
         match self {
@@ -152,7 +540,13 @@ impl PredicateKind {
             | Self::Variable
             | Self::Null(_)
             | Self::Comparison(_, _)
-            | Self::Equality(_, _) => Self::CONSTANT_COST,
+            | Self::Range(_, _)
+            | Self::Equality(_, _)
+            | Self::AnyDefined(_)
+            | Self::NoneDefined(_)
+            | Self::Flag(_, _)
+            | Self::Length(_, _) => Self::CONSTANT_COST,
+            Self::Contains(_, _) => Self::LOGARITHMIC_COST,
             Self::Set(_, ListLiteral::StringList(list)) => {
                 Self::LOGARITHMIC_COST * (list.len() as u64)
             }
@@ -161,6 +555,8 @@ impl PredicateKind {
             }
             Self::List(_, ListLiteral::StringList(list)) => Self::LIST_COST * (list.len() as u64),
             Self::List(_, ListLiteral::IntegerList(list)) => Self::LIST_COST * (list.len() as u64),
+            Self::Quantified(_, _, _) | Self::QuantifiedStringMatch(_, _, _) => Self::LIST_COST,
+            Self::StringMatch(_, _) => Self::LOGARITHMIC_COST,
         }
     }
 }
@@ -172,18 +568,14 @@ impl Not for PredicateKind {
         match self {
             Self::Set(SetOperator::In, value) => Self::Set(SetOperator::NotIn, value),
             Self::Set(SetOperator::NotIn, value) => Self::Set(SetOperator::In, value),
-            Self::Comparison(ComparisonOperator::LessThan, value) => {
-                Self::Comparison(ComparisonOperator::GreaterThanEqual, value)
-            }
-            Self::Comparison(ComparisonOperator::LessThanEqual, value) => {
-                Self::Comparison(ComparisonOperator::GreaterThan, value)
-            }
-            Self::Comparison(ComparisonOperator::GreaterThan, value) => {
-                Self::Comparison(ComparisonOperator::LessThanEqual, value)
-            }
-            Self::Comparison(ComparisonOperator::GreaterThanEqual, value) => {
-                Self::Comparison(ComparisonOperator::LessThan, value)
-            }
+            Self::Range(SetOperator::In, range) => Self::Range(SetOperator::NotIn, range),
+            Self::Range(SetOperator::NotIn, range) => Self::Range(SetOperator::In, range),
+            Self::Contains(operator, value) => Self::Contains(!operator, value),
+            Self::Flag(operator, mask) => Self::Flag(!operator, mask),
+            Self::Length(operator, length) => Self::Length(!operator, length),
+            Self::AnyDefined(ids) => Self::NoneDefined(ids),
+            Self::NoneDefined(ids) => Self::AnyDefined(ids),
+            Self::Comparison(operator, value) => Self::Comparison(!operator, value),
             Self::Null(NullOperator::IsNull) => Self::Null(NullOperator::IsNotNull),
             Self::Null(NullOperator::IsNotNull) => Self::Null(NullOperator::IsNull),
             Self::Null(NullOperator::IsEmpty) => Self::Null(NullOperator::IsNotEmpty),
@@ -198,22 +590,51 @@ impl Not for PredicateKind {
             Self::List(ListOperator::AllOf, value) => Self::List(ListOperator::NotAllOf, value),
             Self::List(ListOperator::NotAllOf, value) => Self::List(ListOperator::AllOf, value),
             Self::List(ListOperator::NoneOf, value) => Self::List(ListOperator::OneOf, value),
+            Self::List(ListOperator::SupersetOf, value) => {
+                Self::List(ListOperator::NotSupersetOf, value)
+            }
+            Self::List(ListOperator::NotSupersetOf, value) => {
+                Self::List(ListOperator::SupersetOf, value)
+            }
             Self::Variable => Self::NegatedVariable,
             Self::NegatedVariable => Self::Variable,
+            // De Morgan: not (all x > v) <=> any x <= v
+            Self::Quantified(quantifier, operator, value) => {
+                Self::Quantified(!quantifier, !operator, value)
+            }
+            Self::StringMatch(operator, pattern) => Self::StringMatch(!operator, pattern),
+            Self::QuantifiedStringMatch(quantifier, operator, pattern) => {
+                Self::QuantifiedStringMatch(!quantifier, !operator, pattern)
+            }
         }
     }
 }
 
 impl Display for PredicateKind {
-    fn fmt(&self, formatter: &mut Formatter) -> std::fmt::Result {
+    fn fmt(&self, formatter: &mut Formatter) -> core::fmt::Result {
         match self {
             Self::Variable => write!(formatter, "id, variable"),
             Self::NegatedVariable => write!(formatter, "not, variable"),
             Self::Set(operator, values) => write!(formatter, "{operator}, {values}"),
+            Self::Range(operator, range) => {
+                write!(formatter, "{operator}, {}..={}", range.start(), range.end())
+            }
             Self::Comparison(operator, values) => write!(formatter, "{operator}, {values}"),
+            Self::Contains(operator, value) => write!(formatter, "{operator}, {value}"),
+            Self::Flag(operator, mask) => write!(formatter, "{operator}, {mask:#x}"),
+            Self::Length(operator, length) => write!(formatter, "length {operator}, {length}"),
+            Self::AnyDefined(_) => write!(formatter, "any defined"),
+            Self::NoneDefined(_) => write!(formatter, "none defined"),
             Self::List(operator, values) => write!(formatter, "{operator}, {values}"),
             Self::Null(operator) => write!(formatter, "{operator}, variable"),
             Self::Equality(operator, values) => write!(formatter, "{operator}, {values}"),
+            Self::Quantified(quantifier, operator, values) => {
+                write!(formatter, "{quantifier} {operator}, {values}")
+            }
+            Self::StringMatch(operator, pattern) => write!(formatter, "{operator}, {pattern:?}"),
+            Self::QuantifiedStringMatch(quantifier, operator, pattern) => {
+                write!(formatter, "{quantifier} {operator}, {pattern:?}")
+            }
         }
     }
 }
@@ -245,10 +666,17 @@ impl SetOperator {
             Self::NotIn => haystack.binary_search(needle).is_err(),
         }
     }
+
+    fn evaluate_range(&self, range: &RangeInclusive<i64>, needle: &i64) -> bool {
+        match self {
+            Self::In => range.contains(needle),
+            Self::NotIn => !range.contains(needle),
+        }
+    }
 }
 
 impl Display for SetOperator {
-    fn fmt(&self, formatter: &mut Formatter) -> std::fmt::Result {
+    fn fmt(&self, formatter: &mut Formatter) -> core::fmt::Result {
         match self {
             Self::In => write!(formatter, "in"),
             Self::NotIn => write!(formatter, "not in"),
@@ -269,6 +697,12 @@ impl ComparisonOperator {
         match (a, b) {
             (ComparisonValue::Float(b), AttributeValue::Float(a)) => self.apply(&a, &b),
             (ComparisonValue::Integer(b), AttributeValue::Integer(a)) => self.apply(&a, &b),
+            (ComparisonValue::Float(b), AttributeValue::Integer(a)) => {
+                self.apply(&Decimal::from(*a), b)
+            }
+            (ComparisonValue::Integer(b), AttributeValue::Float(a)) => {
+                self.apply(a, &Decimal::from(*b))
+            }
             (a, b) => {
                 unreachable!("Comparison ({self:?}) between {a:?} and {b:?} should never happen. This is a bug.")
             }
@@ -285,8 +719,21 @@ impl ComparisonOperator {
     }
 }
 
+impl Not for ComparisonOperator {
+    type Output = Self;
+
+    fn not(self) -> Self::Output {
+        match self {
+            Self::LessThan => Self::GreaterThanEqual,
+            Self::LessThanEqual => Self::GreaterThan,
+            Self::GreaterThan => Self::LessThanEqual,
+            Self::GreaterThanEqual => Self::LessThan,
+        }
+    }
+}
+
 impl Display for ComparisonOperator {
-    fn fmt(&self, formatter: &mut Formatter) -> std::fmt::Result {
+    fn fmt(&self, formatter: &mut Formatter) -> core::fmt::Result {
         match self {
             Self::GreaterThanEqual => write!(formatter, ">="),
             Self::GreaterThan => write!(formatter, ">"),
@@ -303,7 +750,7 @@ pub enum ComparisonValue {
 }
 
 impl Display for ComparisonValue {
-    fn fmt(&self, formatter: &mut Formatter) -> std::fmt::Result {
+    fn fmt(&self, formatter: &mut Formatter) -> core::fmt::Result {
         match self {
             Self::Integer(value) => write!(formatter, "{value}"),
             Self::Float(value) => write!(formatter, "{value}"),
@@ -311,6 +758,277 @@ impl Display for ComparisonValue {
     }
 }
 
+#[derive(Hash, Eq, PartialEq, Clone, Debug)]
+pub enum Quantifier {
+    All,
+    Any,
+}
+
+impl Quantifier {
+    fn evaluate(
+        &self,
+        operator: &ComparisonOperator,
+        value: &ComparisonValue,
+        list: &AttributeValue,
+    ) -> bool {
+        match (value, list) {
+            (ComparisonValue::Integer(b), AttributeValue::IntegerList(list)) => {
+                self.apply(list, |a| operator.apply(a, b))
+            }
+            (a, b) => {
+                unreachable!("Quantified comparison ({self:?} {operator:?}) between {a:?} and {b:?} should never happen. This is a bug.")
+            }
+        }
+    }
+
+    #[inline]
+    fn apply<T>(&self, list: &[T], predicate: impl Fn(&T) -> bool) -> bool {
+        match self {
+            Self::All => list.iter().all(predicate),
+            Self::Any => list.iter().any(predicate),
+        }
+    }
+}
+
+impl Not for Quantifier {
+    type Output = Self;
+
+    fn not(self) -> Self::Output {
+        match self {
+            Self::All => Self::Any,
+            Self::Any => Self::All,
+        }
+    }
+}
+
+impl Display for Quantifier {
+    fn fmt(&self, formatter: &mut Formatter) -> core::fmt::Result {
+        match self {
+            Self::All => write!(formatter, "all"),
+            Self::Any => write!(formatter, "any"),
+        }
+    }
+}
+
+#[derive(Hash, Eq, PartialEq, Clone, Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum StringMatchOperator {
+    StartsWith,
+    EndsWith,
+    NotStartsWith,
+    NotEndsWith,
+    Like,
+    NotLike,
+}
+
+impl StringMatchOperator {
+    #[inline]
+    fn apply(&self, text: &str, pattern: &str) -> bool {
+        match self {
+            Self::StartsWith => text.starts_with(pattern),
+            Self::EndsWith => text.ends_with(pattern),
+            Self::NotStartsWith => !text.starts_with(pattern),
+            Self::NotEndsWith => !text.ends_with(pattern),
+            Self::Like => like(text, pattern),
+            Self::NotLike => !like(text, pattern),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum LikeToken {
+    AnyString,
+    AnyCharacter,
+    Character(char),
+}
+
+/// Read the token of a SQL `LIKE` pattern that starts at `position` along with the position of the
+/// next one.
+#[inline]
+fn next_like_token(pattern: &str, position: usize) -> Option<(LikeToken, usize)> {
+    let mut characters = pattern[position..].chars();
+    let character = characters.next()?;
+    let (token, length) = match character {
+        '%' => (LikeToken::AnyString, 1),
+        '_' => (LikeToken::AnyCharacter, 1),
+        // A trailing backslash matches itself
+        '\\' => characters
+            .next()
+            .map_or((LikeToken::Character('\\'), 1), |escaped| {
+                (LikeToken::Character(escaped), 1 + escaped.len_utf8())
+            }),
+        character => (LikeToken::Character(character), character.len_utf8()),
+    };
+    Some((token, position + length))
+}
+
+/// Match the text against a SQL `LIKE` pattern where `%` matches any string, `_` matches any
+/// character and `\` escapes the character that follows it.
+fn like(text: &str, pattern: &str) -> bool {
+    let (mut text_position, mut pattern_position) = (0, 0);
+    // Where to resume after the last `%` if the rest of the pattern does not match
+    let mut backtrack: Option<(usize, usize)> = None;
+    loop {
+        let token = next_like_token(pattern, pattern_position);
+        if let Some((LikeToken::AnyString, next)) = token {
+            pattern_position = next;
+            backtrack = Some((pattern_position, text_position));
+            continue;
+        }
+
+        let character = text[text_position..].chars().next();
+        match (token, character) {
+            (None, None) => return true,
+            (Some((LikeToken::AnyCharacter, next)), Some(character)) => {
+                pattern_position = next;
+                text_position += character.len_utf8();
+                continue;
+            }
+            (Some((LikeToken::Character(expected), next)), Some(character))
+                if expected == character =>
+            {
+                pattern_position = next;
+                text_position += character.len_utf8();
+                continue;
+            }
+            _ => {}
+        }
+
+        // Let the last `%` consume one more character and try again
+        let Some((resume_pattern, resume_text)) = backtrack else {
+            return false;
+        };
+        let Some(character) = text[resume_text..].chars().next() else {
+            return false;
+        };
+        pattern_position = resume_pattern;
+        text_position = resume_text + character.len_utf8();
+        backtrack = Some((pattern_position, text_position));
+    }
+}
+
+impl Not for StringMatchOperator {
+    type Output = Self;
+
+    fn not(self) -> Self::Output {
+        match self {
+            Self::StartsWith => Self::NotStartsWith,
+            Self::EndsWith => Self::NotEndsWith,
+            Self::NotStartsWith => Self::StartsWith,
+            Self::NotEndsWith => Self::EndsWith,
+            Self::Like => Self::NotLike,
+            Self::NotLike => Self::Like,
+        }
+    }
+}
+
+impl Display for StringMatchOperator {
+    fn fmt(&self, formatter: &mut Formatter) -> core::fmt::Result {
+        match self {
+            Self::StartsWith => write!(formatter, "starts with"),
+            Self::EndsWith => write!(formatter, "ends with"),
+            Self::NotStartsWith => write!(formatter, "not starts with"),
+            Self::NotEndsWith => write!(formatter, "not ends with"),
+            Self::Like => write!(formatter, "like"),
+            Self::NotLike => write!(formatter, "not like"),
+        }
+    }
+}
+
+#[derive(Hash, Eq, PartialEq, Clone, Debug)]
+pub enum ContainsOperator {
+    Contains,
+    NotContains,
+}
+
+impl ContainsOperator {
+    /// Look for a single value in the list of the event, which is sorted when the event is built.
+    fn evaluate(&self, needle: &PrimitiveLiteral, haystack: &AttributeValue) -> bool {
+        let is_contained = match (needle, haystack) {
+            (PrimitiveLiteral::Integer(needle), AttributeValue::IntegerList(haystack)) => {
+                haystack.binary_search(needle).is_ok()
+            }
+            (PrimitiveLiteral::String(needle), AttributeValue::StringList(haystack)) => {
+                haystack.binary_search(needle).is_ok()
+            }
+            (a, b) => {
+                unreachable!("Contains ({self:?}) between {a:?} and {b:?} should never happen. This is a bug.")
+            }
+        };
+        match self {
+            Self::Contains => is_contained,
+            Self::NotContains => !is_contained,
+        }
+    }
+}
+
+impl Not for ContainsOperator {
+    type Output = Self;
+
+    fn not(self) -> Self::Output {
+        match self {
+            Self::Contains => Self::NotContains,
+            Self::NotContains => Self::Contains,
+        }
+    }
+}
+
+impl Display for ContainsOperator {
+    fn fmt(&self, formatter: &mut Formatter) -> core::fmt::Result {
+        match self {
+            Self::Contains => write!(formatter, "contains"),
+            Self::NotContains => write!(formatter, "not contains"),
+        }
+    }
+}
+
+#[derive(Hash, Eq, PartialEq, Clone, Debug)]
+pub enum FlagOperator {
+    All,
+    NotAll,
+    Any,
+    NotAny,
+}
+
+impl FlagOperator {
+    /// Test the bits of the mask in the value: all of them must be set for `has_flag` (so an
+    /// empty mask always matches) while one of them is enough for `has_any_flag` (so an empty mask
+    /// never matches).
+    #[inline]
+    fn evaluate(&self, mask: i64, value: i64) -> bool {
+        match self {
+            Self::All => value & mask == mask,
+            Self::NotAll => value & mask != mask,
+            Self::Any => value & mask != 0,
+            Self::NotAny => value & mask == 0,
+        }
+    }
+}
+
+impl Not for FlagOperator {
+    type Output = Self;
+
+    fn not(self) -> Self::Output {
+        match self {
+            Self::All => Self::NotAll,
+            Self::NotAll => Self::All,
+            Self::Any => Self::NotAny,
+            Self::NotAny => Self::Any,
+        }
+    }
+}
+
+impl Display for FlagOperator {
+    fn fmt(&self, formatter: &mut Formatter) -> core::fmt::Result {
+        match self {
+            Self::All => write!(formatter, "has flag"),
+            Self::NotAll => write!(formatter, "not has flag"),
+            Self::Any => write!(formatter, "has any flag"),
+            Self::NotAny => write!(formatter, "not has any flag"),
+        }
+    }
+}
+
 #[derive(Hash, Eq, PartialEq, Clone, Debug)]
 pub enum EqualityOperator {
     Equal,
@@ -322,6 +1040,12 @@ impl EqualityOperator {
         match (a, b) {
             (PrimitiveLiteral::Float(a), AttributeValue::Float(b)) => self.apply(&a, &b),
             (PrimitiveLiteral::Integer(a), AttributeValue::Integer(b)) => self.apply(&a, &b),
+            (PrimitiveLiteral::Float(a), AttributeValue::Integer(b)) => {
+                self.apply(a, &Decimal::from(*b))
+            }
+            (PrimitiveLiteral::Integer(a), AttributeValue::Float(b)) => {
+                self.apply(&Decimal::from(*a), b)
+            }
             (PrimitiveLiteral::String(a), AttributeValue::String(b)) => self.apply(&a, &b),
             (a, b) => {
                 unreachable!("Equality ({self:?}) between {a:?} and {b:?} should never happen. This is a bug.")
@@ -338,7 +1062,7 @@ impl EqualityOperator {
 }
 
 impl Display for EqualityOperator {
-    fn fmt(&self, formatter: &mut Formatter) -> std::fmt::Result {
+    fn fmt(&self, formatter: &mut Formatter) -> core::fmt::Result {
         match self {
             Self::Equal => write!(formatter, "="),
             Self::NotEqual => write!(formatter, "<>"),
@@ -355,6 +1079,12 @@ pub enum ListOperator {
     // This is an internal operator only This is only to achieve symmetry with
     // the `all_of` operator for the zero suppression filter.
     NotAllOf,
+    // The event's list contains all the elements of the expression's list; this is what
+    // `[...] all of <attribute>` is lowered to.
+    SupersetOf,
+    // This is an internal operator only to achieve symmetry with the `superset_of` operator for
+    // the zero suppression filter.
+    NotSupersetOf,
 }
 
 impl ListOperator {
@@ -378,17 +1108,21 @@ impl ListOperator {
             Self::NoneOf => none_of(left, right),
             Self::AllOf => all_of(left, right),
             Self::NotAllOf => not_all_of(left, right),
+            Self::SupersetOf => all_of(right, left),
+            Self::NotSupersetOf => not_all_of(right, left),
         }
     }
 }
 
 impl Display for ListOperator {
-    fn fmt(&self, formatter: &mut Formatter) -> std::fmt::Result {
+    fn fmt(&self, formatter: &mut Formatter) -> core::fmt::Result {
         match self {
             Self::AllOf => write!(formatter, "all of"),
             Self::OneOf => write!(formatter, "one of"),
             Self::NoneOf => write!(formatter, "none of"),
             Self::NotAllOf => write!(formatter, "not all of"),
+            Self::SupersetOf => write!(formatter, "superset of"),
+            Self::NotSupersetOf => write!(formatter, "not superset of"),
         }
     }
 }
@@ -399,7 +1133,7 @@ fn none_of<T: Ord>(left: &[T], right: &[T]) -> bool {
 }
 
 fn one_of<T: Ord>(left: &[T], right: &[T]) -> bool {
-    use std::cmp::Ordering;
+    use core::cmp::Ordering;
 
     if left.is_empty() || right.is_empty() {
         return false;
@@ -432,7 +1166,7 @@ fn not_all_of<T: Ord>(left: &[T], right: &[T]) -> bool {
 }
 
 fn all_of<T: Ord>(left: &[T], right: &[T]) -> bool {
-    use std::cmp::Ordering;
+    use core::cmp::Ordering;
 
     if left.len() > right.len() {
         return false;
@@ -470,28 +1204,18 @@ pub enum NullOperator {
 }
 
 impl NullOperator {
-    fn evaluate(&self, value: &AttributeValue) -> bool {
+    fn evaluate(&self, value: &AttributeValue) -> Option<bool> {
         match (self, value) {
-            (Self::IsNull, AttributeValue::Undefined) => true,
-            (
-                Self::IsNull,
-                AttributeValue::Integer(_)
-                | AttributeValue::String(_)
-                | AttributeValue::Float(_)
-                | AttributeValue::Boolean(_),
-            ) => false,
-            (Self::IsNotNull, AttributeValue::Undefined) => false,
-            (
-                Self::IsNotNull,
-                AttributeValue::Integer(_)
-                | AttributeValue::String(_)
-                | AttributeValue::Float(_)
-                | AttributeValue::Boolean(_),
-            ) => true,
-            (Self::IsEmpty, AttributeValue::StringList(list)) => list.is_empty(),
-            (Self::IsEmpty, AttributeValue::IntegerList(list)) => list.is_empty(),
-            (Self::IsNotEmpty, AttributeValue::StringList(list)) => !list.is_empty(),
-            (Self::IsNotEmpty, AttributeValue::IntegerList(list)) => !list.is_empty(),
+            (Self::IsNull, AttributeValue::Undefined) => Some(true),
+            (Self::IsNull, _) => Some(false),
+            (Self::IsNotNull, AttributeValue::Undefined) => Some(false),
+            (Self::IsNotNull, _) => Some(true),
+            // A list that was never set is neither empty nor non-empty
+            (Self::IsEmpty | Self::IsNotEmpty, AttributeValue::Undefined) => None,
+            (Self::IsEmpty, AttributeValue::StringList(list)) => Some(list.is_empty()),
+            (Self::IsEmpty, AttributeValue::IntegerList(list)) => Some(list.is_empty()),
+            (Self::IsNotEmpty, AttributeValue::StringList(list)) => Some(!list.is_empty()),
+            (Self::IsNotEmpty, AttributeValue::IntegerList(list)) => Some(!list.is_empty()),
             (_, value) => {
                 unreachable!(
                     "Null check ({self:?}) for {value:?} should never happen. This is a bug."
@@ -502,7 +1226,7 @@ impl NullOperator {
 }
 
 impl Display for NullOperator {
-    fn fmt(&self, formatter: &mut Formatter) -> std::fmt::Result {
+    fn fmt(&self, formatter: &mut Formatter) -> core::fmt::Result {
         match self {
             Self::IsNull => write!(formatter, "is null"),
             Self::IsNotNull => write!(formatter, "is not null"),
@@ -512,14 +1236,31 @@ impl Display for NullOperator {
     }
 }
 
-#[derive(Hash, Eq, PartialEq, Clone, Debug)]
+#[derive(Eq, PartialEq, Clone, Debug)]
 pub enum ListLiteral {
     IntegerList(Vec<i64>),
     StringList(Vec<StringId>),
 }
 
+impl Hash for ListLiteral {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        core::mem::discriminant(self).hash(state);
+        match self {
+            Self::IntegerList(values) => values.hash(state),
+            // The strings are sorted by the order in which they were interned so they are hashed
+            // in the order of their content to get the same hash in every tree
+            Self::StringList(ids) => ids
+                .iter()
+                .map(StringId::content_hash)
+                .sorted_unstable()
+                .collect::<Vec<_>>()
+                .hash(state),
+        }
+    }
+}
+
 impl Display for ListLiteral {
-    fn fmt(&self, formatter: &mut Formatter) -> std::fmt::Result {
+    fn fmt(&self, formatter: &mut Formatter) -> core::fmt::Result {
         match self {
             Self::IntegerList(values) => write!(formatter, "{values:?}"),
             Self::StringList(values) => write!(formatter, "{values:?}"),
@@ -535,7 +1276,7 @@ pub enum PrimitiveLiteral {
 }
 
 impl Display for PrimitiveLiteral {
-    fn fmt(&self, formatter: &mut Formatter) -> std::fmt::Result {
+    fn fmt(&self, formatter: &mut Formatter) -> core::fmt::Result {
         match self {
             Self::Integer(values) => write!(formatter, "{values}"),
             Self::Float(values) => write!(formatter, "{values}"),
@@ -553,8 +1294,9 @@ mod tests {
         test_utils::predicates::{
             all_of, comparison_float, comparison_integer, equal, greater_than, greater_than_equal,
             integer_list, is_empty, is_not_empty, is_not_null, is_null, less_than, less_than_equal,
-            negated_variable, none_of, not_equal, one_of, predicate, primitive_string, set_in,
-            set_not_in, string_list, variable,
+            negated_variable, none_of, not_equal, one_of, predicate, primitive_integer,
+            primitive_string, quantified, range_in, range_not_in, set_in, set_not_in, string_list,
+            superset_of, variable,
         },
     };
     use itertools::Itertools;
@@ -656,6 +1398,41 @@ mod tests {
         assert_eq!(Some(false), predicate.evaluate(&event));
     }
 
+    #[test]
+    fn distinguish_unset_empty_and_non_empty_lists_on_null_and_empty_checks() {
+        let attributes = define_attributes();
+        let strings = StringTable::new();
+        let event = |segment_ids: Option<&[i64]>| {
+            let mut builder = an_event_builder(&attributes, &strings);
+            match segment_ids {
+                Some(segment_ids) => builder.with_integer_list("segment_ids", segment_ids),
+                None => builder.with_undefined("segment_ids"),
+            }
+            .unwrap();
+            builder.build().unwrap()
+        };
+        let unset = event(None);
+        let empty = event(Some(&[]));
+        let non_empty = event(Some(&[1]));
+        let is_null = is_null!(&attributes, "segment_ids");
+        let is_not_null = is_not_null!(&attributes, "segment_ids");
+        let is_empty = is_empty!(&attributes, "segment_ids");
+        let is_not_empty = is_not_empty!(&attributes, "segment_ids");
+
+        assert_eq!(Some(true), is_null.evaluate(&unset));
+        assert_eq!(Some(false), is_null.evaluate(&empty));
+        assert_eq!(Some(false), is_null.evaluate(&non_empty));
+        assert_eq!(Some(false), is_not_null.evaluate(&unset));
+        assert_eq!(Some(true), is_not_null.evaluate(&empty));
+        assert_eq!(Some(true), is_not_null.evaluate(&non_empty));
+        assert_eq!(None, is_empty.evaluate(&unset));
+        assert_eq!(Some(true), is_empty.evaluate(&empty));
+        assert_eq!(Some(false), is_empty.evaluate(&non_empty));
+        assert_eq!(None, is_not_empty.evaluate(&unset));
+        assert_eq!(Some(false), is_not_empty.evaluate(&empty));
+        assert_eq!(Some(true), is_not_empty.evaluate(&non_empty));
+    }
+
     #[test]
     fn return_true_on_empty_check_for_empty_list_variable() {
         let attributes = define_attributes();
@@ -807,7 +1584,7 @@ mod tests {
     fn return_true_when_checking_for_equality_for_two_elements_that_are_equal() {
         let attributes = define_attributes();
         let mut strings = StringTable::new();
-        let string_id = strings.get_or_update(A_COUNTRY);
+        let string_id = strings.get_or_update(A_COUNTRY).unwrap();
         let mut builder = an_event_builder(&attributes, &strings);
         builder.with_string("country", A_COUNTRY).unwrap();
         let event = builder.build().unwrap();
@@ -820,8 +1597,8 @@ mod tests {
     fn return_false_when_checking_for_equality_for_two_elements_that_are_not_equal() {
         let attributes = define_attributes();
         let mut strings = StringTable::new();
-        let _ = strings.get_or_update(A_COUNTRY);
-        let another_string_id = strings.get_or_update(ANOTHER_COUNTRY);
+        let _ = strings.get_or_update(A_COUNTRY).unwrap();
+        let another_string_id = strings.get_or_update(ANOTHER_COUNTRY).unwrap();
         let mut builder = an_event_builder(&attributes, &strings);
         builder.with_string("country", A_COUNTRY).unwrap();
         let event = builder.build().unwrap();
@@ -834,7 +1611,7 @@ mod tests {
     fn return_false_when_checking_for_inequality_for_two_elements_that_are_equal() {
         let attributes = define_attributes();
         let mut strings = StringTable::new();
-        let string_id = strings.get_or_update(A_COUNTRY);
+        let string_id = strings.get_or_update(A_COUNTRY).unwrap();
         let mut builder = an_event_builder(&attributes, &strings);
         builder.with_string("country", A_COUNTRY).unwrap();
         let event = builder.build().unwrap();
@@ -847,8 +1624,8 @@ mod tests {
     fn return_true_when_checking_for_inequality_for_two_elements_that_are_not_equal() {
         let attributes = define_attributes();
         let mut strings = StringTable::new();
-        let _ = strings.get_or_update(A_COUNTRY);
-        let another_string_id = strings.get_or_update(ANOTHER_COUNTRY);
+        let _ = strings.get_or_update(A_COUNTRY).unwrap();
+        let another_string_id = strings.get_or_update(ANOTHER_COUNTRY).unwrap();
         let mut builder = an_event_builder(&attributes, &strings);
         builder.with_string("country", A_COUNTRY).unwrap();
         let event = builder.build().unwrap();
@@ -926,6 +1703,167 @@ mod tests {
         assert_eq!(Some(true), predicate.evaluate(&event));
     }
 
+    #[test]
+    fn can_compare_an_integer_attribute_with_a_float() {
+        let attributes = define_attributes();
+        let strings = StringTable::new();
+        let mut builder = an_event_builder(&attributes, &strings);
+        builder.with_integer("exchange_id", 1).unwrap();
+        let event = builder.build().unwrap();
+
+        let less_than = less_than!(
+            &attributes,
+            "exchange_id",
+            comparison_float!(Decimal::new(15, 1))
+        );
+        let greater_than = greater_than!(
+            &attributes,
+            "exchange_id",
+            comparison_float!(Decimal::new(5, 1))
+        );
+
+        assert_eq!(Some(true), less_than.evaluate(&event));
+        assert_eq!(Some(true), greater_than.evaluate(&event));
+        assert_eq!(Some(false), (!less_than).evaluate(&event));
+    }
+
+    #[test]
+    fn can_compare_a_float_attribute_with_an_integer() {
+        let attributes = define_attributes();
+        let strings = StringTable::new();
+        let mut builder = an_event_builder(&attributes, &strings);
+        builder.with_float("bidfloor", 25, 1).unwrap();
+        let event = builder.build().unwrap();
+
+        let greater_than = greater_than!(&attributes, "bidfloor", comparison_integer!(2));
+        let less_than = less_than!(&attributes, "bidfloor", comparison_integer!(2));
+
+        assert_eq!(Some(true), greater_than.evaluate(&event));
+        assert_eq!(Some(false), less_than.evaluate(&event));
+    }
+
+    #[test]
+    fn compare_integers_and_floats_by_their_exact_value_at_the_boundary() {
+        let attributes = define_attributes();
+        let strings = StringTable::new();
+        let mut builder = an_event_builder(&attributes, &strings);
+        builder.with_integer("exchange_id", 2).unwrap();
+        builder.with_float("bidfloor", 200, 2).unwrap();
+        let event = builder.build().unwrap();
+
+        let exchange_id = |kind| predicate!(&attributes, "exchange_id", kind);
+        let bidfloor = |kind| predicate!(&attributes, "bidfloor", kind);
+
+        assert_eq!(
+            Some(true),
+            exchange_id(PredicateKind::Comparison(
+                ComparisonOperator::LessThanEqual,
+                comparison_float!(Decimal::new(20, 1))
+            ))
+            .evaluate(&event)
+        );
+        assert_eq!(
+            Some(false),
+            exchange_id(PredicateKind::Comparison(
+                ComparisonOperator::LessThan,
+                comparison_float!(Decimal::new(20, 1))
+            ))
+            .evaluate(&event)
+        );
+        assert_eq!(
+            Some(false),
+            exchange_id(PredicateKind::Comparison(
+                ComparisonOperator::GreaterThan,
+                comparison_float!(Decimal::new(2000000001, 9))
+            ))
+            .evaluate(&event)
+        );
+        assert_eq!(
+            Some(true),
+            bidfloor(PredicateKind::Comparison(
+                ComparisonOperator::GreaterThanEqual,
+                comparison_integer!(2)
+            ))
+            .evaluate(&event)
+        );
+    }
+
+    #[test]
+    fn compare_the_equality_of_integers_and_floats_by_their_value() {
+        let attributes = define_attributes();
+        let strings = StringTable::new();
+        let mut builder = an_event_builder(&attributes, &strings);
+        builder.with_integer("exchange_id", 2).unwrap();
+        builder.with_float("bidfloor", 25, 1).unwrap();
+        let event = builder.build().unwrap();
+
+        assert_eq!(
+            Some(true),
+            equal!(
+                &attributes,
+                "exchange_id",
+                PrimitiveLiteral::Float(Decimal::new(20, 1))
+            )
+            .evaluate(&event)
+        );
+        assert_eq!(
+            Some(true),
+            not_equal!(
+                &attributes,
+                "exchange_id",
+                PrimitiveLiteral::Float(Decimal::new(25, 1))
+            )
+            .evaluate(&event)
+        );
+        assert_eq!(
+            Some(false),
+            equal!(&attributes, "bidfloor", primitive_integer!(2)).evaluate(&event)
+        );
+    }
+
+    #[test]
+    fn return_true_when_the_event_list_is_a_superset_of_the_list() {
+        let attributes = define_attributes();
+        let strings = StringTable::new();
+        let mut builder = an_event_builder(&attributes, &strings);
+        builder
+            .with_integer_list("segment_ids", &[1, 2, 3])
+            .unwrap();
+        let event = builder.build().unwrap();
+
+        let predicate = superset_of!(&attributes, "segment_ids", integer_list!(vec![1, 3]));
+
+        assert_eq!(Some(true), predicate.evaluate(&event));
+        assert_eq!(Some(false), (!predicate).evaluate(&event));
+    }
+
+    #[test]
+    fn return_false_when_the_event_list_is_not_a_superset_of_the_list() {
+        let attributes = define_attributes();
+        let strings = StringTable::new();
+        let mut builder = an_event_builder(&attributes, &strings);
+        builder.with_integer_list("segment_ids", &[1, 2]).unwrap();
+        let event = builder.build().unwrap();
+
+        let predicate = superset_of!(&attributes, "segment_ids", integer_list!(vec![1, 3]));
+
+        assert_eq!(Some(false), predicate.evaluate(&event));
+        assert_eq!(Some(true), (!predicate).evaluate(&event));
+    }
+
+    #[test]
+    fn return_true_when_checking_if_a_list_is_a_superset_of_an_empty_list() {
+        let attributes = define_attributes();
+        let strings = StringTable::new();
+        let mut builder = an_event_builder(&attributes, &strings);
+        builder.with_integer_list("segment_ids", &[]).unwrap();
+        let event = builder.build().unwrap();
+
+        let predicate = superset_of!(&attributes, "segment_ids", integer_list!(vec![]));
+
+        assert_eq!(Some(true), predicate.evaluate(&event));
+    }
+
     #[test]
     fn return_false_when_checking_if_subset_of_an_empty_list() {
         let attributes = define_attributes();
@@ -945,8 +1883,8 @@ mod tests {
     fn return_true_when_checking_if_empty_list_is_subset_of_a_list() {
         let attributes = define_attributes();
         let mut strings = StringTable::new();
-        let id = strings.get_or_update("deal-1");
-        let another_id = strings.get_or_update("deal-2");
+        let id = strings.get_or_update("deal-1").unwrap();
+        let another_id = strings.get_or_update("deal-2").unwrap();
         let mut builder = an_event_builder(&attributes, &strings);
         builder.with_string_list("deals", &[]).unwrap();
         let event = builder.build().unwrap();
@@ -960,9 +1898,9 @@ mod tests {
     fn return_false_when_checking_if_list_that_is_bigger_than_the_other_list_is_a_subset() {
         let attributes = define_attributes();
         let mut strings = StringTable::new();
-        let id = strings.get_or_update("deal-1");
-        let another_id = strings.get_or_update("deal-2");
-        let _ = strings.get_or_update("deal-3");
+        let id = strings.get_or_update("deal-1").unwrap();
+        let another_id = strings.get_or_update("deal-2").unwrap();
+        let _ = strings.get_or_update("deal-3").unwrap();
         let mut builder = an_event_builder(&attributes, &strings);
         builder
             .with_string_list("deals", &["deal-1", "deal-2", "deal-3"])
@@ -979,10 +1917,10 @@ mod tests {
     ) {
         let attributes = define_attributes();
         let mut strings = StringTable::new();
-        let id = strings.get_or_update("deal-1");
-        let another_id = strings.get_or_update("deal-2");
-        let a_third_id = strings.get_or_update("deal-3");
-        let a_fourth_id = strings.get_or_update("deal-4");
+        let id = strings.get_or_update("deal-1").unwrap();
+        let another_id = strings.get_or_update("deal-2").unwrap();
+        let a_third_id = strings.get_or_update("deal-3").unwrap();
+        let a_fourth_id = strings.get_or_update("deal-4").unwrap();
         let mut builder = an_event_builder(&attributes, &strings);
         builder
             .with_string_list("deals", &["deal-3", "deal-4"])
@@ -1010,10 +1948,10 @@ mod tests {
     ) {
         let attributes = define_attributes();
         let mut strings = StringTable::new();
-        let id = strings.get_or_update("deal-1");
-        let another_id = strings.get_or_update("deal-2");
-        let a_third_id = strings.get_or_update("deal-3");
-        let a_fourth_id = strings.get_or_update("deal-4");
+        let id = strings.get_or_update("deal-1").unwrap();
+        let another_id = strings.get_or_update("deal-2").unwrap();
+        let a_third_id = strings.get_or_update("deal-3").unwrap();
+        let a_fourth_id = strings.get_or_update("deal-4").unwrap();
         let mut builder = an_event_builder(&attributes, &strings);
         builder
             .with_string_list("deals", &["deal-3", "deal-4"])
@@ -1145,6 +2083,326 @@ mod tests {
         assert_eq!(Some(true), predicate.evaluate(&event));
     }
 
+    #[test]
+    fn return_true_when_the_string_list_contains_the_value() {
+        let attributes = define_attributes();
+        let mut strings = StringTable::new();
+        let id = strings.get_or_update("deal-2").unwrap();
+        let _ = strings.get_or_update("deal-1").unwrap();
+        let builder = an_event_builder(&attributes, &strings);
+        let event = builder.build().unwrap();
+
+        let predicate = Predicate::new(
+            &attributes,
+            "deals",
+            PredicateKind::Contains(ContainsOperator::Contains, PrimitiveLiteral::String(id)),
+        )
+        .unwrap();
+
+        assert_eq!(Some(true), predicate.evaluate(&event));
+    }
+
+    #[test]
+    fn return_false_when_the_string_list_does_not_contain_the_value() {
+        let attributes = define_attributes();
+        let mut strings = StringTable::new();
+        let _ = strings.get_or_update("deal-1").unwrap();
+        let _ = strings.get_or_update("deal-2").unwrap();
+        let id = strings.get_or_update("deal-3").unwrap();
+        let builder = an_event_builder(&attributes, &strings);
+        let event = builder.build().unwrap();
+
+        let predicate = Predicate::new(
+            &attributes,
+            "deals",
+            PredicateKind::Contains(ContainsOperator::Contains, PrimitiveLiteral::String(id)),
+        )
+        .unwrap();
+
+        assert_eq!(Some(false), predicate.evaluate(&event));
+    }
+
+    #[test]
+    fn return_true_when_the_integer_list_contains_the_value() {
+        let attributes = define_attributes();
+        let strings = StringTable::new();
+        let mut builder = an_event_builder(&attributes, &strings);
+        builder
+            .with_integer_list("segment_ids", &[6, 2, 4])
+            .unwrap();
+        let event = builder.build().unwrap();
+
+        let predicate = Predicate::new(
+            &attributes,
+            "segment_ids",
+            PredicateKind::Contains(ContainsOperator::Contains, PrimitiveLiteral::Integer(4)),
+        )
+        .unwrap();
+
+        assert_eq!(Some(true), predicate.evaluate(&event));
+    }
+
+    #[test]
+    fn return_false_when_the_integer_list_does_not_contain_the_value() {
+        let attributes = define_attributes();
+        let strings = StringTable::new();
+        let mut builder = an_event_builder(&attributes, &strings);
+        builder
+            .with_integer_list("segment_ids", &[6, 2, 4])
+            .unwrap();
+        let event = builder.build().unwrap();
+
+        let predicate = Predicate::new(
+            &attributes,
+            "segment_ids",
+            PredicateKind::Contains(ContainsOperator::Contains, PrimitiveLiteral::Integer(5)),
+        )
+        .unwrap();
+
+        assert_eq!(Some(false), predicate.evaluate(&event));
+    }
+
+    #[test]
+    fn return_false_when_an_empty_list_is_checked_for_containing_a_value() {
+        let attributes = define_attributes();
+        let mut strings = StringTable::new();
+        let id = strings.get_or_update("deal-1").unwrap();
+        let mut builder = an_event_builder(&attributes, &strings);
+        builder.with_string_list("deals", &[]).unwrap();
+        builder.with_integer_list("segment_ids", &[]).unwrap();
+        let event = builder.build().unwrap();
+
+        let contains_string = Predicate::new(
+            &attributes,
+            "deals",
+            PredicateKind::Contains(ContainsOperator::Contains, PrimitiveLiteral::String(id)),
+        )
+        .unwrap();
+        let contains_integer = Predicate::new(
+            &attributes,
+            "segment_ids",
+            PredicateKind::Contains(ContainsOperator::Contains, PrimitiveLiteral::Integer(1)),
+        )
+        .unwrap();
+
+        assert_eq!(Some(false), contains_string.evaluate(&event));
+        assert_eq!(Some(false), contains_integer.evaluate(&event));
+    }
+
+    #[test]
+    fn return_the_opposite_when_checking_that_a_list_does_not_contain_a_value() {
+        let attributes = define_attributes();
+        let strings = StringTable::new();
+        let mut builder = an_event_builder(&attributes, &strings);
+        builder.with_integer_list("segment_ids", &[1, 2]).unwrap();
+        let event = builder.build().unwrap();
+        let present = Predicate::new(
+            &attributes,
+            "segment_ids",
+            PredicateKind::Contains(ContainsOperator::NotContains, PrimitiveLiteral::Integer(2)),
+        )
+        .unwrap();
+        let absent = Predicate::new(
+            &attributes,
+            "segment_ids",
+            PredicateKind::Contains(ContainsOperator::NotContains, PrimitiveLiteral::Integer(3)),
+        )
+        .unwrap();
+
+        assert_eq!(Some(false), present.evaluate(&event));
+        assert_eq!(Some(true), absent.evaluate(&event));
+        assert_eq!(Some(true), (!present).evaluate(&event));
+    }
+
+    #[test]
+    fn return_whether_all_or_any_of_the_flags_of_the_mask_are_set() {
+        let attributes = define_attributes();
+        let strings = StringTable::new();
+        // (value, mask, has_flag, has_any_flag)
+        let cases = [
+            (0b0100, 0b0100, true, true),
+            (0b0111, 0b0110, true, true),
+            (0b0010, 0b0110, false, true),
+            (0b0001, 0b0110, false, false),
+            (0b0000, 0b0110, false, false),
+            (-1, i64::MIN, true, true),
+            // An empty mask has all of its (zero) flags set but none of them
+            (0b0101, 0, true, false),
+            (0, 0, true, false),
+        ];
+
+        for (value, mask, has_flag, has_any_flag) in cases {
+            let mut builder = an_event_builder(&attributes, &strings);
+            builder.with_integer("exchange_id", value).unwrap();
+            let event = builder.build().unwrap();
+            let all = Predicate::new(
+                &attributes,
+                "exchange_id",
+                PredicateKind::Flag(FlagOperator::All, mask),
+            )
+            .unwrap();
+            let any = Predicate::new(
+                &attributes,
+                "exchange_id",
+                PredicateKind::Flag(FlagOperator::Any, mask),
+            )
+            .unwrap();
+
+            assert_eq!(Some(has_flag), all.evaluate(&event), "{value:#b} {mask:#b}");
+            assert_eq!(
+                Some(has_any_flag),
+                any.evaluate(&event),
+                "{value:#b} {mask:#b}"
+            );
+            assert_eq!(
+                Some(!has_flag),
+                (!all).evaluate(&event),
+                "{value:#b} {mask:#b}"
+            );
+            assert_eq!(
+                Some(!has_any_flag),
+                (!any).evaluate(&event),
+                "{value:#b} {mask:#b}"
+            );
+        }
+    }
+
+    #[test]
+    fn compare_the_length_of_the_lists_with_the_threshold() {
+        let attributes = define_attributes_with_texts();
+        let strings = StringTable::new();
+        let event = an_event_builder(&attributes, &strings).build().unwrap();
+        // `segment_ids` holds 3 integers and `deals` holds 2 strings
+        let cases = [
+            ("segment_ids", ComparisonOperator::GreaterThan, 2, true),
+            ("segment_ids", ComparisonOperator::GreaterThan, 3, false),
+            ("segment_ids", ComparisonOperator::GreaterThan, 4, false),
+            ("segment_ids", ComparisonOperator::GreaterThanEqual, 3, true),
+            ("segment_ids", ComparisonOperator::LessThan, 3, false),
+            ("segment_ids", ComparisonOperator::LessThanEqual, 3, true),
+            ("deals", ComparisonOperator::LessThan, 3, true),
+            ("deals", ComparisonOperator::LessThan, 2, false),
+            ("deals", ComparisonOperator::LessThan, 1, false),
+            ("deals", ComparisonOperator::GreaterThan, -1, true),
+        ];
+
+        for (name, operator, length, expected) in cases {
+            let predicate = Predicate::new(
+                &attributes,
+                name,
+                PredicateKind::Length(operator.clone(), length),
+            )
+            .unwrap();
+
+            assert_eq!(
+                Some(expected),
+                predicate.evaluate(&event),
+                "len({name}) {operator} {length}"
+            );
+            assert_eq!(
+                Some(!expected),
+                (!predicate).evaluate(&event),
+                "not len({name}) {operator} {length}"
+            );
+        }
+    }
+
+    #[test]
+    fn return_none_when_comparing_the_length_of_an_undefined_list() {
+        let attributes = define_attributes();
+        let strings = StringTable::new();
+        let mut builder = an_event_builder(&attributes, &strings);
+        builder.with_undefined("segment_ids").unwrap();
+        let event = builder.build().unwrap();
+
+        let predicate = Predicate::new(
+            &attributes,
+            "segment_ids",
+            PredicateKind::Length(ComparisonOperator::GreaterThanEqual, 0),
+        )
+        .unwrap();
+
+        assert_eq!(None, predicate.evaluate(&event));
+    }
+
+    #[test]
+    fn return_an_error_when_comparing_the_length_of_an_attribute_that_is_not_a_list() {
+        let attributes = define_attributes();
+
+        for name in ["exchange_id", "bidfloor", "private", "country"] {
+            assert!(matches!(
+                Predicate::new(
+                    &attributes,
+                    name,
+                    PredicateKind::Length(ComparisonOperator::GreaterThan, 1)
+                ),
+                Err(EventError::UnsupportedOperator { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn return_an_error_when_testing_the_flags_of_an_attribute_that_is_not_an_integer() {
+        let attributes = define_attributes();
+
+        for name in ["bidfloor", "segment_ids", "private", "country"] {
+            assert!(matches!(
+                Predicate::new(&attributes, name, PredicateKind::Flag(FlagOperator::All, 1)),
+                Err(EventError::UnsupportedOperator { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn return_false_when_none_of_the_attributes_is_defined() {
+        let attributes = define_attributes();
+        let strings = StringTable::new();
+        let event = EventBuilder::new(&attributes, &strings).build().unwrap();
+
+        let predicate = Predicate::any_defined(&attributes, &["deals", "segment_ids"]).unwrap();
+
+        assert_eq!(Some(false), predicate.evaluate(&event));
+        assert_eq!(Some(true), (!predicate).evaluate(&event));
+    }
+
+    #[test]
+    fn return_true_when_some_of_the_attributes_are_defined() {
+        let attributes = define_attributes();
+        let strings = StringTable::new();
+        let mut builder = EventBuilder::new(&attributes, &strings);
+        builder.with_integer_list("segment_ids", &[]).unwrap();
+        let event = builder.build().unwrap();
+
+        let predicate =
+            Predicate::any_defined(&attributes, &["deals", "segment_ids", "country"]).unwrap();
+
+        assert_eq!(Some(true), predicate.evaluate(&event));
+        assert_eq!(Some(false), (!predicate).evaluate(&event));
+    }
+
+    #[test]
+    fn return_true_when_all_of_the_attributes_are_defined() {
+        let attributes = define_attributes();
+        let strings = StringTable::new();
+        let event = an_event_builder(&attributes, &strings).build().unwrap();
+
+        let predicate = Predicate::any_defined(&attributes, &["deals", "segment_ids"]).unwrap();
+
+        assert_eq!(Some(true), predicate.evaluate(&event));
+    }
+
+    #[test]
+    fn return_an_error_when_any_of_the_attributes_does_not_exist() {
+        let attributes = define_attributes();
+
+        let predicate = Predicate::any_defined(&attributes, &["deals", "unknown"]);
+
+        assert_eq!(
+            Err(EventError::NonExistingAttribute("unknown".to_string())),
+            predicate
+        );
+    }
+
     #[test]
     fn return_none_when_the_attribute_is_undefined() {
         let attributes = define_attributes();
@@ -1168,7 +2426,7 @@ mod tests {
         let predicate = variable!(&attributes, "private");
 
         assert_eq!(
-            predicate.evaluate(&event).map(std::ops::Not::not),
+            predicate.evaluate(&event).map(core::ops::Not::not),
             (!predicate).evaluate(&event)
         )
     }
@@ -1183,7 +2441,7 @@ mod tests {
         let predicate = negated_variable!(&attributes, "private");
 
         assert_eq!(
-            predicate.evaluate(&event).map(std::ops::Not::not),
+            predicate.evaluate(&event).map(core::ops::Not::not),
             (!predicate).evaluate(&event)
         )
     }
@@ -1198,7 +2456,7 @@ mod tests {
         let predicate = is_null!(&attributes, "private");
 
         assert_eq!(
-            predicate.evaluate(&event).map(std::ops::Not::not),
+            predicate.evaluate(&event).map(core::ops::Not::not),
             (!predicate).evaluate(&event)
         )
     }
@@ -1213,7 +2471,7 @@ mod tests {
         let predicate = is_not_null!(&attributes, "private");
 
         assert_eq!(
-            predicate.evaluate(&event).map(std::ops::Not::not),
+            predicate.evaluate(&event).map(core::ops::Not::not),
             (!predicate).evaluate(&event)
         )
     }
@@ -1230,7 +2488,7 @@ mod tests {
         let predicate = is_empty!(&attributes, "segment_ids");
 
         assert_eq!(
-            predicate.evaluate(&event).map(std::ops::Not::not),
+            predicate.evaluate(&event).map(core::ops::Not::not),
             (!predicate).evaluate(&event)
         )
     }
@@ -1247,11 +2505,42 @@ mod tests {
         let predicate = is_not_empty!(&attributes, "segment_ids");
 
         assert_eq!(
-            predicate.evaluate(&event).map(std::ops::Not::not),
+            predicate.evaluate(&event).map(core::ops::Not::not),
             (!predicate).evaluate(&event)
         )
     }
 
+    #[test]
+    fn return_true_on_in_range_at_the_bounds_of_the_range() {
+        let attributes = define_attributes();
+        let strings = StringTable::new();
+        let predicate = range_in!(&attributes, "exchange_id", 9..=16);
+
+        for (exchange_id, expected) in [(8, false), (9, true), (16, true), (17, false)] {
+            let mut builder = an_event_builder(&attributes, &strings);
+            builder.with_integer("exchange_id", exchange_id).unwrap();
+            let event = builder.build().unwrap();
+
+            assert_eq!(Some(expected), predicate.evaluate(&event), "{exchange_id}");
+        }
+    }
+
+    #[test]
+    fn return_the_complement_on_not_in_range() {
+        let attributes = define_attributes();
+        let strings = StringTable::new();
+        let predicate = range_not_in!(&attributes, "exchange_id", 9..=16);
+
+        for (exchange_id, expected) in [(8, true), (9, false), (16, false), (17, true)] {
+            let mut builder = an_event_builder(&attributes, &strings);
+            builder.with_integer("exchange_id", exchange_id).unwrap();
+            let event = builder.build().unwrap();
+
+            assert_eq!(Some(expected), predicate.evaluate(&event), "{exchange_id}");
+            assert_eq!(Some(!expected), (!predicate.clone()).evaluate(&event));
+        }
+    }
+
     #[test]
     fn can_negate_a_set_in_predicate() {
         let attributes = define_attributes();
@@ -1262,7 +2551,7 @@ mod tests {
         let predicate = set_in!(&attributes, "exchange_id", integer_list!(vec![]));
 
         assert_eq!(
-            predicate.evaluate(&event).map(std::ops::Not::not),
+            predicate.evaluate(&event).map(core::ops::Not::not),
             (!predicate).evaluate(&event)
         )
     }
@@ -1277,7 +2566,7 @@ mod tests {
         let predicate = set_not_in!(&attributes, "exchange_id", integer_list!(vec![]));
 
         assert_eq!(
-            predicate.evaluate(&event).map(std::ops::Not::not),
+            predicate.evaluate(&event).map(core::ops::Not::not),
             (!predicate).evaluate(&event)
         )
     }
@@ -1286,14 +2575,14 @@ mod tests {
     fn can_negate_an_equal_predicate() {
         let attributes = define_attributes();
         let mut strings = StringTable::new();
-        let string_id = strings.get_or_update(A_COUNTRY);
+        let string_id = strings.get_or_update(A_COUNTRY).unwrap();
         let mut builder = an_event_builder(&attributes, &strings);
         builder.with_string("country", A_COUNTRY).unwrap();
         let event = builder.build().unwrap();
         let predicate = equal!(&attributes, "country", primitive_string!(string_id));
 
         assert_eq!(
-            predicate.evaluate(&event).map(std::ops::Not::not),
+            predicate.evaluate(&event).map(core::ops::Not::not),
             (!predicate).evaluate(&event)
         )
     }
@@ -1302,14 +2591,14 @@ mod tests {
     fn can_negate_a_not_equal_predicate() {
         let attributes = define_attributes();
         let mut strings = StringTable::new();
-        let string_id = strings.get_or_update(A_COUNTRY);
+        let string_id = strings.get_or_update(A_COUNTRY).unwrap();
         let mut builder = an_event_builder(&attributes, &strings);
         builder.with_string("country", A_COUNTRY).unwrap();
         let event = builder.build().unwrap();
         let predicate = not_equal!(&attributes, "country", primitive_string!(string_id));
 
         assert_eq!(
-            predicate.evaluate(&event).map(std::ops::Not::not),
+            predicate.evaluate(&event).map(core::ops::Not::not),
             (!predicate).evaluate(&event)
         )
     }
@@ -1324,7 +2613,7 @@ mod tests {
         let predicate = less_than!(&attributes, "exchange_id", comparison_integer!(0));
 
         assert_eq!(
-            predicate.evaluate(&event).map(std::ops::Not::not),
+            predicate.evaluate(&event).map(core::ops::Not::not),
             (!predicate).evaluate(&event)
         )
     }
@@ -1339,7 +2628,7 @@ mod tests {
         let predicate = less_than_equal!(&attributes, "exchange_id", comparison_integer!(0));
 
         assert_eq!(
-            predicate.evaluate(&event).map(std::ops::Not::not),
+            predicate.evaluate(&event).map(core::ops::Not::not),
             (!predicate).evaluate(&event)
         )
     }
@@ -1354,7 +2643,7 @@ mod tests {
         let predicate = greater_than!(&attributes, "exchange_id", comparison_integer!(0));
 
         assert_eq!(
-            predicate.evaluate(&event).map(std::ops::Not::not),
+            predicate.evaluate(&event).map(core::ops::Not::not),
             (!predicate).evaluate(&event)
         )
     }
@@ -1369,7 +2658,7 @@ mod tests {
         let predicate = greater_than_equal!(&attributes, "exchange_id", comparison_integer!(0));
 
         assert_eq!(
-            predicate.evaluate(&event).map(std::ops::Not::not),
+            predicate.evaluate(&event).map(core::ops::Not::not),
             (!predicate).evaluate(&event)
         )
     }
@@ -1384,7 +2673,7 @@ mod tests {
         let predicate = one_of!(&attributes, "segment_ids", integer_list!(vec![1, 2, 3, 4]));
 
         assert_eq!(
-            predicate.evaluate(&event).map(std::ops::Not::not),
+            predicate.evaluate(&event).map(core::ops::Not::not),
             (!predicate).evaluate(&event)
         )
     }
@@ -1399,7 +2688,7 @@ mod tests {
         let predicate = none_of!(&attributes, "segment_ids", integer_list!(vec![1, 2, 3, 4]));
 
         assert_eq!(
-            predicate.evaluate(&event).map(std::ops::Not::not),
+            predicate.evaluate(&event).map(core::ops::Not::not),
             (!predicate).evaluate(&event)
         )
     }
@@ -1414,11 +2703,379 @@ mod tests {
         let predicate = all_of!(&attributes, "segment_ids", integer_list!(vec![1, 2, 3, 4]));
 
         assert_eq!(
-            predicate.evaluate(&event).map(std::ops::Not::not),
+            predicate.evaluate(&event).map(core::ops::Not::not),
             (!predicate).evaluate(&event)
         )
     }
 
+    #[test]
+    fn return_the_same_id_for_a_predicate_across_runs() {
+        let attributes = define_attributes();
+        let predicate = equal!(
+            &attributes,
+            "exchange_id",
+            primitive_integer!(AN_EXCHANGE_ID)
+        );
+
+        assert_eq!(17283604162391914934, predicate.id());
+    }
+
+    #[test]
+    fn can_match_the_start_and_the_end_of_a_string() {
+        let attributes = define_attributes_with_texts();
+        let strings = StringTable::new();
+        let mut builder = an_event_builder(&attributes, &strings);
+        builder.with_string("deal", "promo_summer").unwrap();
+        let event = builder.build().unwrap();
+        let starts_with = predicate!(
+            &attributes,
+            "deal",
+            PredicateKind::StringMatch(StringMatchOperator::StartsWith, "promo_".to_string())
+        );
+        let ends_with = predicate!(
+            &attributes,
+            "deal",
+            PredicateKind::StringMatch(StringMatchOperator::EndsWith, "winter".to_string())
+        );
+
+        assert_eq!(Some(true), starts_with.evaluate(&event));
+        assert_eq!(Some(false), (!starts_with).evaluate(&event));
+        assert_eq!(Some(false), ends_with.evaluate(&event));
+        assert_eq!(Some(true), (!ends_with).evaluate(&event));
+    }
+
+    #[test]
+    fn can_match_a_string_against_a_like_pattern() {
+        let attributes = define_attributes_with_texts();
+        let strings = StringTable::new();
+        let mut builder = an_event_builder(&attributes, &strings);
+        builder.with_string("deal", "/shop/cart").unwrap();
+        let event = builder.build().unwrap();
+        let like = |pattern: &str| {
+            predicate!(
+                &attributes,
+                "deal",
+                PredicateKind::StringMatch(StringMatchOperator::Like, pattern.to_string())
+            )
+        };
+
+        assert_eq!(Some(true), like("/shop/%").evaluate(&event));
+        assert_eq!(Some(false), like("/home/%").evaluate(&event));
+        assert_eq!(Some(true), like("%/cart").evaluate(&event));
+        assert_eq!(Some(false), like("%/car").evaluate(&event));
+        assert_eq!(Some(true), like("/shop/c_rt").evaluate(&event));
+        assert_eq!(Some(false), like("/shop/c_t").evaluate(&event));
+        assert_eq!(Some(true), like("%sh%c%").evaluate(&event));
+        assert_eq!(Some(true), like("/shop/cart").evaluate(&event));
+        assert_eq!(Some(false), (!like("/shop/%")).evaluate(&event));
+    }
+
+    #[test]
+    fn can_escape_the_wildcards_of_a_like_pattern() {
+        assert!(like("100%", "100\\%"));
+        assert!(!like("1000", "100\\%"));
+        assert!(like("a_b", "a\\_b"));
+        assert!(!like("axb", "a\\_b"));
+        assert!(like("path\\", "path\\"));
+        assert!(like("", "%"));
+        assert!(!like("", "_"));
+        assert!(like("é", "_"));
+    }
+
+    #[test]
+    fn can_match_the_start_of_the_strings_of_a_list_when_one_element_matches() {
+        let attributes = define_attributes_with_texts();
+        let strings = StringTable::new();
+        let mut builder = an_event_builder(&attributes, &strings);
+        builder
+            .with_string_list("deals", &["deal-1", "promo_summer"])
+            .unwrap();
+        let event = builder.build().unwrap();
+        let (any, all) =
+            quantified_string_matches(&attributes, StringMatchOperator::StartsWith, "promo_");
+
+        assert_eq!(Some(true), any.evaluate(&event));
+        assert_eq!(Some(false), all.evaluate(&event));
+        assert_eq!(Some(false), (!any).evaluate(&event));
+        assert_eq!(Some(true), (!all).evaluate(&event));
+    }
+
+    #[test]
+    fn can_match_the_end_of_the_strings_of_a_list_when_no_element_matches() {
+        let attributes = define_attributes_with_texts();
+        let strings = StringTable::new();
+        let mut builder = an_event_builder(&attributes, &strings);
+        builder
+            .with_string_list("deals", &["deal-1", "promo_summer"])
+            .unwrap();
+        let event = builder.build().unwrap();
+        let (any, all) =
+            quantified_string_matches(&attributes, StringMatchOperator::EndsWith, "_winter");
+
+        assert_eq!(Some(false), any.evaluate(&event));
+        assert_eq!(Some(false), all.evaluate(&event));
+    }
+
+    #[test]
+    fn can_match_the_strings_of_a_list_when_all_the_elements_match() {
+        let attributes = define_attributes_with_texts();
+        let strings = StringTable::new();
+        let mut builder = an_event_builder(&attributes, &strings);
+        builder
+            .with_string_list("deals", &["promo_winter", "promo_summer"])
+            .unwrap();
+        let event = builder.build().unwrap();
+        let (any, all) =
+            quantified_string_matches(&attributes, StringMatchOperator::StartsWith, "promo_");
+
+        assert_eq!(Some(true), any.evaluate(&event));
+        assert_eq!(Some(true), all.evaluate(&event));
+    }
+
+    #[test]
+    fn can_match_the_strings_of_an_empty_list() {
+        let attributes = define_attributes_with_texts();
+        let strings = StringTable::new();
+        let mut builder = an_event_builder(&attributes, &strings);
+        builder.with_string_list("deals", &[]).unwrap();
+        let event = builder.build().unwrap();
+        let (any, all) =
+            quantified_string_matches(&attributes, StringMatchOperator::StartsWith, "promo_");
+
+        assert_eq!(Some(false), any.evaluate(&event));
+        assert_eq!(Some(true), all.evaluate(&event));
+    }
+
+    #[test]
+    fn return_an_error_when_matching_the_strings_of_a_scalar_attribute_with_a_quantifier() {
+        let attributes = define_attributes();
+
+        let result = Predicate::new(
+            &attributes,
+            "deal",
+            PredicateKind::QuantifiedStringMatch(
+                Quantifier::Any,
+                StringMatchOperator::StartsWith,
+                "promo_".to_string(),
+            ),
+        );
+
+        assert!(result.is_err());
+    }
+
+    fn quantified_string_matches(
+        attributes: &AttributeTable,
+        operator: StringMatchOperator,
+        pattern: &str,
+    ) -> (Predicate, Predicate) {
+        let any = predicate!(
+            attributes,
+            "deals",
+            PredicateKind::QuantifiedStringMatch(
+                Quantifier::Any,
+                operator.clone(),
+                pattern.to_string()
+            )
+        );
+        let all = predicate!(
+            attributes,
+            "deals",
+            PredicateKind::QuantifiedStringMatch(Quantifier::All, operator, pattern.to_string())
+        );
+        (any, all)
+    }
+
+    #[test]
+    fn return_true_when_all_the_elements_of_a_list_satisfy_the_comparison() {
+        let attributes = define_attributes();
+        let strings = StringTable::new();
+        let mut builder = an_event_builder(&attributes, &strings);
+        builder
+            .with_integer_list("segment_ids", &[101, 200, 300])
+            .unwrap();
+        let event = builder.build().unwrap();
+        let all = quantified!(
+            &attributes,
+            "segment_ids",
+            Quantifier::All,
+            ComparisonOperator::GreaterThan,
+            comparison_integer!(100)
+        );
+        let any = quantified!(
+            &attributes,
+            "segment_ids",
+            Quantifier::Any,
+            ComparisonOperator::GreaterThan,
+            comparison_integer!(250)
+        );
+
+        assert_eq!(Some(true), all.evaluate(&event));
+        assert_eq!(Some(true), any.evaluate(&event));
+    }
+
+    #[test]
+    fn return_false_when_some_elements_of_a_list_do_not_satisfy_the_comparison() {
+        let attributes = define_attributes();
+        let strings = StringTable::new();
+        let mut builder = an_event_builder(&attributes, &strings);
+        builder
+            .with_integer_list("segment_ids", &[50, 200, 300])
+            .unwrap();
+        let event = builder.build().unwrap();
+        let all = quantified!(
+            &attributes,
+            "segment_ids",
+            Quantifier::All,
+            ComparisonOperator::GreaterThan,
+            comparison_integer!(100)
+        );
+        let any = quantified!(
+            &attributes,
+            "segment_ids",
+            Quantifier::Any,
+            ComparisonOperator::GreaterThan,
+            comparison_integer!(300)
+        );
+
+        assert_eq!(Some(false), all.evaluate(&event));
+        assert_eq!(Some(false), any.evaluate(&event));
+    }
+
+    #[test]
+    fn return_true_for_all_and_false_for_any_on_an_empty_list() {
+        let attributes = define_attributes();
+        let strings = StringTable::new();
+        let mut builder = an_event_builder(&attributes, &strings);
+        builder.with_integer_list("segment_ids", &[]).unwrap();
+        let event = builder.build().unwrap();
+        let all = quantified!(
+            &attributes,
+            "segment_ids",
+            Quantifier::All,
+            ComparisonOperator::GreaterThan,
+            comparison_integer!(100)
+        );
+        let any = quantified!(
+            &attributes,
+            "segment_ids",
+            Quantifier::Any,
+            ComparisonOperator::GreaterThan,
+            comparison_integer!(100)
+        );
+
+        assert_eq!(Some(true), all.evaluate(&event));
+        assert_eq!(Some(false), any.evaluate(&event));
+    }
+
+    #[test]
+    fn can_negate_a_quantified_predicate() {
+        let attributes = define_attributes();
+        let strings = StringTable::new();
+        let all = quantified!(
+            &attributes,
+            "segment_ids",
+            Quantifier::All,
+            ComparisonOperator::GreaterThan,
+            comparison_integer!(100)
+        );
+        let any = quantified!(
+            &attributes,
+            "segment_ids",
+            Quantifier::Any,
+            ComparisonOperator::LessThan,
+            comparison_integer!(100)
+        );
+        assert_eq!(
+            quantified!(
+                &attributes,
+                "segment_ids",
+                Quantifier::Any,
+                ComparisonOperator::LessThanEqual,
+                comparison_integer!(100)
+            ),
+            !all.clone()
+        );
+        assert_eq!(
+            quantified!(
+                &attributes,
+                "segment_ids",
+                Quantifier::All,
+                ComparisonOperator::GreaterThanEqual,
+                comparison_integer!(100)
+            ),
+            !any.clone()
+        );
+
+        for list in [&[][..], &[50, 100], &[100, 200], &[150, 200]] {
+            let mut builder = an_event_builder(&attributes, &strings);
+            builder.with_integer_list("segment_ids", list).unwrap();
+            let event = builder.build().unwrap();
+            for predicate in [&all, &any] {
+                assert_eq!(
+                    predicate.evaluate(&event).map(core::ops::Not::not),
+                    (!predicate.clone()).evaluate(&event)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn describe_the_attributes_supported_by_an_operator_when_they_mismatch() {
+        let attributes = define_attributes();
+        let error = |name, kind| {
+            Predicate::new(&attributes, name, kind)
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert_eq!(
+            "comparison operators require an integer or a float attribute, got String for 'country'",
+            error(
+                "country",
+                PredicateKind::Comparison(ComparisonOperator::LessThan, comparison_integer!(1))
+            )
+        );
+        assert_eq!(
+            "boolean variables require a boolean attribute, got Integer for 'exchange_id'",
+            error("exchange_id", PredicateKind::Variable)
+        );
+        assert_eq!(
+            "emptiness checks require a list attribute, got String for 'deal'",
+            error("deal", PredicateKind::Null(NullOperator::IsEmpty))
+        );
+        assert_eq!(
+            "list operators with integers require a list of integers attribute, got StringList for 'deals'",
+            error(
+                "deals",
+                PredicateKind::List(ListOperator::OneOf, ListLiteral::IntegerList(vec![1]))
+            )
+        );
+        assert_eq!(
+            "string matching operators require a string attribute, got Float for 'bidfloor'",
+            error(
+                "bidfloor",
+                PredicateKind::StringMatch(StringMatchOperator::Like, "1%".to_string())
+            )
+        );
+    }
+
+    #[test]
+    fn return_an_error_when_quantifying_a_scalar_attribute() {
+        let attributes = define_attributes();
+
+        let result = Predicate::new(
+            &attributes,
+            "exchange_id",
+            PredicateKind::Quantified(
+                Quantifier::All,
+                ComparisonOperator::GreaterThan,
+                comparison_integer!(100),
+            ),
+        );
+
+        assert!(result.is_err());
+    }
+
     proptest! {
         #[test]
         #[cfg_attr(miri, ignore)]
@@ -1440,7 +3097,7 @@ mod tests {
         #[cfg_attr(miri, ignore)]
         fn can_find_an_element_common_from_both_lists((value, index, _) in vec_and_index(), (mut variable, variable_index, _) in vec_and_index()) {
             variable[variable_index] = value[index];
-            let variable = variable.into_iter().sorted().unique().collect_vec();
+            let variable = variable.into_iter().sorted().dedup().collect_vec();
 
             let attributes = define_attributes();
             let strings = StringTable::new();
@@ -1461,8 +3118,8 @@ mod tests {
             let attributes = define_attributes();
             let strings = StringTable::new();
             let mut builder = an_event_builder(&attributes, &strings);
-            let start = std::cmp::min(index, index_2);
-            let end = std::cmp::max(index, index_2);
+            let start = core::cmp::min(index, index_2);
+            let end = core::cmp::max(index, index_2);
             builder
                 .with_integer_list("segment_ids", &value[start..end])
                 .unwrap();
@@ -1487,6 +3144,14 @@ mod tests {
         AttributeTable::new(&definitions).unwrap()
     }
 
+    fn define_attributes_with_texts() -> AttributeTable {
+        let mut attributes = define_attributes();
+        for name in ["deals", "deal", "country"] {
+            attributes.retain_text(attributes.by_name(name).unwrap());
+        }
+        attributes
+    }
+
     fn an_event_builder<'a>(
         attributes: &'a AttributeTable,
         strings: &'a StringTable,
@@ -1505,7 +3170,7 @@ mod tests {
 
     fn vec_and_index() -> impl Strategy<Value = (Vec<i64>, usize, usize)> {
         prop::collection::vec(any::<i64>(), 1..100).prop_flat_map(|vec| {
-            let vec = vec.into_iter().sorted().unique().collect_vec();
+            let vec = vec.into_iter().sorted().dedup().collect_vec();
             let length = vec.len();
             (Just(vec), 0..length, 0..length)
         })
@@ -1,43 +1,284 @@
-use std::collections::HashMap;
+use crate::{collections::HashMap, hash::FnvHasher};
+use alloc::{
+    borrow::Cow,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{
+    cmp::Ordering,
+    fmt::{Debug, Formatter},
+    hash::{Hash, Hasher},
+    num::NonZeroUsize,
+};
+use thiserror::Error;
+#[cfg(feature = "normalization")]
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
 
+#[derive(Error, Clone, PartialEq, Debug)]
+pub enum StringTableError {
+    #[error("the string table is full since it already holds the maximum of {0} distinct strings")]
+    LimitReached(usize),
+}
+
+/// Interns the strings found in the expressions and looks up the ones of the events.
+///
+/// [`StringTable`] is the default implementation. A custom implementation can be shared by many
+/// [`ATree`](crate::ATree)s (see [`ATree::with_interner()`](crate::ATree::with_interner)) so that
+/// their [`StringId`]s can be compared with each other.
+pub trait Interner: Debug {
+    /// Return the ID of the string or [`StringId::UNKNOWN`] if it was never interned.
+    fn get(&self, value: &str) -> StringId;
+
+    /// Return the ID of the string, interning it first if needed.
+    fn get_or_update(&mut self, value: &str) -> Result<StringId, StringTableError>;
+
+    /// Return the string of the ID or `None` if it was not interned by this [`Interner`].
+    fn resolve(&self, id: StringId) -> Option<String>;
+
+    /// Return a mark of the strings interned so far that can be given to
+    /// [`Interner::rollback()`].
+    fn checkpoint(&self) -> usize {
+        0
+    }
+
+    /// Forget the strings interned since the checkpoint was taken.
+    ///
+    /// The [`ATree`](crate::ATree) rolls back the strings of the expressions it rejects so that
+    /// they do not count towards the limit of the [`Interner`]. The default implementation keeps
+    /// them, which is what an [`Interner`] shared by many [`ATree`](crate::ATree)s should do.
+    fn rollback(&mut self, _checkpoint: usize) {}
+}
+
+/// Interns the strings found in the expressions.
+///
+/// The table can be capped to a maximum amount of distinct strings. This is meant as a safety
+/// valve against unbounded memory growth: strings are never evicted (unless they are rolled back
+/// with [`StringTable::rollback()`]), so once the limit is reached, any expression that would
+/// introduce a new string is rejected.
 #[derive(Clone, Debug)]
 pub struct StringTable {
-    by_values: HashMap<String, usize>,
+    by_values: HashMap<Arc<str>, StringId>,
+    // The strings by their index (minus one) so that the IDs can be resolved
+    values: Vec<Arc<str>>,
     counter: usize,
+    max_strings: Option<usize>,
+    #[cfg(feature = "normalization")]
+    normalize: bool,
 }
 
 impl StringTable {
-    const SENTINEL_ID: usize = 0;
-
+    /// Create an empty table without any limit.
     pub fn new() -> Self {
         Self {
-            by_values: HashMap::new(),
+            by_values: HashMap::default(),
+            values: Vec::new(),
             counter: 1,
+            max_strings: None,
+            #[cfg(feature = "normalization")]
+            normalize: false,
         }
     }
 
+    /// Cap the amount of distinct strings; the strings that are already interned count towards
+    /// the limit.
+    pub fn set_max_strings(&mut self, max_strings: usize) {
+        self.max_strings = Some(max_strings);
+    }
+
+    /// Normalize the strings to their NFC form before interning them or looking them up so that
+    /// the strings that only differ by their Unicode normalization form (i.e. `"café"` written
+    /// with a combining accent) share the same [`StringId`].
+    #[cfg(feature = "normalization")]
+    pub fn set_normalize(&mut self, normalize: bool) {
+        self.normalize = normalize;
+    }
+
+    /// Return the ID of the string or [`StringId::UNKNOWN`] if it was never interned.
     pub fn get(&self, value: &str) -> StringId {
-        let index = self
-            .by_values
-            .get(value)
-            .cloned()
-            .unwrap_or(Self::SENTINEL_ID);
-        StringId(index)
+        self.by_values
+            .get(&*self.normalized(value))
+            .copied()
+            .unwrap_or(StringId::UNKNOWN)
+    }
+
+    /// Return the ID of the string, interning it first if the limit allows it.
+    pub fn get_or_update(&mut self, value: &str) -> Result<StringId, StringTableError> {
+        let value = self.normalized(value);
+        if let Some(id) = self.by_values.get(&*value) {
+            return Ok(*id);
+        }
+
+        if let Some(max_strings) = self.max_strings {
+            if self.by_values.len() >= max_strings {
+                return Err(StringTableError::LimitReached(max_strings));
+            }
+        }
+
+        Ok(self.intern(&value))
+    }
+
+    /// Return the string of the ID or `None` if it was not interned by this table.
+    pub fn resolve(&self, id: StringId) -> Option<&str> {
+        id.index
+            .checked_sub(1)
+            .and_then(|index| self.values.get(index))
+            .map(|value| &**value)
+    }
+
+    /// Return the amount of strings interned so far.
+    pub fn checkpoint(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Forget the strings interned after the checkpoint, freeing their share of the limit.
+    ///
+    /// The IDs of the forgotten strings are handed out again so they must not be in use anymore.
+    pub fn rollback(&mut self, checkpoint: usize) {
+        if checkpoint >= self.values.len() {
+            return;
+        }
+        for value in self.values.drain(checkpoint..) {
+            self.by_values.remove(&value);
+        }
+        self.counter = checkpoint + 1;
+    }
+
+    /// Return the string in the form it is interned with.
+    #[inline]
+    fn normalized<'a>(&self, value: &'a str) -> Cow<'a, str> {
+        #[cfg(feature = "normalization")]
+        if self.normalize && is_nfc_quick(value.chars()) != IsNormalized::Yes {
+            return Cow::Owned(value.nfc().collect());
+        }
+        Cow::Borrowed(value)
+    }
+
+    /// Intern the string without checking the limit.
+    fn intern(&mut self, value: &str) -> StringId {
+        if let Some(id) = self.by_values.get(value) {
+            return *id;
+        }
+
+        let id = StringId {
+            index: self.counter,
+            hash: StringId::hash_content(value),
+        };
+        self.counter += 1;
+        let value: Arc<str> = Arc::from(value);
+        self.values.push(value.clone());
+        self.by_values.insert(value, id);
+        id
+    }
+}
+
+impl Default for StringTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interner for StringTable {
+    #[inline]
+    fn get(&self, value: &str) -> StringId {
+        StringTable::get(self, value)
+    }
+
+    #[inline]
+    fn get_or_update(&mut self, value: &str) -> Result<StringId, StringTableError> {
+        StringTable::get_or_update(self, value)
     }
 
-    pub fn get_or_update(&mut self, value: &str) -> StringId {
-        let counter = self.by_values.entry(value.to_string()).or_insert_with(|| {
-            let counter = self.counter;
-            self.counter += 1;
-            counter
-        });
+    #[inline]
+    fn resolve(&self, id: StringId) -> Option<String> {
+        StringTable::resolve(self, id).map(ToString::to_string)
+    }
+
+    #[inline]
+    fn checkpoint(&self) -> usize {
+        StringTable::checkpoint(self)
+    }
 
-        StringId(*counter)
+    #[inline]
+    fn rollback(&mut self, checkpoint: usize) {
+        StringTable::rollback(self, checkpoint)
     }
 }
 
-#[derive(Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Debug, Hash)]
-pub struct StringId(usize);
+/// The identifier of an interned string
+///
+/// The identifiers are compared and ordered by the order in which their strings were interned but
+/// they are hashed by the content of their strings so that the IDs of the predicates (and thus of
+/// the expressions) do not depend on the [`StringTable`] that interned them.
+#[derive(Clone, Copy)]
+pub struct StringId {
+    index: usize,
+    hash: u64,
+}
+
+impl StringId {
+    /// The ID of the strings that were never interned
+    pub const UNKNOWN: Self = Self { index: 0, hash: 0 };
+
+    /// Create the ID of the string interned at the specified index.
+    ///
+    /// The IDs are equal when their indexes are so the [`Interner`] has to give each of its
+    /// strings a distinct index.
+    pub fn new(index: NonZeroUsize, value: &str) -> Self {
+        Self {
+            index: index.get(),
+            hash: Self::hash_content(value),
+        }
+    }
+
+    /// The hash of the content of the string
+    #[inline]
+    pub(crate) const fn content_hash(&self) -> u64 {
+        self.hash
+    }
+
+    fn hash_content(value: &str) -> u64 {
+        let mut hasher = FnvHasher::default();
+        value.hash(&mut hasher);
+        hasher.finish_mixed()
+    }
+}
+
+impl PartialEq for StringId {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl Eq for StringId {}
+
+impl PartialOrd for StringId {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for StringId {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.index.cmp(&other.index)
+    }
+}
+
+impl Hash for StringId {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
+
+impl Debug for StringId {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(formatter, "StringId({})", self.index)
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -59,7 +300,7 @@ mod tests {
     fn update_the_table_with_the_new_string_when_it_is_not_present() {
         let mut table = StringTable::new();
 
-        let id = table.get_or_update(A_KEY);
+        let id = table.get_or_update(A_KEY).unwrap();
 
         assert_eq!(id, table.get(A_KEY));
     }
@@ -68,19 +309,90 @@ mod tests {
     fn return_the_same_id_when_the_same_string_is_given() {
         let mut table = StringTable::new();
 
-        let id = table.get_or_update(A_KEY);
+        let id = table.get_or_update(A_KEY).unwrap();
 
-        assert_eq!(id, table.get_or_update(A_KEY));
+        assert_eq!(id, table.get_or_update(A_KEY).unwrap());
     }
 
     #[test]
     fn can_add_multiple_strings() {
         let mut table = StringTable::new();
 
-        let id = table.get_or_update(A_KEY);
-        let another_id = table.get_or_update(ANOTHER_KEY);
+        let id = table.get_or_update(A_KEY).unwrap();
+        let another_id = table.get_or_update(ANOTHER_KEY).unwrap();
+
+        assert_eq!(id, table.get_or_update(A_KEY).unwrap());
+        assert_eq!(another_id, table.get_or_update(ANOTHER_KEY).unwrap());
+    }
+
+    #[test]
+    fn return_an_error_when_adding_a_new_string_to_a_full_table() {
+        let mut table = StringTable::new();
+        table.set_max_strings(1);
+        let id = table.get_or_update(A_KEY).unwrap();
+
+        let result = table.get_or_update(ANOTHER_KEY);
+
+        assert_eq!(Err(StringTableError::LimitReached(1)), result);
+        assert_eq!(Ok(id), table.get_or_update(A_KEY));
+        assert_eq!(id, table.get(A_KEY));
+    }
+
+    #[test]
+    fn can_resolve_the_strings_of_the_ids() {
+        let mut table = StringTable::new();
+        let id = table.get_or_update(A_KEY).unwrap();
+        let another_id = table.get_or_update(ANOTHER_KEY).unwrap();
+
+        assert_eq!(Some(A_KEY), table.resolve(id));
+        assert_eq!(Some(ANOTHER_KEY), table.resolve(another_id));
+        assert_eq!(None, table.resolve(StringId::UNKNOWN));
+    }
+
+    #[test]
+    #[cfg(feature = "normalization")]
+    fn give_the_same_id_to_the_normalization_forms_of_a_string_when_normalizing() {
+        const NFC: &str = "caf\u{e9}";
+        const NFD: &str = "cafe\u{301}";
+        let mut table = StringTable::new();
+        let mut normalizing_table = StringTable::new();
+        normalizing_table.set_normalize(true);
+
+        let id = table.get_or_update(NFC).unwrap();
+        let normalized_id = normalizing_table.get_or_update(NFD).unwrap();
+
+        assert_eq!(StringId::UNKNOWN, table.get(NFD));
+        assert_eq!(normalized_id, normalizing_table.get(NFC));
+        assert_eq!(Ok(normalized_id), normalizing_table.get_or_update(NFC));
+        assert_eq!(Some(NFC), normalizing_table.resolve(normalized_id));
+        assert_eq!(id.content_hash(), normalized_id.content_hash());
+    }
+
+    #[test]
+    fn hash_the_same_string_the_same_way_regardless_of_the_interning_order() {
+        let mut table = StringTable::new();
+        let mut another_table = StringTable::new();
+        table.intern(ANOTHER_KEY);
+
+        let id = table.intern(A_KEY);
+        let another_id = another_table.intern(A_KEY);
+
+        assert_ne!(format!("{id:?}"), format!("{another_id:?}"));
+        assert_eq!(id.content_hash(), another_id.content_hash());
+    }
+
+    #[test]
+    fn forget_the_strings_interned_after_the_checkpoint_when_rolling_back() {
+        let mut table = StringTable::new();
+        table.set_max_strings(2);
+        let id = table.get_or_update(A_KEY).unwrap();
+        let checkpoint = table.checkpoint();
+        table.get_or_update(ANOTHER_KEY).unwrap();
+
+        table.rollback(checkpoint);
 
-        assert_eq!(id, table.get_or_update(A_KEY));
-        assert_eq!(another_id, table.get_or_update(ANOTHER_KEY));
+        assert_eq!(StringId::UNKNOWN, table.get(ANOTHER_KEY));
+        assert_eq!(Some(A_KEY), table.resolve(id));
+        assert!(table.get_or_update("test_3").is_ok());
     }
 }
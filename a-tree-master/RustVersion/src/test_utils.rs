@@ -32,13 +32,13 @@ pub mod ast {
 pub mod optimized_node {
     macro_rules! or {
         ($left:expr, $right:expr) => {
-            OptimizedNode::Or(Box::new($left), Box::new($right))
+            OptimizedNode::or($left, $right)
         };
     }
 
     macro_rules! and {
         ($left:expr, $right:expr) => {
-            OptimizedNode::And(Box::new($left), Box::new($right))
+            OptimizedNode::and($left, $right)
         };
     }
 
@@ -126,6 +126,26 @@ pub mod predicates {
         };
     }
 
+    macro_rules! range_in {
+        ($attributes:expr, $name:expr, $range:expr) => {
+            predicate!(
+                $attributes,
+                $name,
+                PredicateKind::Range(SetOperator::In, $range)
+            )
+        };
+    }
+
+    macro_rules! range_not_in {
+        ($attributes:expr, $name:expr, $range:expr) => {
+            predicate!(
+                $attributes,
+                $name,
+                PredicateKind::Range(SetOperator::NotIn, $range)
+            )
+        };
+    }
+
     macro_rules! equal {
         ($attributes:expr, $name:expr, $value:expr) => {
             predicate!(
@@ -216,6 +236,26 @@ pub mod predicates {
         };
     }
 
+    macro_rules! superset_of {
+        ($attributes:expr, $name:expr, $value:expr) => {
+            predicate!(
+                $attributes,
+                $name,
+                PredicateKind::List(ListOperator::SupersetOf, $value)
+            )
+        };
+    }
+
+    macro_rules! quantified {
+        ($attributes:expr, $name:expr, $quantifier:expr, $operator:expr, $value:expr) => {
+            predicate!(
+                $attributes,
+                $name,
+                PredicateKind::Quantified($quantifier, $operator, $value)
+            )
+        };
+    }
+
     macro_rules! comparison_float {
         ($value:expr) => {
             ComparisonValue::Float($value)
@@ -278,8 +318,85 @@ pub mod predicates {
     pub(crate) use predicate;
     pub(crate) use primitive_integer;
     pub(crate) use primitive_string;
+    pub(crate) use quantified;
+    pub(crate) use range_in;
+    pub(crate) use range_not_in;
     pub(crate) use set_in;
     pub(crate) use set_not_in;
     pub(crate) use string_list;
+    pub(crate) use superset_of;
     pub(crate) use variable;
 }
+
+#[cfg(feature = "tracing")]
+pub mod tracing {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+    use tracing::{
+        field::{Field, Visit},
+        span, Event, Metadata, Subscriber,
+    };
+
+    type Fields = HashMap<&'static str, u64>;
+
+    /// A subscriber that keeps the integer fields of the spans and of the events
+    #[derive(Clone, Default)]
+    pub struct Recorder {
+        spans: Arc<Mutex<Vec<(&'static str, Fields)>>>,
+        events: Arc<Mutex<Vec<Fields>>>,
+    }
+
+    impl Recorder {
+        pub fn spans(&self) -> Vec<(&'static str, Fields)> {
+            self.spans.lock().unwrap().clone()
+        }
+
+        pub fn events(&self) -> Vec<Fields> {
+            self.events.lock().unwrap().clone()
+        }
+    }
+
+    struct FieldsVisitor<'a>(&'a mut Fields);
+
+    impl Visit for FieldsVisitor<'_> {
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            self.0.insert(field.name(), value);
+        }
+
+        fn record_debug(&mut self, _: &Field, _: &dyn core::fmt::Debug) {}
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+            let mut fields = Fields::new();
+            span.record(&mut FieldsVisitor(&mut fields));
+            let mut spans = self.spans.lock().unwrap();
+            spans.push((span.metadata().name(), fields));
+            span::Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, span: &span::Id, values: &span::Record<'_>) {
+            let mut spans = self.spans.lock().unwrap();
+            let (_, fields) = &mut spans[span.into_u64() as usize - 1];
+            values.record(&mut FieldsVisitor(fields));
+        }
+
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields::new();
+            event.record(&mut FieldsVisitor(&mut fields));
+            self.events.lock().unwrap().push(fields);
+        }
+
+        fn enter(&self, _: &span::Id) {}
+
+        fn exit(&self, _: &span::Id) {}
+    }
+}